@@ -265,7 +265,7 @@ fn execute_import(cmd: ImportBlockchain) -> Result<(), String> {
     Ok(())
 }
 
-fn start_client(
+pub(crate) fn start_client(
     dirs: Directories,
     spec: SpecType,
     pruning: Pruning,
@@ -213,6 +213,29 @@ usage! {
             }
         }
 
+        CMD cmd_hbbft
+        {
+            "Honey Badger BFT validator tools",
+
+            CMD cmd_hbbft_share_proof
+            {
+                "Create a proof that the --engine-signer holds its key share of the current POSDAO epoch, without revealing the share",
+
+                ARG arg_hbbft_share_proof_file: (Option<String>) = None,
+                "[FILE]",
+                "Path to the file to write the proof to",
+            }
+
+            CMD cmd_hbbft_verify_share_proof
+            {
+                "Verify a key share proof offline",
+
+                ARG arg_hbbft_verify_share_proof_file: (Option<String>) = None,
+                "[FILE]",
+                "Path to the proof file",
+            }
+        }
+
         CMD cmd_db
         {
             "Manage the database representing the state of the blockchain on this system",
@@ -411,7 +434,7 @@ usage! {
 
             ARG arg_jsonrpc_apis: (String) = "web3,eth,pubsub,net,parity,parity_pubsub,traces,rpc", or |c: &Config| c.rpc.as_ref()?.apis.as_ref().map(|vec| vec.join(",")),
             "--jsonrpc-apis=[APIS]",
-            "Specify the APIs available through the HTTP JSON-RPC interface using a comma-delimited list of API names. Possible names are: all, safe, debug, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
+            "Specify the APIs available through the HTTP JSON-RPC interface using a comma-delimited list of API names. Possible names are: all, safe, debug, hbbft, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
 
             ARG arg_jsonrpc_hosts: (String) = "none", or |c: &Config| c.rpc.as_ref()?.hosts.as_ref().map(|vec| vec.join(",")),
             "--jsonrpc-hosts=[HOSTS]",
@@ -452,7 +475,7 @@ usage! {
 
             ARG arg_ws_apis: (String) = "web3,eth,pubsub,net,parity,parity_pubsub,traces,rpc", or |c: &Config| c.websockets.as_ref()?.apis.as_ref().map(|vec| vec.join(",")),
             "--ws-apis=[APIS]",
-            "Specify the JSON-RPC APIs available through the WebSockets interface using a comma-delimited list of API names. Possible names are: all, safe, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
+            "Specify the JSON-RPC APIs available through the WebSockets interface using a comma-delimited list of API names. Possible names are: all, safe, hbbft, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
 
             ARG arg_ws_origins: (String) = "parity://*,chrome-extension://*,moz-extension://*", or |c: &Config| c.websockets.as_ref()?.origins.as_ref().map(|vec| vec.join(",")),
             "--ws-origins=[URL]",
@@ -498,7 +521,7 @@ usage! {
 
             ARG arg_ipc_apis: (String) = "web3,eth,pubsub,net,parity,parity_pubsub,parity_accounts,traces,rpc", or |c: &Config| c.ipc.as_ref()?.apis.as_ref().map(|vec| vec.join(",")),
             "--ipc-apis=[APIS]",
-            "Specify custom API set available via JSON-RPC over IPC using a comma-delimited list of API names. Possible names are: all, safe, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
+            "Specify custom API set available via JSON-RPC over IPC using a comma-delimited list of API names. Possible names are: all, safe, hbbft, web3, net, eth, pubsub, personal, signer, parity, parity_pubsub, parity_accounts, parity_set, traces, rpc, secretstore. You can also disable a specific API by putting '-' in the front, example: all,-personal. 'safe' enables the following APIs: web3, net, eth, pubsub, parity, parity_pubsub, traces, rpc",
 
         ["Secret Store Options"]
             FLAG flag_no_secretstore: (bool) = false, or |c: &Config| c.secretstore.as_ref()?.disable.clone(),
@@ -694,6 +717,14 @@ usage! {
             "--engine-signer=[ADDRESS]",
             "Specify the address which should be used to sign consensus messages and issue blocks. Relevant only to non-PoW chains.",
 
+            ARG arg_engine_signer_url: (Option<String>) = None, or |c: &Config| c.mining.as_ref()?.engine_signer_url.clone(),
+            "--engine-signer-url=[URL]",
+            "Sign consensus messages of the --engine-signer account through an external signing service at URL, given as tcp://HOST:PORT or unix://PATH, instead of a local account.",
+
+            ARG arg_engine_decrypt_key: (Option<String>) = None, or |c: &Config| c.mining.as_ref()?.engine_decrypt_key.clone(),
+            "--engine-decrypt-key=[FILE]",
+            "Decrypt consensus key generation data with the hex-encoded secret key in FILE instead of through the --engine-signer-url service, for signing services that cannot decrypt.",
+
             ARG arg_tx_gas_limit: (Option<String>) = None, or |c: &Config| c.mining.as_ref()?.tx_gas_limit.clone(),
             "--tx-gas-limit=[GAS]",
             "Apply a limit of GAS as the maximum amount of gas a single transaction may have for it to be mined.",
@@ -959,6 +990,8 @@ struct SecretStore {
 struct Mining {
     author: Option<String>,
     engine_signer: Option<String>,
+    engine_signer_url: Option<String>,
+    engine_decrypt_key: Option<String>,
     force_sealing: Option<bool>,
     reseal_on_uncle: Option<bool>,
     reseal_on_txs: Option<String>,
@@ -1243,6 +1276,9 @@ mod tests {
                 cmd_restore: false,
                 cmd_tools: false,
                 cmd_tools_hash: false,
+                cmd_hbbft: false,
+                cmd_hbbft_share_proof: false,
+                cmd_hbbft_verify_share_proof: false,
                 cmd_db: false,
                 cmd_db_kill: false,
                 cmd_db_reset: false,
@@ -1258,6 +1294,8 @@ mod tests {
                 arg_snapshot_file: None,
                 arg_restore_file: None,
                 arg_tools_hash_file: None,
+                arg_hbbft_share_proof_file: None,
+                arg_hbbft_verify_share_proof_file: None,
 
                 arg_signer_sign_id: None,
                 arg_signer_reject_id: None,
@@ -1370,6 +1408,8 @@ mod tests {
                 // -- Sealing/Mining Options
                 arg_author: Some("0xdeadbeefcafe0000000000000000000000000001".into()),
                 arg_engine_signer: Some("0xdeadbeefcafe0000000000000000000000000001".into()),
+                arg_engine_signer_url: None,
+                arg_engine_decrypt_key: None,
                 flag_force_sealing: true,
                 arg_reseal_on_txs: "all".into(),
                 arg_reseal_min_period: 4000u64,
@@ -1575,6 +1615,8 @@ mod tests {
                 mining: Some(Mining {
                     author: Some("0xdeadbeefcafe0000000000000000000000000001".into()),
                     engine_signer: Some("0xdeadbeefcafe0000000000000000000000000001".into()),
+                    engine_signer_url: None,
+                    engine_decrypt_key: None,
                     force_sealing: Some(true),
                     reseal_on_txs: Some("all".into()),
                     reseal_on_uncle: None,
@@ -27,6 +27,7 @@ use ansi_term::Colour;
 use crypto::publickey::{Public, Secret};
 use ethcore::{
     client::VMType,
+    engines::remote_signer::Endpoint,
     miner::{stratum, MinerOptions},
     snapshot::SnapshotConfiguration,
     verification::queue::VerifierSettings,
@@ -53,6 +54,7 @@ use crate::{
         ResetBlockchain,
     },
     cache::CacheConfig,
+    hbbft::{HbbftCmd, ShareProof, VerifyShareProof},
     helpers::{
         parity_ipc_path, to_address, to_addresses, to_block_id, to_bootnodes, to_duration, to_mode,
         to_pending_set, to_price, to_queue_penalization, to_queue_strategy, to_u256,
@@ -107,6 +109,7 @@ pub enum Cmd {
     },
     Snapshot(SnapshotCommand),
     Hash(Option<String>),
+    Hbbft(HbbftCmd),
 }
 
 pub struct Execute {
@@ -205,6 +208,26 @@ impl Configuration {
             }
         } else if self.args.cmd_tools && self.args.cmd_tools_hash {
             Cmd::Hash(self.args.arg_tools_hash_file)
+        } else if self.args.cmd_hbbft && self.args.cmd_hbbft_share_proof {
+            Cmd::Hbbft(HbbftCmd::ShareProof(ShareProof {
+                spec,
+                cache_config,
+                dirs,
+                file_path: self.args.arg_hbbft_share_proof_file.clone(),
+                pruning,
+                pruning_history,
+                pruning_memory: self.args.arg_pruning_memory,
+                compaction,
+                fat_db,
+                tracing,
+                max_round_blocks_to_import: self.args.arg_max_round_blocks_to_import,
+                acc_conf: self.accounts_config()?,
+                engine_signer: self.engine_signer()?,
+            }))
+        } else if self.args.cmd_hbbft && self.args.cmd_hbbft_verify_share_proof {
+            Cmd::Hbbft(HbbftCmd::VerifyShareProof(VerifyShareProof {
+                file_path: self.args.arg_hbbft_verify_share_proof_file.clone(),
+            }))
         } else if self.args.cmd_db && self.args.cmd_db_reset {
             Cmd::Blockchain(BlockchainCmd::Reset(ResetBlockchain {
                 dirs,
@@ -454,6 +477,8 @@ impl Configuration {
             extra_data: self.extra_data()?,
             gas_range_target: (floor, ceil),
             engine_signer: self.engine_signer()?,
+            engine_signer_endpoint: self.engine_signer_endpoint()?,
+            engine_decrypt_key: self.engine_decrypt_key()?,
             work_notify: self.work_notify(),
             local_accounts: HashSet::from_iter(
                 to_addresses(&self.args.arg_tx_queue_locals)?.into_iter(),
@@ -471,6 +496,25 @@ impl Configuration {
         to_address(self.args.arg_engine_signer.clone())
     }
 
+    fn engine_signer_endpoint(&self) -> Result<Option<Endpoint>, String> {
+        match self.args.arg_engine_signer_url {
+            Some(_) if self.args.arg_engine_signer.is_none() => {
+                Err("--engine-signer-url requires the --engine-signer address.".into())
+            }
+            Some(ref url) => url.parse().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn engine_decrypt_key(&self) -> Result<Option<String>, String> {
+        match self.args.arg_engine_decrypt_key {
+            Some(_) if self.args.arg_engine_signer_url.is_none() => {
+                Err("--engine-decrypt-key requires the --engine-signer-url signing service.".into())
+            }
+            ref path => Ok(path.clone()),
+        }
+    }
+
     fn format(&self) -> Result<Option<DataFormat>, String> {
         match self
             .args
@@ -2040,4 +2084,52 @@ mod tests {
             _ => panic!("Should be Cmd::Run"),
         }
     }
+
+    #[test]
+    fn should_parse_engine_signer_url() {
+        let args = vec![
+            "openethereum",
+            "--engine-signer=0xdeadbeefcafe0000000000000000000000000001",
+            "--engine-signer-url=unix:///run/signer.sock",
+        ];
+        let conf = Configuration::parse_cli(&args).unwrap();
+        match conf.into_command().unwrap().cmd {
+            Cmd::Run(c) => assert_eq!(
+                c.miner_extras.engine_signer_endpoint,
+                Some(Endpoint::Unix("/run/signer.sock".into()))
+            ),
+            _ => panic!("Should be Cmd::Run"),
+        }
+
+        let args = vec!["openethereum", "--engine-signer-url=tcp://127.0.0.1:8551"];
+        let conf = Configuration::parse_cli(&args).unwrap();
+        assert!(conf.into_command().is_err());
+    }
+
+    #[test]
+    fn should_parse_engine_decrypt_key() {
+        let args = vec![
+            "openethereum",
+            "--engine-signer=0xdeadbeefcafe0000000000000000000000000001",
+            "--engine-signer-url=unix:///run/signer.sock",
+            "--engine-decrypt-key=/run/decrypt.key",
+        ];
+        let conf = Configuration::parse_cli(&args).unwrap();
+        match conf.into_command().unwrap().cmd {
+            Cmd::Run(c) => assert_eq!(
+                c.miner_extras.engine_decrypt_key,
+                Some("/run/decrypt.key".into())
+            ),
+            _ => panic!("Should be Cmd::Run"),
+        }
+
+        // Local accounts decrypt with their own key.
+        let args = vec![
+            "openethereum",
+            "--engine-signer=0xdeadbeefcafe0000000000000000000000000001",
+            "--engine-decrypt-key=/run/decrypt.key",
+        ];
+        let conf = Configuration::parse_cli(&args).unwrap();
+        assert!(conf.into_command().is_err());
+    }
 }
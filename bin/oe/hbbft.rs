@@ -0,0 +1,153 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Honey Badger BFT validator commands.
+
+use std::{fs, io, sync::Arc};
+
+use crate::{
+    account_utils,
+    blockchain::start_client,
+    cache::CacheConfig,
+    helpers::passwords_from_files,
+    params::{AccountsConfig, Pruning, SpecType, Switch},
+};
+use dir::Directories;
+use ethcore::{
+    client::DatabaseCompactionProfile,
+    engines::hbbft::api::{verify_key_share_proof, HbbftHandle, KeyShareProof},
+    miner::Author,
+};
+use ethereum_types::Address;
+
+#[derive(Debug, PartialEq)]
+pub enum HbbftCmd {
+    ShareProof(ShareProof),
+    VerifyShareProof(VerifyShareProof),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ShareProof {
+    pub spec: SpecType,
+    pub cache_config: CacheConfig,
+    pub dirs: Directories,
+    pub file_path: Option<String>,
+    pub pruning: Pruning,
+    pub pruning_history: u64,
+    pub pruning_memory: usize,
+    pub compaction: DatabaseCompactionProfile,
+    pub fat_db: Switch,
+    pub tracing: Switch,
+    pub max_round_blocks_to_import: usize,
+    pub acc_conf: AccountsConfig,
+    pub engine_signer: Address,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VerifyShareProof {
+    pub file_path: Option<String>,
+}
+
+pub fn execute(cmd: HbbftCmd) -> Result<String, String> {
+    match cmd {
+        HbbftCmd::ShareProof(proof_cmd) => execute_share_proof(proof_cmd),
+        HbbftCmd::VerifyShareProof(verify_cmd) => execute_verify_share_proof(verify_cmd),
+    }
+}
+
+fn execute_share_proof(cmd: ShareProof) -> Result<String, String> {
+    if cmd.engine_signer == Address::default() {
+        return Err("A key share proof requires the validator's --engine-signer.".into());
+    }
+
+    let spec = cmd.spec.spec(&cmd.dirs.cache)?;
+    let passwords = passwords_from_files(&cmd.acc_conf.password_files)?;
+    let account_provider = Arc::new(account_utils::prepare_account_provider(
+        &cmd.spec,
+        &cmd.dirs,
+        &spec.data_dir,
+        cmd.acc_conf,
+        &passwords,
+    )?);
+    let signer = match account_utils::miner_author(
+        &cmd.spec,
+        &cmd.dirs,
+        &account_provider,
+        cmd.engine_signer,
+        &passwords,
+    )? {
+        Some(Author::Sealer(signer)) => signer,
+        _ => return Err("The engine signer account is not available.".into()),
+    };
+
+    let service = start_client(
+        cmd.dirs,
+        cmd.spec,
+        cmd.pruning,
+        cmd.pruning_history,
+        cmd.pruning_memory,
+        cmd.tracing,
+        cmd.fat_db,
+        cmd.compaction,
+        cmd.cache_config,
+        false,
+        cmd.max_round_blocks_to_import,
+    )?;
+    let client = service.client();
+
+    let handle = HbbftHandle::from_engine(client.engine_arc())
+        .ok_or_else(|| "The chain does not use the HoneyBadgerBFT engine.".to_owned())?;
+    handle.set_signer(signer);
+    let proof = handle.key_share_proof().ok_or_else(|| {
+        format!(
+            "{} is not a validator of the current POSDAO epoch.",
+            cmd.engine_signer
+        )
+    })?;
+
+    let json = serde_json::to_string_pretty(&proof)
+        .map_err(|e| format!("Failed to serialize the key share proof: {}", e))?;
+    match cmd.file_path {
+        Some(f) => {
+            fs::write(&f, json).map_err(|_| format!("Cannot write to file given: {}", f))?;
+            Ok(format!(
+                "Key share proof for POSDAO epoch {} written to {}",
+                proof.posdao_epoch, f
+            ))
+        }
+        None => Ok(json),
+    }
+}
+
+fn execute_verify_share_proof(cmd: VerifyShareProof) -> Result<String, String> {
+    let proof: KeyShareProof = match cmd.file_path {
+        Some(f) => {
+            let file = fs::File::open(&f).map_err(|_| format!("Cannot open given file: {}", f))?;
+            serde_json::from_reader(file)
+        }
+        None => serde_json::from_reader(io::stdin()),
+    }
+    .map_err(|e| format!("Invalid key share proof: {}", e))?;
+
+    if verify_key_share_proof(&proof) {
+        Ok(format!(
+            "Valid key share proof of {} for POSDAO epoch {}.\nPublic key share: {:?}",
+            proof.node_id, proof.posdao_epoch, proof.public_key_share
+        ))
+    } else {
+        Err("The key share proof is invalid.".into())
+    }
+}
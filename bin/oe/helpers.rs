@@ -21,6 +21,7 @@ use crate::{
     sync::{self, validate_node_url},
     upgrade::{upgrade, upgrade_data_paths},
 };
+use crypto::publickey::{KeyPair, Secret};
 use dir::{helpers::replace_home, DatabaseDirectories};
 use ethcore::{
     client::{BlockId, ClientConfig, DatabaseCompactionProfile, Mode, VMType, VerifierType},
@@ -33,7 +34,8 @@ use std::{
     collections::HashSet,
     fs::File,
     io,
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Write},
+    str::FromStr,
     time::Duration,
 };
 
@@ -373,19 +375,36 @@ pub fn passwords_from_files(files: &[String]) -> Result<Vec<Password>, String> {
     Ok(passwords?.into_iter().flat_map(|x| x).collect())
 }
 
+/// Reads a key pair from a file containing its hex-encoded secret.
+pub fn keypair_from_file(path: &str) -> Result<KeyPair, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|_| {
+            format!(
+                "{} Unable to read key file. Ensure it exists and permissions are correct.",
+                path
+            )
+        })?;
+    Secret::from_str(contents.trim().trim_start_matches("0x"))
+        .and_then(KeyPair::from_secret)
+        .map_err(|e| format!("{} Invalid secret key: {}", path, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        join_set, password_from_file, to_address, to_addresses, to_block_id, to_bootnodes,
-        to_duration, to_mode, to_pending_set, to_price, to_u256,
+        join_set, keypair_from_file, password_from_file, to_address, to_addresses, to_block_id,
+        to_bootnodes, to_duration, to_mode, to_pending_set, to_price, to_u256,
     };
+    use crypto::publickey::Secret;
     use ethcore::{
         client::{BlockId, Mode},
         miner::PendingSet,
     };
     use ethereum_types::U256;
     use ethkey::Password;
-    use std::{collections::HashSet, fs::File, io::Write, time::Duration};
+    use std::{collections::HashSet, fs::File, io::Write, str::FromStr, time::Duration};
     use tempdir::TempDir;
 
     #[test]
@@ -539,6 +558,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_keypair_from_file() {
+        let tempdir = TempDir::new("").unwrap();
+        let path = tempdir.path().join("file");
+        let mut file = File::create(path.as_path()).unwrap();
+        file.write_all(b"0x17d08f5fe8c77af811caa0c9a187e668ce3b74a99acc3f6d976f075fa8e0be55\n")
+            .unwrap();
+        assert_eq!(
+            keypair_from_file(path.to_str().unwrap()).unwrap().secret(),
+            &Secret::from_str("17d08f5fe8c77af811caa0c9a187e668ce3b74a99acc3f6d976f075fa8e0be55")
+                .unwrap()
+        );
+
+        File::create(path.as_path())
+            .unwrap()
+            .write_all(b"not a key")
+            .unwrap();
+        assert!(keypair_from_file(path.to_str().unwrap()).is_err());
+    }
+
     #[test]
     fn test_password_multiline() {
         let tempdir = TempDir::new("").unwrap();
@@ -97,6 +97,7 @@ mod cache;
 mod cli;
 mod configuration;
 mod db;
+mod hbbft;
 mod helpers;
 mod informant;
 mod metrics;
@@ -225,6 +226,9 @@ fn execute(command: Execute, logger: Arc<RotatingLogger>) -> Result<ExecutionAct
         Cmd::Snapshot(snapshot_cmd) => {
             snapshot::execute(snapshot_cmd).map(|s| ExecutionAction::Instant(Some(s)))
         }
+        Cmd::Hbbft(hbbft_cmd) => {
+            hbbft::execute(hbbft_cmd).map(|s| ExecutionAction::Instant(Some(s)))
+        }
     }
 }
 
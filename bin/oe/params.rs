@@ -25,6 +25,7 @@ use crate::{
 };
 use ethcore::{
     client::Mode,
+    engines::remote_signer::Endpoint,
     ethereum,
     spec::{Spec, SpecParams},
 };
@@ -291,6 +292,8 @@ impl GasPricerConfig {
 pub struct MinerExtras {
     pub author: Address,
     pub engine_signer: Address,
+    pub engine_signer_endpoint: Option<Endpoint>,
+    pub engine_decrypt_key: Option<String>,
     pub extra_data: Vec<u8>,
     pub gas_range_target: (U256, U256),
     pub work_notify: Vec<String>,
@@ -302,6 +305,8 @@ impl Default for MinerExtras {
         MinerExtras {
             author: Default::default(),
             engine_signer: Default::default(),
+            engine_signer_endpoint: None,
+            engine_decrypt_key: None,
             extra_data: version_data(),
             gas_range_target: (8_000_000.into(), 10_000_000.into()),
             work_notify: Default::default(),
@@ -71,6 +71,9 @@ pub enum Api {
     /// Geth-compatible (best-effort) debug API (Potentially UNSAFE)
     /// NOTE We don't aim to support all methods, only the ones that are useful.
     Debug,
+    /// Honey Badger BFT consensus status (Safe). Methods altering the consensus state are part
+    /// of ParitySet.
+    Hbbft,
 }
 
 impl FromStr for Api {
@@ -82,6 +85,7 @@ impl FromStr for Api {
         match s {
             "debug" => Ok(Debug),
             "eth" => Ok(Eth),
+            "hbbft" => Ok(Hbbft),
             "net" => Ok(Net),
             "parity" => Ok(Parity),
             "parity_accounts" => Ok(ParityAccounts),
@@ -163,6 +167,7 @@ fn to_modules(apis: &HashSet<Api>) -> BTreeMap<String, String> {
             Api::Debug => ("debug", "1.0"),
             Api::Eth => ("eth", "1.0"),
             Api::EthPubSub => ("pubsub", "1.0"),
+            Api::Hbbft => ("hbbft", "1.0"),
             Api::Net => ("net", "1.0"),
             Api::Parity => ("parity", "1.0"),
             Api::ParityAccounts => ("parity_accounts", "1.0"),
@@ -270,6 +275,9 @@ impl FullDependencies {
                 Api::Net => {
                     handler.extend_with(NetClient::new(&self.sync).to_delegate());
                 }
+                Api::Hbbft => {
+                    handler.extend_with(HbbftClient::new(self.client.engine_arc()).to_delegate());
+                }
                 Api::Eth => {
                     let client = EthClient::new(
                         &self.client,
@@ -408,6 +416,9 @@ impl FullDependencies {
                     handler.extend_with(
                         ParitySetAccountsClient::new(&self.accounts, &self.miner).to_delegate(),
                     );
+                    handler.extend_with(
+                        HbbftSetClient::new(self.client.engine_arc(), &self.miner).to_delegate(),
+                    );
                 }
                 Api::Traces => handler.extend_with(TracesClient::new(&self.client).to_delegate()),
                 Api::Rpc => {
@@ -474,6 +485,7 @@ impl ApiSet {
             }
             ApiSet::All => {
                 public_list.insert(Api::Debug);
+                public_list.insert(Api::Hbbft);
                 public_list.insert(Api::Traces);
                 public_list.insert(Api::ParityPubSub);
                 public_list.insert(Api::ParityAccounts);
@@ -507,6 +519,7 @@ mod test {
         assert_eq!(Api::Web3, "web3".parse().unwrap());
         assert_eq!(Api::Net, "net".parse().unwrap());
         assert_eq!(Api::Eth, "eth".parse().unwrap());
+        assert_eq!(Api::Hbbft, "hbbft".parse().unwrap());
         assert_eq!(Api::EthPubSub, "pubsub".parse().unwrap());
         assert_eq!(Api::Personal, "personal".parse().unwrap());
         assert_eq!(Api::Signer, "signer".parse().unwrap());
@@ -590,6 +603,7 @@ mod test {
                     Api::Signer,
                     Api::Personal,
                     Api::Debug,
+                    Api::Hbbft,
                 ]
                 .into_iter()
                 .collect()
@@ -616,6 +630,7 @@ mod test {
                     Api::ParitySet,
                     Api::Signer,
                     Api::Debug,
+                    Api::Hbbft,
                 ]
                 .into_iter()
                 .collect()
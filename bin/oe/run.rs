@@ -16,6 +16,7 @@
 
 use std::{
     any::Any,
+    collections::BTreeSet,
     str::FromStr,
     sync::{atomic, Arc, Weak},
     thread,
@@ -26,7 +27,7 @@ use crate::{
     account_utils,
     cache::CacheConfig,
     db,
-    helpers::{execute_upgrades, passwords_from_files, to_client_config},
+    helpers::{execute_upgrades, keypair_from_file, passwords_from_files, to_client_config},
     informant::{FullNodeInformantData, Informant},
     metrics::{start_prometheus_metrics, MetricsConfiguration},
     miner::{external::ExternalMiner, work_notify::WorkPoster},
@@ -36,7 +37,7 @@ use crate::{
         GasPricerConfig, MinerExtras, Pruning, SpecType, Switch,
     },
     rpc, rpc_apis, secretstore, signer,
-    sync::{self, SyncConfig, SyncProvider},
+    sync::{self, ManageNetwork, SyncConfig, SyncProvider},
     user_defaults::UserDefaults,
 };
 use ansi_term::Colour;
@@ -45,13 +46,14 @@ use ethcore::{
     client::{
         BlockChainClient, BlockInfo, ChainSyncing, Client, DatabaseCompactionProfile, Mode, VMType,
     },
+    engines::{hbbft::api::HbbftHandle, remote_signer::RemoteSigner, signer::with_decrypt_key},
     miner::{self, stratum, Miner, MinerOptions, MinerService},
     snapshot::{self, SnapshotConfiguration},
     verification::queue::VerifierSettings,
 };
 use ethcore_logger::{Config as LogConfig, RotatingLogger};
 use ethcore_service::ClientService;
-use ethereum_types::{H256, U64};
+use ethereum_types::{H256, H512, U64};
 use journaldb::Algorithm;
 use jsonrpc_core;
 use node_filter::NodeFilter;
@@ -143,6 +145,7 @@ impl crate::local_store::NodeInfo for FullNodeInfo {
 
 struct SyncProviderWrapper {
     sync_provider: Weak<dyn SyncProvider>,
+    manage_network: Weak<dyn ManageNetwork>,
     client: Weak<Client>,
 }
 
@@ -160,6 +163,31 @@ impl ChainSyncing for SyncProviderWrapper {
             None => true,
         }
     }
+
+    fn connected_peers(&self) -> Option<BTreeSet<H512>> {
+        self.sync_provider.upgrade().map(|sync_arc| {
+            sync_arc
+                .peers()
+                .into_iter()
+                .filter_map(|peer| peer.id)
+                .filter_map(|id| H512::from_str(&id).ok())
+                .collect()
+        })
+    }
+
+    fn add_reserved_peer(&self, enode: String) -> Result<(), String> {
+        match self.manage_network.upgrade() {
+            Some(manage_network) => manage_network.add_reserved_peer(enode),
+            None => Err("The network has been shut down.".into()),
+        }
+    }
+
+    fn remove_reserved_peer(&self, enode: String) -> Result<(), String> {
+        match self.manage_network.upgrade() {
+            Some(manage_network) => manage_network.remove_reserved_peer(enode),
+            None => Err("The network has been shut down.".into()),
+        }
+    }
 }
 
 /// Executes the given run command.
@@ -257,6 +285,7 @@ pub fn execute(cmd: RunCmd, logger: Arc<RotatingLogger>) -> Result<RunningClient
     }
 
     sync_config.fork_block = spec.fork_block();
+    sync_config.disable_peers_sending_invalid_blocks = spec.engine.invalid_blocks_are_peer_faults();
     let mut warp_sync = spec.engine.supports_warp() && cmd.warp_sync;
     if warp_sync {
         // Logging is not initialized yet, so we print directly to stderr
@@ -322,13 +351,27 @@ pub fn execute(cmd: RunCmd, logger: Arc<RotatingLogger>) -> Result<RunningClient
 
     let engine_signer = cmd.miner_extras.engine_signer;
     if engine_signer != Default::default() {
-        if let Some(author) = account_utils::miner_author(
-            &cmd.spec,
-            &cmd.dirs,
-            &account_provider,
-            engine_signer,
-            &passwords,
-        )? {
+        let author = match cmd.miner_extras.engine_signer_endpoint {
+            Some(endpoint) => {
+                let signer = RemoteSigner::connect(endpoint, engine_signer)
+                    .map_err(|e| format!("Consensus signer unavailable: {}", e))?;
+                match cmd.miner_extras.engine_decrypt_key {
+                    Some(ref path) => Some(miner::Author::Sealer(with_decrypt_key(
+                        Box::new(signer),
+                        keypair_from_file(path)?,
+                    ))),
+                    None => Some(miner::Author::Sealer(Box::new(signer))),
+                }
+            }
+            None => account_utils::miner_author(
+                &cmd.spec,
+                &cmd.dirs,
+                &account_provider,
+                engine_signer,
+                &passwords,
+            )?,
+        };
+        if let Some(author) = author {
             miner.set_author(author);
         }
     }
@@ -388,8 +431,19 @@ pub fn execute(cmd: RunCmd, logger: Arc<RotatingLogger>) -> Result<RunningClient
 
     // take handle to client
     let client = service.client();
-    // Update miners block gas limit
-    miner.update_transaction_queue_limits(*client.best_block_header().gas_limit());
+    // Announce our version to the hbbft feature registry
+    if let Some(hbbft) = HbbftHandle::from_engine(client.engine_arc()) {
+        hbbft.set_client_version(version());
+        let enodes: Vec<String> = net_conf
+            .reserved_nodes
+            .iter()
+            .chain(&net_conf.boot_nodes)
+            .cloned()
+            .collect();
+        hbbft.set_configured_enodes(&enodes);
+    }
+    // Update miners block gas limit and minimal gas price
+    miner.update_transaction_queue_limits(&client.best_block_header());
 
     let connection_filter = connection_filter_address.map(|a| {
         Arc::new(NodeFilter::new(
@@ -613,6 +667,7 @@ pub fn execute(cmd: RunCmd, logger: Arc<RotatingLogger>) -> Result<RunningClient
     // is fully configured.
     client.set_sync_provider(Box::new(SyncProviderWrapper {
         sync_provider: Arc::downgrade(&sync_provider),
+        manage_network: Arc::downgrade(&manage_network),
         client: Arc::downgrade(&client),
     }));
 
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
 
-use account::{Aes128Ctr, Cipher, Kdf, Pbkdf2, Prf};
+use account::{Aes128Ctr, Cipher, Kdf, Pbkdf2, Prf, Scrypt};
 use crypto::{self, publickey::Secret, Keccak256};
 use ethkey::Password;
 use json;
@@ -82,6 +82,32 @@ impl Crypto {
         Crypto::with_plain(secret.as_bytes(), password, iterations)
     }
 
+    /// Encrypt account secret, deriving the key with scrypt
+    pub fn with_secret_scrypt(
+        secret: &Secret,
+        password: &Password,
+        n: u32,
+        p: u32,
+        r: u32,
+    ) -> Result<Self, crypto::Error> {
+        let salt: [u8; 32] = Random::random();
+        let (derived_left_bits, derived_right_bits) =
+            crypto::scrypt::derive_key(password.as_bytes(), &salt, n, p, r)?;
+        let kdf = Kdf::Scrypt(Scrypt {
+            dklen: crypto::KEY_LENGTH as u32,
+            p,
+            n,
+            r,
+            salt: salt.to_vec(),
+        });
+        Crypto::encrypt(
+            secret.as_bytes(),
+            &derived_left_bits,
+            &derived_right_bits,
+            kdf,
+        )
+    }
+
     /// Encrypt custom plain data
     pub fn with_plain(
         plain: &[u8],
@@ -89,12 +115,28 @@ impl Crypto {
         iterations: NonZeroU32,
     ) -> Result<Self, crypto::Error> {
         let salt: [u8; 32] = Random::random();
-        let iv: [u8; 16] = Random::random();
 
         // two parts of derived key
         // DK = [ DK[0..15] DK[16..31] ] = [derived_left_bits, derived_right_bits]
         let (derived_left_bits, derived_right_bits) =
             crypto::derive_key_iterations(password.as_bytes(), &salt, iterations.get());
+        let kdf = Kdf::Pbkdf2(Pbkdf2 {
+            dklen: crypto::KEY_LENGTH as u32,
+            salt: salt.to_vec(),
+            c: iterations,
+            prf: Prf::HmacSha256,
+        });
+        Crypto::encrypt(plain, &derived_left_bits, &derived_right_bits, kdf)
+    }
+
+    /// Encrypt plain data with the two parts of a key derived with the given parameters
+    fn encrypt(
+        plain: &[u8],
+        derived_left_bits: &[u8],
+        derived_right_bits: &[u8],
+        kdf: Kdf,
+    ) -> Result<Self, crypto::Error> {
+        let iv: [u8; 16] = Random::random();
 
         // preallocated (on-stack in case of `Secret`) buffer to hold cipher
         // length = length(plain) as we are using CTR-approach
@@ -102,20 +144,15 @@ impl Crypto {
         let mut ciphertext: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; plain_len]);
 
         // aes-128-ctr with initial vector of iv
-        crypto::aes::encrypt_128_ctr(&derived_left_bits, &iv, plain, &mut *ciphertext)?;
+        crypto::aes::encrypt_128_ctr(derived_left_bits, &iv, plain, &mut *ciphertext)?;
 
         // KECCAK(DK[16..31] ++ <ciphertext>), where DK[16..31] - derived_right_bits
-        let mac = crypto::derive_mac(&derived_right_bits, &*ciphertext).keccak256();
+        let mac = crypto::derive_mac(derived_right_bits, &*ciphertext).keccak256();
 
         Ok(Crypto {
             cipher: Cipher::Aes128Ctr(Aes128Ctr { iv: iv }),
             ciphertext: ciphertext.into_vec(),
-            kdf: Kdf::Pbkdf2(Pbkdf2 {
-                dklen: crypto::KEY_LENGTH as u32,
-                salt: salt.to_vec(),
-                c: iterations,
-                prf: Prf::HmacSha256,
-            }),
+            kdf: kdf,
             mac: mac,
         })
     }
@@ -194,6 +231,15 @@ mod tests {
         assert_eq!(keypair.secret(), &secret);
     }
 
+    #[test]
+    fn crypto_with_secret_scrypt_create() {
+        let keypair = Random.generate();
+        let passwd = "this is sparta".into();
+        let crypto = Crypto::with_secret_scrypt(keypair.secret(), &passwd, 1024, 1, 8).unwrap();
+        let secret = crypto.secret(&passwd).unwrap();
+        assert_eq!(keypair.secret(), &secret);
+    }
+
     #[test]
     fn crypto_with_secret_invalid_password() {
         let keypair = Random.generate();
@@ -78,6 +78,25 @@ impl SafeAccount {
         })
     }
 
+    /// Create a new account whose secret is already encrypted
+    pub fn with_crypto(
+        keypair: &KeyPair,
+        id: [u8; 16],
+        crypto: Crypto,
+        name: String,
+        meta: String,
+    ) -> Self {
+        SafeAccount {
+            id: id,
+            version: Version::V3,
+            crypto: crypto,
+            address: keypair.address(),
+            filename: None,
+            name: name,
+            meta: meta,
+        }
+    }
+
     /// Create a new `SafeAccount` from the given `json`; if it was read from a
     /// file, the `filename` should be `Some` name. If it is as yet anonymous, then it
     /// can be left `None`.
@@ -36,7 +36,7 @@ pub mod verifier;
 mod tests;
 
 pub use self::{
-    queue::{Status as QueueStatus, TransactionQueue},
+    queue::{Snapshot as QueueSnapshot, Status as QueueStatus, TransactionQueue},
     txpool::{Options, VerifiedTransaction as PoolVerifiedTransaction},
 };
 
@@ -27,7 +27,7 @@ use std::{
 };
 
 use ethereum_types::{Address, H256, U256};
-use parking_lot::RwLock;
+use parking_lot::{RwLock, RwLockWriteGuard};
 use txpool::{self, Verifier};
 use types::transaction;
 
@@ -218,6 +218,16 @@ impl RecentlyRejected {
 /// Minimal size of rejection cache, by default it's equal to queue size.
 const MIN_REJECTED_CACHE_SIZE: usize = 2048;
 
+/// A consistent view of all transactions in the queue.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Identifies the state of the queue the snapshot was taken of. It increases with every
+    /// modification, so equal sequence numbers mean equal transactions.
+    pub sequence: usize,
+    /// All transactions in the queue, without explicit ordering.
+    pub transactions: Vec<Arc<pool::VerifiedTransaction>>,
+}
+
 /// Ethereum Transaction Queue
 ///
 /// Responsible for:
@@ -227,6 +237,7 @@ const MIN_REJECTED_CACHE_SIZE: usize = 2048;
 #[derive(Debug)]
 pub struct TransactionQueue {
     insertion_id: Arc<AtomicUsize>,
+    modification_id: AtomicUsize,
     pool: RwLock<Pool>,
     options: RwLock<verifier::Options>,
     cached_pending: RwLock<CachedPending>,
@@ -243,6 +254,7 @@ impl TransactionQueue {
         let max_count = limits.max_count;
         TransactionQueue {
             insertion_id: Default::default(),
+            modification_id: Default::default(),
             pool: RwLock::new(txpool::Pool::new(
                 Default::default(),
                 scoring::NonceAndGasPrice(strategy),
@@ -269,7 +281,7 @@ impl TransactionQueue {
     where
         F: Fn(&H256) -> bool + Send + Sync + 'static,
     {
-        self.pool.write().listener_mut().0.set_in_chain_checker(f)
+        self.pool_mut().listener_mut().0.set_in_chain_checker(f)
     }
 
     // t_nb 10.2
@@ -327,7 +339,7 @@ impl TransactionQueue {
 				let imported = verifier
 					.verify_transaction(transaction)
 					.and_then(|verified| {
-						self.pool.write().import(verified, &mut replace).map_err(convert_error)
+						self.pool_mut().import(verified, &mut replace).map_err(convert_error)
 					});
 
 				match imported {
@@ -341,7 +353,7 @@ impl TransactionQueue {
 			.collect::<Vec<_>>();
 
         // Notify about imported transactions.
-        (self.pool.write().listener_mut().1).0.notify();
+        (self.pool_mut().listener_mut().1).0.notify();
 
         if results.iter().any(|r| r.is_ok()) {
             self.cached_pending.write().clear();
@@ -356,6 +368,17 @@ impl TransactionQueue {
         self.pool.read().unordered_pending(ready).collect()
     }
 
+    /// Returns all transactions in the queue together with the sequence number of the queue's
+    /// state, read atomically.
+    pub fn snapshot(&self) -> Snapshot {
+        let ready = |_tx: &pool::VerifiedTransaction| txpool::Readiness::Ready;
+        let pool = self.pool.read();
+        Snapshot {
+            sequence: self.modification_id.load(atomic::Ordering::SeqCst),
+            transactions: pool.unordered_pending(ready).collect(),
+        }
+    }
+
     /// Returns all transaction hashes in the queue without explicit ordering.
     pub fn all_transaction_hashes(&self) -> Vec<H256> {
         let ready = |_tx: &pool::VerifiedTransaction| txpool::Readiness::Ready;
@@ -546,7 +569,7 @@ impl TransactionQueue {
         for chunk in senders.chunks(CULL_SENDERS_CHUNK) {
             trace_time!("pool::cull::chunk");
             let state_readiness = ready::State::new(client.clone(), stale_id, nonce_cap);
-            removed += self.pool.write().cull(Some(chunk), state_readiness);
+            removed += self.pool_mut().cull(Some(chunk), state_readiness);
         }
         debug!(target: "txqueue", "Removed {} stalled transactions. {}", removed, self.status());
     }
@@ -588,7 +611,7 @@ impl TransactionQueue {
         is_invalid: bool,
     ) -> Vec<Option<Arc<pool::VerifiedTransaction>>> {
         let results = {
-            let mut pool = self.pool.write();
+            let mut pool = self.pool_mut();
 
             hashes
                 .into_iter()
@@ -605,12 +628,12 @@ impl TransactionQueue {
 
     /// Clear the entire pool.
     pub fn clear(&self) {
-        self.pool.write().clear();
+        self.pool_mut().clear();
     }
 
     /// Penalize given senders.
     pub fn penalize<'a, T: IntoIterator<Item = &'a Address>>(&self, senders: T) {
-        let mut pool = self.pool.write();
+        let mut pool = self.pool_mut();
         for sender in senders {
             pool.update_scores(sender, ());
         }
@@ -663,10 +686,20 @@ impl TransactionQueue {
 
     /// Add a callback to be notified about all transactions entering the pool.
     pub fn add_listener(&self, f: Box<dyn Fn(&[H256]) + Send + Sync>) {
-        let mut pool = self.pool.write();
+        let mut pool = self.pool_mut();
         (pool.listener_mut().1).0.add(f);
     }
 
+    /// Locks the pool for modification and advances the snapshot sequence number.
+    ///
+    /// The number is advanced while holding the lock, so snapshots never see a sequence number
+    /// that does not match the transactions they contain.
+    fn pool_mut(&self) -> RwLockWriteGuard<Pool> {
+        let pool = self.pool.write();
+        self.modification_id.fetch_add(1, atomic::Ordering::SeqCst);
+        pool
+    }
+
     /// Check if pending set is cached.
     #[cfg(test)]
     pub fn is_pending_cached(&self) -> bool {
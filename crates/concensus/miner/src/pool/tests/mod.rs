@@ -1215,3 +1215,31 @@ fn should_not_reject_early_in_case_gas_price_is_less_than_min_effective() {
     assert_eq!(txq.status().status.transaction_count, 2);
     assert!(client.was_verification_triggered());
 }
+
+#[test]
+fn should_advance_snapshot_sequence_on_modification() {
+    // given
+    let txq = new_queue();
+    let (tx1, tx2) = Tx::default().signed_pair();
+    let hash2 = tx2.hash();
+    let initial = txq.snapshot();
+    assert!(initial.transactions.is_empty());
+    assert_eq!(txq.snapshot().sequence, initial.sequence);
+
+    // when
+    let res = txq.import(TestClient::new(), vec![tx1, tx2].local());
+    assert_eq!(res, vec![Ok(()), Ok(())]);
+    let imported = txq.snapshot();
+
+    // then
+    assert!(imported.sequence > initial.sequence);
+    assert_eq!(imported.transactions.len(), 2);
+
+    // when
+    txq.remove(vec![&hash2], true);
+    let removed = txq.snapshot();
+
+    // then
+    assert!(removed.sequence > imported.sequence);
+    assert_eq!(removed.transactions.len(), 1);
+}
@@ -0,0 +1,53 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Measures the initialization of the key generation from the Parts and Acks of the validators,
+//! with concurrent and with sequential decoding.
+
+#[macro_use]
+extern crate criterion;
+extern crate ethcore;
+
+use criterion::Criterion;
+use ethcore::engines::hbbft::keygen_bench::KeygenFixture;
+use std::rc::Rc;
+
+fn bench_keygen(c: &mut Criterion, num_validators: usize) {
+    let fixture = Rc::new(KeygenFixture::new(num_validators));
+    assert!(fixture.initialize());
+    assert!(fixture.initialize_sequentially());
+
+    let concurrent = fixture.clone();
+    c.bench_function(
+        &format!("synckeygen_initialization_{}", num_validators),
+        move |b| b.iter(|| concurrent.initialize()),
+    );
+    c.bench_function(
+        &format!("synckeygen_initialization_sequential_{}", num_validators),
+        move |b| b.iter(|| fixture.initialize_sequentially()),
+    );
+}
+
+fn keygen_25(c: &mut Criterion) {
+    bench_keygen(c, 25);
+}
+
+fn keygen_50(c: &mut Criterion) {
+    bench_keygen(c, 50);
+}
+
+criterion_group!(hbbft_keygen, keygen_25, keygen_50);
+criterion_main!(hbbft_keygen);
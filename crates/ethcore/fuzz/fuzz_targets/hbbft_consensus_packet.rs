@@ -0,0 +1,12 @@
+//! Feeds arbitrary packets to the decoding of hbbft consensus messages received from peers.
+//!
+//! Run with `cargo fuzz run hbbft_consensus_packet` from `crates/ethcore`.
+
+#![no_main]
+
+use ethcore::engines::hbbft::decode_packet;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|packet: &[u8]| {
+    let _ = decode_packet(packet);
+});
@@ -16,7 +16,7 @@
 
 use std::{
     cmp,
-    collections::{BTreeMap, HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
     convert::TryFrom,
     io::{BufRead, BufReader},
     str::{from_utf8, FromStr},
@@ -34,7 +34,7 @@ use blockchain::{
 use bytes::{Bytes, ToPretty};
 use call_contract::CallContract;
 use db::{DBTransaction, DBValue, KeyValueDB};
-use ethcore_miner::pool::VerifiedTransaction;
+use ethcore_miner::pool::{QueueSnapshot, VerifiedTransaction};
 use ethereum_types::{Address, H256, H264, H512, U256};
 use hash::keccak;
 use itertools::Itertools;
@@ -1083,6 +1083,7 @@ impl Client {
 
     /// signals shutdown of application. We do cleanup here.
     pub fn shutdown(&self) {
+        self.engine.on_shutdown();
         let mut abe = self.queued_ancient_blocks_executer.lock();
         if abe.is_some() {
             abe.as_mut().unwrap().end()
@@ -1117,6 +1118,11 @@ impl Client {
         &*self.engine
     }
 
+    /// Returns a shared handle to the engine.
+    pub fn engine_arc(&self) -> Arc<dyn EthEngine> {
+        self.engine.clone()
+    }
+
     fn notify<F>(&self, f: F)
     where
         F: Fn(&dyn ChainNotify),
@@ -2748,6 +2754,13 @@ impl BlockChainClient for Client {
         }
     }
 
+    fn connected_peers(&self) -> Option<BTreeSet<H512>> {
+        self.sync_provider
+            .lock()
+            .as_ref()
+            .and_then(|sync_provider| sync_provider.connected_peers())
+    }
+
     fn next_nonce(&self, address: &Address) -> U256 {
         self.importer.miner.next_nonce(self, address)
     }
@@ -3087,6 +3100,10 @@ impl super::traits::EngineClient for Client {
         self.importer.miner.queued_transactions()
     }
 
+    fn queued_transactions_snapshot(&self) -> QueueSnapshot {
+        self.importer.miner.queued_transactions_snapshot()
+    }
+
     fn create_pending_block_at(
         &self,
         txns: Vec<SignedTransaction>,
@@ -3097,6 +3114,41 @@ impl super::traits::EngineClient for Client {
             .miner
             .create_pending_block_at(self, txns, timestamp, block_number)
     }
+
+    fn engine_data(&self, key: &[u8]) -> Option<Bytes> {
+        match self.db.read().key_value().get(::db::COL_NODE_INFO, key) {
+            Ok(value) => value.map(|v| v.to_vec()),
+            Err(e) => {
+                warn!(target: "client", "Failed to read engine data: {}", e);
+                None
+            }
+        }
+    }
+
+    fn set_engine_data(&self, key: &[u8], value: Option<Bytes>) {
+        let mut batch = DBTransaction::new();
+        match value {
+            Some(value) => batch.put_vec(::db::COL_NODE_INFO, key, value),
+            None => batch.delete(::db::COL_NODE_INFO, key),
+        }
+        if let Err(e) = self.db.read().key_value().write(batch) {
+            warn!(target: "client", "Failed to write engine data: {}", e);
+        }
+    }
+
+    fn add_reserved_peer(&self, enode: String) -> Result<(), String> {
+        match &*self.sync_provider.lock() {
+            Some(sync_provider) => sync_provider.add_reserved_peer(enode),
+            None => Err("The network is not started yet.".into()),
+        }
+    }
+
+    fn remove_reserved_peer(&self, enode: String) -> Result<(), String> {
+        match &*self.sync_provider.lock() {
+            Some(sync_provider) => sync_provider.remove_reserved_peer(enode),
+            None => Err("The network is not started yet.".into()),
+        }
+    }
 }
 
 impl ProvingBlockChainClient for Client {
@@ -3522,6 +3574,9 @@ impl PrometheusMetrics for Client {
             queue.verifying_queue_size as i64,
         );
 
+        // engine info
+        self.engine.prometheus_metrics(r);
+
         // database info
         self.db.read().key_value().prometheus_metrics(r);
     }
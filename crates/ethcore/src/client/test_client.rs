@@ -17,7 +17,7 @@
 //! Test client.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     str::FromStr,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrder},
@@ -29,7 +29,7 @@ use blockchain::{BlockReceipts, TreeRoute};
 use bytes::Bytes;
 use crypto::publickey::{Generator, Random};
 use db::{COL_STATE, NUM_COLUMNS};
-use ethcore_miner::pool::VerifiedTransaction;
+use ethcore_miner::pool::{QueueSnapshot, VerifiedTransaction};
 use ethereum_types::{Address, H256, H512, U256};
 use ethtrie;
 use hash::keccak;
@@ -127,6 +127,10 @@ pub struct TestBlockChainClient {
     pub history: RwLock<Option<u64>>,
     /// Is disabled
     pub disabled: AtomicBool,
+    /// Engine-specific persisted data.
+    pub engine_data: RwLock<HashMap<Bytes, Bytes>>,
+    /// Reserved peers.
+    pub reserved_peers: RwLock<BTreeSet<String>>,
 }
 
 /// Used for generating test client blocks.
@@ -196,6 +200,8 @@ impl TestBlockChainClient {
             history: RwLock::new(None),
             disabled: AtomicBool::new(false),
             error_on_logs: RwLock::new(None),
+            engine_data: RwLock::new(HashMap::new()),
+            reserved_peers: RwLock::new(BTreeSet::new()),
         };
 
         // insert genesis hash.
@@ -1085,6 +1091,10 @@ impl BlockChainClient for TestBlockChainClient {
         false
     }
 
+    fn connected_peers(&self) -> Option<BTreeSet<H512>> {
+        None
+    }
+
     fn next_nonce(&self, address: &Address) -> U256 {
         self.miner.next_nonce(self, address)
     }
@@ -1176,6 +1186,10 @@ impl super::traits::EngineClient for TestBlockChainClient {
         self.miner.queued_transactions()
     }
 
+    fn queued_transactions_snapshot(&self) -> QueueSnapshot {
+        self.miner.queued_transactions_snapshot()
+    }
+
     fn create_pending_block_at(
         &self,
         txns: Vec<SignedTransaction>,
@@ -1185,6 +1199,27 @@ impl super::traits::EngineClient for TestBlockChainClient {
         self.miner
             .create_pending_block_at(self, txns, timestamp, block_number)
     }
+
+    fn engine_data(&self, key: &[u8]) -> Option<Bytes> {
+        self.engine_data.read().get(key).cloned()
+    }
+
+    fn set_engine_data(&self, key: &[u8], value: Option<Bytes>) {
+        match value {
+            Some(value) => self.engine_data.write().insert(key.to_vec(), value),
+            None => self.engine_data.write().remove(key),
+        };
+    }
+
+    fn add_reserved_peer(&self, enode: String) -> Result<(), String> {
+        self.reserved_peers.write().insert(enode);
+        Ok(())
+    }
+
+    fn remove_reserved_peer(&self, enode: String) -> Result<(), String> {
+        self.reserved_peers.write().remove(&enode);
+        Ok(())
+    }
 }
 
 impl PrometheusMetrics for TestBlockChainClient {
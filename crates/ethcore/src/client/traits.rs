@@ -16,12 +16,15 @@
 
 //! Traits implemented by client.
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use blockchain::{BlockReceipts, TreeRoute};
 use bytes::Bytes;
 use call_contract::{CallContract, RegistryInfo};
-use ethcore_miner::pool::VerifiedTransaction;
+use ethcore_miner::pool::{QueueSnapshot, VerifiedTransaction};
 use ethereum_types::{Address, H256, H512, U256};
 use evm::Schedule;
 use itertools::Itertools;
@@ -212,6 +215,21 @@ pub trait EngineInfo {
 pub trait ChainSyncing: Send + Sync {
     /// are we in the middle of a major sync?
     fn is_major_syncing(&self) -> bool;
+
+    /// Node ids of the currently connected peers, if known.
+    fn connected_peers(&self) -> Option<BTreeSet<H512>> {
+        None
+    }
+
+    /// Reserves a connection slot for the given enode and connects to it.
+    fn add_reserved_peer(&self, _enode: String) -> Result<(), String> {
+        Err("Reserved peers are not supported.".into())
+    }
+
+    /// Removes the reservation of the given enode.
+    fn remove_reserved_peer(&self, _enode: String) -> Result<(), String> {
+        Err("Reserved peers are not supported.".into())
+    }
 }
 
 /// IO operations that should off-load heavy work to another thread.
@@ -456,6 +474,9 @@ pub trait BlockChainClient:
     /// Returns true if the chain is currently syncing.
     fn is_major_syncing(&self) -> bool;
 
+    /// Returns the node ids of the currently connected peers, if known.
+    fn connected_peers(&self) -> Option<BTreeSet<H512>>;
+
     /// Returns the next nonce for the given address, taking the transaction queue into account.
     fn next_nonce(&self, address: &Address) -> U256;
 
@@ -598,6 +619,10 @@ pub trait EngineClient: Sync + Send + ChainInfo {
     /// Get currently pending transactions
     fn queued_transactions(&self) -> Vec<Arc<VerifiedTransaction>>;
 
+    /// Get currently pending transactions, together with the sequence number of the queue state
+    /// they were read from.
+    fn queued_transactions_snapshot(&self) -> QueueSnapshot;
+
     /// Create block and queue it for sealing. Will return None if a block is already pending.
     fn create_pending_block_at(
         &self,
@@ -605,6 +630,18 @@ pub trait EngineClient: Sync + Send + ChainInfo {
         timestamp: u64,
         block_number: u64,
     ) -> Option<Header>;
+
+    /// Read engine-specific data persisted under the given key.
+    fn engine_data(&self, key: &[u8]) -> Option<Bytes>;
+
+    /// Persist engine-specific data under the given key, or remove it if `value` is `None`.
+    fn set_engine_data(&self, key: &[u8], value: Option<Bytes>);
+
+    /// Reserves a connection slot for the given enode and connects to it.
+    fn add_reserved_peer(&self, enode: String) -> Result<(), String>;
+
+    /// Removes the reservation of the given enode.
+    fn remove_reserved_peer(&self, enode: String) -> Result<(), String>;
 }
 
 /// Extended client interface for providing proofs of the state.
@@ -0,0 +1,105 @@
+//! Activation of hbbft on a chain that was started with another engine.
+//!
+//! With `activationBlock` set, hbbft takes over an existing AuRa chain from that block on,
+//! without a new genesis. The blocks before it are handed to an `AuthorityRound` engine built
+//! from the spec's `legacyAuthorityRound` params: it verifies their seals, steps and scores,
+//! follows contract-based validator sets, and applies the AuRa block rewards, so the legacy
+//! chain is imported as it was created. The hbbft and POSDAO contracts must be deployed, and the
+//! initial key generation written to the key generation history contract, before the activation
+//! block. The engine does not seal blocks before it.
+
+use engines::authority_round::AuthorityRound;
+use error::Error;
+use ethjson::spec::HbbftParams;
+use machine::EthereumMachine;
+use std::sync::Arc;
+
+/// Creates the AuRa engine of the blocks before the activation block, if the params configure
+/// one. The legacy params are moved out of `params`.
+pub fn legacy_engine(
+    params: &mut HbbftParams,
+    machine: &EthereumMachine,
+) -> Result<Option<Arc<AuthorityRound>>, Error> {
+    match params.legacy_authority_round.take() {
+        Some(legacy_params) => {
+            AuthorityRound::new(legacy_params.into(), machine.to_regular()).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::publickey::{sign, Generator, KeyPair, Random};
+    use engines::{hbbft::test::hbbft_test_client::hbbft_spec_from_json, EngineError, EthEngine};
+    use error::{BlockError, Error, ErrorKind};
+    use ethereum_types::H520;
+    use rlp::encode;
+    use serde_json;
+    use spec::Spec;
+    use types::{header::Header, BlockNumber};
+
+    const ACTIVATION_BLOCK: BlockNumber = 10;
+
+    /// The test spec switching from AuRa with a single validator at `ACTIVATION_BLOCK`. The AuRa
+    /// step is fixed at 100.
+    fn legacy_spec(validator: &KeyPair) -> Spec {
+        let mut spec: serde_json::Value = serde_json::from_slice(include_bytes!(
+            "../../../res/chainspec/honey_badger_bft.json"
+        ))
+        .expect("Chain spec must be valid JSON.");
+        let legacy = format!(
+            r#"{{"stepDuration": 5, "startStep": 100, "immediateTransitions": true,
+                "validateScoreTransition": 1000000, "validators": {{"list": ["{:?}"]}}}}"#,
+            validator.address()
+        );
+        let params = &mut spec["engine"]["hbbft"]["params"];
+        params["activationBlock"] = ACTIVATION_BLOCK.into();
+        params["legacyAuthorityRound"] =
+            serde_json::from_str(&legacy).expect("Legacy params must be valid JSON.");
+        hbbft_spec_from_json(&spec)
+    }
+
+    fn sealed_header(key: &KeyPair, number: BlockNumber, step: u64) -> Header {
+        let mut header = Header::new();
+        header.set_number(number);
+        header.set_author(key.address());
+        let signature = sign(key.secret(), &header.bare_hash()).unwrap();
+        header.set_seal(vec![
+            encode(&step),
+            encode(&H520::from(signature).as_bytes()),
+        ]);
+        header
+    }
+
+    #[test]
+    fn legacy_blocks_are_verified_by_authority_round() {
+        let validator = Random.generate();
+        let spec = legacy_spec(&validator);
+        let engine: &dyn EthEngine = &*spec.engine;
+        let parent = sealed_header(&validator, 1, 49);
+        let header = sealed_header(&validator, 2, 50);
+        assert_eq!(engine.seal_fields(&header), 2);
+        let mut activation_header = Header::new();
+        activation_header.set_number(ACTIVATION_BLOCK);
+        assert_eq!(engine.seal_fields(&activation_header), 1);
+
+        assert!(engine.verify_block_basic(&header).is_ok());
+        assert!(engine.verify_block_family(&header, &parent).is_ok());
+        assert!(engine.verify_block_external(&header).is_ok());
+
+        // AuRa rejects steps that do not increase, blocks from the future and other proposers.
+        match engine.verify_block_family(&sealed_header(&validator, 2, 49), &parent) {
+            Err(Error(ErrorKind::Engine(EngineError::DoubleVote(_)), _)) => (),
+            other => panic!("Expected a double vote, got {:?}", other),
+        }
+        match engine.verify_block_basic(&sealed_header(&validator, 2, 1000)) {
+            Err(Error(ErrorKind::Block(BlockError::InvalidSeal), _)) => (),
+            other => panic!("Expected an invalid seal, got {:?}", other),
+        }
+        let stranger = Random.generate();
+        assert!(engine
+            .verify_block_external(&sealed_header(&stranger, 2, 50))
+            .is_err());
+    }
+}
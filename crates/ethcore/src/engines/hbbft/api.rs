@@ -0,0 +1,694 @@
+//! Public API for embedding the Honey Badger BFT engine.
+//!
+//! Projects running POSDAO chains on top of `ethcore` should only depend on the items exported
+//! from this module. They follow semantic versioning: items are not removed or changed in an
+//! incompatible way without a major version bump, and new status fields and notifications are
+//! only ever added. Everything else in the `hbbft` module is an implementation detail.
+//!
+//! # Example
+//!
+//! ```no_run
+//! extern crate ethcore;
+//! extern crate parity_crypto;
+//!
+//! use ethcore::engines::{hbbft::api::{self, HbbftNotify}, signer};
+//! use parity_crypto::publickey::{Generator, Random};
+//! use std::{fs::File, path::Path, sync::Arc};
+//!
+//! struct EpochLogger;
+//!
+//! impl HbbftNotify for EpochLogger {
+//!     fn posdao_epoch_changed(&self, epoch: u64) {
+//!         println!("Switched to POSDAO epoch {}", epoch);
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let cache_dir = Path::new("/tmp/hbbft-cache");
+//! let (spec, handle) = api::load_spec(&cache_dir, File::open("spec.json").unwrap()).unwrap();
+//!
+//! handle.set_signer(signer::from_keypair(Random.generate()));
+//!
+//! let logger = Arc::new(EpochLogger);
+//! handle.add_notify(logger.clone());
+//!
+//! // ... create the client from `spec` ...
+//! # let _ = spec;
+//! println!("{:?}", handle.status());
+//! # }
+//! ```
+
+use engines::{signer::EngineSigner, EthEngine};
+use ethereum_types::{Address, H256, U256};
+use hbbft::crypto::{PublicKey, PublicKeyShare, SignatureShare};
+use spec::{Spec, SpecParams};
+use std::{collections::BTreeMap, io::Read, sync::Arc};
+use types::{header::Header, BlockNumber};
+
+use super::HoneyBadgerBFT;
+
+pub use super::{
+    batch_policy::{BatchPolicy, DefaultBatchPolicy},
+    encryption::encrypt_transaction,
+    NodeId,
+};
+
+/// A snapshot of the engine's consensus state.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct HbbftStatus {
+    /// The POSDAO epoch the engine's validator set belongs to.
+    pub posdao_epoch: u64,
+    /// The current hbbft epoch, if this node is a validator or an observer of the current
+    /// POSDAO epoch.
+    pub hbbft_epoch: Option<u64>,
+    /// Whether this node is a validator of the current POSDAO epoch.
+    pub is_validator: bool,
+    /// Whether this node observes the consensus of the current POSDAO epoch's validators.
+    pub is_observer: bool,
+    /// The number of contributions received in the current hbbft epoch, if this node is a
+    /// validator or an observer.
+    pub received_proposals: Option<usize>,
+    /// Whether more than `max_faulty` contributions were received in the current hbbft epoch,
+    /// so all validators contribute to it, if this node is a validator or an observer.
+    pub proposal_threshold_reached: Option<bool>,
+    /// The number of validators of the current POSDAO epoch, if this node is one of them.
+    pub network_size: Option<usize>,
+    /// The number of faulty validators the current POSDAO epoch tolerates, if this node is one
+    /// of its validators.
+    pub max_faulty: Option<usize>,
+    /// The number of blocks whose seal is still being created.
+    pub pending_seals: usize,
+    /// Whether a signer is configured.
+    pub has_signer: bool,
+    /// The address of the configured signer.
+    pub signer: Option<Address>,
+    /// Whether too few of the other validators are connected to reach agreement.
+    pub is_isolated: bool,
+    /// The number of calls made to the key generation history contract.
+    pub keygen_history_reads: u64,
+    /// The number of bytes of Parts and Acks read from the key generation history contract.
+    pub keygen_history_read_bytes: u64,
+    /// The feature bits supported by enough validators of the current POSDAO epoch to be active.
+    pub active_features: u64,
+    /// The on-chain standing of our pool, as of the last check, if a signer is configured.
+    pub pool_status: Option<PoolStatus>,
+    /// Whether enough blocks in a row failed seal verification to suspect out of sync epoch
+    /// keys or peers sending bogus blocks.
+    pub seal_failure_alert: bool,
+    /// Whether the validator withdrew with [`HbbftHandle::withdraw`].
+    pub withdrawn: bool,
+    /// The number of competing blocks with valid seals seen since startup.
+    pub forks_detected: u64,
+}
+
+/// The progress of a block's seal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SealingStatus {
+    /// Signature shares are still being collected.
+    InProgress,
+    /// The signature shares were combined into the seal.
+    Complete,
+}
+
+/// The engine's in-memory consensus state, for debugging a stalled chain.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct DebugState {
+    /// The current hbbft epoch, if this node is a validator of the current POSDAO epoch.
+    pub hbbft_epoch: Option<u64>,
+    /// The number of contributions received in the current hbbft epoch, if this node is a
+    /// validator of the current POSDAO epoch.
+    pub received_proposals: Option<usize>,
+    /// The blocks whose seal is being created or was created, with their progress.
+    pub sealing: BTreeMap<BlockNumber, SealingStatus>,
+    /// The number of consensus messages cached for future hbbft epochs, by epoch.
+    pub future_messages: BTreeMap<u64, usize>,
+}
+
+/// The key transactions are encrypted with, so they are only revealed once the validators
+/// agreed on the batch proposing them.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct EncryptionKey {
+    /// The POSDAO epoch whose validators can decrypt with the key.
+    pub posdao_epoch: u64,
+    /// The public master key of the epoch's validators.
+    pub public_key: PublicKey,
+}
+
+/// Two different blocks at the same height, both with a valid seal.
+///
+/// Under hbbft this must never happen: it means the epoch keys were compromised or there is a
+/// serious bug.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ForkEvidence {
+    /// The header of the block that was chosen.
+    pub chosen: Header,
+    /// The header of the block that was rejected.
+    pub rejected: Header,
+}
+
+/// The transaction that removes a withdrawn validator's pool.
+///
+/// The staking contract only accepts it from the pool's staking address, whose key the node
+/// does not have, so it has to be sent by the operator.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Withdrawal {
+    /// The staking address of our pool, which has to send the transaction.
+    pub staking_address: Address,
+    /// The address of the staking contract.
+    pub staking_contract: Address,
+    /// The call data of the staking contract's `removeMyPool` function.
+    pub call_data: Vec<u8>,
+}
+
+/// The on-chain standing of the pool our mining address belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolStatus {
+    /// The pool is active and the validator is marked as available: it can be elected.
+    Active,
+    /// The mining address does not belong to any pool, e.g. because the pool was removed.
+    NoPool,
+    /// The pool exists, but is not active, e.g. because its stake dropped below the minimum.
+    Inactive,
+    /// The validator is banned, e.g. after being reported as malicious.
+    Banned,
+    /// The validator is not marked as available.
+    Unavailable,
+}
+
+/// A validator of the current POSDAO epoch.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct HbbftValidator {
+    /// The address the validator signs blocks and keygen transactions with.
+    pub mining_address: Address,
+    /// The validator's node ID, which is also its public key.
+    pub node_id: NodeId,
+    /// Whether this is the local node.
+    pub is_self: bool,
+}
+
+/// A node the engine keeps a reserved peer connection to.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ValidatorPeer {
+    /// The node's enode URL.
+    pub enode: String,
+    /// Whether the node belongs to a validator of the current POSDAO epoch.
+    pub is_validator: bool,
+    /// Whether the node belongs to a validator elected for the next POSDAO epoch.
+    pub is_pending_validator: bool,
+    /// Whether the node was pinned by the operator.
+    pub is_pinned: bool,
+    /// Whether the reservation was accepted by the network.
+    pub is_reserved: bool,
+}
+
+/// This node's progress in the key generation for the next POSDAO epoch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeygenProgress {
+    /// Whether this node is a validator of the next POSDAO epoch.
+    pub is_pending_validator: bool,
+    /// Whether our Part is stored in the key generation history contract.
+    pub part_confirmed: bool,
+    /// Whether our Acks are stored in the key generation history contract.
+    pub acks_confirmed: bool,
+    /// Whether enough Parts and Acks are on chain to generate the new epoch key.
+    pub key_generated: bool,
+}
+
+impl KeygenProgress {
+    /// Returns false while this node is needed to complete the key generation.
+    ///
+    /// Restarting other validators in that time can make the key generation fail, so
+    /// orchestration should wait until every pending validator is ready.
+    pub fn is_ready(&self) -> bool {
+        !self.is_pending_validator
+            || (self.part_confirmed && self.acks_confirmed && self.key_generated)
+    }
+}
+
+/// The key generation data a validator of the next POSDAO epoch stored on chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeygenValidatorStatus {
+    /// The validator's mining address.
+    pub mining_address: Address,
+    /// Whether the validator's Part is stored in the key generation history contract.
+    pub has_part: bool,
+    /// Whether all of the validator's Acks are stored in the key generation history contract.
+    pub has_acks: bool,
+}
+
+/// The progress of all pending validators in the key generation for the next POSDAO epoch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeygenPhaseStatus {
+    /// The POSDAO epoch the key is generated for.
+    pub upcoming_epoch: u64,
+    /// The validators of the next POSDAO epoch. Empty if no key generation is in progress.
+    pub validators: Vec<KeygenValidatorStatus>,
+    /// The block our Part transaction was last sent in, if it is not on chain yet.
+    pub part_in_flight: Option<u64>,
+    /// The block our Acks transactions were last sent in, if they are not on chain yet.
+    pub acks_in_flight: Option<u64>,
+}
+
+impl KeygenPhaseStatus {
+    /// Returns the number of validators whose Part is not on chain yet.
+    pub fn missing_parts(&self) -> usize {
+        self.validators.iter().filter(|v| !v.has_part).count()
+    }
+
+    /// Returns the number of validators whose Acks are not on chain yet.
+    pub fn missing_acks(&self) -> usize {
+        self.validators.iter().filter(|v| !v.has_acks).count()
+    }
+}
+
+/// How recently we received consensus messages from another validator.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ValidatorLiveness {
+    /// The validator's node ID.
+    pub node_id: NodeId,
+    /// The latest hbbft epoch we received a message for from the validator in the current
+    /// POSDAO epoch, if any.
+    pub last_message_epoch: Option<u64>,
+    /// The number of hbbft epochs since that message, or since we joined the POSDAO epoch if the
+    /// validator sent none.
+    pub silent_epochs: u64,
+}
+
+/// Whether another validator of the current POSDAO epoch has a peer session with this node.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ValidatorConnection {
+    /// The validator's node ID.
+    pub node_id: NodeId,
+    /// Whether the validator's node is a connected peer.
+    pub is_connected: bool,
+    /// The number of consensus message packets waiting for the validator to reconnect.
+    pub queued_packets: usize,
+}
+
+/// Invalid consensus messages or contributions received from a validator of the current
+/// POSDAO epoch.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct FaultReport {
+    /// The validator's node ID.
+    pub node_id: NodeId,
+    /// The number of faults in the current POSDAO epoch.
+    pub faults: u64,
+    /// A description of the most recent fault.
+    pub last_fault: String,
+}
+
+/// The engine state to discard in [`HbbftHandle::reset_state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetScope {
+    /// Consensus messages cached for future hbbft epochs.
+    FutureMessages,
+    /// Signature shares collected for block seals, and agreed batches waiting for their parent
+    /// block.
+    Sealing,
+    /// The keys and Honey Badger instance of the current POSDAO epoch, rebuilt from the chain.
+    EpochKeys,
+    /// The resend timers of Part and Ack transactions.
+    KeygenSender,
+    /// All of the above.
+    All,
+}
+
+/// The kind of a consensus message, by the part of the protocol it belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum MessageKind {
+    /// Reliable broadcast of the contributions.
+    Broadcast,
+    /// Binary agreement on which contributions to accept.
+    Agreement,
+    /// Threshold decryption of the accepted contributions or encrypted transactions.
+    Decryption,
+    /// Threshold signing of the block seal.
+    Sealing,
+    /// A key generation Part sent between pending validators.
+    Keygen,
+    /// A validator's notice that it is shutting down.
+    Offline,
+    /// An observer's subscription to the broadcast Honey Badger messages.
+    Observe,
+    /// A message that could not be classified.
+    Unknown,
+}
+
+/// Processing latency percentiles of recently received consensus messages.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageLatency {
+    /// The kind of the messages.
+    pub kind: MessageKind,
+    /// The sender of the messages, or `None` if aggregated over all senders.
+    pub sender: Option<NodeId>,
+    /// The number of samples the percentiles are computed from.
+    pub samples: usize,
+    /// The median processing time, in microseconds.
+    pub p50_micros: u64,
+    /// The 90th percentile of the processing time, in microseconds.
+    pub p90_micros: u64,
+    /// The 99th percentile of the processing time, in microseconds.
+    pub p99_micros: u64,
+}
+
+/// Proof that a validator holds its secret key share of a POSDAO epoch.
+///
+/// The proof is a signature with the key share over a message binding the epoch and the node,
+/// so it can be published and checked with [`verify_key_share_proof`] without revealing the share.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyShareProof {
+    /// The POSDAO epoch the key share belongs to.
+    pub posdao_epoch: u64,
+    /// The node holding the key share.
+    pub node_id: NodeId,
+    /// The public counterpart of the key share.
+    pub public_key_share: PublicKeyShare,
+    /// The signature over the proof message, created with the key share.
+    pub signature: SignatureShare,
+}
+
+/// The message signed in a key share proof for the given epoch and node.
+pub fn key_share_proof_message(posdao_epoch: u64, node_id: &NodeId) -> Vec<u8> {
+    format!("hbbft key share proof: epoch {}, node {:x}", posdao_epoch, node_id.0).into_bytes()
+}
+
+/// Checks that the proof's signature was created with the secret counterpart of its public key share.
+///
+/// This only needs the proof itself and works offline. To make sure the key share is the one
+/// used on chain, compare `public_key_share` with the validator's share in the key generation
+/// history of that epoch.
+pub fn verify_key_share_proof(proof: &KeyShareProof) -> bool {
+    let message = key_share_proof_message(proof.posdao_epoch, &proof.node_id);
+    proof.public_key_share.verify(&proof.signature, message)
+}
+
+/// Receives notifications about consensus events.
+///
+/// All methods have empty default implementations, so implementors only need to override
+/// the events they are interested in. They are called from the engine's threads and should
+/// return quickly.
+pub trait HbbftNotify: Send + Sync {
+    /// The engine switched to a new POSDAO epoch.
+    fn posdao_epoch_changed(&self, _epoch: u64) {}
+
+    /// The validators agreed on the contributions for the given hbbft epoch.
+    fn batch_agreed(&self, _hbbft_epoch: u64, _contributions: usize) {}
+
+    /// The node lost or regained connectivity to enough validators to take part in consensus.
+    fn isolation_changed(&self, _isolated: bool) {}
+
+    /// The on-chain standing of our pool changed.
+    fn pool_status_changed(&self, _status: PoolStatus) {}
+
+    /// Competing blocks with valid seals were imported.
+    fn fork_detected(&self, _evidence: &ForkEvidence) {}
+
+    /// The key generation for the next POSDAO epoch has made no progress for the given number
+    /// of blocks. `missing` are the pending validators whose Part or Acks are not on chain.
+    fn keygen_stalled(&self, _blocks: u64, _missing: &[Address]) {}
+
+    /// The contribution of the given validator to an agreed batch exceeded the contribution
+    /// limits and was dropped.
+    fn contribution_rejected(&self, _hbbft_epoch: u64, _proposer: Address, _reason: &str) {}
+
+    /// The validator with the given address announced that it is shutting down.
+    fn validator_offline(&self, _validator: Address) {}
+
+    /// The validator with the given address sent invalid consensus messages or contributions.
+    fn validator_faulty(&self, _validator: Address, _fault: &str) {}
+}
+
+/// A handle to a Honey Badger BFT engine.
+#[derive(Clone)]
+pub struct HbbftHandle {
+    engine: Arc<dyn EthEngine>,
+}
+
+impl HbbftHandle {
+    /// Creates a handle for the given engine, if it is a Honey Badger BFT engine.
+    pub fn from_engine(engine: Arc<dyn EthEngine>) -> Option<Self> {
+        engine.as_any().downcast_ref::<HoneyBadgerBFT>()?;
+        Some(HbbftHandle { engine })
+    }
+
+    /// Creates a handle for the engine of the given spec, if it is a Honey Badger BFT engine.
+    pub fn from_spec(spec: &Spec) -> Option<Self> {
+        Self::from_engine(spec.engine.clone())
+    }
+
+    fn hbbft(&self) -> &HoneyBadgerBFT {
+        self.engine
+            .as_any()
+            .downcast_ref::<HoneyBadgerBFT>()
+            .expect("the handle is only created for hbbft engines; qed")
+    }
+
+    /// Sets the key the engine signs consensus messages and keygen transactions with.
+    ///
+    /// The signer also decrypts the key generation data sent to this validator. If the signing
+    /// key cannot decrypt, e.g. because it is kept in an HSM, combine it with a separate
+    /// decryption key using `engines::signer::with_decrypt_key`, as the node does for an
+    /// `--engine-signer-url` service given `--engine-decrypt-key`.
+    pub fn set_signer(&self, signer: Box<dyn EngineSigner>) {
+        self.engine.set_signer(Some(signer));
+    }
+
+    /// Sets the client version announced to the feature registry contract.
+    pub fn set_client_version(&self, version: String) {
+        self.hbbft().set_client_version(version);
+    }
+
+    /// Takes the devp2p ports of the other validators from the given configured enodes, e.g. the
+    /// reserved peers and boot nodes. The staking contract only announces their IP addresses, so
+    /// validators without a configured enode are assumed to listen on the default port.
+    pub fn set_configured_enodes(&self, enodes: &[String]) {
+        self.hbbft().set_configured_enodes(enodes);
+    }
+
+    /// Removes the signer, turning the node into a regular, non-validating node.
+    pub fn clear_signer(&self) {
+        self.engine.set_signer(None);
+    }
+
+    /// Observes the consensus of the current validators while this node is not one of them.
+    ///
+    /// The observer subscribes to the validators' broadcast consensus messages, once enough of
+    /// them support observers, and verifies them. Its progress is reported by
+    /// [`HbbftHandle::status`], faulty messages by [`HbbftHandle::fault_reports`].
+    pub fn set_observer(&self, enabled: bool) {
+        self.hbbft().set_observer(enabled);
+    }
+
+    /// Returns a snapshot of the engine's consensus state.
+    pub fn status(&self) -> HbbftStatus {
+        self.hbbft().status()
+    }
+
+    /// Returns the engine's in-memory consensus state.
+    ///
+    /// Meant for debugging a stalled chain; the layout of the state may change between releases.
+    pub fn debug_state(&self) -> DebugState {
+        self.hbbft().debug_state()
+    }
+
+    /// Returns the key of the current POSDAO epoch, encoded as an epoch transition proof.
+    ///
+    /// The proof contains the epoch's public master key, start block and validators, signed by
+    /// the engine signer. A client
+    /// without contract state verifies seals with it, after registering it with
+    /// [`HbbftHandle::add_epoch_key_proof`].
+    pub fn epoch_key_proof(&self) -> Result<Vec<u8>, String> {
+        self.hbbft().epoch_key_proof()
+    }
+
+    /// Adds the signatures of an epoch key proof, encoded as returned by
+    /// [`HbbftHandle::epoch_key_proof`]. Returns whether the key is registered.
+    ///
+    /// A node without a full client verifies seals only with registered keys. A key is
+    /// registered once more than two thirds of the validators of the previously registered epoch
+    /// signed it, see `epoch_keys`. Keys that do not match the chain are rejected by full clients.
+    pub fn add_epoch_key_proof(&self, proof: &[u8]) -> Result<bool, String> {
+        self.hbbft().add_epoch_key_proof(proof)
+    }
+
+    /// Discards the given in-memory engine state and re-initializes it from the chain.
+    ///
+    /// Meant for recovering a node whose consensus state is wedged without restarting it.
+    /// Consensus messages and signature shares dropped this way are lost, so the node may miss
+    /// the current hbbft epoch.
+    pub fn reset_state(&self, scope: ResetScope) -> Result<(), String> {
+        self.hbbft().reset_state(scope)
+    }
+
+    /// Gracefully withdraws this validator, e.g. before shutting the node down for good.
+    ///
+    /// The node stops contributing to new hbbft epochs, persists its cached consensus messages and
+    /// drops its pending sealing state. It still sends its key generation transactions if it was
+    /// elected for the next POSDAO epoch, since the other validators would wait for them.
+    /// Returns the transaction the operator has to send to remove the pool. The withdrawal is
+    /// persisted, so the node stays withdrawn after a restart; setting the signer of another
+    /// mining address resumes validating.
+    pub fn withdraw(&self) -> Result<Withdrawal, String> {
+        self.hbbft().withdraw()
+    }
+
+    /// Returns the validators of the current POSDAO epoch, as of the latest block.
+    pub fn validator_set(&self) -> Result<Vec<HbbftValidator>, String> {
+        self.hbbft().validator_set()
+    }
+
+    /// Reports for how many hbbft epochs each of the other validators has been silent.
+    ///
+    /// Returns an empty list if this node is not a validator of the current POSDAO epoch.
+    pub fn validator_liveness(&self) -> Vec<ValidatorLiveness> {
+        self.hbbft().validator_liveness()
+    }
+
+    /// Reports which of the other validators of the current POSDAO epoch are connected peers.
+    ///
+    /// Consensus messages to disconnected validators are kept for a while and delivered when they
+    /// reconnect. Returns an empty list if this node is not a validator of the current epoch, or
+    /// if the client does not report its peers.
+    pub fn validator_connections(&self) -> Vec<ValidatorConnection> {
+        self.hbbft().validator_connections()
+    }
+
+    /// Returns the nodes of the other current and pending validators and the pinned nodes, which
+    /// the engine keeps reserved peer connections to.
+    ///
+    /// A validator's node is only known if its pool announced an IP address in the staking
+    /// contract. It is assumed to listen on the default port.
+    pub fn validator_peers(&self) -> Vec<ValidatorPeer> {
+        self.hbbft().validator_peers()
+    }
+
+    /// Keeps a reserved peer connection to the given enode, even if it is not a validator's.
+    ///
+    /// The pin is persisted, so it survives restarts.
+    pub fn pin_peer(&self, enode: String) -> Result<(), String> {
+        self.hbbft().pin_peer(enode)
+    }
+
+    /// Removes the pin of the given enode. Its connection stays reserved if it is a validator's.
+    pub fn unpin_peer(&self, enode: &str) -> Result<(), String> {
+        self.hbbft().unpin_peer(enode)
+    }
+
+    /// Returns this node's progress in the key generation for the next POSDAO epoch, as of the
+    /// latest block.
+    pub fn keygen_progress(&self) -> Result<KeygenProgress, String> {
+        self.hbbft().keygen_progress()
+    }
+
+    /// Returns which Parts and Acks of the key generation for the next POSDAO epoch are on
+    /// chain, and which of ours are still in flight.
+    pub fn keygen_phase(&self) -> Result<KeygenPhaseStatus, String> {
+        self.hbbft().keygen_phase()
+    }
+
+    /// Creates a proof of possession of this validator's key share for the current POSDAO epoch.
+    ///
+    /// Returns `None` if this node is not a validator of the current epoch.
+    pub fn key_share_proof(&self) -> Option<KeyShareProof> {
+        self.hbbft().key_share_proof()
+    }
+
+    /// Returns the key to encrypt transactions with for the validators of the current POSDAO
+    /// epoch, using [`encrypt_transaction`].
+    pub fn encryption_key(&self) -> Result<EncryptionKey, String> {
+        self.hbbft().encryption_key()
+    }
+
+    /// Adds an encrypted transaction to this validator's next contributions, and returns the
+    /// hash of its ciphertext.
+    ///
+    /// Fails if this node is not a validator, encrypted transactions are not enabled yet, or the
+    /// ciphertext is malformed. A ciphertext encrypted with the key of an epoch that is over is
+    /// discarded.
+    pub fn submit_encrypted_transaction(&self, ciphertext: Vec<u8>) -> Result<H256, String> {
+        self.hbbft().submit_encrypted_transaction(ciphertext)
+    }
+
+    /// Returns the random number committed by the given block, or `None` if the block is
+    /// unknown or was created before hbbft was activated.
+    ///
+    /// Fails if the chain has no randomness contract.
+    pub fn block_random(&self, block_number: u64) -> Result<Option<U256>, String> {
+        self.hbbft().block_random(block_number)
+    }
+
+    /// Returns the validators of the current POSDAO epoch that sent invalid consensus messages
+    /// or contributions.
+    pub fn fault_reports(&self) -> Vec<FaultReport> {
+        self.hbbft().fault_reports()
+    }
+
+    /// Returns the evidence of the most recent competing blocks with valid seals.
+    pub fn fork_evidence(&self) -> Vec<ForkEvidence> {
+        self.hbbft().fork_evidence()
+    }
+
+    /// Returns processing latency percentiles of received messages, per message kind and sender.
+    ///
+    /// The time is measured from receiving a message until the consensus step it triggered is
+    /// completed, over the most recent messages of each kind and sender.
+    pub fn message_latencies(&self) -> Vec<MessageLatency> {
+        self.hbbft().message_latencies()
+    }
+
+    /// Claims the staking rewards and ordered withdrawals of our pool's staker every `interval`
+    /// POSDAO epochs.
+    ///
+    /// The claim transactions are sent from the staking address, so `signer` must hold the key
+    /// of the pool's staking address, not the mining key.
+    pub fn set_reward_claimer(&self, signer: Box<dyn EngineSigner>, interval: u64) {
+        self.hbbft().set_reward_claimer(signer, interval);
+    }
+
+    /// Replaces the rules deriving a block from the agreed contributions of an hbbft epoch.
+    ///
+    /// All validators of the chain must use the same policy. It should be set before the client
+    /// is started.
+    pub fn set_batch_policy(&self, policy: Arc<dyn BatchPolicy>) {
+        self.hbbft().set_batch_policy(policy);
+    }
+
+    /// Registers a target to be notified about consensus events.
+    ///
+    /// Only a weak reference is kept: notifications stop once the target is dropped.
+    pub fn add_notify(&self, target: Arc<dyn HbbftNotify>) {
+        self.hbbft().add_notify(target);
+    }
+}
+
+/// Loads a chain spec from JSON and returns it together with a handle to its engine.
+///
+/// Fails if the spec is invalid or does not use the Honey Badger BFT engine.
+pub fn load_spec<'a, T: Into<SpecParams<'a>>, R: Read>(
+    params: T,
+    reader: R,
+) -> Result<(Spec, HbbftHandle), String> {
+    let spec = Spec::load(params, reader)?;
+    let handle = HbbftHandle::from_spec(&spec)
+        .ok_or_else(|| format!("Spec {} does not use the HoneyBadgerBFT engine", spec.name))?;
+    Ok((spec, handle))
+}
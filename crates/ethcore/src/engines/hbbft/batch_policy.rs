@@ -0,0 +1,90 @@
+//! The rules turning an agreed batch of contributions into a block.
+//!
+//! Every validator contributes transactions, a timestamp and random data to each hbbft epoch.
+//! Once the contributions are agreed on, a `BatchPolicy` derives the block's timestamp, the
+//! epoch's random number and the block's transactions from them. All validators of a chain must
+//! use the same policy, or they create different blocks and can't combine their seal shares.
+
+use ethereum_types::U256;
+use itertools::Itertools;
+use std::{collections::BTreeMap, ops::BitXor};
+use types::transaction::UnverifiedTransaction;
+
+use super::NodeId;
+
+/// Aggregates the contributions of an hbbft epoch.
+///
+/// The default implementations are the rules the engine uses unless a different policy is set.
+pub trait BatchPolicy: Send + Sync {
+    /// Returns the block timestamp, given the timestamp of each contribution.
+    ///
+    /// Defaults to the median, so a minority of validators can't move it arbitrarily.
+    fn timestamp(&self, timestamps: &BTreeMap<NodeId, u64>) -> Option<u64> {
+        let sorted: Vec<u64> = timestamps.values().cloned().sorted();
+        sorted.get(sorted.len() / 2).cloned()
+    }
+
+    /// Returns the random number of the epoch, given the random data of each contribution.
+    ///
+    /// Defaults to the XOR of the first 32 bytes of each contribution, which is unpredictable as
+    /// long as one contributor is honest. Contributions with less data are ignored.
+    fn random_number(&self, random_data: &BTreeMap<NodeId, &[u8]>) -> U256 {
+        random_data.iter().fold(U256::zero(), |acc, (n, data)| {
+            if data.len() >= 32 {
+                U256::from(&data[0..32]).bitxor(acc)
+            } else {
+                // TODO: Report malicious behavior by node!
+                error!(target: "consensus", "Insufficient random data from node {}", n);
+                acc
+            }
+        })
+    }
+
+    /// Returns the transactions to include in the block, given the decodable transactions of
+    /// all contributions in the order of the contributors' node IDs.
+    ///
+    /// Defaults to dropping duplicates, keeping the first occurrence.
+    fn transactions(&self, transactions: Vec<UnverifiedTransaction>) -> Vec<UnverifiedTransaction> {
+        transactions.into_iter().unique().collect()
+    }
+}
+
+/// The engine's built-in batch policy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultBatchPolicy;
+
+impl BatchPolicy for DefaultBatchPolicy {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+
+    fn node(n: u64) -> NodeId {
+        NodeId(H512::from_low_u64_be(n))
+    }
+
+    #[test]
+    fn default_timestamp_is_the_median() {
+        let timestamps: BTreeMap<_, _> = vec![(node(1), 30), (node(2), 10), (node(3), 1000)]
+            .into_iter()
+            .collect();
+        assert_eq!(DefaultBatchPolicy.timestamp(&timestamps), Some(30));
+        assert_eq!(DefaultBatchPolicy.timestamp(&BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn default_random_number_ignores_short_contributions() {
+        let a = [1u8; 32];
+        let b = [3u8; 40];
+        let short = [7u8; 31];
+        let random_data: BTreeMap<_, _> =
+            vec![(node(1), &a[..]), (node(2), &b[..]), (node(3), &short[..])]
+                .into_iter()
+                .collect();
+        assert_eq!(
+            DefaultBatchPolicy.random_number(&random_data),
+            U256::from(&[2u8; 32][..])
+        );
+    }
+}
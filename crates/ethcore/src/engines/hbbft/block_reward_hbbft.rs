@@ -16,12 +16,17 @@
 
 //! Types for declaring block rewards and a client interface for interacting with a
 //! block reward contract.
+//!
+//! The contract can also be told which validators contributed to a block's batch, so it can
+//! reward them by participation. The contributors are stored in the block header's extra data, as
+//! a bit field over the validators in address order, so importing nodes make the same call.
 
-use engines::{SystemOrCodeCall, SystemOrCodeCallKind};
+use engines::{hbbft::extra_data, SystemOrCodeCall, SystemOrCodeCallKind};
 use error::Error;
 use ethabi::FunctionOutputDecoder;
 use ethabi_contract::use_contract;
 use ethereum_types::{Address, U256};
+use std::collections::BTreeSet;
 
 use_contract!(
     block_reward_contract,
@@ -51,7 +56,28 @@ impl BlockRewardContract {
     /// `machine.execute_as_system`).
     pub fn reward(&self, caller: &mut SystemOrCodeCall, is_epoch_end: bool) -> Result<U256, Error> {
         let (input, decoder) = block_reward_contract::functions::reward::call(is_epoch_end);
+        self.call(caller, input, decoder)
+    }
+
+    /// Like `reward`, but also passes the mining addresses of the validators that contributed to
+    /// the block's batch.
+    pub fn reward_contributors(
+        &self,
+        caller: &mut SystemOrCodeCall,
+        is_epoch_end: bool,
+        contributors: Vec<Address>,
+    ) -> Result<U256, Error> {
+        let (input, decoder) =
+            block_reward_contract::functions::reward_contributors::call(is_epoch_end, contributors);
+        self.call(caller, input, decoder)
+    }
 
+    fn call<D: FunctionOutputDecoder<Output = U256>>(
+        &self,
+        caller: &mut SystemOrCodeCall,
+        input: Vec<u8>,
+        decoder: D,
+    ) -> Result<U256, Error> {
         let output = caller(self.kind.clone(), input)
             .map_err(Into::into)
             .map_err(::engines::EngineError::FailedSystemCall)?;
@@ -64,3 +90,57 @@ impl BlockRewardContract {
         Ok(rewards_native)
     }
 }
+
+/// Encodes the contributors of a batch as a bit field over the validators, which must be sorted.
+pub fn encode_contributors(validators: &[Address], contributors: &BTreeSet<Address>) -> Vec<u8> {
+    let mut bits = vec![0u8; extra_data::contributors_len(validators.len())];
+    for (i, validator) in validators.iter().enumerate() {
+        if contributors.contains(validator) {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bits
+}
+
+/// Decodes the contributors of a batch from a bit field over the validators, which must be
+/// sorted. Returns `None` if the bit field does not match the number of validators.
+pub fn decode_contributors(validators: &[Address], bits: &[u8]) -> Option<Vec<Address>> {
+    if bits.len() != extra_data::contributors_len(validators.len()) {
+        return None;
+    }
+    let is_set = |i: usize| bits[i / 8] & (1 << (i % 8)) != 0;
+    if (validators.len()..bits.len() * 8).any(is_set) {
+        return None;
+    }
+    Some(
+        validators
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| is_set(*i))
+            .map(|(_, validator)| *validator)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contributors_roundtrip() {
+        let validators: Vec<Address> = (1..=10).map(Address::from_low_u64_be).collect();
+        let contributors: BTreeSet<Address> = vec![validators[0], validators[3], validators[9]]
+            .into_iter()
+            .collect();
+        let bits = encode_contributors(&validators, &contributors);
+        assert_eq!(bits, vec![0b0000_1001, 0b0000_0010]);
+        assert_eq!(
+            decode_contributors(&validators, &bits),
+            Some(contributors.into_iter().collect())
+        );
+
+        assert_eq!(decode_contributors(&validators, &bits[..1]), None);
+        assert_eq!(decode_contributors(&validators, &[0, 0b0000_0100]), None);
+        assert_eq!(decode_contributors(&[], &[]), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,82 @@
+//! The engine's source of time.
+//!
+//! The timer decides when to start an hbbft epoch, e.g. once the minimum or maximum block time
+//! passed, and contributions carry the time they were proposed at. All of these read the
+//! engine's clock, which is the system clock unless tests replace it with a [`VirtualClock`]:
+//! a clock that only advances when told to, so the timer-driven code paths can be tested without
+//! waiting for real time to pass.
+
+#[cfg(test)]
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::contribution::{unix_now_millis, unix_now_secs};
+
+/// A source of the current UNIX time.
+pub trait Clock: Send + Sync {
+    /// Returns the current UNIX time, in milliseconds.
+    fn now_millis(&self) -> u128;
+
+    /// Returns the current UNIX time, in seconds.
+    fn now_secs(&self) -> u64 {
+        (self.now_millis() / 1000) as u64
+    }
+}
+
+/// The operating system's clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u128 {
+        unix_now_millis()
+    }
+
+    fn now_secs(&self) -> u64 {
+        unix_now_secs()
+    }
+}
+
+/// A clock standing still until it is advanced.
+#[cfg(test)]
+pub struct VirtualClock {
+    millis: AtomicU64,
+}
+
+#[cfg(test)]
+impl VirtualClock {
+    /// Creates a clock showing the given UNIX time, in seconds.
+    pub fn new(secs: u64) -> Self {
+        VirtualClock {
+            millis: AtomicU64::new(secs * 1000),
+        }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        self.millis
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for VirtualClock {
+    fn now_millis(&self) -> u128 {
+        self.millis.load(Ordering::SeqCst) as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_time_only_passes_when_advanced() {
+        let clock = VirtualClock::new(100);
+        assert_eq!(clock.now_secs(), 100);
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now_millis(), 101_500);
+        assert_eq!(clock.now_secs(), 101);
+    }
+}
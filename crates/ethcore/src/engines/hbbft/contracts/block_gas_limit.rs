@@ -0,0 +1,25 @@
+//! Reads the block gas limit set by on-chain governance.
+//!
+//! The gas limit of a new block is otherwise derived from the gas limit targets configured by
+//! each operator. All validators create the same block from an agreed batch, so they only agree
+//! on its gas limit if it is read from the chain.
+
+use client::traits::EngineClient;
+use engines::hbbft::utils::bound_contract::{BoundContract, CallError};
+use ethereum_types::{Address, U256};
+use types::ids::BlockId;
+
+use_contract!(
+    block_gas_limit_contract,
+    "res/contracts/block_gas_limit.json"
+);
+
+/// Returns the gas limit of the blocks following the given block.
+pub fn block_gas_limit(
+    client: &dyn EngineClient,
+    contract: Address,
+    block_id: BlockId,
+) -> Result<U256, CallError> {
+    let c = BoundContract::bind(client, block_id, contract);
+    c.call_const(block_gas_limit_contract::functions::block_gas_limit::call())
+}
@@ -0,0 +1,133 @@
+use client::traits::{EngineClient, TransactionRequest};
+use engines::hbbft::{
+    contracts::validator_set::{get_validator_pubkeys, ValidatorType},
+    keygen_transactions::service_gas_price,
+    utils::bound_contract::{BoundContract, CallError},
+};
+use ethereum_types::{Address, U256};
+use ethjson::spec::HbbftKeygenGasPrice;
+use types::ids::BlockId;
+
+use_contract!(
+    node_feature_registry,
+    "res/contracts/node_feature_registry.json"
+);
+
+/// Encodes consensus data with `WireVersion::V1`.
+pub const FEATURE_WIRE_V1: u64 = 1 << 0;
+
+/// Batches and compresses consensus messages with `WireVersion::V2`.
+pub const FEATURE_WIRE_V2: u64 = 1 << 1;
+
+/// Binds packets of consensus messages to the chain and POSDAO epoch with `WireVersion::V3`.
+pub const FEATURE_WIRE_V3: u64 = 1 << 2;
+
+/// Signs packets of consensus messages with the sender's validator key with `WireVersion::V4`.
+pub const FEATURE_WIRE_V4: u64 = 1 << 3;
+
+/// Tells the other validators when the node shuts down.
+pub const FEATURE_OFFLINE_NOTICE: u64 = 1 << 4;
+
+/// Sends the broadcast Honey Badger messages to subscribed observers as well.
+pub const FEATURE_OBSERVERS: u64 = 1 << 5;
+
+/// The features this node supports.
+pub const SUPPORTED_FEATURES: u64 = FEATURE_WIRE_V1
+    | FEATURE_WIRE_V2
+    | FEATURE_WIRE_V3
+    | FEATURE_WIRE_V4
+    | FEATURE_OFFLINE_NOTICE
+    | FEATURE_OBSERVERS;
+
+macro_rules! call_const_registry {
+	($c:ident, $x:ident $(, $a:expr )*) => {
+		$c.call_const(node_feature_registry::functions::$x::call($($a),*))
+	};
+}
+
+/// Returns the version and feature bits last announced by the validator with the given mining address.
+pub fn announced_features(
+    client: &dyn EngineClient,
+    registry: Address,
+    block_id: BlockId,
+    mining_address: Address,
+) -> Result<(String, U256), CallError> {
+    let c = BoundContract::bind(client, block_id, registry);
+    let version = call_const_registry!(c, announced_version, mining_address)?;
+    let features = call_const_registry!(c, announced_features, mining_address)?;
+    Ok((version, features))
+}
+
+/// Returns the features announced by at least `n - f` of the current validators at the given block.
+pub fn active_features(
+    client: &dyn EngineClient,
+    registry: Address,
+    block_id: BlockId,
+) -> Result<u64, CallError> {
+    let validators = get_validator_pubkeys(client, block_id, ValidatorType::Current)?;
+    if validators.is_empty() {
+        return Ok(0);
+    }
+    let mut announced = Vec::with_capacity(validators.len());
+    for address in validators.keys() {
+        let (_, features) = announced_features(client, registry, block_id, *address)?;
+        announced.push(features.low_u64());
+    }
+    let num_faulty = (validators.len() - 1) / 3;
+    Ok(features_with_support(
+        &announced,
+        validators.len() - num_faulty,
+    ))
+}
+
+/// Returns the feature bits set in at least `threshold` of the announcements.
+fn features_with_support(announced: &[u64], threshold: usize) -> u64 {
+    (0..64)
+        .map(|bit| 1u64 << bit)
+        .filter(|feature| announced.iter().filter(|a| *a & feature != 0).count() >= threshold)
+        .fold(0, |acc, feature| acc | feature)
+}
+
+/// Announces our version and supported features, unless the registry already holds them.
+pub fn announce_features(
+    client: &dyn EngineClient,
+    registry: Address,
+    mining_address: Address,
+    version: &str,
+    gas_price: Option<HbbftKeygenGasPrice>,
+) -> Result<bool, CallError> {
+    let full_client = client.as_full_client().ok_or(CallError::NotFullClient)?;
+    let (announced_version, announced) =
+        announced_features(client, registry, BlockId::Latest, mining_address)?;
+    if announced_version == version && announced == U256::from(SUPPORTED_FEATURES) {
+        return Ok(false);
+    }
+
+    let (data, _) = node_feature_registry::functions::announce::call(
+        version.to_owned(),
+        U256::from(SUPPORTED_FEATURES),
+    );
+    // The next nonce accounts for our queued keygen and other service transactions.
+    let transaction = TransactionRequest::call(registry, data)
+        .gas(U256::from(200_000))
+        .nonce(full_client.next_nonce(&mining_address))
+        .gas_price(service_gas_price(gas_price, full_client));
+    full_client
+        .transact_silently(transaction)
+        .map_err(|_| CallError::ReturnValueInvalid)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::features_with_support;
+
+    #[test]
+    fn features_need_threshold_support() {
+        let announced = [0b011, 0b001, 0b111, 0b000];
+        assert_eq!(features_with_support(&announced, 3), 0b001);
+        assert_eq!(features_with_support(&announced, 2), 0b011);
+        assert_eq!(features_with_support(&announced, 4), 0);
+        assert_eq!(features_with_support(&[], 1), 0);
+    }
+}
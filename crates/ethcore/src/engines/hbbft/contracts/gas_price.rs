@@ -0,0 +1,24 @@
+//! Reads the minimum gas price set by on-chain governance.
+//!
+//! The minimum gas price of the transaction queue is otherwise configured by each operator. With
+//! a governance contract, it can be adjusted for the whole network at once.
+
+use client::traits::EngineClient;
+use engines::hbbft::utils::bound_contract::{BoundContract, CallError};
+use ethereum_types::{Address, U256};
+use types::ids::BlockId;
+
+use_contract!(
+    gas_price_governance,
+    "res/contracts/gas_price_governance.json"
+);
+
+/// Returns the minimum gas price of transactions at the given block.
+pub fn minimum_gas_price(
+    client: &dyn EngineClient,
+    contract: Address,
+    block_id: BlockId,
+) -> Result<U256, CallError> {
+    let c = BoundContract::bind(client, block_id, contract);
+    c.call_const(gas_price_governance::functions::minimum_gas_price::call())
+}
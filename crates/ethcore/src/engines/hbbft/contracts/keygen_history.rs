@@ -3,8 +3,9 @@ use crypto::{self, publickey::Public};
 use engines::{
     hbbft::{
         contracts::validator_set::{get_validator_pubkeys, ValidatorType},
+        rng::engine_rng,
         utils::bound_contract::{BoundContract, CallError},
-        NodeId,
+        wire, NodeId,
     },
     signer::EngineSigner,
 };
@@ -19,17 +20,66 @@ use hbbft::{
 };
 use itertools::Itertools;
 use parking_lot::RwLock;
-use std::{collections::BTreeMap, str::FromStr, sync::Arc};
-use types::ids::BlockId;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use types::{
+    ids::BlockId,
+    transaction::{Action, SignedTransaction},
+};
 
 use_contract!(
     key_history_contract,
     "res/contracts/key_history_contract.json"
 );
 
+/// The maximum number of validators whose keygen data is read or decoded concurrently.
+const MAX_CONCURRENT_KEYGEN_READS: usize = 8;
+
 lazy_static! {
     pub static ref KEYGEN_HISTORY_ADDRESS: Address =
         Address::from_str("7000000000000000000000000000000000000001").unwrap();
+    static ref KEYGEN_READ_POOL: ThreadPool = ThreadPoolBuilder::new()
+        .num_threads(MAX_CONCURRENT_KEYGEN_READS)
+        .thread_name(|i| format!("hbbft-keygen-read-{}", i))
+        .build()
+        .expect("keygen read thread pool creation must succeed");
+}
+
+/// Counters of the engine's reads from the key generation history contract.
+#[derive(Debug, Default)]
+pub struct KeygenReadStats {
+    calls: AtomicU64,
+    bytes: AtomicU64,
+    synckeygen_initializations: AtomicU64,
+}
+
+impl KeygenReadStats {
+    fn record_call(&self, bytes: usize) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// The number of contract calls made.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes of Parts and Acks read.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The number of SyncKeyGen instances initialized from contract data.
+    pub fn synckeygen_initializations(&self) -> u64 {
+        self.synckeygen_initializations.load(Ordering::Relaxed)
+    }
 }
 
 macro_rules! call_const_key_history {
@@ -51,7 +101,7 @@ pub fn engine_signer_to_synckeygen<'a>(
             .expect("Signer's public key must be available!"),
         None => Public::from(H512::from_low_u64_be(0)),
     };
-    let mut rng = rand_065::thread_rng();
+    let mut rng = engine_rng();
     let num_nodes = pub_keys.len();
     SyncKeyGen::new(public, wrapper, pub_keys, max_faulty(num_nodes), &mut rng)
 }
@@ -86,33 +136,91 @@ pub fn synckeygen_to_network_info(
     ))
 }
 
+/// The network info of a node observing the consensus of the validators the key generation was
+/// for. It verifies their messages, but has no key share to take part with.
+pub fn synckeygen_to_observer_network_info(
+    synckeygen: &SyncKeyGen<Public, PublicWrapper>,
+    pks: PublicKeySet,
+) -> NetworkInfo<NodeId> {
+    let pub_keys = synckeygen
+        .public_keys()
+        .keys()
+        .map(|p| NodeId(*p))
+        .collect::<Vec<_>>();
+    NetworkInfo::new(
+        NodeId(synckeygen.our_id().clone()),
+        None::<SecretKeyShare>,
+        pks,
+        pub_keys,
+    )
+}
+
+fn read_part(
+    c: &BoundContract,
+    address: Address,
+    stats: &KeygenReadStats,
+) -> Result<Vec<u8>, CallError> {
+    let serialized_part = call_const_key_history!(c, parts, address)?;
+    stats.record_call(serialized_part.len());
+    Ok(serialized_part)
+}
+
+fn read_acks(
+    c: &BoundContract,
+    address: Address,
+    stats: &KeygenReadStats,
+) -> Result<Vec<Vec<u8>>, CallError> {
+    let serialized_length = call_const_key_history!(c, get_acks_length, address)?;
+    stats.record_call(0);
+    (0..serialized_length.low_u64())
+        .map(|n| {
+            let serialized_ack = call_const_key_history!(c, acks, address, n)?;
+            stats.record_call(serialized_ack.len());
+            Ok(serialized_ack)
+        })
+        .collect()
+}
+
 pub fn has_part_of_address_data(
     client: &dyn EngineClient,
     address: Address,
+    stats: &KeygenReadStats,
 ) -> Result<bool, CallError> {
     let c = BoundContract::bind(client, BlockId::Latest, *KEYGEN_HISTORY_ADDRESS);
-    let serialized_part = call_const_key_history!(c, parts, address)?;
-    //println!("Part for address {}: {:?}", address, serialized_part);
+    let serialized_part = read_part(&c, address, stats)?;
     Ok(!serialized_part.is_empty())
 }
 
-pub fn part_of_address(
+/// Returns the serialized Part the validator wrote to the contract, or an empty one.
+pub fn serialized_part_of_address(
     client: &dyn EngineClient,
     address: Address,
-    vmap: &BTreeMap<Address, Public>,
-    skg: &mut SyncKeyGen<Public, PublicWrapper>,
     block_id: BlockId,
-) -> Result<Option<Ack>, CallError> {
+    stats: &KeygenReadStats,
+) -> Result<Vec<u8>, CallError> {
     let c = BoundContract::bind(client, block_id, *KEYGEN_HISTORY_ADDRESS);
-    let serialized_part = call_const_key_history!(c, parts, address)?;
-    //println!("Part for address {}: {:?}", address, serialized_part);
+    read_part(&c, address, stats)
+}
+
+pub fn decode_part(address: Address, serialized_part: &[u8]) -> Result<Part, CallError> {
     if serialized_part.is_empty() {
         return Err(CallError::ReturnValueInvalid);
     }
-    let deserialized_part: Part = bincode::deserialize(&serialized_part).unwrap();
-    let mut rng = rand_065::thread_rng();
+    wire::decode_part(serialized_part).map_err(|e| {
+        error!(target: "engine", "Invalid Part of {}: {}", address, e);
+        CallError::ReturnValueInvalid
+    })
+}
+
+pub fn handle_part(
+    address: Address,
+    part: Part,
+    vmap: &BTreeMap<Address, Public>,
+    skg: &mut SyncKeyGen<Public, PublicWrapper>,
+) -> Result<Option<Ack>, CallError> {
+    let mut rng = engine_rng();
     let outcome = skg
-        .handle_part(vmap.get(&address).unwrap(), deserialized_part, &mut rng)
+        .handle_part(vmap.get(&address).unwrap(), part, &mut rng)
         .unwrap();
 
     match outcome {
@@ -121,39 +229,53 @@ pub fn part_of_address(
     }
 }
 
-pub fn has_acks_of_address_data(
+/// Returns the number of Acks the validator wrote to the contract so far.
+pub fn acks_count_of_address(
     client: &dyn EngineClient,
     address: Address,
-) -> Result<bool, CallError> {
+    stats: &KeygenReadStats,
+) -> Result<usize, CallError> {
     let c = BoundContract::bind(client, BlockId::Latest, *KEYGEN_HISTORY_ADDRESS);
     let serialized_length = call_const_key_history!(c, get_acks_length, address)?;
-    Ok(serialized_length.low_u64() != 0)
+    stats.record_call(0);
+    Ok(serialized_length.low_u64() as usize)
 }
 
-pub fn acks_of_address(
+/// Returns true if the validator wrote all of its Acks, one for the Part of each of the
+/// `validators` pending validators. Acks too large for one block are written in several
+/// transactions.
+pub fn has_acks_of_address_data(
     client: &dyn EngineClient,
     address: Address,
+    validators: usize,
+    stats: &KeygenReadStats,
+) -> Result<bool, CallError> {
+    Ok(acks_count_of_address(client, address, stats)? >= validators.max(1))
+}
+
+pub fn decode_acks(address: Address, serialized_acks: &[Vec<u8>]) -> Result<Vec<Ack>, CallError> {
+    serialized_acks
+        .iter()
+        .map(|serialized_ack| {
+            if serialized_ack.is_empty() {
+                return Err(CallError::ReturnValueInvalid);
+            }
+            wire::decode_ack(serialized_ack).map_err(|e| {
+                error!(target: "engine", "Invalid Ack of {}: {}", address, e);
+                CallError::ReturnValueInvalid
+            })
+        })
+        .collect()
+}
+
+pub fn handle_acks(
+    address: Address,
+    acks: Vec<Ack>,
     vmap: &BTreeMap<Address, Public>,
     skg: &mut SyncKeyGen<Public, PublicWrapper>,
-    block_id: BlockId,
 ) -> Result<(), CallError> {
-    let c = BoundContract::bind(client, block_id, *KEYGEN_HISTORY_ADDRESS);
-    let serialized_length = call_const_key_history!(c, get_acks_length, address)?;
-
-    // println!(
-    // 	"Acks for address {} is of size: {:?}",
-    // 	address, serialized_length
-    // );
-    for n in 0..serialized_length.low_u64() {
-        let serialized_ack = call_const_key_history!(c, acks, address, n)?;
-        //println!("Ack #{} for address {}: {:?}", n, address, serialized_ack);
-        if serialized_ack.is_empty() {
-            return Err(CallError::ReturnValueInvalid);
-        }
-        let deserialized_ack: Ack = bincode::deserialize(&serialized_ack).unwrap();
-        let outcome = skg
-            .handle_ack(vmap.get(&address).unwrap(), deserialized_ack)
-            .unwrap();
+    for ack in acks {
+        let outcome = skg.handle_ack(vmap.get(&address).unwrap(), ack).unwrap();
         if let AckOutcome::Invalid(fault) = outcome {
             panic!("Expected Ack Outcome to be valid. {}", fault);
         }
@@ -167,6 +289,10 @@ pub struct PublicWrapper {
     pub inner: Public,
 }
 
+/// The engine signer's decryption capability, used as the secret key of the key generation.
+///
+/// Key generation data is encrypted to the public key the validator registered on chain, which
+/// is the signer's decryption key. It may differ from the key the signer signs with.
 #[derive(Clone)]
 pub struct KeyPairWrapper {
     pub inner: Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
@@ -187,22 +313,66 @@ impl<'a> PublicKey for PublicWrapper {
 impl<'a> SecretKey for KeyPairWrapper {
     type Error = crypto::publickey::Error;
     fn decrypt(&self, ct: &[u8]) -> Result<Vec<u8>, Self::Error> {
-        self.inner
-            .read()
-            .as_ref()
-            .ok_or(parity_crypto::publickey::Error::InvalidSecretKey)
-            .expect("Signer must be set!")
-            .decrypt(b"", ct)
+        match self.inner.read().as_ref() {
+            Some(signer) => signer.decrypt(b"", ct),
+            None => {
+                error!(target: "engine", "Cannot decrypt key generation data without an engine signer.");
+                Err(parity_crypto::publickey::Error::InvalidSecretKey)
+            }
+        }
+    }
+}
+
+/// The serialized Part and Acks of a validator, as stored in the key generation history contract.
+pub type SerializedKeygenData = (Address, Vec<u8>, Vec<Vec<u8>>);
+
+/// Handles the Parts and Acks of the validators: all Parts before all Acks, in validator order.
+///
+/// Decoding, which checks every commitment point, is independent per validator and done
+/// concurrently. `SyncKeyGen` itself can only handle one Part or Ack at a time.
+pub fn handle_keygen_data(
+    keygen_data: &[SerializedKeygenData],
+    vmap: &BTreeMap<Address, Public>,
+    skg: &mut SyncKeyGen<Public, PublicWrapper>,
+) -> Result<(), CallError> {
+    let decoded = KEYGEN_READ_POOL.install(|| {
+        keygen_data
+            .par_iter()
+            .map(|(v, serialized_part, serialized_acks)| {
+                Ok((
+                    *v,
+                    decode_part(*v, serialized_part)?,
+                    decode_acks(*v, serialized_acks)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, CallError>>()
+    })?;
+
+    let mut all_acks = Vec::with_capacity(decoded.len());
+    for (v, part, acks) in decoded {
+        handle_part(v, part, vmap, skg)?;
+        all_acks.push((v, acks));
+    }
+    for (v, acks) in all_acks {
+        handle_acks(v, acks, vmap, skg)?;
     }
+    Ok(())
 }
 
 /// Read available keygen data from the blockchain and initialize a SyncKeyGen instance with it.
+///
+/// The Parts and Acks of the validators are read and decoded concurrently, with at most
+/// `MAX_CONCURRENT_KEYGEN_READS` validators at a time, and then handled in validator order.
 pub fn initialize_synckeygen(
     client: &dyn EngineClient,
     signer: &Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
     block_id: BlockId,
     validator_type: ValidatorType,
+    stats: &KeygenReadStats,
 ) -> Result<SyncKeyGen<Public, PublicWrapper>, CallError> {
+    stats
+        .synckeygen_initializations
+        .fetch_add(1, Ordering::Relaxed);
     let vmap = get_validator_pubkeys(&*client, block_id, validator_type)?;
     let pub_keys: BTreeMap<_, _> = vmap
         .values()
@@ -214,23 +384,70 @@ pub fn initialize_synckeygen(
     let (mut synckeygen, _) = engine_signer_to_synckeygen(signer, Arc::new(pub_keys))
         .map_err(|_| CallError::ReturnValueInvalid)?;
 
-    for v in vmap.keys().sorted() {
-        part_of_address(&*client, *v, &vmap, &mut synckeygen, block_id)?;
-    }
-    for v in vmap.keys().sorted() {
-        acks_of_address(&*client, *v, &vmap, &mut synckeygen, block_id)?;
-    }
+    let addresses: Vec<Address> = vmap.keys().sorted().cloned().collect();
+    let keygen_data = KEYGEN_READ_POOL.install(|| {
+        addresses
+            .par_iter()
+            .map(|v| {
+                let c = BoundContract::bind(client, block_id, *KEYGEN_HISTORY_ADDRESS);
+                Ok((*v, read_part(&c, *v, stats)?, read_acks(&c, *v, stats)?))
+            })
+            .collect::<Result<Vec<_>, CallError>>()
+    })?;
+    handle_keygen_data(&keygen_data, &vmap, &mut synckeygen)?;
 
     Ok(synckeygen)
 }
 
+/// Returns true if the transaction calls the key generation history contract and was sent by one
+/// of the given validators. Anyone can send transactions to the contract, but only the
+/// validators' parts and acks take part in the key generation.
+pub fn is_validator_keygen_transaction(
+    txn: &SignedTransaction,
+    validators: &BTreeSet<Address>,
+) -> bool {
+    txn.tx().action == Action::Call(*KEYGEN_HISTORY_ADDRESS) && validators.contains(&txn.sender())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crypto::publickey::{KeyPair, Secret};
-    use engines::signer::{from_keypair, EngineSigner};
+    use crypto::publickey::{Generator, KeyPair, Random, Secret};
+    use engines::{
+        hbbft::test::create_transactions::{create_call, create_transaction},
+        signer::{from_keypair, EngineSigner},
+    };
+    use ethereum_types::U256;
     use std::{collections::BTreeMap, sync::Arc};
 
+    #[test]
+    fn only_validators_send_keygen_transactions() {
+        let validator = Random.generate();
+        let stranger = Random.generate();
+        let validators: BTreeSet<Address> = vec![validator.address()].into_iter().collect();
+        let keygen_call = |keypair: &KeyPair| {
+            create_call(
+                keypair,
+                &KEYGEN_HISTORY_ADDRESS,
+                vec![],
+                &U256::zero(),
+                &U256::zero(),
+            )
+        };
+        assert!(is_validator_keygen_transaction(
+            &keygen_call(&validator),
+            &validators
+        ));
+        assert!(!is_validator_keygen_transaction(
+            &keygen_call(&stranger),
+            &validators
+        ));
+        assert!(!is_validator_keygen_transaction(
+            &create_transaction(&validator, &U256::zero()),
+            &validators
+        ));
+    }
+
     #[test]
     fn test_synckeygen_initialization() {
         // Create a keypair
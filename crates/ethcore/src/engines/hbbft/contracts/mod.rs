@@ -1,3 +1,7 @@
+pub mod block_gas_limit;
+pub mod feature_registry;
+pub mod gas_price;
 pub mod keygen_history;
+pub mod random_hbbft;
 pub mod staking;
 pub mod validator_set;
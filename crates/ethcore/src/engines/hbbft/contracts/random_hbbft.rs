@@ -0,0 +1,51 @@
+//! Commits the validators' shared random number to the on-chain randomness contract.
+//!
+//! The random number of a block is the XOR of the random data of all contributions agreed on
+//! for it. The block's creators store it in the header's extra data, so nodes importing the
+//! block can commit the same number: the extra data is covered by the block's seal.
+
+use engines::{hbbft::extra_data::SEED_LEN, EngineError, SystemOrCodeCall, SystemOrCodeCallKind};
+use ethereum_types::{Address, U256};
+
+use_contract!(random_hbbft, "res/contracts/random_hbbft.json");
+
+/// Encodes a random seed as block header extra data.
+pub fn encode_seed(seed: &U256) -> Vec<u8> {
+    let mut bytes = vec![0u8; SEED_LEN];
+    seed.to_big_endian(&mut bytes);
+    bytes
+}
+
+/// Decodes the random seed from block header extra data.
+pub fn decode_seed(extra_data: &[u8]) -> Option<U256> {
+    if extra_data.len() != SEED_LEN {
+        return None;
+    }
+    Some(U256::from_big_endian(extra_data))
+}
+
+/// Sets the current seed of the randomness contract. Must be called by the system address.
+pub fn set_current_seed(
+    caller: &mut SystemOrCodeCall,
+    contract: Address,
+    seed: U256,
+) -> Result<(), EngineError> {
+    let (input, _) = random_hbbft::functions::set_current_seed::call(seed);
+    caller(SystemOrCodeCallKind::Address(contract), input)
+        .map(|_| ())
+        .map_err(EngineError::FailedSystemCall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_roundtrip() {
+        let seed = U256::from(0x1234_5678u64) << 200;
+        let extra_data = encode_seed(&seed);
+        assert_eq!(extra_data.len(), 32);
+        assert_eq!(decode_seed(&extra_data), Some(seed));
+        assert_eq!(decode_seed(b"OpenEthereum"), None);
+    }
+}
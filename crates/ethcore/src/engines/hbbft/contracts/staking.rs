@@ -7,7 +7,7 @@ use types::ids::BlockId;
 use_contract!(staking_contract, "res/contracts/staking_contract.json");
 
 lazy_static! {
-    static ref STAKING_CONTRACT_ADDRESS: Address =
+    pub static ref STAKING_CONTRACT_ADDRESS: Address =
         Address::from_str("1100000000000000000000000000000000000001").unwrap();
 }
 
@@ -35,6 +35,112 @@ pub fn start_time_of_next_phase_transition(client: &dyn EngineClient) -> Result<
     call_const_staking!(c, start_time_of_next_phase_transition)
 }
 
+pub fn is_pool_active(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    staking_address: Address,
+) -> Result<bool, CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    call_const_staking!(c, is_pool_active, staking_address)
+}
+
+/// Returns the IP address the given pool announced for its validator node.
+pub fn get_pool_internet_address(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    staking_address: Address,
+) -> Result<[u8; 16], CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    call_const_staking!(c, get_pool_internet_address, staking_address)
+}
+
+/// Returns the call data of the transaction removing the sender's pool, to be sent from its
+/// staking address.
+pub fn remove_my_pool_call() -> ethabi::Bytes {
+    let (abi_bytes, _) = staking_contract::functions::remove_my_pool::call();
+    abi_bytes
+}
+
+/// Returns the first staking epoch of the staker's current stake in the given pool.
+pub fn stake_first_epoch(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    pool_staking_address: Address,
+    staker: Address,
+) -> Result<U256, CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    call_const_staking!(c, stake_first_epoch, pool_staking_address, staker)
+}
+
+/// Returns whether the staker already claimed its reward of the given staking epoch.
+pub fn reward_was_taken(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    pool_staking_address: Address,
+    staker: Address,
+    staking_epoch: U256,
+) -> Result<bool, CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    call_const_staking!(
+        c,
+        reward_was_taken,
+        pool_staking_address,
+        staker,
+        staking_epoch
+    )
+}
+
+/// Returns the staker's reward for the given staking epochs.
+pub fn get_reward_amount(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    staking_epochs: Vec<U256>,
+    pool_staking_address: Address,
+    staker: Address,
+) -> Result<U256, CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    call_const_staking!(
+        c,
+        get_reward_amount,
+        staking_epochs,
+        pool_staking_address,
+        staker
+    )
+}
+
+/// Returns the amount the staker ordered to withdraw from the given pool, and the staking
+/// epoch it was ordered in.
+pub fn ordered_withdrawal(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    pool_staking_address: Address,
+    staker: Address,
+) -> Result<(U256, U256), CallError> {
+    let c = BoundContract::bind(client, block_id, *STAKING_CONTRACT_ADDRESS);
+    let amount = call_const_staking!(c, ordered_withdraw_amount, pool_staking_address, staker)?;
+    let epoch = call_const_staking!(c, order_withdraw_epoch, pool_staking_address, staker)?;
+    Ok((amount, epoch))
+}
+
+/// Returns the call data of the transaction claiming the sender's rewards of the given staking
+/// epochs from the given pool.
+pub fn claim_reward_call(
+    staking_epochs: Vec<U256>,
+    pool_staking_address: Address,
+) -> ethabi::Bytes {
+    let (abi_bytes, _) =
+        staking_contract::functions::claim_reward::call(staking_epochs, pool_staking_address);
+    abi_bytes
+}
+
+/// Returns the call data of the transaction claiming the sender's ordered withdrawal from the
+/// given pool.
+pub fn claim_ordered_withdraw_call(pool_staking_address: Address) -> ethabi::Bytes {
+    let (abi_bytes, _) =
+        staking_contract::functions::claim_ordered_withdraw::call(pool_staking_address);
+    abi_bytes
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -46,14 +152,6 @@ pub mod tests {
         call_const_staking!(c, candidate_min_stake)
     }
 
-    pub fn is_pool_active(
-        client: &dyn EngineClient,
-        staking_address: Address,
-    ) -> Result<bool, CallError> {
-        let c = BoundContract::bind(client, BlockId::Latest, *STAKING_CONTRACT_ADDRESS);
-        call_const_staking!(c, is_pool_active, staking_address)
-    }
-
     pub fn add_pool(mining_address: Address, mining_public_key: Public) -> ethabi::Bytes {
         let (abi_bytes, _) = staking_contract::functions::add_pool::call(
             mining_address,
@@ -1,7 +1,14 @@
-use client::traits::EngineClient;
+use client::traits::{EngineClient, TransactionRequest};
 use crypto::publickey::Public;
-use engines::hbbft::utils::bound_contract::{BoundContract, CallError};
-use ethereum_types::Address;
+use engines::{
+    hbbft::{
+        api::PoolStatus,
+        contracts::staking::is_pool_active,
+        utils::bound_contract::{BoundContract, CallError},
+    },
+    EngineError, SystemOrCodeCall, SystemOrCodeCallKind,
+};
+use ethereum_types::{Address, U256};
 use std::{collections::BTreeMap, str::FromStr};
 use types::ids::BlockId;
 
@@ -11,7 +18,7 @@ use_contract!(
 );
 
 lazy_static! {
-    static ref VALIDATOR_SET_ADDRESS: Address =
+    pub static ref VALIDATOR_SET_ADDRESS: Address =
         Address::from_str("1000000000000000000000000000000000000001").unwrap();
 }
 
@@ -60,13 +67,55 @@ pub fn mining_by_staking_address(
     call_const_validator!(c, mining_by_staking_address, staking_address.clone())
 }
 
-// pub fn staking_by_mining_address(
-// 	client: &dyn EngineClient,
-// 	mining_address: &Address,
-// ) -> Result<Address, CallError> {
-// 	let c = BoundContract::bind(client, BlockId::Latest, *VALIDATOR_SET_ADDRESS);
-// 	call_const_validator!(c, staking_by_mining_address, mining_address.clone())
-// }
+pub fn staking_by_mining_address(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    mining_address: &Address,
+) -> Result<Address, CallError> {
+    let c = BoundContract::bind(client, block_id, *VALIDATOR_SET_ADDRESS);
+    call_const_validator!(c, staking_by_mining_address, mining_address.clone())
+}
+
+pub fn is_validator_banned(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    mining_address: &Address,
+) -> Result<bool, CallError> {
+    let c = BoundContract::bind(client, block_id, *VALIDATOR_SET_ADDRESS);
+    call_const_validator!(c, is_validator_banned, mining_address.clone())
+}
+
+/// Returns the time since which the validator is marked as available, or zero if it is not.
+pub fn validator_available_since(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    mining_address: &Address,
+) -> Result<U256, CallError> {
+    let c = BoundContract::bind(client, block_id, *VALIDATOR_SET_ADDRESS);
+    call_const_validator!(c, validator_available_since, mining_address.clone())
+}
+
+/// Returns the on-chain standing of the pool the given mining address belongs to.
+pub fn get_pool_status(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    mining_address: &Address,
+) -> Result<PoolStatus, CallError> {
+    let staking_address = staking_by_mining_address(client, block_id, mining_address)?;
+    if staking_address.is_zero() {
+        return Ok(PoolStatus::NoPool);
+    }
+    if is_validator_banned(client, block_id, mining_address)? {
+        return Ok(PoolStatus::Banned);
+    }
+    if !is_pool_active(client, block_id, staking_address)? {
+        return Ok(PoolStatus::Inactive);
+    }
+    if validator_available_since(client, block_id, mining_address)?.is_zero() {
+        return Ok(PoolStatus::Unavailable);
+    }
+    Ok(PoolStatus::Active)
+}
 
 pub fn is_pending_validator(
     client: &dyn EngineClient,
@@ -80,3 +129,77 @@ pub fn get_pending_validators(client: &dyn EngineClient) -> Result<Vec<Address>,
     let c = BoundContract::bind(client, BlockId::Latest, *VALIDATOR_SET_ADDRESS);
     call_const_validator!(c, get_pending_validators)
 }
+
+/// Returns whether the validator set contract accepts a report of `reporter` against `malicious`
+/// for the given block.
+pub fn report_malicious_callable(
+    client: &dyn EngineClient,
+    reporter: Address,
+    malicious: Address,
+    block_number: u64,
+) -> Result<bool, CallError> {
+    let c = BoundContract::bind(client, BlockId::Latest, *VALIDATOR_SET_ADDRESS);
+    let (callable, _) = call_const_validator!(
+        c,
+        report_malicious_callable,
+        reporter,
+        malicious,
+        U256::from(block_number)
+    )?;
+    Ok(callable)
+}
+
+/// Sends a transaction from our mining address reporting the validator as malicious at the given
+/// block.
+pub fn report_malicious(
+    client: &dyn EngineClient,
+    malicious: Address,
+    block_number: u64,
+    gas_price: U256,
+) -> Result<(), CallError> {
+    let full_client = client.as_full_client().ok_or(CallError::NotFullClient)?;
+    let (data, _) =
+        validator_set_hbbft::functions::report_malicious::call(malicious, U256::from(block_number));
+    let transaction = TransactionRequest::call(*VALIDATOR_SET_ADDRESS, data)
+        .gas(U256::from(300_000))
+        .gas_price(gas_price);
+    full_client
+        .transact_silently(transaction)
+        .map_err(|_| CallError::ReturnValueInvalid)
+}
+
+/// Makes the pending validators the current ones, at the end of a POSDAO epoch. Must be called by
+/// the system address.
+pub fn finalize_change(caller: &mut SystemOrCodeCall) -> Result<(), EngineError> {
+    let (input, _) = validator_set_hbbft::functions::finalize_change::call();
+    caller(SystemOrCodeCallKind::Address(*VALIDATOR_SET_ADDRESS), input)
+        .map(|_| ())
+        .map_err(EngineError::FailedSystemCall)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::keccak;
+
+    #[test]
+    fn finalize_change_calls_the_validator_set() {
+        let mut calls = Vec::new();
+        let mut caller = |kind: SystemOrCodeCallKind, input: Vec<u8>| {
+            calls.push((kind, input));
+            Ok::<_, String>(Vec::new())
+        };
+        finalize_change(&mut caller).unwrap();
+        assert_eq!(
+            calls,
+            vec![(
+                SystemOrCodeCallKind::Address(*VALIDATOR_SET_ADDRESS),
+                keccak("finalizeChange()").as_bytes()[..4].to_vec()
+            )]
+        );
+
+        let mut failing =
+            |_: SystemOrCodeCallKind, _: Vec<u8>| Err::<Vec<u8>, _>("reverted".to_string());
+        assert!(finalize_change(&mut failing).is_err());
+    }
+}
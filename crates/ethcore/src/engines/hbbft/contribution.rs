@@ -1,7 +1,14 @@
-use rand_065::{self, distributions::Standard, Rng};
-use rlp::RlpStream;
-use std::time::UNIX_EPOCH;
-use types::transaction::SignedTransaction;
+use ethereum_types::{Address, U256};
+use ethjson::spec::HbbftParams;
+use rand_065::{distributions::Standard, Rng};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, VecDeque},
+    time::UNIX_EPOCH,
+};
+use types::transaction::{SignedTransaction, TypedTransaction};
+
+use super::rng::engine_rng;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
 pub(crate) struct Contribution {
@@ -33,20 +40,15 @@ pub fn unix_now_millis() -> u128 {
 }
 
 impl Contribution {
-    pub fn new(txns: &Vec<SignedTransaction>) -> Self {
-        let ser_txns: Vec<_> = txns
-            .iter()
-            .map(|txn| {
-                let mut s = RlpStream::new();
-                txn.rlp_append(&mut s);
-                s.drain()
-            })
-            .collect();
-        let mut rng = rand_065::thread_rng();
+    pub fn new(txns: &Vec<SignedTransaction>, timestamp: u64) -> Self {
+        // Typed transactions are encoded as their EIP-2718 envelope, not as an RLP string
+        // containing it, so `TypedTransaction::decode` accepts them.
+        let ser_txns: Vec<_> = txns.iter().map(|txn| txn.encode()).collect();
+        let mut rng = engine_rng();
 
         Contribution {
             transactions: ser_txns,
-            timestamp: unix_now_secs(),
+            timestamp,
             random_data: rng
                 .sample_iter(&Standard)
                 .take(RANDOM_BYTES_PER_EPOCH)
@@ -55,19 +57,264 @@ impl Contribution {
     }
 }
 
+/// The default maximum total size of the encoded transactions of a contribution, in bytes.
+pub const DEFAULT_MAX_CONTRIBUTION_BYTES: usize = 2 * 1024 * 1024;
+
+/// The default maximum number of transactions of a contribution. The size limit alone admits
+/// about 20000 minimal transactions.
+pub const DEFAULT_MAX_CONTRIBUTION_TRANSACTIONS: usize = 25_000;
+
+/// The limits of the contributions a validator proposes, and accepts in agreed batches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContributionLimits {
+    /// The maximum number of transactions, including encrypted ones.
+    pub max_transactions: usize,
+    /// The maximum total size of the encoded transactions, in bytes.
+    pub max_bytes: usize,
+    /// The minimum gas price of each transaction. The engine replaces the `minimumGasPrice`
+    /// parameter with the one of the gas price governance contract, if it is configured.
+    pub min_gas_price: U256,
+}
+
+impl Default for ContributionLimits {
+    fn default() -> Self {
+        ContributionLimits {
+            max_transactions: DEFAULT_MAX_CONTRIBUTION_TRANSACTIONS,
+            max_bytes: DEFAULT_MAX_CONTRIBUTION_BYTES,
+            min_gas_price: U256::zero(),
+        }
+    }
+}
+
+impl ContributionLimits {
+    /// Creates the limits configured in the engine parameters.
+    pub fn from_params(params: &HbbftParams) -> Self {
+        ContributionLimits {
+            max_transactions: params
+                .max_contribution_transactions
+                .unwrap_or(DEFAULT_MAX_CONTRIBUTION_TRANSACTIONS),
+            max_bytes: params
+                .max_contribution_bytes
+                .unwrap_or(DEFAULT_MAX_CONTRIBUTION_BYTES),
+            min_gas_price: params.minimum_gas_price.unwrap_or(0).into(),
+        }
+    }
+
+    /// Returns the limits left for the queued transactions once the given entries, e.g.
+    /// encrypted transactions, are part of the contribution.
+    pub fn without(&self, entries: &[Vec<u8>]) -> Self {
+        let bytes: usize = entries.iter().map(Vec::len).sum();
+        ContributionLimits {
+            max_transactions: self.max_transactions.saturating_sub(entries.len()),
+            max_bytes: self.max_bytes.saturating_sub(bytes),
+            min_gas_price: self.min_gas_price,
+        }
+    }
+
+    /// Returns an error describing the exceeded limit if the contribution is too large.
+    pub(crate) fn check(&self, contribution: &Contribution) -> Result<(), String> {
+        let count = contribution.transactions.len();
+        if count > self.max_transactions {
+            return Err(format!(
+                "{} transactions exceed the limit of {}",
+                count, self.max_transactions
+            ));
+        }
+        let bytes: usize = contribution.transactions.iter().map(Vec::len).sum();
+        if bytes > self.max_bytes {
+            return Err(format!(
+                "{} bytes of transactions exceed the limit of {}",
+                bytes, self.max_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns whether the transaction pays at least the minimum gas price.
+    ///
+    /// There is no exception for zero gas price service transactions of certified addresses:
+    /// with a minimum gas price, they are not proposed and are dropped from agreed batches.
+    pub fn is_priced_enough(&self, txn: &TypedTransaction) -> bool {
+        txn.tx().gas_price >= self.min_gas_price
+    }
+}
+
+fn encoded_len(txn: &SignedTransaction) -> usize {
+    txn.encode().len()
+}
+
+/// Selects the transactions to contribute, with at most `max_gas` gas in total and within the
+/// given limits.
+///
+/// Higher gas prices are preferred, but the transactions of each sender stay in nonce order: if
+/// one of them does not fit, none of the sender's later transactions are selected. Ties keep the
+/// order of `txns`. A transaction exceeding a limit on its own is skipped with a warning, and an
+/// underpriced one is skipped silently.
+pub fn select_transactions(
+    txns: &[SignedTransaction],
+    max_gas: U256,
+    limits: &ContributionLimits,
+) -> Vec<SignedTransaction> {
+    let max_bytes = limits.max_bytes;
+    let mut by_sender: BTreeMap<Address, Vec<(usize, &SignedTransaction)>> = BTreeMap::new();
+    for (index, txn) in txns.iter().enumerate() {
+        by_sender
+            .entry(txn.sender())
+            .or_default()
+            .push((index, txn));
+    }
+    let mut queues: BTreeMap<Address, VecDeque<(usize, &SignedTransaction)>> = by_sender
+        .into_iter()
+        .map(|(sender, mut txns)| {
+            txns.sort_by_key(|(_, txn)| txn.tx().nonce);
+            (sender, txns.into())
+        })
+        .collect();
+
+    // The next transaction of every sender, by gas price and then by position in `txns`.
+    let mut heads: BinaryHeap<(U256, Reverse<usize>, Address)> = queues
+        .iter()
+        .filter_map(|(sender, queue)| {
+            let (index, txn) = queue.front()?;
+            Some((txn.tx().gas_price, Reverse(*index), *sender))
+        })
+        .collect();
+
+    let mut selected = Vec::new();
+    let (mut gas, mut bytes) = (U256::zero(), 0);
+    while let Some((_, _, sender)) = heads.pop() {
+        if selected.len() >= limits.max_transactions {
+            break;
+        }
+        let queue = queues
+            .get_mut(&sender)
+            .expect("every head has a queue; qed");
+        let (_, txn) = queue
+            .pop_front()
+            .expect("queues in the heap are not empty; qed");
+        if !limits.is_priced_enough(txn) {
+            continue;
+        }
+        let len = encoded_len(txn);
+        if txn.tx().gas > max_gas || len > max_bytes {
+            warn!(target: "consensus", "Not contributing transaction {} of {}: its gas {} or size {} exceeds the contribution limits.",
+                  txn.hash(), sender, txn.tx().gas, len);
+            continue;
+        }
+        if gas + txn.tx().gas > max_gas || bytes + len > max_bytes {
+            continue;
+        }
+        gas = gas + txn.tx().gas;
+        bytes += len;
+        selected.push(txn.clone());
+        if let Some((index, next)) = queue.front() {
+            heads.push((next.tx().gas_price, Reverse(*index), sender));
+        }
+    }
+    selected
+}
+
 #[cfg(test)]
 mod tests {
-    use crypto::publickey::{Generator, Random};
-    use engines::hbbft::test::create_transactions::create_transaction;
-    use ethereum_types::U256;
-    use types::transaction::{SignedTransaction, TypedTransaction};
+    use super::{select_transactions, ContributionLimits};
+    use crypto::publickey::{Generator, KeyPair, Random};
+    use engines::hbbft::{rng::seed_thread_rng, test::create_transactions::create_transaction};
+    use ethereum_types::{Address, U256};
+    use types::transaction::{
+        AccessListTx, Action, SignedTransaction, Transaction, TypedTransaction,
+    };
+
+    #[test]
+    fn random_data_follows_the_engine_rng() {
+        seed_thread_rng(5);
+        let first = super::Contribution::new(&Vec::new(), 0);
+        seed_thread_rng(5);
+        let second = super::Contribution::new(&Vec::new(), 0);
+        assert_eq!(first.random_data.len(), super::RANDOM_BYTES_PER_EPOCH);
+        assert_eq!(first.random_data, second.random_data);
+    }
+
+    #[test]
+    fn transactions_are_selected_by_gas_price_within_limits() {
+        let (a, b) = (Random.generate(), Random.generate());
+        let txn = |key: &KeyPair, nonce: u64, gas: u64, gas_price: u64| {
+            TypedTransaction::Legacy(Transaction {
+                action: Action::Call(Address::from_low_u64_be(1)),
+                value: U256::zero(),
+                data: vec![],
+                gas: gas.into(),
+                gas_price: gas_price.into(),
+                nonce: nonce.into(),
+            })
+            .sign(key.secret(), None)
+        };
+        let txns = vec![
+            txn(&a, 1, 30_000, 5),
+            txn(&a, 0, 30_000, 1),
+            txn(&b, 0, 30_000, 3),
+            txn(&b, 1, 200_000, 9),
+            txn(&b, 2, 30_000, 9),
+        ];
+        let selected = |max_gas: u64| {
+            select_transactions(&txns, max_gas.into(), &ContributionLimits::default())
+                .iter()
+                .map(|selected| txns.iter().position(|t| t == selected).unwrap())
+                .collect::<Vec<_>>()
+        };
+        // `a`'s first transaction is cheap, so `b`'s comes first. `b`'s second one exceeds the
+        // limit on its own, which also excludes the third one.
+        assert_eq!(selected(100_000), vec![2, 1, 0]);
+        assert_eq!(selected(60_000), vec![2, 1]);
+        assert_eq!(selected(1_000_000), vec![2, 3, 4, 1, 0]);
+        let tiny = ContributionLimits {
+            max_bytes: 10,
+            ..ContributionLimits::default()
+        };
+        assert!(select_transactions(&txns, 1_000_000.into(), &tiny).is_empty());
+        let two = ContributionLimits {
+            max_transactions: 2,
+            ..ContributionLimits::default()
+        };
+        assert_eq!(select_transactions(&txns, 1_000_000.into(), &two).len(), 2);
+        // `a`'s first transaction is below the minimum gas price, which also excludes its second.
+        let floor = ContributionLimits {
+            min_gas_price: 2.into(),
+            ..ContributionLimits::default()
+        };
+        assert_eq!(
+            select_transactions(&txns, 1_000_000.into(), &floor),
+            vec![txns[2].clone(), txns[3].clone(), txns[4].clone()]
+        );
+    }
+
+    #[test]
+    fn oversized_contributions_are_detected() {
+        let keypair = Random.generate();
+        let txns: Vec<_> = (0..3u64)
+            .map(|nonce| create_transaction(&keypair, &U256::from(nonce)))
+            .collect();
+        let contribution = super::Contribution::new(&txns, 0);
+        let bytes: usize = contribution.transactions.iter().map(Vec::len).sum();
+        let limits = |max_transactions, max_bytes| ContributionLimits {
+            max_transactions,
+            max_bytes,
+            ..ContributionLimits::default()
+        };
+        assert_eq!(limits(3, bytes).check(&contribution), Ok(()));
+        assert!(limits(2, bytes).check(&contribution).is_err());
+        assert!(limits(3, bytes - 1).check(&contribution).is_err());
+        assert_eq!(
+            limits(3, bytes).without(&contribution.transactions[..1]),
+            limits(2, bytes - contribution.transactions[0].len())
+        );
+    }
 
     #[test]
     fn test_contribution_serialization() {
         let mut pending: Vec<SignedTransaction> = Vec::new();
         let keypair = Random.generate();
         pending.push(create_transaction(&keypair, &U256::from(1)));
-        let contribution = super::Contribution::new(&pending);
+        let contribution = super::Contribution::new(&pending, super::unix_now_secs());
 
         let deser_txns: Vec<_> = contribution
             .transactions
@@ -82,4 +329,26 @@ mod tests {
             deser_txns.iter().nth(0).unwrap()
         );
     }
+
+    #[test]
+    fn typed_transactions_survive_serialization() {
+        let keypair = Random.generate();
+        let txn = TypedTransaction::AccessList(AccessListTx::new(
+            Transaction {
+                action: Action::Call(Address::from_low_u64_be(1)),
+                value: U256::zero(),
+                data: vec![],
+                gas: 30_000.into(),
+                gas_price: 1.into(),
+                nonce: 0.into(),
+            },
+            vec![(Address::from_low_u64_be(2), vec![Default::default()])],
+        ))
+        .sign(keypair.secret(), Some(1));
+        let contribution = super::Contribution::new(&vec![txn.clone()], 0);
+
+        let decoded = TypedTransaction::decode(&contribution.transactions[0])
+            .expect("typed transactions are decodable");
+        assert_eq!(SignedTransaction::new(decoded).unwrap(), txn);
+    }
 }
@@ -0,0 +1,156 @@
+use crate::{create_miner::json_key_path, rpc::RpcClient};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// The result of a single check.
+enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+}
+
+/// A check of the node and what it found.
+struct Check {
+    outcome: Outcome,
+    message: String,
+}
+
+impl Check {
+    fn pass(message: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Pass,
+            message: message.into(),
+        }
+    }
+
+    fn fail(message: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Fail,
+            message: message.into(),
+        }
+    }
+
+    fn skip(message: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Skip,
+            message: message.into(),
+        }
+    }
+}
+
+/// Normalizes a hex encoded address for comparison.
+fn normalize_address(address: &str) -> String {
+    address.trim_start_matches("0x").to_lowercase()
+}
+
+/// Reads the address of the miner account from the node's JSON keystore.
+fn keystore_address(base_path: &Path) -> Result<String, String> {
+    let path = json_key_path(base_path);
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Could not read the keystore {}: {}", path.display(), e))?;
+    let key: Value = serde_json::from_str(&json)
+        .map_err(|e| format!("The keystore {} is invalid: {}", path.display(), e))?;
+    key["address"]
+        .as_str()
+        .map(normalize_address)
+        .ok_or_else(|| format!("The keystore {} has no address", path.display()))
+}
+
+fn check_signer(status: &Value, base_path: &Path) -> Check {
+    let signer = match status["signer"].as_str() {
+        Some(signer) => normalize_address(signer),
+        None => return Check::fail("The node has no engine signer configured"),
+    };
+    match keystore_address(base_path) {
+        Ok(address) if address == signer => Check::pass(format!(
+            "The engine signer 0x{} matches the keystore",
+            signer
+        )),
+        Ok(address) => Check::fail(format!(
+            "The engine signer 0x{} does not match the keystore address 0x{}",
+            signer, address
+        )),
+        Err(e) => Check::fail(e),
+    }
+}
+
+fn check_availability(status: &Value) -> Check {
+    match status["poolStatus"].as_str() {
+        Some("active") => Check::pass("Availability is announced and the pool is active"),
+        Some("unavailable") => Check::fail("Availability is not announced"),
+        Some("noPool") => Check::fail("The engine signer does not belong to a pool"),
+        Some("inactive") => Check::fail("The pool is not active"),
+        Some("banned") => Check::fail("The validator is banned"),
+        _ => Check::skip("The pool status is unknown"),
+    }
+}
+
+fn check_validator(status: &Value, keygen: Option<&Value>) -> Check {
+    if status["isValidator"].as_bool() == Some(true) {
+        return Check::pass(format!(
+            "The node is a validator of POSDAO epoch {}",
+            status["posdaoEpoch"].as_str().unwrap_or("?")
+        ));
+    }
+    match keygen.and_then(|keygen| keygen["isPendingValidator"].as_bool()) {
+        Some(true) => Check::pass("The node is a pending validator of the next POSDAO epoch"),
+        _ => Check::fail("The node is neither a current nor a pending validator"),
+    }
+}
+
+fn check_keygen(keygen: Result<&Value, &String>) -> Check {
+    let keygen = match keygen {
+        Ok(keygen) => keygen,
+        Err(e) => return Check::fail(format!("Could not read the key generation status: {}", e)),
+    };
+    if keygen["isPendingValidator"].as_bool() != Some(true) {
+        return Check::skip("The node takes no part in a key generation round");
+    }
+    let part = keygen["partConfirmed"].as_bool() == Some(true);
+    let acks = keygen["acksConfirmed"].as_bool() == Some(true);
+    match (part, acks) {
+        (true, true) => Check::pass("The Part and Acks transactions have landed on chain"),
+        (true, false) => Check::fail("The Acks transaction has not landed on chain yet"),
+        (false, _) => Check::fail("The Part transaction has not landed on chain yet"),
+    }
+}
+
+/// Checks a running validator node and prints a report. Returns false if any check failed.
+pub fn check_node(rpc_url: &str, base_path: &Path) -> bool {
+    println!("Checking the dmd v4 node at {}...", rpc_url);
+    let client = match RpcClient::new(rpc_url) {
+        Ok(client) => client,
+        Err(e) => {
+            println!("[FAIL] {}", e);
+            return false;
+        }
+    };
+    let status = match client.call("hbbft_nodeStatus", Value::Array(Vec::new())) {
+        Ok(status) => status,
+        Err(e) => {
+            println!("[FAIL] {}", e);
+            return false;
+        }
+    };
+    let keygen = client.call("hbbft_keygenStatus", Value::Array(Vec::new()));
+
+    let checks = vec![
+        check_signer(&status, base_path),
+        check_availability(&status),
+        check_validator(&status, keygen.as_ref().ok()),
+        check_keygen(keygen.as_ref()),
+    ];
+    let mut healthy = true;
+    for check in &checks {
+        let tag = match check.outcome {
+            Outcome::Pass => " ok ",
+            Outcome::Fail => {
+                healthy = false;
+                "FAIL"
+            }
+            Outcome::Skip => " -- ",
+        };
+        println!("[{}] {}", tag, check.message);
+    }
+    healthy
+}
@@ -1,50 +1,120 @@
-use ethstore::{KeyFile, SafeAccount};
+use ethstore::{Crypto, KeyFile, SafeAccount};
 use parity_crypto::publickey::{Generator, KeyPair, Random, Secret};
-use std::{fs, num::NonZeroU32, path::Path};
-
-fn write_json_for_secret(secret: Secret, filename: &str) {
-    let json_key: KeyFile = SafeAccount::create(
-        &KeyPair::from_secret(secret).unwrap(),
-        [0u8; 16],
-        &"test".into(),
-        NonZeroU32::new(10240).expect("We know 10240 is not zero."),
-        "Test".to_owned(),
-        "{}".to_owned(),
-    )
-    .expect("json key object creation should succeed")
-    .into();
+use std::{
+    fs,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+/// The default number of PBKDF2 iterations the JSON keystore is encrypted with.
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 10240;
+
+/// The key derivation function the JSON keystore is encrypted with.
+#[derive(Clone, Copy, Debug)]
+pub enum KeystoreKdf {
+    Pbkdf2 { iterations: NonZeroU32 },
+    Scrypt { n: u32, p: u32, r: u32 },
+}
+
+impl Default for KeystoreKdf {
+    fn default() -> Self {
+        KeystoreKdf::Pbkdf2 {
+            iterations: NonZeroU32::new(DEFAULT_PBKDF2_ITERATIONS)
+                .expect("The default iterations are not zero."),
+        }
+    }
+}
+
+/// Where and how the miner's keys are stored.
+pub struct KeyOptions {
+    /// The node's base path, containing the network key and the keystore.
+    pub base_path: PathBuf,
+    /// The name of the miner account in the keystore.
+    pub account_name: String,
+    /// The password the JSON keystore is encrypted with.
+    pub password: String,
+    /// The key derivation function the JSON keystore is encrypted with.
+    pub kdf: KeystoreKdf,
+    /// Whether to write the password to `password.txt` for the node's `--password` option.
+    pub write_password_file: bool,
+    /// The directory `password.txt` and `public_key.txt` are written to.
+    pub output_dir: PathBuf,
+}
+
+impl KeyOptions {
+    /// The file the node's network key is written to.
+    pub fn network_key_path(&self) -> PathBuf {
+        self.base_path.join("network").join("key")
+    }
+
+    /// The JSON keystore file of the miner account.
+    pub fn json_key_path(&self) -> PathBuf {
+        json_key_path(&self.base_path)
+    }
+}
+
+/// The JSON keystore file of the miner account of the node with the given base path.
+pub fn json_key_path(base_path: &Path) -> PathBuf {
+    base_path
+        .join("keys")
+        .join("DPoSChain")
+        .join("dmd_miner_key.json")
+}
+
+pub fn write_json_for_secret(secret: Secret, filename: &Path, options: &KeyOptions) {
+    let keypair = KeyPair::from_secret(secret).unwrap();
+    let name = options.account_name.clone();
+    let meta = "{}".to_owned();
+    let account = match options.kdf {
+        KeystoreKdf::Pbkdf2 { iterations } => SafeAccount::create(
+            &keypair,
+            [0u8; 16],
+            &options.password.as_str().into(),
+            iterations,
+            name,
+            meta,
+        ),
+        KeystoreKdf::Scrypt { n, p, r } => {
+            let password = options.password.as_str().into();
+            Crypto::with_secret_scrypt(keypair.secret(), &password, n, p, r)
+                .map(|crypto| SafeAccount::with_crypto(&keypair, [0u8; 16], crypto, name, meta))
+        }
+    };
+    let json_key: KeyFile = account
+        .expect("json key object creation should succeed")
+        .into();
 
     let serialized_json_key =
         serde_json::to_string(&json_key).expect("json key object serialization should succeed");
     fs::write(filename, serialized_json_key).expect("Unable to write json key file");
 }
 
-pub fn create_miner() {
+/// Writes the network key, the JSON keystore and the password file of the given key pair.
+pub fn write_keys(acc: &KeyPair, options: &KeyOptions) {
+    let network_key = options.network_key_path();
+    let json_key = options.json_key_path();
+    for path in &[&network_key, &json_key] {
+        fs::create_dir_all(path.parent().expect("Key files are in a directory"))
+            .expect("Could not create key directory");
+    }
+    // Write the private key for the hbbft node
+    fs::write(network_key, acc.secret().to_hex()).expect("Unable to write the network key file");
+    write_json_for_secret(acc.secret().clone(), &json_key, options);
+    if options.write_password_file {
+        fs::write(options.output_dir.join("password.txt"), &options.password)
+            .expect("Unable to write password.txt file");
+    }
+    fs::write(
+        options.output_dir.join("public_key.txt"),
+        format!("{:?}", acc.public()),
+    )
+    .expect("Unable to write public_key.txt file");
+}
+
+pub fn create_miner(options: &KeyOptions) {
     println!("Creating dmd v4 miner...");
     let acc = Random.generate();
-
-    // Create "data" and "network" subfolders.
-    let network_key_dir = Path::new("./data/network");
-    fs::create_dir_all(network_key_dir).expect("Could not create network key directory");
-    // Write the private key for the hbbft node
-    fs::write(network_key_dir.join("key"), acc.secret().to_hex())
-        .expect("Unable to write the network key file");
-
-    // Create "keys" and "DPoSChain" subfolders.
-    let accounts_dir = Path::new("./data/keys/DPoSChain");
-    fs::create_dir_all(accounts_dir).expect("Could not create accounts directory");
-
-    // Write JSON account.
-    write_json_for_secret(
-        acc.secret().clone(),
-        accounts_dir
-            .join("dmd_miner_key.json")
-            .to_str()
-            .expect("Could not convert the JSON account path to a string"),
-    );
-    fs::write("password.txt", "test").expect("Unable to write password.txt file");
-    fs::write("public_key.txt", format!("{:?}", acc.public()))
-        .expect("Unable to write password.txt file");
+    write_keys(&acc, options);
 
     println!("Miner address: {:?}", acc.address());
     println!("Miner public key: {:?}", acc.public());
@@ -0,0 +1,83 @@
+use crate::create_miner::{write_keys, KeyOptions, KeystoreKdf};
+use hbbft_config_generator::{generate, ConfigType, GeneratorOptions};
+use parity_crypto::publickey::KeyPair;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+/// Returns the directory of the miner with the given index.
+fn miner_dir(dir: &Path, idx: usize) -> PathBuf {
+    dir.join(format!("miner{}", idx))
+}
+
+/// Adapts a generated node config to a miner directory: the chain spec and the reserved peers
+/// are shared by all miners, one level up.
+fn miner_config(toml: &str) -> Result<String, String> {
+    let mut config: Value =
+        toml::from_str(toml).map_err(|e| format!("Invalid node config: {}", e))?;
+    config["parity"]["chain"] = Value::String("../spec.json".into());
+    config["network"]["reserved_peers"] = Value::String("../reserved-peers".into());
+    toml::to_string(&config).map_err(|e| format!("Unable to write the node config: {}", e))
+}
+
+/// Creates the keys and configs of a local network of `num_nodes` miners, which are all initial
+/// validators.
+///
+/// Every miner gets a directory `miner<i>`, laid out like the node directory of `create_miner`,
+/// with a `config.toml` to run the node from it. The reserved peers, the key generation history
+/// and, if a template was given, the chain spec are shared in `dir`.
+pub fn create_network(
+    num_nodes: usize,
+    dir: &Path,
+    spec_template: Option<String>,
+    account_name: &str,
+    password: &str,
+    kdf: KeystoreKdf,
+) -> Result<(), String> {
+    println!("Creating a dmd v4 network of {} miners...", num_nodes);
+    let mut generator_options = GeneratorOptions::new(num_nodes, num_nodes);
+    generator_options.config_type = ConfigType::Docker;
+    generator_options.spec_template = spec_template;
+    let config = generate(&generator_options).map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+    let write = |name: &str, contents: &str| {
+        fs::write(dir.join(name), contents).map_err(|e| format!("Unable to write {}: {}", name, e))
+    };
+    write("reserved-peers", &config.reserved_peers)?;
+    write("keygen_history.json", &config.keygen_history)?;
+    write("nodes_info.json", &config.nodes_info)?;
+    match &config.spec {
+        Some(spec) => write("spec.json", spec)?,
+        None => println!(
+            "No spec template given: add the validators and key generation history of keygen_history.json to the chain spec, and save it as spec.json."
+        ),
+    }
+
+    for node in &config.nodes {
+        let miner_dir = miner_dir(dir, node.enode.idx);
+        let key_options = KeyOptions {
+            base_path: miner_dir.join("data"),
+            account_name: account_name.to_owned(),
+            password: password.to_owned(),
+            kdf,
+            // The node config reads the password from the miner's directory.
+            write_password_file: true,
+            output_dir: miner_dir.clone(),
+        };
+        let acc = KeyPair::from_secret(node.enode.secret.clone())
+            .map_err(|e| format!("Invalid generated key: {}", e))?;
+        write_keys(&acc, &key_options);
+        fs::write(miner_dir.join("config.toml"), miner_config(&node.toml)?)
+            .map_err(|e| format!("Unable to write the node config: {}", e))?;
+        println!(
+            "Miner {}: address {:?}, enode {}",
+            node.enode.idx,
+            node.enode.address,
+            node.enode.to_string()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::{
+    create_miner::{write_keys, KeyOptions},
+    rotate_keys::back_up,
+};
+use ethstore::Crypto;
+use parity_crypto::publickey::{KeyPair, Secret};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Where the imported secret comes from.
+pub enum KeySource<'a> {
+    /// A hex encoded secret, with or without `0x` prefix.
+    Secret(&'a str),
+    /// A JSON keystore and the password it is encrypted with.
+    Keystore { path: &'a Path, password: &'a str },
+}
+
+/// Parses a hex encoded secret.
+fn parse_secret(hex: &str) -> Result<Secret, String> {
+    hex.trim()
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| "The secret must be 32 hex encoded bytes".to_owned())
+}
+
+/// Decrypts the secret of a JSON keystore.
+fn keystore_secret(path: &Path, password: &str) -> Result<Secret, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read the keystore {}: {}", path.display(), e))?;
+    let key: Value = serde_json::from_str(&json)
+        .map_err(|e| format!("The keystore {} is invalid: {}", path.display(), e))?;
+    // Some wallets write the crypto section with a capital letter.
+    let crypto: Crypto = key
+        .get("crypto")
+        .or_else(|| key.get("Crypto"))
+        .ok_or_else(|| format!("The keystore {} has no crypto section", path.display()))?
+        .to_string()
+        .parse()
+        .map_err(|e| format!("The keystore {} is invalid: {}", path.display(), e))?;
+    crypto
+        .secret(&password.into())
+        .map_err(|e| format!("Could not decrypt the keystore {}: {}", path.display(), e))
+}
+
+/// Imports an existing key as the miner's key, laying out the network key and the JSON keystore
+/// in the node's base path like `create_miner`. Existing keys are backed up.
+pub fn import_key(source: KeySource, options: &KeyOptions) -> Result<(), String> {
+    println!("Importing dmd v4 miner key...");
+    let secret = match source {
+        KeySource::Secret(hex) => parse_secret(hex)?,
+        KeySource::Keystore { path, password } => keystore_secret(path, password)?,
+    };
+    let acc = KeyPair::from_secret(secret).map_err(|e| format!("Invalid secret: {}", e))?;
+
+    back_up(&options.network_key_path());
+    back_up(&options.json_key_path());
+    write_keys(&acc, options);
+
+    println!("Miner address: {:?}", acc.address());
+    println!("Miner public key: {:?}", acc.public());
+    Ok(())
+}
@@ -1,7 +1,166 @@
+mod check_node;
 mod create_miner;
+mod create_network;
+mod import_key;
+mod rotate_keys;
+mod rpc;
 
-use clap::{App, AppSettings, SubCommand};
-use create_miner::create_miner;
+use check_node::check_node;
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+use create_miner::{create_miner, KeyOptions, KeystoreKdf, DEFAULT_PBKDF2_ITERATIONS};
+use create_network::create_network;
+use import_key::{import_key, KeySource};
+use rotate_keys::rotate_keys;
+use std::{
+    fs,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// The password used if none is given, only suitable for test setups.
+const TEST_PASSWORD: &str = "test";
+
+fn keystore_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("password")
+            .long("password")
+            .help("The password to encrypt the JSON keystore with")
+            .takes_value(true)
+            .conflicts_with_all(&["password_file", "prompt_password"]),
+        Arg::with_name("password_file")
+            .long("password-file")
+            .help("A file containing the password to encrypt the JSON keystore with")
+            .takes_value(true)
+            .conflicts_with("prompt_password"),
+        Arg::with_name("prompt_password")
+            .long("prompt-password")
+            .help("Prompts for the password to encrypt the JSON keystore with"),
+        Arg::with_name("account_name")
+            .long("account-name")
+            .help("The name of the miner account in the keystore")
+            .takes_value(true)
+            .default_value("Test"),
+        Arg::with_name("kdf")
+            .long("kdf")
+            .help("The key derivation function the JSON keystore is encrypted with")
+            .takes_value(true)
+            .possible_values(&["pbkdf2", "scrypt"])
+            .default_value("pbkdf2"),
+        Arg::with_name("kdf_iterations")
+            .long("kdf-iterations")
+            .help("The number of PBKDF2 iterations")
+            .takes_value(true),
+        Arg::with_name("scrypt_n")
+            .long("scrypt-n")
+            .help("The scrypt CPU/memory cost parameter, a power of two")
+            .takes_value(true)
+            .default_value("262144"),
+        Arg::with_name("scrypt_p")
+            .long("scrypt-p")
+            .help("The scrypt parallelization parameter")
+            .takes_value(true)
+            .default_value("1"),
+        Arg::with_name("scrypt_r")
+            .long("scrypt-r")
+            .help("The scrypt block size parameter")
+            .takes_value(true)
+            .default_value("8"),
+    ]
+}
+
+fn key_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = keystore_args();
+    args.push(
+        Arg::with_name("base_path")
+            .long("base-path")
+            .help("The node's base path the keys are written to")
+            .takes_value(true)
+            .default_value("./data"),
+    );
+    args
+}
+
+fn prompt_password() -> String {
+    let password =
+        rpassword::prompt_password_stdout("Password: ").expect("Unable to read password");
+    let confirmation =
+        rpassword::prompt_password_stdout("Repeat password: ").expect("Unable to read password");
+    if password != confirmation {
+        panic!("Passwords do not match");
+    }
+    password
+}
+
+/// Returns the password given by the arguments, and whether it needs to be written to a file.
+fn password(matches: &ArgMatches) -> (String, bool) {
+    if let Some(password) = matches.value_of("password") {
+        (password.to_owned(), true)
+    } else if let Some(path) = matches.value_of("password_file") {
+        let password = fs::read_to_string(path).expect("Unable to read the password file");
+        // The node ignores the trailing line break of its password file as well.
+        (
+            password.trim_end_matches(&['\r', '\n'][..]).to_owned(),
+            false,
+        )
+    } else if matches.is_present("prompt_password") {
+        (prompt_password(), true)
+    } else {
+        println!("Warning: using the test password. Use --password, --password-file or --prompt-password for production miners.");
+        (TEST_PASSWORD.to_owned(), true)
+    }
+}
+
+/// Returns the value of a numeric argument with a default value.
+fn number_arg(matches: &ArgMatches, name: &str) -> u32 {
+    matches
+        .value_of(name)
+        .expect("has a default value")
+        .parse()
+        .unwrap_or_else(|_| panic!("{} must be a number", name))
+}
+
+/// Returns the key derivation function given by the arguments.
+fn kdf(matches: &ArgMatches) -> KeystoreKdf {
+    match matches.value_of("kdf") {
+        Some("scrypt") => {
+            let n = number_arg(matches, "scrypt_n");
+            if n < 2 || !n.is_power_of_two() {
+                panic!("scrypt_n must be a power of two greater than 1");
+            }
+            KeystoreKdf::Scrypt {
+                n,
+                p: number_arg(matches, "scrypt_p"),
+                r: number_arg(matches, "scrypt_r"),
+            }
+        }
+        _ => {
+            let iterations = matches
+                .value_of("kdf_iterations")
+                .map_or(Some(DEFAULT_PBKDF2_ITERATIONS), |iterations| {
+                    iterations.parse().ok()
+                })
+                .and_then(NonZeroU32::new)
+                .expect("kdf_iterations must be a positive number");
+            KeystoreKdf::Pbkdf2 { iterations }
+        }
+    }
+}
+
+fn key_options(matches: &ArgMatches) -> KeyOptions {
+    let (password, write_password_file) = password(matches);
+    KeyOptions {
+        base_path: PathBuf::from(matches.value_of("base_path").expect("has a default value")),
+        account_name: matches
+            .value_of("account_name")
+            .expect("has a default value")
+            .to_owned(),
+        password,
+        kdf: kdf(matches),
+        write_password_file,
+        output_dir: PathBuf::from("."),
+    }
+}
 
 fn main() {
     let matches = App::new("dmd v4 swiss army knife")
@@ -11,11 +170,173 @@ fn main() {
         .setting(AppSettings::ArgRequiredElseHelp)
         .subcommand(
             SubCommand::with_name("create_miner")
-                .about("Creates the keys and config for a new dmd v4 miner"),
+                .about("Creates the keys and config for a new dmd v4 miner")
+                .args(&key_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("create_network")
+                .about("Creates the keys and configs of a local dmd v4 network, with the reserved peers and key generation history")
+                .args(&keystore_args())
+                .arg(
+                    Arg::with_name("nodes")
+                        .help("The number of miners, which are all initial validators")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output_dir")
+                        .long("output-dir")
+                        .help("The directory the network is created in")
+                        .takes_value(true)
+                        .default_value("."),
+                )
+                .arg(
+                    Arg::with_name("spec_template")
+                        .long("spec-template")
+                        .help("A chain spec to write as spec.json, with the initial validators and their key generation history")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import_key")
+                .about("Imports an existing key as the key of a dmd v4 miner")
+                .args(&key_args())
+                .arg(
+                    Arg::with_name("secret")
+                        .long("secret")
+                        .help("The hex encoded secret to import")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("secret_file")
+                        .long("secret-file")
+                        .help("A file containing the hex encoded secret to import")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keystore")
+                        .long("keystore")
+                        .help("A JSON keystore containing the key to import")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keystore_password_file")
+                        .long("keystore-password-file")
+                        .help("A file containing the password of the imported JSON keystore, prompted for if omitted")
+                        .takes_value(true)
+                        .requires("keystore"),
+                )
+                .group(
+                    ArgGroup::with_name("source")
+                        .args(&["secret", "secret_file", "keystore"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rotate_keys")
+                .about("Replaces the keys of a dmd v4 miner and prints the transaction announcing the new public key")
+                .args(&key_args())
+                .arg(
+                    Arg::with_name("ip")
+                        .long("ip")
+                        .help("The IP address announced for the pool")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check_node")
+                .about("Checks that a running dmd v4 node is set up to take part in the consensus")
+                .arg(
+                    Arg::with_name("rpc")
+                        .long("rpc")
+                        .help("The node's JSON-RPC endpoint")
+                        .takes_value(true)
+                        .default_value("http://127.0.0.1:8545"),
+                )
+                .arg(
+                    Arg::with_name("base_path")
+                        .long("base-path")
+                        .help("The node's base path containing the keystore")
+                        .takes_value(true)
+                        .default_value("./data"),
+                ),
         )
         .get_matches();
 
-    if let Some(_) = matches.subcommand_matches("create_miner") {
-        create_miner();
+    if let Some(matches) = matches.subcommand_matches("create_miner") {
+        create_miner(&key_options(matches));
+    }
+    if let Some(matches) = matches.subcommand_matches("create_network") {
+        let nodes = matches
+            .value_of("nodes")
+            .expect("nodes is required")
+            .parse()
+            .expect("nodes must be a number");
+        let spec_template = matches.value_of("spec_template").map(|path| {
+            fs::read_to_string(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e))
+        });
+        let account_name = matches
+            .value_of("account_name")
+            .expect("has a default value");
+        let (password, _) = password(matches);
+        let dir = PathBuf::from(matches.value_of("output_dir").expect("has a default value"));
+        if let Err(e) = create_network(
+            nodes,
+            &dir,
+            spec_template,
+            account_name,
+            &password,
+            kdf(matches),
+        ) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("import_key") {
+        let secret_file = matches.value_of("secret_file").map(|path| {
+            fs::read_to_string(path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e))
+        });
+        let keystore_password = matches.value_of("keystore").map(|_| {
+            match matches.value_of("keystore_password_file") {
+                Some(path) => fs::read_to_string(path)
+                    .expect("Unable to read the keystore password file")
+                    .trim_end_matches(&['\r', '\n'][..])
+                    .to_owned(),
+                None => rpassword::prompt_password_stdout("Keystore password: ")
+                    .expect("Unable to read password"),
+            }
+        });
+        let source = match (matches.value_of("secret"), &secret_file, &keystore_password) {
+            (Some(secret), _, _) => KeySource::Secret(secret),
+            (None, Some(secret), _) => KeySource::Secret(secret),
+            (None, None, Some(password)) => KeySource::Keystore {
+                path: matches
+                    .value_of("keystore")
+                    .map(Path::new)
+                    .expect("source is required"),
+                password,
+            },
+            (None, None, None) => unreachable!("source is required"),
+        };
+        if let Err(e) = import_key(source, &key_options(matches)) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("rotate_keys") {
+        let ip = matches
+            .value_of("ip")
+            .expect("ip is required")
+            .parse()
+            .expect("ip must be a valid IP address");
+        rotate_keys(&key_options(matches), ip);
+    }
+    if let Some(matches) = matches.subcommand_matches("check_node") {
+        let rpc = matches.value_of("rpc").expect("has a default value");
+        let base_path = matches.value_of("base_path").expect("has a default value");
+        if !check_node(rpc, &PathBuf::from(base_path)) {
+            process::exit(1);
+        }
     }
 }
@@ -0,0 +1,61 @@
+use crate::create_miner::{write_keys, KeyOptions};
+use ethabi::{Contract, Token};
+use parity_crypto::publickey::{Generator, Random};
+use rustc_hex::ToHex;
+use std::{fs, net::IpAddr, path::Path};
+
+/// The address of the staking contract, which stores the public keys and IP addresses of pools.
+const STAKING_CONTRACT_ADDRESS: &str = "0x1100000000000000000000000000000000000001";
+
+/// Moves an existing key file out of the way, so it is not overwritten.
+pub fn back_up(path: &Path) {
+    if path.exists() {
+        let backup = path.with_extension("old");
+        fs::rename(path, &backup).expect("Unable to back up the previous key file");
+        println!("Previous key moved to {}", backup.display());
+    }
+}
+
+/// Returns the call data of `setPoolInfo`, which updates the public key and IP address of the
+/// pool of the transaction's sender.
+fn set_pool_info_call(public_key: &[u8], ip: IpAddr) -> Vec<u8> {
+    let contract =
+        Contract::load(&include_bytes!("../../../../../res/contracts/staking_contract.json")[..])
+            .expect("The staking contract ABI is valid");
+    // The staking contract expects IPv4 addresses mapped into IPv6.
+    let ip = match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    };
+    contract
+        .function("setPoolInfo")
+        .and_then(|function| {
+            function.encode_input(&[
+                Token::Bytes(public_key.to_vec()),
+                Token::FixedBytes(ip.to_vec()),
+            ])
+        })
+        .expect("setPoolInfo call data encoding should succeed")
+}
+
+/// Replaces the miner's keys created by `create_miner` with new ones.
+///
+/// The new public key only becomes effective once the printed transaction is sent from the
+/// pool's staking address. Until then, the node can't take part in the consensus.
+pub fn rotate_keys(options: &KeyOptions, ip: IpAddr) {
+    println!("Rotating dmd v4 miner keys...");
+    let acc = Random.generate();
+
+    back_up(&options.network_key_path());
+    back_up(&options.json_key_path());
+    write_keys(&acc, options);
+
+    let call_data = set_pool_info_call(acc.public().as_bytes(), ip);
+    println!("Miner address: {:?}", acc.address());
+    println!("Miner public key: {:?}", acc.public());
+    println!(
+        "Update the engine_signer in the node config, and send the following transaction from the pool's staking address:"
+    );
+    println!("To: {}", STAKING_CONTRACT_ADDRESS);
+    println!("Data: 0x{}", call_data.to_hex::<String>());
+}
@@ -0,0 +1,93 @@
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+/// A minimal JSON-RPC client for a node's HTTP endpoint.
+pub struct RpcClient {
+    /// The `host:port` of the endpoint.
+    address: String,
+}
+
+impl RpcClient {
+    /// Creates a client for an endpoint given as `http://host:port`.
+    pub fn new(url: &str) -> Result<Self, String> {
+        let address = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("Only http:// RPC endpoints are supported: {}", url))?
+            .trim_end_matches('/');
+        Ok(RpcClient {
+            address: address.to_owned(),
+        })
+    }
+
+    /// Calls the given method and returns its result.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.address,
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect(&self.address)
+            .map_err(|e| format!("Could not connect to {}: {}", self.address, e))?;
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("Could not send the request: {}", e))?;
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|e| format!("Could not read the response: {}", e))?;
+
+        let (head, body) = split(&response, "\r\n\r\n").ok_or("The response has no body")?;
+        if !head.starts_with("HTTP/1.1 200") {
+            return Err(format!(
+                "Unexpected response: {}",
+                head.lines().next().unwrap_or_default()
+            ));
+        }
+        let body = if head.to_lowercase().contains("transfer-encoding: chunked") {
+            dechunk(body)?
+        } else {
+            body.to_owned()
+        };
+        let mut response: Value =
+            serde_json::from_str(&body).map_err(|e| format!("Invalid JSON response: {}", e))?;
+        if let Some(error) = response.get("error") {
+            return Err(format!(
+                "{} failed: {}",
+                method,
+                error["message"].as_str().unwrap_or_default()
+            ));
+        }
+        Ok(response["result"].take())
+    }
+}
+
+/// Splits the text at the first occurrence of the separator.
+fn split<'a>(text: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
+    let index = text.find(separator)?;
+    Some((&text[..index], &text[index + separator.len()..]))
+}
+
+/// Decodes a body sent with chunked transfer encoding.
+fn dechunk(mut body: &str) -> Result<String, String> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = split(body, "\r\n").ok_or("Invalid chunked response")?;
+        let size = usize::from_str_radix(size.trim(), 16).map_err(|_| "Invalid chunk size")?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        decoded.push_str(rest.get(..size).ok_or("Truncated chunk")?);
+        body = rest.get(size + 2..).ok_or("Truncated chunk")?;
+    }
+}
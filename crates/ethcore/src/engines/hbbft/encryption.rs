@@ -0,0 +1,408 @@
+//! Threshold encrypted transactions.
+//!
+//! Honey Badger BFT encrypts contributions until the validators agreed on a batch, but a
+//! validator still sees the plain transactions it proposes, and can order its own transactions
+//! around them. Users can instead encrypt a transaction with the public master key of the
+//! current POSDAO epoch. The validators propose the ciphertext, and only decrypt it together once
+//! the batch containing it is agreed, so nobody knows the transaction before its position in the
+//! block is fixed.
+//!
+//! Ciphertexts are proposed as entries of a contribution's `transactions`, tagged with
+//! [`ENCRYPTED_TRANSACTION_TYPE`]. This keeps the encoding of contributions unchanged: a node
+//! unaware of encrypted transactions fails to decode the entry as a transaction and skips it.
+
+use ethereum_types::H256;
+use hash::keccak;
+use hbbft::{
+    crypto::{Ciphertext, PublicKey},
+    threshold_decrypt::ThresholdDecrypt,
+    NetworkInfo,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::Arc,
+};
+use types::BlockNumber;
+
+use super::{contribution::Contribution, rng::engine_rng, NodeId};
+
+pub use hbbft::threshold_decrypt::{Message, Result};
+
+pub type Step = hbbft::threshold_decrypt::Step<NodeId>;
+
+/// The first byte of a contribution entry holding a ciphertext instead of a transaction.
+///
+/// It is not a valid EIP-2718 transaction type, nor the first byte of a legacy transaction.
+pub const ENCRYPTED_TRANSACTION_TYPE: u8 = 0x7f;
+
+/// The maximum total size of the ciphertexts of a contribution, in bytes.
+pub const MAX_ENCRYPTED_CONTRIBUTION_BYTES: usize = 256 * 1024;
+
+/// The maximum number of ciphertexts waiting to be proposed.
+const MAX_POOLED_CIPHERTEXTS: usize = 1024;
+
+/// The maximum number of decryption shares received before the batch they belong to.
+const MAX_EARLY_SHARES: usize = 10_000;
+
+/// Encrypts the RLP of a signed transaction with the public master key of a POSDAO epoch.
+pub fn encrypt_transaction(key: &PublicKey, transaction: &[u8]) -> Vec<u8> {
+    let ciphertext = key.encrypt_with_rng(&mut engine_rng(), transaction);
+    bincode::serialize(&ciphertext).expect("serializing a ciphertext cannot fail; qed")
+}
+
+/// Decodes a serialized ciphertext, and returns it if it is well formed.
+///
+/// Every validator reaches the same result for the same bytes, so the ciphertexts of a batch
+/// are decrypted or skipped unanimously.
+pub fn parse_ciphertext(bytes: &[u8]) -> Option<Ciphertext> {
+    let ciphertext: Ciphertext = bincode::deserialize(bytes).ok()?;
+    if ciphertext.verify() {
+        Some(ciphertext)
+    } else {
+        None
+    }
+}
+
+/// Returns the contribution entry proposing the given serialized ciphertext.
+pub fn tag(ciphertext: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(ciphertext.len() + 1);
+    entry.push(ENCRYPTED_TRANSACTION_TYPE);
+    entry.extend_from_slice(ciphertext);
+    entry
+}
+
+/// Returns the serialized ciphertext of a contribution entry, if it proposes one.
+pub fn untag(entry: &[u8]) -> Option<&[u8]> {
+    match entry.split_first() {
+        Some((&ENCRYPTED_TRANSACTION_TYPE, ciphertext)) => Some(ciphertext),
+        _ => None,
+    }
+}
+
+/// Returns the well formed ciphertexts proposed in a batch, once each, in contribution order.
+pub fn batch_ciphertexts(contributions: &BTreeMap<NodeId, Contribution>) -> Vec<Ciphertext> {
+    let mut seen = BTreeSet::new();
+    contributions
+        .values()
+        .flat_map(|c| &c.transactions)
+        .filter_map(|entry| untag(entry))
+        .filter(|ciphertext| seen.insert(keccak(ciphertext)))
+        .filter_map(parse_ciphertext)
+        .collect()
+}
+
+/// Ciphertexts submitted to this validator, waiting to be proposed.
+#[derive(Default)]
+pub struct EncryptedPool {
+    ciphertexts: VecDeque<(H256, Vec<u8>)>,
+}
+
+impl EncryptedPool {
+    /// Adds a serialized ciphertext, evicting the oldest one if the pool is full. Returns its
+    /// hash.
+    pub fn insert(&mut self, ciphertext: Vec<u8>) -> H256 {
+        let hash = keccak(&ciphertext);
+        if self.ciphertexts.iter().any(|(h, _)| *h == hash) {
+            return hash;
+        }
+        if self.ciphertexts.len() == MAX_POOLED_CIPHERTEXTS {
+            self.ciphertexts.pop_front();
+        }
+        self.ciphertexts.push_back((hash, ciphertext));
+        hash
+    }
+
+    /// Returns the contribution entries of the oldest ciphertexts, with at most `max_bytes`
+    /// bytes in total.
+    pub fn entries(&self, max_bytes: usize) -> Vec<Vec<u8>> {
+        let mut bytes = 0;
+        self.ciphertexts
+            .iter()
+            .map(|(_, ciphertext)| tag(ciphertext))
+            .take_while(|entry| {
+                bytes += entry.len();
+                bytes <= max_bytes
+            })
+            .collect()
+    }
+
+    /// Removes the ciphertexts that were proposed in an agreed batch.
+    pub fn remove_agreed(&mut self, contributions: &BTreeMap<NodeId, Contribution>) {
+        let agreed: BTreeSet<_> = contributions
+            .values()
+            .flat_map(|c| &c.transactions)
+            .filter_map(|entry| untag(entry))
+            .map(keccak)
+            .collect();
+        self.ciphertexts.retain(|(hash, _)| !agreed.contains(hash));
+    }
+
+    /// Removes all ciphertexts, e.g. because they were encrypted with the key of an epoch that
+    /// is over.
+    pub fn clear(&mut self) {
+        self.ciphertexts.clear();
+    }
+}
+
+/// The decryption of the ciphertexts of one agreed batch.
+struct Decryption {
+    instances: Vec<ThresholdDecrypt<NodeId>>,
+    plaintexts: Vec<Option<Vec<u8>>>,
+}
+
+/// The ongoing decryptions, by the number of the block the batch belongs to.
+#[derive(Default)]
+pub struct Decryptions {
+    ongoing: BTreeMap<BlockNumber, Decryption>,
+    early_shares: BTreeMap<BlockNumber, Vec<(usize, NodeId, Message)>>,
+    early_share_count: usize,
+}
+
+impl Decryptions {
+    /// Returns true if the decryption of the block's ciphertexts was started.
+    pub fn is_started(&self, block_num: BlockNumber) -> bool {
+        self.ongoing.contains_key(&block_num)
+    }
+
+    /// Starts decrypting the ciphertexts of a block's batch, and returns the resulting steps by
+    /// ciphertext index, including those of shares that arrived early.
+    pub fn start(
+        &mut self,
+        block_num: BlockNumber,
+        ciphertexts: Vec<Ciphertext>,
+        network_info: &NetworkInfo<NodeId>,
+    ) -> Vec<(usize, Result<Step>)> {
+        if self.is_started(block_num) {
+            return Vec::new();
+        }
+        let network_info = Arc::new(network_info.clone());
+        let mut steps = Vec::new();
+        let mut decryption = Decryption {
+            instances: Vec::with_capacity(ciphertexts.len()),
+            plaintexts: vec![None; ciphertexts.len()],
+        };
+        for (index, ciphertext) in ciphertexts.into_iter().enumerate() {
+            let mut instance = ThresholdDecrypt::new(network_info.clone());
+            let step = instance
+                .set_ciphertext(ciphertext)
+                .and_then(|()| instance.start_decryption());
+            decryption.instances.push(instance);
+            steps.push((index, step));
+        }
+        self.ongoing.insert(block_num, decryption);
+
+        let early_shares = self.early_shares.remove(&block_num).unwrap_or_default();
+        self.early_share_count -= early_shares.len();
+        for (index, sender_id, message) in early_shares {
+            if let Some(step) = self.handle_message(block_num, index, &sender_id, message) {
+                steps.push((index, step));
+            }
+        }
+        steps
+    }
+
+    /// Handles a decryption share. Returns `None` if the decryption was not started yet: the
+    /// share is kept until it is, unless too many shares are waiting already. Shares of unknown
+    /// ciphertexts are dropped.
+    pub fn handle_message(
+        &mut self,
+        block_num: BlockNumber,
+        index: usize,
+        sender_id: &NodeId,
+        message: Message,
+    ) -> Option<Result<Step>> {
+        match self.ongoing.get_mut(&block_num) {
+            Some(decryption) => Some(
+                decryption
+                    .instances
+                    .get_mut(index)?
+                    .handle_message(sender_id, message),
+            ),
+            None => {
+                if self.early_share_count < MAX_EARLY_SHARES {
+                    self.early_share_count += 1;
+                    self.early_shares
+                        .entry(block_num)
+                        .or_default()
+                        .push((index, *sender_id, message));
+                }
+                None
+            }
+        }
+    }
+
+    /// Records the plaintext of a ciphertext.
+    pub fn record_output(&mut self, block_num: BlockNumber, index: usize, plaintext: Vec<u8>) {
+        if let Some(slot) = self
+            .ongoing
+            .get_mut(&block_num)
+            .and_then(|decryption| decryption.plaintexts.get_mut(index))
+        {
+            *slot = Some(plaintext);
+        }
+    }
+
+    /// Returns the plaintexts of a block's ciphertexts, once all of them are decrypted.
+    pub fn plaintexts(&self, block_num: BlockNumber) -> Option<Vec<Vec<u8>>> {
+        self.ongoing
+            .get(&block_num)?
+            .plaintexts
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Discards the decryptions and early shares of blocks up to the given one.
+    pub fn prune(&mut self, latest: BlockNumber) {
+        self.ongoing = self.ongoing.split_off(&(latest + 1));
+        self.early_shares = self.early_shares.split_off(&(latest + 1));
+        self.early_share_count = self.early_shares.values().map(Vec::len).sum();
+    }
+
+    /// Discards all decryptions and early shares.
+    pub fn clear(&mut self) {
+        self.ongoing.clear();
+        self.early_shares.clear();
+        self.early_share_count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+    use rand_065;
+
+    fn contribution(transactions: Vec<Vec<u8>>) -> Contribution {
+        Contribution {
+            transactions,
+            timestamp: 0,
+            random_data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn entries_are_tagged() {
+        let entry = tag(b"ciphertext");
+        assert_eq!(untag(&entry), Some(&b"ciphertext"[..]));
+        // Legacy and typed transactions are not mistaken for ciphertexts.
+        assert_eq!(untag(&[0xf8, 0x6c]), None);
+        assert_eq!(untag(&[0x02, 0xf8]), None);
+        assert_eq!(untag(&[]), None);
+    }
+
+    #[test]
+    fn pool_deduplicates_and_evicts_oldest() {
+        let mut pool = EncryptedPool::default();
+        let first = pool.insert(vec![0]);
+        assert_eq!(pool.insert(vec![0]), first);
+        assert_eq!(pool.entries(usize::MAX).len(), 1);
+        for i in 1..=MAX_POOLED_CIPHERTEXTS {
+            pool.insert(vec![(i % 256) as u8, (i / 256) as u8]);
+        }
+        assert_eq!(pool.entries(usize::MAX).len(), MAX_POOLED_CIPHERTEXTS);
+        assert_eq!(pool.entries(3), vec![tag(&[1, 0])]);
+
+        pool.remove_agreed(
+            &vec![(NodeId::default(), contribution(vec![tag(&[1, 0])]))]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(pool.entries(usize::MAX).len(), MAX_POOLED_CIPHERTEXTS - 1);
+        assert_eq!(pool.entries(3), vec![tag(&[2, 0])]);
+    }
+
+    fn network(size: u64) -> BTreeMap<NodeId, NetworkInfo<NodeId>> {
+        let ids = (0..size).map(|i| NodeId(H512::from_low_u64_be(i)));
+        NetworkInfo::generate_map(ids, &mut rand_065::thread_rng())
+            .expect("NetworkInfo generation is expected to always succeed")
+    }
+
+    #[test]
+    fn malformed_ciphertexts_are_skipped() {
+        let netinfos = network(1);
+        let (id, netinfo) = netinfos.iter().next().unwrap();
+        let valid = encrypt_transaction(&netinfo.public_key_set().public_key(), b"transaction");
+        let mut corrupted = valid.clone();
+        corrupted.pop();
+
+        let contributions = vec![
+            (*id, contribution(vec![tag(&valid), tag(&corrupted)])),
+            (NodeId::default(), contribution(vec![tag(&valid)])),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(batch_ciphertexts(&contributions).len(), 1);
+    }
+
+    #[test]
+    fn validators_decrypt_together() {
+        let netinfos = network(4);
+        let key = netinfos
+            .values()
+            .next()
+            .unwrap()
+            .public_key_set()
+            .public_key();
+        let ciphertext = parse_ciphertext(&encrypt_transaction(&key, b"transaction")).unwrap();
+
+        let mut decryptions: BTreeMap<NodeId, Decryptions> = BTreeMap::new();
+        let mut steps = Vec::new();
+        for (id, netinfo) in &netinfos {
+            let mut decryption = Decryptions::default();
+            for (index, step) in decryption.start(1, vec![ciphertext.clone()], netinfo) {
+                steps.push((*id, index, step.unwrap()));
+            }
+            decryptions.insert(*id, decryption);
+        }
+        while let Some((id, index, step)) = steps.pop() {
+            for plaintext in step.output {
+                decryptions
+                    .get_mut(&id)
+                    .unwrap()
+                    .record_output(1, index, plaintext);
+            }
+            for msg in step.messages {
+                for (other, decryption) in decryptions.iter_mut().filter(|(other, _)| **other != id)
+                {
+                    let step = decryption.handle_message(1, index, &id, msg.message.clone());
+                    steps.push((*other, index, step.unwrap().unwrap()));
+                }
+            }
+        }
+        for decryption in decryptions.values() {
+            assert_eq!(
+                decryption.plaintexts(1),
+                Some(vec![b"transaction".to_vec()])
+            );
+        }
+    }
+
+    #[test]
+    fn early_shares_are_replayed() {
+        let netinfos = network(4);
+        let mut ids = netinfos.keys();
+        let (first, second) = (*ids.next().unwrap(), *ids.next().unwrap());
+        let key = netinfos[&first].public_key_set().public_key();
+        let ciphertext = parse_ciphertext(&encrypt_transaction(&key, b"transaction")).unwrap();
+
+        let mut early = Decryptions::default();
+        let mut sender = Decryptions::default();
+        let (_, step) = sender
+            .start(1, vec![ciphertext.clone()], &netinfos[&first])
+            .pop()
+            .unwrap();
+        let message = step.unwrap().messages.pop().unwrap().message;
+        assert!(early.handle_message(1, 0, &first, message).is_none());
+
+        let steps = early.start(1, vec![ciphertext], &netinfos[&second]);
+        // Our own share and the replayed one.
+        assert_eq!(steps.len(), 2);
+        assert!(steps
+            .into_iter()
+            .all(|(index, step)| index == 0 && step.is_ok()));
+
+        early.prune(1);
+        assert!(!early.is_started(1));
+    }
+}
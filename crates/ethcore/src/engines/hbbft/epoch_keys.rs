@@ -0,0 +1,343 @@
+//! Seal verification from block headers alone.
+//!
+//! A full node verifies the seal of a block with the public key set of its parent's POSDAO epoch,
+//! which it derives by replaying the epoch's key generation from the key generation history
+//! contract. Verifying from headers alone, e.g. when restoring a snapshot, the engine's epoch
+//! verifier uses the epoch keys instead: per POSDAO epoch, the public master key, the epoch's
+//! start block, and the validators, which may seal recovery blocks.
+//!
+//! Epoch keys are exchanged as epoch transition proofs, carrying the ECDSA signatures of
+//! validators over the key. The engine only builds an epoch verifier from a proof whose key is
+//! registered, and a key is only registered once more than two thirds of the validators
+//! of the previously registered epoch signed it, so each registered key vouches for the next one.
+//! The first registered key is the trust anchor: it is signed by its own validators, so it must
+//! come from a trusted source, e.g. a synced full node or a snapshot. Registered keys must cover
+//! every epoch from the first header to verify on: a header is verified with the key of the
+//! latest epoch starting before it.
+
+use crypto::publickey::{public_to_address, recover, Public, Signature as EcdsaSignature};
+use engines::EpochVerifier;
+use error::{BlockError, Error};
+use ethereum_types::{Address, H256, H520};
+use hash::keccak;
+use hbbft::crypto::{PublicKey, Signature, PK_SIZE};
+use machine::EthereumMachine;
+use rlp::{self, DecoderError, Rlp, RlpStream};
+use std::collections::{BTreeMap, BTreeSet};
+use types::{header::Header, BlockNumber};
+
+use super::{recovery, sealing::RlpSig};
+
+/// The key that seals the blocks of a POSDAO epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochKey {
+    /// The POSDAO epoch.
+    pub posdao_epoch: u64,
+    /// The block in which the epoch started. It is sealed with the previous epoch's key.
+    pub start_block: BlockNumber,
+    /// The public master key of the epoch's validators.
+    pub public_key: PublicKey,
+    /// The epoch's validators, by mining address.
+    pub validators: BTreeMap<Address, Public>,
+}
+
+impl EpochKey {
+    /// Encodes the key as an epoch transition proof.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new_list(4);
+        s.append(&self.posdao_epoch);
+        s.append(&self.start_block);
+        s.append(&self.public_key.to_bytes().to_vec());
+        s.begin_list(self.validators.len());
+        for (address, public) in &self.validators {
+            s.begin_list(2).append(address).append(public);
+        }
+        s.out()
+    }
+
+    /// Decodes a key encoded as an epoch transition proof.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(bytes);
+        let key_bytes: Vec<u8> = rlp.val_at(2)?;
+        if key_bytes.len() != PK_SIZE {
+            return Err(DecoderError::RlpInvalidLength);
+        }
+        let mut public_key = [0u8; PK_SIZE];
+        public_key.copy_from_slice(&key_bytes);
+        let public_key = PublicKey::from_bytes(public_key)
+            .map_err(|_| DecoderError::Custom("invalid public master key"))?;
+        let validators = rlp
+            .at(3)?
+            .iter()
+            .map(|v| Ok((v.val_at(0)?, v.val_at(1)?)))
+            .collect::<Result<_, DecoderError>>()?;
+        Ok(EpochKey {
+            posdao_epoch: rlp.val_at(0)?,
+            start_block: rlp.val_at(1)?,
+            public_key,
+            validators,
+        })
+    }
+
+    /// The hash the validators sign to vouch for this key.
+    pub fn hash(&self) -> H256 {
+        keccak(self.encode())
+    }
+
+    /// Checks the seal of a block of this epoch: the threshold signature of the validators, or
+    /// the signature of the designated validator if it is a recovery block.
+    ///
+    /// Unlike a full node, this cannot check the timestamp of a recovery block against its parent.
+    pub fn verify_seal(&self, header: &Header) -> Result<(), BlockError> {
+        if header.number() <= self.start_block {
+            return Err(BlockError::InvalidSeal);
+        }
+        if header.seal().len() != 1 {
+            return Err(BlockError::InvalidSeal);
+        }
+        let seal = &header.seal()[0];
+        let valid = match recovery::decode_recovery_seal(seal) {
+            Some(signature) => recovery::is_signed_by_designated_proposer(
+                &self.validators,
+                header.number(),
+                &header.bare_hash(),
+                &signature,
+            ),
+            None => match rlp::decode::<RlpSig<Signature>>(seal) {
+                Ok(RlpSig(signature)) => self.public_key.verify(&signature, header.bare_hash()),
+                Err(_) => false,
+            },
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(BlockError::InvalidSeal)
+        }
+    }
+}
+
+impl EpochVerifier<EthereumMachine> for EpochKey {
+    fn verify_light(&self, header: &Header) -> Result<(), Error> {
+        self.verify_seal(header).map_err(Into::into)
+    }
+}
+
+/// An epoch key with the signatures of validators vouching for it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochKeyProof {
+    /// The signed key.
+    pub key: EpochKey,
+    /// The validators' signatures over the key's hash.
+    pub signatures: Vec<EcdsaSignature>,
+}
+
+impl EpochKeyProof {
+    /// Encodes the proof as an epoch transition proof.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new_list(2);
+        s.append(&self.key.encode());
+        s.begin_list(self.signatures.len());
+        for signature in &self.signatures {
+            s.append(&H520::from(signature.clone()));
+        }
+        s.out()
+    }
+
+    /// Decodes a proof encoded as an epoch transition proof.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(bytes);
+        let key_bytes: Vec<u8> = rlp.val_at(0)?;
+        let signatures = rlp
+            .at(1)?
+            .iter()
+            .map(|s| s.as_val::<H520>().map(Into::into))
+            .collect::<Result<_, DecoderError>>()?;
+        Ok(EpochKeyProof {
+            key: EpochKey::decode(&key_bytes)?,
+            signatures,
+        })
+    }
+
+    /// Returns the mining addresses of the signers, or an error if a signature is invalid.
+    fn signers(&self) -> Result<BTreeSet<Address>, String> {
+        let hash = self.key.hash();
+        self.signatures
+            .iter()
+            .map(|signature| {
+                recover(signature, &hash)
+                    .map(|public| public_to_address(&public))
+                    .map_err(|e| format!("Invalid signature in the epoch key proof: {}", e))
+            })
+            .collect()
+    }
+}
+
+/// Epoch keys by the block their epoch started in.
+#[derive(Default)]
+pub struct EpochKeyRegistry {
+    keys: BTreeMap<BlockNumber, EpochKey>,
+    /// Keys still lacking signatures, by hash, with the validators that signed them so far.
+    pending: BTreeMap<H256, (EpochKey, BTreeSet<Address>)>,
+}
+
+impl EpochKeyRegistry {
+    /// Registers the key of a POSDAO epoch, replacing a key with the same start block.
+    pub fn insert(&mut self, key: EpochKey) {
+        self.keys.insert(key.start_block, key);
+    }
+
+    /// Adds the signatures of the given proof to its key, and registers the key once more than two
+    /// thirds of the validators of the latest registered epoch before it signed it. Without such
+    /// an epoch, the key's own validators must sign it. Returns whether the key is registered.
+    ///
+    /// Fails if a signature is invalid or not created by one of these validators, e.g. because
+    /// the key was forged or altered.
+    pub fn add_proof(&mut self, proof: EpochKeyProof) -> Result<bool, String> {
+        let signers = proof.signers()?;
+        let key = proof.key;
+        if self.keys.get(&key.start_block) == Some(&key) {
+            return Ok(true);
+        }
+        let trusted = match self.key_for(key.start_block) {
+            Some(previous) => &previous.validators,
+            None => &key.validators,
+        };
+        if let Some(unknown) = signers.iter().find(|signer| !trusted.contains_key(signer)) {
+            return Err(format!(
+                "The epoch key proof is signed by {:?}, which is not a trusted validator.",
+                unknown
+            ));
+        }
+        let trusted_count = trusted.len();
+        let hash = key.hash();
+        let signed = {
+            let (_, signed) = self
+                .pending
+                .entry(hash)
+                .or_insert_with(|| (key, BTreeSet::new()));
+            signed.extend(signers);
+            signed.len()
+        };
+        if signed * 3 <= trusted_count * 2 {
+            return Ok(false);
+        }
+        if let Some((key, _)) = self.pending.remove(&hash) {
+            self.insert(key);
+        }
+        Ok(true)
+    }
+
+    /// Returns the key of the latest epoch that started before the given block.
+    pub fn key_for(&self, block_number: BlockNumber) -> Option<&EpochKey> {
+        self.keys
+            .range(..block_number)
+            .next_back()
+            .map(|(_, key)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{sign, Generator, KeyPair, Random};
+    use hbbft::crypto::SecretKey;
+    use rand_065::Rng;
+
+    fn epoch_key(posdao_epoch: u64, start_block: BlockNumber, secret: &SecretKey) -> EpochKey {
+        let validator = Random.generate();
+        EpochKey {
+            posdao_epoch,
+            start_block,
+            public_key: secret.public_key(),
+            validators: vec![(public_to_address(validator.public()), *validator.public())]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn sealed_header(number: BlockNumber, secret: &SecretKey) -> Header {
+        let mut header = Header::default();
+        header.set_number(number);
+        let signature = secret.sign(header.bare_hash());
+        header.set_seal(vec![rlp::encode(&RlpSig(&signature))]);
+        header
+    }
+
+    #[test]
+    fn epoch_keys_roundtrip() {
+        let secret: SecretKey = rand_065::thread_rng().gen();
+        let key = epoch_key(3, 120, &secret);
+        assert_eq!(EpochKey::decode(&key.encode()), Ok(key));
+        assert!(EpochKey::decode(&[0xc0]).is_err());
+    }
+
+    fn signed_proof(key: &EpochKey, signers: &[KeyPair]) -> EpochKeyProof {
+        EpochKeyProof {
+            key: key.clone(),
+            signatures: signers
+                .iter()
+                .map(|signer| sign(signer.secret(), &key.hash()).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn forged_epoch_key_proofs_are_rejected() {
+        let mut rng = rand_065::thread_rng();
+        let validators: Vec<KeyPair> = (0..4).map(|_| Random.generate()).collect();
+        let mut anchor = epoch_key(1, 10, &rng.gen());
+        anchor.validators = validators
+            .iter()
+            .map(|v| (public_to_address(v.public()), *v.public()))
+            .collect();
+        let mut registry = EpochKeyRegistry::default();
+        // Three of four validators are needed.
+        let proof = signed_proof(&anchor, &validators[..2]);
+        assert_eq!(EpochKeyProof::decode(&proof.encode()), Ok(proof.clone()));
+        assert_eq!(registry.add_proof(proof), Ok(false));
+        assert!(registry.key_for(11).is_none());
+        assert_eq!(
+            registry.add_proof(signed_proof(&anchor, &validators[2..3])),
+            Ok(true)
+        );
+        assert_eq!(registry.key_for(11), Some(&anchor));
+
+        // The next key must be signed by the anchor's validators, not its own.
+        let next = epoch_key(2, 20, &rng.gen());
+        let outsiders: Vec<KeyPair> = (0..3).map(|_| Random.generate()).collect();
+        assert!(registry.add_proof(signed_proof(&next, &outsiders)).is_err());
+
+        // A key altered after signing does not match the signatures.
+        let mut forged = signed_proof(&next, &validators[..3]);
+        forged.key.public_key = rng.gen::<SecretKey>().public_key();
+        assert!(registry.add_proof(forged).is_err());
+        assert_eq!(registry.key_for(21), Some(&anchor));
+
+        assert_eq!(
+            registry.add_proof(signed_proof(&next, &validators[1..])),
+            Ok(true)
+        );
+        assert_eq!(registry.key_for(21), Some(&next));
+    }
+
+    #[test]
+    fn seals_are_verified_with_the_key_of_their_epoch() {
+        let mut rng = rand_065::thread_rng();
+        let (first, second): (SecretKey, SecretKey) = (rng.gen(), rng.gen());
+        let mut registry = EpochKeyRegistry::default();
+        registry.insert(epoch_key(1, 10, &first));
+        registry.insert(epoch_key(2, 20, &second));
+
+        assert!(registry.key_for(10).is_none());
+        // The start block of an epoch is still sealed with the previous epoch's key.
+        let header = sealed_header(20, &first);
+        assert_eq!(registry.key_for(20).unwrap().verify_seal(&header), Ok(()));
+        let header = sealed_header(21, &first);
+        assert_eq!(
+            registry.key_for(21).unwrap().verify_seal(&header),
+            Err(BlockError::InvalidSeal)
+        );
+        let header = sealed_header(21, &second);
+        assert_eq!(registry.key_for(21).unwrap().verify_seal(&header), Ok(()));
+    }
+}
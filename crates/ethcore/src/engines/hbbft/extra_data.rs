@@ -0,0 +1,100 @@
+//! The layout of the extra data of blocks created by the hbbft engine.
+//!
+//! The extra data is covered by the block's seal, and carries what importing nodes need to make
+//! the same system calls as the block's creators:
+//!
+//! | Bytes                  | Content                                   | Present                                      |
+//! |------------------------|-------------------------------------------|----------------------------------------------|
+//! | `0..32`                | The agreed random seed, big endian        | If `randomnessContractAddress` is set        |
+//! | `32..32 + ceil(n / 8)` | One contributor bit per sorted validator  | From `blockRewardContributorsTransition` on  |
+//!
+//! Without a randomness contract, the contributor bits start at byte 0. Bit `i % 8` of byte
+//! `i / 8` is set if the `i`-th of the `n` validators contributed to the block's batch.
+//! `maximumExtraDataSize` must fit both for the largest validator set, see `size`.
+
+/// The length of the random seed.
+pub const SEED_LEN: usize = 32;
+
+/// The length of the contributor bit field of the given number of validators.
+pub fn contributors_len(validators: usize) -> usize {
+    (validators + 7) / 8
+}
+
+/// The length of the extra data of a block with the given number of validators.
+pub fn size(has_seed: bool, has_contributors: bool, validators: usize) -> usize {
+    let seed_len = if has_seed { SEED_LEN } else { 0 };
+    let contributors_len = if has_contributors {
+        contributors_len(validators)
+    } else {
+        0
+    };
+    seed_len + contributors_len
+}
+
+/// Splits a block's extra data into the random seed and the contributor bit field. The seed is
+/// the whole extra data if the block carries no contributors.
+pub fn split(extra_data: &[u8], has_seed: bool, has_contributors: bool) -> (&[u8], &[u8]) {
+    let seed_len = if has_seed { SEED_LEN } else { 0 };
+    if !has_contributors || extra_data.len() < seed_len {
+        return (extra_data, &[]);
+    }
+    extra_data.split_at(seed_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engines::hbbft::{
+        block_reward_hbbft::encode_contributors, contracts::random_hbbft::encode_seed,
+        test::hbbft_test_client::hbbft_spec_from_json,
+    };
+    use error::{BlockError, Error, ErrorKind};
+    use ethereum_types::{Address, U256};
+    use std::collections::BTreeSet;
+    use test_helpers::create_test_block;
+    use types::header::Header;
+    use verification::{queue::kind::blocks::Unverified, verify_block_basic};
+
+    #[test]
+    fn seed_and_contributors_pass_basic_verification() {
+        let validators: Vec<Address> = (1..=25).map(Address::from_low_u64_be).collect();
+        let contributors: BTreeSet<Address> = validators.iter().step_by(2).cloned().collect();
+        let seed = U256::from(0x1234_5678u64) << 100;
+        let mut extra_data = encode_seed(&seed);
+        extra_data.extend(encode_contributors(&validators, &contributors));
+        assert_eq!(extra_data.len(), size(true, true, validators.len()));
+        assert_eq!(
+            split(&extra_data, true, true),
+            (&extra_data[..SEED_LEN], &extra_data[SEED_LEN..])
+        );
+
+        let mut spec: serde_json::Value = serde_json::from_slice(include_bytes!(
+            "../../../res/chainspec/honey_badger_bft.json"
+        ))
+        .expect("Chain spec must be valid JSON.");
+        let params = &mut spec["engine"]["hbbft"]["params"];
+        params["randomnessContractAddress"] = "0x3000000000000000000000000000000000000001".into();
+        params["blockRewardContributorsTransition"] = 0u64.into();
+        spec["params"]["maximumExtraDataSize"] = format!("{:#x}", extra_data.len()).into();
+        let spec = hbbft_spec_from_json(&spec);
+
+        let mut header = Header::new();
+        header.set_number(1);
+        header.set_gas_limit(*spec.genesis_header().gas_limit());
+        header.set_timestamp(spec.genesis_header().timestamp() + 1);
+        header.set_extra_data(extra_data.clone());
+        let unverified = Unverified::from_rlp(create_test_block(&header))
+            .expect("Test block must be valid RLP.");
+        assert!(verify_block_basic(&unverified, &*spec.engine, false).is_ok());
+
+        // One more byte exceeds the limit the layout was checked against.
+        extra_data.push(0);
+        header.set_extra_data(extra_data);
+        let unverified = Unverified::from_rlp(create_test_block(&header))
+            .expect("Test block must be valid RLP.");
+        match verify_block_basic(&unverified, &*spec.engine, false) {
+            Err(Error(ErrorKind::Block(BlockError::ExtraDataOutOfBounds(_)), _)) => (),
+            other => panic!("Expected ExtraDataOutOfBounds, got {:?}", other),
+        }
+    }
+}
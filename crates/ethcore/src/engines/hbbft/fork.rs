@@ -0,0 +1,118 @@
+//! Detection of competing blocks.
+//!
+//! Regular hbbft blocks are sealed with the threshold signature of the validators over the block
+//! they agreed on, so there is at most one threshold-sealed block per height. Two different
+//! threshold-sealed blocks at the same height mean that the epoch keys were compromised or that
+//! there is a serious bug. The engine reports them, and picks one of them by a rule all nodes
+//! agree on, so the network does not split further.
+//!
+//! A recovery block, sealed by a single designated validator, may legitimately compete with a
+//! threshold-sealed block that arrives late. That is no evidence of compromised keys, and the
+//! threshold-sealed block, backed by the whole validator set, is always preferred.
+
+use super::recovery::is_recovery_seal;
+use engines::ForkChoice;
+use types::header::{ExtendedHeader, Header};
+
+/// Returns true if the new block competes with the best block: both are at the same height.
+pub fn is_competing(new: &ExtendedHeader, best: &ExtendedHeader) -> bool {
+    new.header.number() == best.header.number() && new.header.hash() != best.header.hash()
+}
+
+/// Returns true if the block is a recovery block, sealed by a single designated validator.
+pub fn is_recovery_block(header: &Header) -> bool {
+    header
+        .seal()
+        .first()
+        .map_or(false, |seal| is_recovery_seal(seal))
+}
+
+/// Returns true if competing blocks are evidence of compromised epoch keys: neither of them is a
+/// recovery block.
+pub fn is_evidence(new: &ExtendedHeader, best: &ExtendedHeader) -> bool {
+    !is_recovery_block(&new.header) && !is_recovery_block(&best.header)
+}
+
+/// Chooses between competing blocks: a threshold-sealed block over a recovery block, otherwise
+/// the block with the higher total difficulty, or the one with the lower hash if both are equal.
+pub fn choose(new: &ExtendedHeader, best: &ExtendedHeader) -> ForkChoice {
+    match (
+        is_recovery_block(&new.header),
+        is_recovery_block(&best.header),
+    ) {
+        (false, true) => return ForkChoice::New,
+        (true, false) => return ForkChoice::Old,
+        _ => (),
+    }
+    let new_score = new.total_score();
+    let best_score = best.total_score();
+    if new_score > best_score || (new_score == best_score && new.header.hash() < best.header.hash())
+    {
+        ForkChoice::New
+    } else {
+        ForkChoice::Old
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::recovery::encode_recovery_seal, *};
+    use crypto::publickey::{sign, Generator, Random};
+    use ethereum_types::U256;
+    use rlp;
+
+    fn extended(number: u64, timestamp: u64) -> ExtendedHeader {
+        let mut header = Header::new();
+        header.set_number(number);
+        header.set_timestamp(timestamp);
+        ExtendedHeader {
+            header,
+            is_finalized: false,
+            parent_total_difficulty: U256::from(number),
+        }
+    }
+
+    #[test]
+    fn only_blocks_at_the_same_height_compete() {
+        let block = extended(5, 1);
+        assert!(!is_competing(&block, &block));
+        assert!(!is_competing(&extended(6, 1), &block));
+        assert!(is_competing(&extended(5, 2), &block));
+    }
+
+    #[test]
+    fn all_nodes_choose_the_same_block() {
+        let a = extended(5, 1);
+        let b = extended(5, 2);
+        let (lower, higher) = if a.header.hash() < b.header.hash() {
+            (&a, &b)
+        } else {
+            (&b, &a)
+        };
+        assert_eq!(choose(lower, higher), ForkChoice::New);
+        assert_eq!(choose(higher, lower), ForkChoice::Old);
+        assert!(is_evidence(lower, higher));
+    }
+
+    #[test]
+    fn threshold_block_is_preferred_over_recovery_block() {
+        let threshold_seal = rlp::encode(&vec![1u8; 96]);
+        let key = Random.generate();
+        // Try both hash orders, the tie-break must not matter.
+        for timestamp in 2..4 {
+            let mut threshold = extended(5, 1);
+            threshold.header.set_seal(vec![threshold_seal.clone()]);
+            let mut recovery = extended(5, timestamp);
+            let signature = sign(key.secret(), &recovery.header.bare_hash()).unwrap();
+            recovery
+                .header
+                .set_seal(vec![encode_recovery_seal(&signature)]);
+
+            assert!(is_competing(&threshold, &recovery));
+            assert!(!is_evidence(&threshold, &recovery));
+            assert!(!is_evidence(&recovery, &threshold));
+            assert_eq!(choose(&threshold, &recovery), ForkChoice::New);
+            assert_eq!(choose(&recovery, &threshold), ForkChoice::Old);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Adds nodes to a network generated before.
+//!
+//! The keys of the existing nodes are not needed: the network is read from its key generation
+//! history and its reserved peers. The new nodes get the next free indices and ports. They
+//! are not initial validators, since the initial key generation is part of the existing chain
+//! spec, but can become validators by staking.
+
+use super::{
+    check_node_options, generate_enodes,
+    keygen_history_helpers::{internet_address, KeyGenHistoryData, KeygenHistoryStats},
+    node_configs, rpc_node_toml, GeneratorError, GeneratorOptions, NetworkConfig,
+};
+use parity_crypto::publickey::{public_to_address, Address, Public};
+use std::{fmt::Write, str::FromStr};
+
+/// A node of the network to extend, as listed in its reserved peers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peer {
+    /// The node's public key.
+    pub public: Public,
+    /// The IP address or host name the node is reachable at.
+    pub ip: String,
+    /// The node's devp2p port.
+    pub port: u16,
+}
+
+impl FromStr for Peer {
+    type Err = GeneratorError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || GeneratorError::ReservedPeer(line.to_owned());
+        let enode = line.trim().strip_prefix("enode://").ok_or_else(invalid)?;
+        let at = enode.find('@').ok_or_else(invalid)?;
+        let address = &enode[at + 1..];
+        let colon = address.rfind(':').ok_or_else(invalid)?;
+        Ok(Peer {
+            public: Public::from_str(&enode[..at]).map_err(|_| invalid())?,
+            ip: address[..colon].to_owned(),
+            port: address[colon + 1..].parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A network generated before, read from its `keygen_history.json` and `reserved-peers` files.
+#[derive(Clone, Debug)]
+pub struct ExistingNetwork {
+    /// The initial validators and their key generation history.
+    pub keygen_history: KeyGenHistoryData,
+    /// All nodes, in the order of the reserved peers.
+    pub peers: Vec<Peer>,
+}
+
+impl ExistingNetwork {
+    /// Parses the contents of the network's key generation history and reserved peers files.
+    pub fn parse(keygen_history: &str, reserved_peers: &str) -> Result<Self, GeneratorError> {
+        let keygen_history: KeyGenHistoryData = serde_json::from_str(keygen_history)
+            .map_err(|e| GeneratorError::KeygenHistory(e.to_string()))?;
+        let num_validators = keygen_history.validators.len();
+        if keygen_history.public_keys.len() != num_validators
+            || keygen_history.parts.len() != num_validators
+            || keygen_history.acks.len() != num_validators
+        {
+            return Err(GeneratorError::KeygenHistory(
+                "expected a public key, a Part and Acks for each validator".into(),
+            ));
+        }
+        let peers = reserved_peers
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(ExistingNetwork {
+            keygen_history,
+            peers,
+        })
+    }
+
+    /// Returns true if the node with the given public key is part of the network.
+    fn contains(&self, public: &Public) -> bool {
+        self.keygen_history.public_keys.contains(public)
+            || self.peers.iter().any(|peer| peer.public == *public)
+    }
+
+    /// The index of the first new node, so its port follows the highest port in use. The new
+    /// nodes' ports can't conflict with the existing ones even if they use other base ports.
+    fn next_idx(&self, base_port: u16) -> usize {
+        let max_port = self.peers.iter().map(|peer| peer.port).max().unwrap_or(0);
+        max_port.saturating_sub(base_port) as usize + 1
+    }
+}
+
+/// Generates the keys and configs of `options.num_nodes` new nodes of the existing network.
+///
+/// `options.num_validators` and `options.spec_template` are ignored, the chain spec stays as it
+/// is. The config contains only the new nodes, but the reserved peers and the nodes info of the
+/// whole network.
+pub fn extend(
+    existing: &ExistingNetwork,
+    options: &GeneratorOptions,
+) -> Result<NetworkConfig, GeneratorError> {
+    if options.num_nodes == 0 {
+        return Err(GeneratorError::NoNewNodes);
+    }
+    check_node_options(options)?;
+
+    let mut enodes = generate_enodes(
+        options.num_nodes,
+        options.private_keys.clone(),
+        options.external_ip.as_ref().map(String::as_str),
+        &options.node_ips,
+        options.ports.network,
+    )?;
+    let offset = existing.next_idx(options.ports.network) - 1;
+    for enode in enodes.values_mut() {
+        if existing.contains(&enode.public) {
+            return Err(GeneratorError::DuplicateNode(enode.public));
+        }
+        enode.idx += offset;
+    }
+
+    let mut reserved_peers = String::new();
+    for peer in &existing.peers {
+        writeln!(
+            &mut reserved_peers,
+            "enode://{:x}@{}:{}",
+            peer.public, peer.ip, peer.port
+        )
+        .expect("enode should be written to the reserved peers string");
+    }
+    for enode in enodes.values() {
+        writeln!(&mut reserved_peers, "{}", enode.to_string())
+            .expect("enode should be written to the reserved peers string");
+    }
+
+    // The nodes info lists the initial validators with their Parts and Acks first, followed by
+    // all other nodes.
+    let mut nodes_info = existing.keygen_history.clone();
+    let mut staking_counter = nodes_info
+        .staking_addresses
+        .iter()
+        .map(|address| address.to_low_u64_be())
+        .max()
+        .unwrap_or(0);
+    let nodes = existing
+        .peers
+        .iter()
+        .map(|peer| (peer.public, peer.ip.as_str()))
+        .chain(
+            enodes
+                .values()
+                .map(|enode| (enode.public, enode.ip.as_str())),
+        );
+    for (public, ip) in nodes {
+        if nodes_info.public_keys.contains(&public) {
+            continue;
+        }
+        staking_counter += 1;
+        nodes_info.validators.push(public_to_address(&public));
+        nodes_info
+            .staking_addresses
+            .push(Address::from_low_u64_be(staking_counter));
+        nodes_info.public_keys.push(public);
+        nodes_info.ip_addresses.push(internet_address(ip));
+    }
+
+    Ok(NetworkConfig {
+        nodes: node_configs(enodes, options),
+        rpc_node_toml: rpc_node_toml(options),
+        reserved_peers,
+        keygen_history: serde_json::to_string(&existing.keygen_history)
+            .expect("Keygen History must convert to JSON"),
+        nodes_info: serde_json::to_string(&nodes_info)
+            .expect("Keygen History must convert to JSON"),
+        keygen_stats: KeygenHistoryStats::of(&existing.keygen_history),
+        spec: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generate;
+
+    #[test]
+    fn test_extend_network() {
+        let config = generate(&GeneratorOptions::new(2, 3)).unwrap();
+        let existing = ExistingNetwork::parse(&config.keygen_history, &config.reserved_peers)
+            .expect("the generated files are valid");
+        assert_eq!(existing.peers.len(), 3);
+        assert_eq!(existing.peers[2].port, 30303);
+
+        let extended = extend(&existing, &GeneratorOptions::new(0, 2)).unwrap();
+        let indices: Vec<_> = extended.nodes.iter().map(|node| node.enode.idx).collect();
+        assert_eq!(indices, vec![4, 5]);
+        assert_eq!(
+            extended.reserved_peers.lines().take(3).collect::<Vec<_>>(),
+            config.reserved_peers.lines().collect::<Vec<_>>()
+        );
+        assert!(extended
+            .reserved_peers
+            .lines()
+            .nth(4)
+            .unwrap()
+            .ends_with(":30305"));
+        assert_eq!(extended.keygen_stats, config.keygen_stats);
+
+        let history: KeyGenHistoryData = serde_json::from_str(&extended.keygen_history).unwrap();
+        assert_eq!(history, existing.keygen_history);
+        let nodes_info: KeyGenHistoryData = serde_json::from_str(&extended.nodes_info).unwrap();
+        assert_eq!(nodes_info.validators.len(), 5);
+        assert_eq!(nodes_info.parts.len(), 2);
+        assert_eq!(
+            nodes_info.staking_addresses.last(),
+            Some(&Address::from_low_u64_be(5))
+        );
+    }
+
+    #[test]
+    fn test_invalid_networks_are_rejected() {
+        let config = generate(&GeneratorOptions::new(1, 1)).unwrap();
+        assert_eq!(
+            ExistingNetwork::parse(&config.keygen_history, "enode://1234@127.0.0.1").err(),
+            Some(GeneratorError::ReservedPeer(
+                "enode://1234@127.0.0.1".into()
+            ))
+        );
+        assert!(ExistingNetwork::parse("{}", &config.reserved_peers).is_err());
+
+        let existing =
+            ExistingNetwork::parse(&config.keygen_history, &config.reserved_peers).unwrap();
+        assert_eq!(
+            extend(&existing, &GeneratorOptions::new(0, 0)).err(),
+            Some(GeneratorError::NoNewNodes)
+        );
+        let mut options = GeneratorOptions::new(0, 1);
+        options.private_keys = vec![config.nodes[0].enode.secret.clone()];
+        assert_eq!(
+            extend(&existing, &options).err(),
+            Some(GeneratorError::DuplicateNode(config.nodes[0].enode.public))
+        );
+    }
+}
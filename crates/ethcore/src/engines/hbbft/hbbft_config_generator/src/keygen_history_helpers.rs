@@ -3,7 +3,7 @@ use ethereum_types::H128;
 use hbbft::sync_key_gen::{AckOutcome, Part, PartOutcome, PublicKey, SecretKey, SyncKeyGen};
 use parity_crypto::publickey::{public_to_address, Address, Public, Secret};
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
 
 #[derive(Clone)]
 pub struct KeyPairWrapper {
@@ -111,35 +111,80 @@ pub fn enodes_to_pub_keys(
     )
 }
 
-#[derive(Serialize, Deserialize)]
-struct KeyGenHistoryData {
-    validators: Vec<String>,
-    staking_addresses: Vec<String>,
-    public_keys: Vec<String>,
-    ip_addresses: Vec<String>,
-    parts: Vec<Vec<u8>>,
-    acks: Vec<Vec<Vec<u8>>>,
+/// Returns the address of a node as stored in the staking contract: IPv4 addresses are mapped
+/// into IPv6. Host names can't be stored, they are replaced with the IPv6 loopback address.
+pub fn internet_address(ip: &str) -> H128 {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => H128(ip.to_ipv6_mapped().octets()),
+        Ok(IpAddr::V6(ip)) => H128(ip.octets()),
+        Err(_) => H128::from_low_u64_be(1),
+    }
+}
+
+/// The initial validators and their key generation history, as the genesis contracts store them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyGenHistoryData {
+    /// The mining addresses.
+    pub validators: Vec<Address>,
+    /// The staking addresses, in the order of the mining addresses.
+    pub staking_addresses: Vec<Address>,
+    /// The node public keys.
+    pub public_keys: Vec<Public>,
+    /// The announced IP addresses, see [`internet_address`].
+    pub ip_addresses: Vec<H128>,
+    /// The serialized Parts of the validators.
+    pub parts: Vec<Vec<u8>>,
+    /// The serialized Acks of each validator, for the Parts in order.
+    pub acks: Vec<Vec<Vec<u8>>>,
+}
+
+/// The number and encoded sizes of the Parts and Acks in a key generation history.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KeygenHistoryStats {
+    pub num_parts: usize,
+    pub num_acks: usize,
+    pub parts_total_bytes: usize,
+    pub acks_total_bytes: usize,
+}
+
+impl KeygenHistoryStats {
+    /// Counts the Parts and Acks of the given key generation history.
+    pub fn of(data: &KeyGenHistoryData) -> Self {
+        let acks = data.acks.iter().flatten();
+        KeygenHistoryStats {
+            num_parts: data.parts.len(),
+            num_acks: acks.clone().count(),
+            parts_total_bytes: data.parts.iter().map(Vec::len).sum(),
+            acks_total_bytes: acks.map(Vec::len).sum(),
+        }
+    }
 }
 
+/// Returns the key generation history as JSON, see [`key_sync_history`].
 pub fn key_sync_history_data(
     parts: &BTreeMap<Public, Part>,
     acks: &BTreeMap<Public, Vec<PartOutcome>>,
     enodes: &BTreeMap<Public, Enode>,
     include_validators_only: bool,
-) -> String {
-    let mut data = KeyGenHistoryData {
-        validators: Vec::new(),
-        staking_addresses: Vec::new(),
-        public_keys: Vec::new(),
-        ip_addresses: Vec::new(),
-        parts: Vec::new(),
-        acks: Vec::new(),
-    };
-
-    let mut parts_total_bytes = 0;
-    let mut num_parts = 0;
-    let mut acks_total_bytes = 0;
-    let mut num_acks = 0;
+) -> (String, KeygenHistoryStats) {
+    let (data, stats) = key_sync_history(parts, acks, enodes, include_validators_only);
+    let json = serde_json::to_string(&data).expect("Keygen History must convert to JSON");
+    (json, stats)
+}
+
+/// Collects the addresses of the nodes and the Parts and Acks of the validators among them.
+///
+/// Nodes without a Part are not initial validators. They are only included if
+/// `include_validators_only` is false, without Parts and Acks.
+pub fn key_sync_history(
+    parts: &BTreeMap<Public, Part>,
+    acks: &BTreeMap<Public, Vec<PartOutcome>>,
+    enodes: &BTreeMap<Public, Enode>,
+    include_validators_only: bool,
+) -> (KeyGenHistoryData, KeygenHistoryStats) {
+    let mut data = KeyGenHistoryData::default();
+
+    let mut stats = KeygenHistoryStats::default();
 
     let ids = enodes.keys();
     let mut staking_counter = 1;
@@ -153,14 +198,13 @@ pub fn key_sync_history_data(
             continue;
         }
 
-        data.validators.push(format!("{:?}", public_to_address(id)));
+        data.validators.push(public_to_address(id));
         data.staking_addresses
-            .push(format!("{:?}", Address::from_low_u64_be(staking_counter)));
+            .push(Address::from_low_u64_be(staking_counter));
         staking_counter += 1;
-        data.public_keys
-            .push(format!("{:?}", enodes.get(id).unwrap().public));
+        data.public_keys.push(enodes.get(id).unwrap().public);
         data.ip_addresses
-            .push(format!("{:?}", H128::from_low_u64_be(1)));
+            .push(internet_address(&enodes.get(id).unwrap().ip));
 
         if !is_validator {
             continue;
@@ -168,8 +212,8 @@ pub fn key_sync_history_data(
         // Append to parts vector
         let part = parts.get(id).unwrap();
         let serialized = bincode::serialize(part).expect("Part has to serialize");
-        parts_total_bytes += serialized.len();
-        num_parts += 1;
+        stats.parts_total_bytes += serialized.len();
+        stats.num_parts += 1;
         data.parts.push(serialized);
 
         // Append to parts vector of vectors
@@ -181,8 +225,8 @@ pub fn key_sync_history_data(
                         if let Some(ack) = ack_option {
                             let ack_serialized =
                                 bincode::serialize(&ack).expect("Ack has to serialize");
-                            acks_total_bytes += ack_serialized.len();
-                            num_acks += 1;
+                            stats.acks_total_bytes += ack_serialized.len();
+                            stats.num_acks += 1;
                             ack_serialized
                         } else {
                             panic!("Unexpected valid part outcome without Ack message")
@@ -194,28 +238,7 @@ pub fn key_sync_history_data(
         );
     }
 
-    println!(
-        "{} parts, total number of bytes: {}",
-        num_parts, parts_total_bytes
-    );
-    println!(
-        "{} Acks, total number of bytes: {}",
-        num_acks, acks_total_bytes
-    );
-    println!(
-        "Total number of bytes: {}",
-        parts_total_bytes + acks_total_bytes
-    );
-    println!(
-        "{},{},{},{},{}",
-        num_parts,
-        num_acks,
-        parts_total_bytes,
-        acks_total_bytes,
-        parts_total_bytes + acks_total_bytes
-    );
-
-    serde_json::to_string(&data).expect("Keygen History must convert to JSON")
+    (data, stats)
 }
 
 #[cfg(test)]
@@ -0,0 +1,851 @@
+//! Generates the configuration of a hbbft validator network.
+//!
+//! [`generate`] creates the node keys and enodes, runs the initial key generation ceremony of the
+//! validators and renders the node configs, the reserved peers and the key generation history
+//! for the chain spec, or a complete chain spec based on a template. The result is kept in memory, so integration tests and provisioning tools
+//! can use it directly; [`NetworkConfig::write_to`] writes the files the `hbbft_config_generator`
+//! binary produces. [`extend::extend`] adds nodes to a network generated before.
+
+extern crate bincode;
+extern crate ethabi;
+extern crate ethcore;
+extern crate ethereum_types;
+extern crate ethkey;
+extern crate ethstore;
+extern crate hbbft;
+extern crate parity_crypto;
+extern crate rand;
+extern crate rustc_hex;
+extern crate serde;
+extern crate serde_json;
+extern crate toml;
+
+pub mod extend;
+pub mod keygen_history_helpers;
+pub mod orchestration;
+pub mod spec;
+
+use ethstore::{KeyFile, SafeAccount};
+use keygen_history_helpers::{
+    enodes_to_pub_keys, generate_keygens, key_sync_history, key_sync_history_data,
+    KeygenHistoryStats,
+};
+use parity_crypto::publickey::{Address, Generator, KeyPair, Public, Random, Secret};
+use spec::StakingParams;
+use std::{
+    collections::BTreeMap, fmt, fmt::Write, fs, io, net::IpAddr, num::NonZeroU32, path::Path,
+    str::FromStr, sync::Arc,
+};
+use toml::{map::Map, Value};
+
+/// The default devp2p port of the node with index 0; node `i` listens on `BASE_PORT + i`.
+pub const BASE_PORT: u16 = 30300;
+/// The default JSON-RPC port of the node with index 0.
+pub const BASE_RPC_PORT: u16 = 8540;
+/// The default WebSocket port of the node with index 0.
+pub const BASE_WS_PORT: u16 = 9540;
+/// The JSON-RPC APIs the nodes enable by default.
+pub const DEFAULT_APIS: &[&str] = &[
+    "web3",
+    "eth",
+    "pubsub",
+    "net",
+    "parity",
+    "parity_set",
+    "parity_pubsub",
+    "personal",
+    "traces",
+];
+
+/// The ports of the node with index 0; node `i` uses each of them plus `i`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BasePorts {
+    /// The devp2p port.
+    pub network: u16,
+    /// The JSON-RPC port.
+    pub rpc: u16,
+    /// The WebSocket port.
+    pub ws: u16,
+}
+
+impl Default for BasePorts {
+    fn default() -> Self {
+        BasePorts {
+            network: BASE_PORT,
+            rpc: BASE_RPC_PORT,
+            ws: BASE_WS_PORT,
+        }
+    }
+}
+
+pub fn create_account() -> (Secret, Public, Address) {
+    let acc = Random.generate();
+    (
+        acc.secret().clone(),
+        acc.public().clone(),
+        acc.address().clone(),
+    )
+}
+
+pub struct Enode {
+    pub secret: Secret,
+    pub public: Public,
+    pub address: Address,
+    pub idx: usize,
+    pub ip: String,
+    /// The devp2p port of the node with index 0; this node listens on `base_port + idx`.
+    pub base_port: u16,
+}
+
+impl ToString for Enode {
+    fn to_string(&self) -> String {
+        // Example:
+        // enode://30ccdeb8c31972f570e4eea0673cd08cbe7cefc5de1d70119b39c63b1cba33b48e494e9916c0d1eab7d296774f3573da46025d1accdef2f3690bc9e6659a34b4@192.168.0.101:30300
+        let port = self.base_port as usize + self.idx;
+        format!("enode://{:x}@{}:{}", self.public, self.ip, port)
+    }
+}
+
+/// Generates the keys and enodes of `num_nodes` nodes.
+///
+/// If `node_ips` is not empty, node `i` is reachable at `node_ips[i - 1]`, in the order of the
+/// reassigned indices. Otherwise all nodes are reachable at `external_ip`, or locally.
+pub fn generate_enodes(
+    num_nodes: usize,
+    private_keys: Vec<Secret>,
+    external_ip: Option<&str>,
+    node_ips: &[String],
+    base_port: u16,
+) -> Result<BTreeMap<Public, Enode>, GeneratorError> {
+    let mut map = BTreeMap::new();
+    for i in 0..num_nodes {
+        // Note: node 0 is a regular full node (not a validator) in the testnet setup, so we start at index 1.
+        let idx = i + 1;
+        let ip = match external_ip {
+            Some(ip) => ip,
+            None => "127.0.0.1",
+        };
+        let (secret, public, address) = if private_keys.len() > i {
+            let acc = KeyPair::from_secret(private_keys[i].clone())
+                .map_err(|_| GeneratorError::InvalidPrivateKey(i))?;
+            (
+                acc.secret().clone(),
+                acc.public().clone(),
+                acc.address().clone(),
+            )
+        } else {
+            create_account()
+        };
+        map.insert(
+            public,
+            Enode {
+                secret,
+                public,
+                address,
+                idx,
+                ip: ip.into(),
+                base_port,
+            },
+        );
+    }
+    // the map has the element order by their public key.
+    // we reassign the idx here, so the index of the nodes follows
+    // the same order like everything else.
+    let mut new_index = 1;
+    for public in map.iter_mut() {
+        public.1.idx = new_index;
+        if let Some(ip) = node_ips.get(new_index - 1) {
+            public.1.ip = ip.clone();
+        }
+        new_index = new_index + 1;
+    }
+    Ok(map)
+}
+
+fn to_toml_array(vec: Vec<&str>) -> Value {
+    Value::Array(vec.iter().map(|s| Value::String(s.to_string())).collect())
+}
+
+pub fn to_toml(
+    i: usize,
+    config_type: &ConfigType,
+    external_ip: Option<&str>,
+    signer_address: &Address,
+    ports: &BasePorts,
+    apis: &[String],
+) -> Value {
+    let base_port = ports.network as i64;
+    let base_rpc_port = ports.rpc as i64;
+    let base_ws_port = ports.ws as i64;
+
+    let mut parity = Map::new();
+    match config_type {
+        ConfigType::PosdaoSetup => {
+            parity.insert("chain".into(), Value::String("./spec/spec.json".into()));
+            parity.insert("chain".into(), Value::String("./spec/spec.json".into()));
+            let node_data_path = format!("parity-data/node{}", i);
+            parity.insert("base_path".into(), Value::String(node_data_path));
+        }
+        _ => {
+            parity.insert("chain".into(), Value::String("spec.json".into()));
+            parity.insert("chain".into(), Value::String("spec.json".into()));
+            let node_data_path = "data".to_string();
+            parity.insert("base_path".into(), Value::String(node_data_path));
+        }
+    }
+
+    let mut network = Map::new();
+    network.insert("port".into(), Value::Integer(base_port + i as i64));
+    match config_type {
+        ConfigType::PosdaoSetup => {
+            network.insert(
+                "reserved_peers".into(),
+                Value::String("parity-data/reserved-peers".into()),
+            );
+        }
+        _ => {
+            network.insert(
+                "reserved_peers".into(),
+                Value::String("reserved-peers".into()),
+            );
+        }
+    }
+
+    match external_ip {
+        // Host names can't be announced, the node discovers its address instead.
+        Some(host) if host.parse::<IpAddr>().is_err() => {
+            network.insert("allow_ips".into(), Value::String("public".into()));
+            network.insert("nat".into(), Value::String("any".into()));
+        }
+        Some(extip) => {
+            network.insert("allow_ips".into(), Value::String("public".into()));
+            network.insert("nat".into(), Value::String(format!("extip:{}", extip)));
+        }
+        None => {
+            network.insert("nat".into(), Value::String("none".into()));
+            network.insert("interface".into(), Value::String("all".into()));
+        }
+    }
+
+    let mut rpc = Map::new();
+    rpc.insert("interface".into(), Value::String("all".into()));
+    rpc.insert("cors".into(), to_toml_array(vec!["all"]));
+    rpc.insert("hosts".into(), to_toml_array(vec!["all"]));
+    let apis = to_toml_array(apis.iter().map(String::as_str).collect());
+    rpc.insert("apis".into(), apis);
+    rpc.insert("port".into(), Value::Integer(base_rpc_port + i as i64));
+
+    let mut websockets = Map::new();
+    websockets.insert("interface".into(), Value::String("all".into()));
+    websockets.insert("origins".into(), to_toml_array(vec!["all"]));
+    websockets.insert("port".into(), Value::Integer(base_ws_port + i as i64));
+
+    let mut ipc = Map::new();
+    ipc.insert("disable".into(), Value::Boolean(true));
+
+    let mut secretstore = Map::new();
+    secretstore.insert("disable".into(), Value::Boolean(true));
+
+    let signer_address = format!("{:?}", signer_address);
+
+    let mut account = Map::new();
+    match config_type {
+        ConfigType::PosdaoSetup => {
+            account.insert(
+                "unlock".into(),
+                to_toml_array(vec![
+                    "0xbbcaa8d48289bb1ffcf9808d9aa4b1d215054c78",
+                    "0x32e4e4c7c5d1cea5db5f9202a9e4d99e56c91a24",
+                ]),
+            );
+            account.insert("password".into(), to_toml_array(vec!["config/password"]));
+        }
+        ConfigType::Docker => {
+            account.insert("unlock".into(), to_toml_array(vec![&signer_address]));
+            account.insert("password".into(), to_toml_array(vec!["password.txt"]));
+        }
+        _ => (),
+    }
+
+    let mut mining = Map::new();
+
+    if config_type != &ConfigType::Rpc {
+        mining.insert("engine_signer".into(), Value::String(signer_address));
+    }
+
+    mining.insert("force_sealing".into(), Value::Boolean(true));
+    mining.insert("min_gas_price".into(), Value::Integer(1000000000));
+    mining.insert(
+        "gas_floor_target".into(),
+        Value::String("1000000000".into()),
+    );
+    mining.insert("reseal_on_txs".into(), Value::String("none".into()));
+    mining.insert("extra_data".into(), Value::String("Parity".into()));
+    mining.insert("reseal_min_period".into(), Value::Integer(0));
+
+    let mut misc = Map::new();
+    misc.insert(
+        "logging".into(),
+        Value::String("txqueue=trace,consensus=trace,engine=trace".into()),
+    );
+    misc.insert("log_file".into(), Value::String("parity.log".into()));
+
+    let mut map = Map::new();
+    map.insert("parity".into(), Value::Table(parity));
+    map.insert("network".into(), Value::Table(network));
+    map.insert("rpc".into(), Value::Table(rpc));
+    map.insert("websockets".into(), Value::Table(websockets));
+    map.insert("ipc".into(), Value::Table(ipc));
+    map.insert("secretstore".into(), Value::Table(secretstore));
+    map.insert("account".into(), Value::Table(account));
+    map.insert("mining".into(), Value::Table(mining));
+    map.insert("misc".into(), Value::Table(misc));
+    Value::Table(map)
+}
+
+/// The setup the generated node configs are meant for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigType {
+    PosdaoSetup,
+    Docker,
+    Rpc,
+}
+
+impl ConfigType {
+    /// The names of all config types, as accepted by `from_str`.
+    pub fn variants() -> [&'static str; 3] {
+        ["PosdaoSetup", "Docker", "Rpc"]
+    }
+}
+
+impl FromStr for ConfigType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "posdaosetup" => Ok(ConfigType::PosdaoSetup),
+            "docker" => Ok(ConfigType::Docker),
+            "rpc" => Ok(ConfigType::Rpc),
+            _ => Err(format!(
+                "valid values: {}",
+                ConfigType::variants().join(", ")
+            )),
+        }
+    }
+}
+
+fn key_file_json(secret: Secret) -> String {
+    let json_key: KeyFile = SafeAccount::create(
+        &KeyPair::from_secret(secret).unwrap(),
+        [0u8; 16],
+        &"test".into(),
+        NonZeroU32::new(10240).expect("We know 10240 is not zero."),
+        "Test".to_owned(),
+        "{}".to_owned(),
+    )
+    .expect("json key object creation should succeed")
+    .into();
+
+    serde_json::to_string(&json_key).expect("json key object serialization should succeed")
+}
+
+/// Errors in the generator options.
+#[derive(Debug, PartialEq)]
+pub enum GeneratorError {
+    /// At least one initial validator is needed.
+    NoValidators,
+    /// There are fewer nodes than initial validators.
+    TooFewNodes,
+    /// Private keys were given, but not one for every node.
+    PrivateKeyCount { expected: usize, found: usize },
+    /// The private key with the given index is invalid.
+    InvalidPrivateKey(usize),
+    /// Node IP addresses were given, but not one for every node.
+    NodeIpCount { expected: usize, found: usize },
+    /// The spec template can't be completed.
+    SpecTemplate(String),
+    /// No nodes were requested to extend a network with.
+    NoNewNodes,
+    /// The key generation history of the network to extend is invalid.
+    KeygenHistory(String),
+    /// A line of the reserved peers of the network to extend is not a valid enode.
+    ReservedPeer(String),
+    /// A node with the given public key is already part of the network to extend.
+    DuplicateNode(Public),
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorError::NoValidators => write!(f, "At least one validator is required"),
+            GeneratorError::TooFewNodes => {
+                write!(
+                    f,
+                    "The total number of nodes must not be below the validators"
+                )
+            }
+            GeneratorError::PrivateKeyCount { expected, found } => write!(
+                f,
+                "Expected a private key for each of the {} nodes, found {}",
+                expected, found
+            ),
+            GeneratorError::InvalidPrivateKey(i) => write!(f, "Private key {} is invalid", i),
+            GeneratorError::NodeIpCount { expected, found } => write!(
+                f,
+                "Expected an IP address or host name for each of the {} nodes, found {}",
+                expected, found
+            ),
+            GeneratorError::SpecTemplate(msg) => write!(f, "Invalid spec template: {}", msg),
+            GeneratorError::NoNewNodes => write!(f, "At least one new node is required"),
+            GeneratorError::KeygenHistory(msg) => {
+                write!(f, "Invalid key generation history: {}", msg)
+            }
+            GeneratorError::ReservedPeer(line) => write!(f, "Invalid reserved peer: {}", line),
+            GeneratorError::DuplicateNode(public) => {
+                write!(f, "The node {:x} is already part of the network", public)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+/// The options of a generated network.
+#[derive(Clone, Debug)]
+pub struct GeneratorOptions {
+    /// The number of initial validators, taking part in the initial key generation.
+    pub num_validators: usize,
+    /// The total number of nodes, including the initial validators.
+    pub num_nodes: usize,
+    /// The setup the node configs are generated for.
+    pub config_type: ConfigType,
+    /// The external IP address of the nodes, if they are not only reachable locally.
+    pub external_ip: Option<String>,
+    /// The external IP addresses or host names of the nodes, one per node in index order, for
+    /// networks spread over several hosts. Takes precedence over `external_ip`, which is still
+    /// used for the RPC node.
+    pub node_ips: Vec<String>,
+    /// The secret keys of the nodes. Random keys are generated if empty.
+    pub private_keys: Vec<Secret>,
+    /// A chain spec to initialize the hbbft system contracts in, as JSON.
+    pub spec_template: Option<String>,
+    /// The staking parameters the spec template is initialized with.
+    pub staking_params: StakingParams,
+    /// The ports of the node with index 0.
+    pub ports: BasePorts,
+    /// The JSON-RPC APIs the nodes enable.
+    pub apis: Vec<String>,
+}
+
+impl GeneratorOptions {
+    /// Options for a local network of `num_nodes` nodes with random keys, the first
+    /// `num_validators` of which are initial validators.
+    pub fn new(num_validators: usize, num_nodes: usize) -> Self {
+        GeneratorOptions {
+            num_validators,
+            num_nodes,
+            config_type: ConfigType::PosdaoSetup,
+            external_ip: None,
+            node_ips: Vec::new(),
+            private_keys: Vec::new(),
+            spec_template: None,
+            staking_params: StakingParams::default(),
+            ports: BasePorts::default(),
+            apis: DEFAULT_APIS.iter().map(|api| api.to_string()).collect(),
+        }
+    }
+}
+
+/// The generated configuration of one node.
+pub struct NodeConfig {
+    /// The node's keys and network address.
+    pub enode: Enode,
+    /// The node's config file.
+    pub toml: String,
+    /// The node's key, as an encrypted JSON key file with the password `test`.
+    pub key_file: String,
+}
+
+/// The generated configuration of a network.
+pub struct NetworkConfig {
+    /// The node configs, ordered by public key.
+    pub nodes: Vec<NodeConfig>,
+    /// The config file of the non-validating RPC node.
+    pub rpc_node_toml: String,
+    /// The enodes of all nodes, one per line.
+    pub reserved_peers: String,
+    /// The key generation history of the initial validators, as JSON.
+    pub keygen_history: String,
+    /// The key generation history and the addresses of all nodes, as JSON.
+    pub nodes_info: String,
+    /// The sizes of the Parts and Acks of the initial key generation.
+    pub keygen_stats: KeygenHistoryStats,
+    /// The spec template with the initial validators and key generation history, if a template
+    /// was given.
+    pub spec: Option<String>,
+}
+
+impl NetworkConfig {
+    /// Writes the config files into the given directory.
+    pub fn write_to(&self, dir: &Path) -> io::Result<()> {
+        for node in &self.nodes {
+            let i = node.enode.idx;
+            fs::write(dir.join(format!("hbbft_validator_{}.toml", i)), &node.toml)?;
+            fs::write(
+                dir.join(format!("hbbft_validator_key_{}", i)),
+                node.enode.secret.to_hex(),
+            )?;
+            fs::write(
+                dir.join(format!("hbbft_validator_key_{}.json", i)),
+                &node.key_file,
+            )?;
+        }
+        fs::write(dir.join("rpc_node.toml"), &self.rpc_node_toml)?;
+        fs::write(dir.join("reserved-peers"), &self.reserved_peers)?;
+        fs::write(dir.join("password.txt"), "test")?;
+        fs::write(dir.join("keygen_history.json"), &self.keygen_history)?;
+        fs::write(dir.join("nodes_info.json"), &self.nodes_info)?;
+        if let Some(spec) = &self.spec {
+            fs::write(dir.join("spec.json"), spec)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the private keys and node IP addresses, if any, match the number of nodes.
+fn check_node_options(options: &GeneratorOptions) -> Result<(), GeneratorError> {
+    // If private keys are specified we expect as many as there are nodes.
+    if !options.private_keys.is_empty() && options.private_keys.len() != options.num_nodes {
+        return Err(GeneratorError::PrivateKeyCount {
+            expected: options.num_nodes,
+            found: options.private_keys.len(),
+        });
+    }
+    if !options.node_ips.is_empty() && options.node_ips.len() != options.num_nodes {
+        return Err(GeneratorError::NodeIpCount {
+            expected: options.num_nodes,
+            found: options.node_ips.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Renders the config files of the given nodes.
+fn node_configs(enodes: BTreeMap<Public, Enode>, options: &GeneratorOptions) -> Vec<NodeConfig> {
+    let external_ip = options.external_ip.as_ref().map(String::as_str);
+    enodes
+        .into_iter()
+        .map(|(_, enode)| NodeConfig {
+            toml: toml::to_string(&to_toml(
+                enode.idx,
+                &options.config_type,
+                if options.node_ips.is_empty() {
+                    external_ip
+                } else {
+                    Some(enode.ip.as_str())
+                },
+                &enode.address,
+                &options.ports,
+                &options.apis,
+            ))
+            .expect("TOML string generation should succeed"),
+            key_file: key_file_json(enode.secret.clone()),
+            enode,
+        })
+        .collect()
+}
+
+/// Renders the config file of the non-validating RPC node.
+fn rpc_node_toml(options: &GeneratorOptions) -> String {
+    toml::to_string(&to_toml(
+        0,
+        &ConfigType::Rpc,
+        options.external_ip.as_ref().map(String::as_str),
+        &Address::default(),
+        &options.ports,
+        &options.apis,
+    ))
+    .expect("TOML string generation should succeed")
+}
+
+/// Generates the keys and configs of a network and runs the initial key generation.
+pub fn generate(options: &GeneratorOptions) -> Result<NetworkConfig, GeneratorError> {
+    if options.num_validators == 0 {
+        return Err(GeneratorError::NoValidators);
+    }
+    if options.num_nodes < options.num_validators {
+        return Err(GeneratorError::TooFewNodes);
+    }
+    check_node_options(options)?;
+
+    let external_ip = options.external_ip.as_ref().map(String::as_str);
+    let enodes_map = generate_enodes(
+        options.num_nodes,
+        options.private_keys.clone(),
+        external_ip,
+        &options.node_ips,
+        options.ports.network,
+    )?;
+    let mut rng = rand::thread_rng();
+
+    let pub_keys = enodes_to_pub_keys(&enodes_map);
+
+    // we only need the first x pub_keys
+    let pub_keys_for_key_gen_btree = pub_keys
+        .iter()
+        .take(options.num_validators)
+        .map(|x| (x.0.clone(), x.1.clone()))
+        .collect();
+
+    let (_sync_keygen, parts, acks) = generate_keygens(
+        Arc::new(pub_keys_for_key_gen_btree),
+        &mut rng,
+        (options.num_validators - 1) / 3,
+    );
+
+    let mut reserved_peers = String::new();
+    for enode in enodes_map.values() {
+        writeln!(&mut reserved_peers, "{}", enode.to_string())
+            .expect("enode should be written to the reserved peers string");
+    }
+
+    // only pass over enodes in the enodes_map that are also available for acks and parts.
+    let (keygen_history, keygen_stats) = key_sync_history_data(&parts, &acks, &enodes_map, true);
+    let (nodes_info, _) = key_sync_history_data(&parts, &acks, &enodes_map, false);
+    let spec = match &options.spec_template {
+        Some(template) => {
+            let (history, _) = key_sync_history(&parts, &acks, &enodes_map, true);
+            let spec = spec::bake_spec(template, &history, &options.staking_params)
+                .map_err(GeneratorError::SpecTemplate)?;
+            Some(spec)
+        }
+        None => None,
+    };
+
+    Ok(NetworkConfig {
+        nodes: node_configs(enodes_map, options),
+        rpc_node_toml: rpc_node_toml(options),
+        reserved_peers,
+        keygen_history,
+        nodes_info,
+        keygen_stats,
+        spec,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hbbft::sync_key_gen::{AckOutcome, PartOutcome, SyncKeyGen};
+    use keygen_history_helpers::{KeyGenHistoryData, KeyPairWrapper};
+    use rand;
+    use std::{collections::BTreeMap, sync::Arc};
+
+    #[test]
+    fn test_network_config_generation() {
+        let config = generate(&GeneratorOptions::new(4, 5)).unwrap();
+        assert_eq!(config.nodes.len(), 5);
+        assert_eq!(config.reserved_peers.lines().count(), 5);
+        for (i, node) in config.nodes.iter().enumerate() {
+            assert_eq!(node.enode.idx, i + 1);
+            let toml: Value = toml::from_str(&node.toml).unwrap();
+            assert_eq!(
+                toml["mining"]["engine_signer"].as_str(),
+                Some(format!("{:?}", node.enode.address).as_str())
+            );
+        }
+        assert_eq!(config.keygen_stats.num_parts, 4);
+        assert_eq!(config.keygen_stats.num_acks, 16);
+
+        let history: serde_json::Value = serde_json::from_str(&config.keygen_history).unwrap();
+        assert_eq!(history["validators"].as_array().unwrap().len(), 4);
+        let nodes_info: serde_json::Value = serde_json::from_str(&config.nodes_info).unwrap();
+        assert_eq!(nodes_info["validators"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_invalid_options_are_rejected() {
+        assert_eq!(
+            generate(&GeneratorOptions::new(0, 1)).err(),
+            Some(GeneratorError::NoValidators)
+        );
+        assert_eq!(
+            generate(&GeneratorOptions::new(2, 1)).err(),
+            Some(GeneratorError::TooFewNodes)
+        );
+        let mut options = GeneratorOptions::new(1, 2);
+        options.private_keys = vec![create_account().0];
+        assert_eq!(
+            generate(&options).err(),
+            Some(GeneratorError::PrivateKeyCount {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_per_node_ips() {
+        let mut options = GeneratorOptions::new(2, 3);
+        options.external_ip = Some("10.0.0.100".into());
+        options.node_ips = vec!["10.0.0.1".into(), "10.0.0.2".into(), "node3.example".into()];
+        let config = generate(&options).unwrap();
+        let mut peers = config.reserved_peers.lines();
+        assert!(peers.next().unwrap().ends_with("@10.0.0.1:30301"));
+        assert!(peers.next().unwrap().ends_with("@10.0.0.2:30302"));
+        assert!(peers.next().unwrap().ends_with("@node3.example:30303"));
+
+        let nat = |toml: &str| {
+            let toml: Value = toml::from_str(toml).unwrap();
+            toml["network"]["nat"].as_str().unwrap().to_owned()
+        };
+        assert_eq!(nat(&config.nodes[1].toml), "extip:10.0.0.2");
+        assert_eq!(nat(&config.nodes[2].toml), "any");
+        assert_eq!(nat(&config.rpc_node_toml), "extip:10.0.0.100");
+
+        let nodes_info: serde_json::Value = serde_json::from_str(&config.nodes_info).unwrap();
+        assert_eq!(
+            nodes_info["ip_addresses"][0].as_str(),
+            Some("0x00000000000000000000ffff0a000001")
+        );
+
+        options.node_ips.pop();
+        assert_eq!(
+            generate(&options).err(),
+            Some(GeneratorError::NodeIpCount {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_config_type_parsing() {
+        for name in ConfigType::variants().iter() {
+            assert!(name.parse::<ConfigType>().is_ok());
+        }
+        assert_eq!("docker".parse::<ConfigType>(), Ok(ConfigType::Docker));
+        assert!("unknown".parse::<ConfigType>().is_err());
+    }
+
+    #[test]
+    fn test_ports_and_apis() {
+        let mut options = GeneratorOptions::new(1, 2);
+        options.ports = BasePorts {
+            network: 40000,
+            rpc: 41000,
+            ws: 42000,
+        };
+        options.apis = vec!["eth".into(), "net".into()];
+        let config = generate(&options).unwrap();
+        assert!(config
+            .reserved_peers
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with(":40002"));
+
+        let toml: Value = toml::from_str(&config.nodes[1].toml).unwrap();
+        assert_eq!(toml["network"]["port"].as_integer(), Some(40002));
+        assert_eq!(toml["rpc"]["port"].as_integer(), Some(41002));
+        assert_eq!(toml["websockets"]["port"].as_integer(), Some(42002));
+        assert_eq!(toml["rpc"]["apis"], to_toml_array(vec!["eth", "net"]));
+        let rpc_toml: Value = toml::from_str(&config.rpc_node_toml).unwrap();
+        assert_eq!(rpc_toml["rpc"]["port"].as_integer(), Some(41000));
+    }
+
+    #[test]
+    fn test_network_info_serde() {
+        let num_nodes = 1;
+        let mut rng = rand::thread_rng();
+        let enodes_map = generate_enodes(num_nodes, Vec::new(), None, &[], BASE_PORT).unwrap();
+        let pub_keys = enodes_to_pub_keys(&enodes_map);
+        let (sync_keygen, parts, acks) =
+            generate_keygens(pub_keys.clone(), &mut rng, (num_nodes - 1) / 3);
+        let keygen = sync_keygen.iter().nth(0).unwrap();
+
+        // The nodes restore the network info from the key generation history in the spec.
+        let (json, _) = key_sync_history_data(&parts, &acks, &enodes_map, true);
+        let history: KeyGenHistoryData = serde_json::from_str(&json).unwrap();
+        assert!(keygen.public_keys().keys().eq(history.public_keys.iter()));
+
+        let (our_id, our_keys) = pub_keys.iter().nth(0).unwrap();
+        let (mut restored, _) = SyncKeyGen::new(
+            *our_id,
+            our_keys.clone(),
+            pub_keys.clone(),
+            (num_nodes - 1) / 3,
+            &mut rng,
+        )
+        .unwrap();
+        for (id, part) in pub_keys.keys().zip(&history.parts) {
+            let part = bincode::deserialize(part).unwrap();
+            match restored.handle_part(id, part, &mut rng).unwrap() {
+                PartOutcome::Valid(_) => (),
+                PartOutcome::Invalid(fault) => panic!("Invalid Part: {:?}", fault),
+            }
+        }
+        for (id, acks) in pub_keys.keys().zip(&history.acks) {
+            for ack in acks {
+                let ack = bincode::deserialize(ack).unwrap();
+                match restored.handle_ack(id, ack).unwrap() {
+                    AckOutcome::Valid => (),
+                    AckOutcome::Invalid(fault) => panic!("Invalid Ack: {:?}", fault),
+                }
+            }
+        }
+        assert_eq!(restored.generate().unwrap(), keygen.generate().unwrap());
+    }
+
+    #[test]
+    fn test_threshold_encryption_single() {
+        let (secret, public, _) = create_account();
+        let keypair = KeyPairWrapper { public, secret };
+        let mut pub_keys: BTreeMap<Public, KeyPairWrapper> = BTreeMap::new();
+        pub_keys.insert(public, keypair.clone());
+        let mut rng = rand::thread_rng();
+        let mut key_gen =
+            SyncKeyGen::new(public, keypair, Arc::new(pub_keys), 0, &mut rng).unwrap();
+        let part = key_gen.1.unwrap();
+        let outcome = key_gen.0.handle_part(&public, part, &mut rng);
+        assert!(outcome.is_ok());
+        match outcome.unwrap() {
+            PartOutcome::Valid(ack) => {
+                assert!(ack.is_some());
+                let ack_outcome = key_gen.0.handle_ack(&public, ack.unwrap());
+                assert!(ack_outcome.is_ok());
+                match ack_outcome.unwrap() {
+                    AckOutcome::Valid => {
+                        assert!(key_gen.0.is_ready());
+                        let key_shares = key_gen.0.generate();
+                        assert!(key_shares.is_ok());
+                        assert!(key_shares.unwrap().1.is_some());
+                    }
+                    AckOutcome::Invalid(_) => assert!(false),
+                }
+            }
+            PartOutcome::Invalid(_) => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_threshold_encryption_multiple() {
+        let num_nodes = 4;
+        let t = 1;
+
+        let enodes = generate_enodes(num_nodes, Vec::new(), None, &[], BASE_PORT).unwrap();
+        let pub_keys = enodes_to_pub_keys(&enodes);
+        let mut rng = rand::thread_rng();
+
+        let (sync_keygen, _, _) = generate_keygens(pub_keys, &mut rng, t);
+
+        let compare_to = sync_keygen.iter().nth(0).unwrap().generate().unwrap().0;
+
+        // Check key generation
+        for s in sync_keygen {
+            assert!(s.is_ready());
+            assert!(s.generate().is_ok());
+            assert_eq!(s.generate().unwrap().0, compare_to);
+        }
+    }
+}
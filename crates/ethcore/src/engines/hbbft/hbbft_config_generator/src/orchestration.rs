@@ -0,0 +1,245 @@
+//! Orchestration files for running a generated network.
+//!
+//! Besides the node configs, the generator can render a `docker-compose.yml` with one service
+//! per node and the RPC node, and a systemd unit per node, so test networks can be started
+//! without writing them by hand.
+
+use super::{BasePorts, NetworkConfig, NodeConfig};
+use std::{fmt::Write, fs, io, path::Path, str::FromStr};
+
+/// The files written in addition to the node configs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Only the node configs.
+    Toml,
+    /// A `docker-compose.yml` with one service per node.
+    DockerCompose,
+    /// A systemd unit file per node.
+    Systemd,
+}
+
+impl OutputFormat {
+    /// The names of all output formats, as accepted by `from_str`.
+    pub fn variants() -> [&'static str; 3] {
+        ["toml", "docker-compose", "systemd"]
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "toml" => Ok(OutputFormat::Toml),
+            "docker-compose" => Ok(OutputFormat::DockerCompose),
+            "systemd" => Ok(OutputFormat::Systemd),
+            _ => Err(format!(
+                "valid values: {}",
+                OutputFormat::variants().join(", ")
+            )),
+        }
+    }
+}
+
+/// The settings of the rendered orchestration files.
+#[derive(Clone, Debug)]
+pub struct OrchestrationOptions {
+    /// The docker image the services run.
+    pub image: String,
+    /// The directory name of the chain's keys, which is the spec's `dataDir` or `name`.
+    pub chain_name: String,
+    /// The external IP address of the nodes. Without it, the services use the host's network,
+    /// since the enodes point to the loopback address.
+    pub external_ip: Option<String>,
+    /// The path of the node executable the systemd units run.
+    pub executable: String,
+    /// The directory the systemd units run in, containing the generated files.
+    pub working_directory: String,
+    /// The ports of the node with index 0, as in the node configs.
+    pub ports: BasePorts,
+}
+
+impl Default for OrchestrationOptions {
+    fn default() -> Self {
+        OrchestrationOptions {
+            image: "openethereum/openethereum:latest".into(),
+            chain_name: "DPoSChain".into(),
+            external_ip: None,
+            executable: "/usr/bin/openethereum".into(),
+            working_directory: "/etc/openethereum".into(),
+            ports: BasePorts::default(),
+        }
+    }
+}
+
+/// The working directory of the official docker images.
+const CONTAINER_HOME: &str = "/home/openethereum";
+
+/// Writes the service running the node with the given index, up to its list of volumes.
+///
+/// The published ports match the ones in the node's config: the base ports offset by the index.
+fn write_service(
+    out: &mut String,
+    name: &str,
+    volume: &str,
+    i: usize,
+    options: &OrchestrationOptions,
+) {
+    let home = CONTAINER_HOME;
+    writeln!(out, "  {}:", name).unwrap();
+    writeln!(out, "    image: {}", options.image).unwrap();
+    writeln!(out, "    command: [\"--config\", \"config.toml\"]").unwrap();
+    writeln!(out, "    restart: on-failure").unwrap();
+    // Give the node time to shut down cleanly, as the systemd units do.
+    writeln!(out, "    stop_grace_period: 5m").unwrap();
+    match options.external_ip {
+        Some(_) => {
+            let ports = &options.ports;
+            let port = ports.network as usize + i;
+            writeln!(out, "    ports:").unwrap();
+            writeln!(out, "      - \"{0}:{0}\"", port).unwrap();
+            writeln!(out, "      - \"{0}:{0}/udp\"", port).unwrap();
+            writeln!(out, "      - \"{0}:{0}\"", ports.rpc as usize + i).unwrap();
+            writeln!(out, "      - \"{0}:{0}\"", ports.ws as usize + i).unwrap();
+        }
+        None => writeln!(out, "    network_mode: host").unwrap(),
+    }
+    writeln!(out, "    volumes:").unwrap();
+    writeln!(out, "      - {}:{}/data", volume, home).unwrap();
+    for file in &["spec.json", "reserved-peers"] {
+        writeln!(out, "      - ./{0}:{1}/{0}:ro", file, home).unwrap();
+    }
+}
+
+/// Renders a `docker-compose.yml` running each node and the RPC node in its own service.
+///
+/// The node configs must be generated with `ConfigType::Docker`, and the chain spec is expected
+/// as `spec.json` next to the generated files. Each node keeps its database in a named volume.
+pub fn docker_compose(config: &NetworkConfig, options: &OrchestrationOptions) -> String {
+    let home = CONTAINER_HOME;
+    let mut out = String::new();
+    let mut volumes = String::new();
+    writeln!(out, "version: \"3\"").unwrap();
+    writeln!(out, "services:").unwrap();
+    for node in &config.nodes {
+        let i = node.enode.idx;
+        let name = format!("hbbft_validator_{}", i);
+        let volume = format!("node{}_data", i);
+        write_service(&mut out, &name, &volume, i, options);
+        writeln!(out, "      - ./{}.toml:{}/config.toml:ro", name, home).unwrap();
+        writeln!(
+            out,
+            "      - ./hbbft_validator_key_{0}.json:{1}/data/keys/{2}/hbbft_validator_key_{0}.json:ro",
+            i, home, options.chain_name
+        )
+        .unwrap();
+        writeln!(out, "      - ./password.txt:{}/password.txt:ro", home).unwrap();
+        writeln!(volumes, "  {}:", volume).unwrap();
+    }
+    // The RPC node has the index 0 and no key.
+    write_service(&mut out, "rpc_node", "rpc_node_data", 0, options);
+    writeln!(out, "      - ./rpc_node.toml:{}/config.toml:ro", home).unwrap();
+    writeln!(volumes, "  rpc_node_data:").unwrap();
+    writeln!(out, "volumes:").unwrap();
+    out + &volumes
+}
+
+/// Renders the systemd unit running the given node.
+///
+/// All units share the working directory, so the node configs must use distinct base paths,
+/// as the `ConfigType::PosdaoSetup` configs do.
+pub fn systemd_unit(node: &NodeConfig, options: &OrchestrationOptions) -> String {
+    let i = node.enode.idx;
+    format!(
+        "[Unit]
+Description=hbbft validator node {i}
+After=network.target
+
+[Service]
+WorkingDirectory={dir}
+ExecStart={exe} --config {dir}/hbbft_validator_{i}.toml
+Restart=on-failure
+
+# SIGTERM gives the node time to exit cleanly before sending SIGKILL.
+KillSignal=SIGTERM
+TimeoutStopSec=300
+
+[Install]
+WantedBy=default.target
+",
+        i = i,
+        dir = options.working_directory,
+        exe = options.executable,
+    )
+}
+
+/// Writes the files of the given output formats into the given directory.
+pub fn write_to(
+    config: &NetworkConfig,
+    formats: &[OutputFormat],
+    options: &OrchestrationOptions,
+    dir: &Path,
+) -> io::Result<()> {
+    if formats.contains(&OutputFormat::DockerCompose) {
+        fs::write(
+            dir.join("docker-compose.yml"),
+            docker_compose(config, options),
+        )?;
+    }
+    if formats.contains(&OutputFormat::Systemd) {
+        for node in &config.nodes {
+            fs::write(
+                dir.join(format!("hbbft_validator_{}.service", node.enode.idx)),
+                systemd_unit(node, options),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {generate, GeneratorOptions};
+
+    #[test]
+    fn test_docker_compose_has_a_service_per_node() {
+        let config = generate(&GeneratorOptions::new(1, 2)).unwrap();
+        let mut options = OrchestrationOptions::default();
+        let compose = docker_compose(&config, &options);
+        assert!(compose.contains("  hbbft_validator_1:\n"));
+        assert!(compose.contains("  hbbft_validator_2:\n"));
+        assert!(compose.contains("  node2_data:\n"));
+        assert!(compose.contains("  rpc_node:\n"));
+        assert!(compose.contains("      - ./rpc_node.toml:/home/openethereum/config.toml:ro\n"));
+        assert_eq!(compose.matches("network_mode: host").count(), 3);
+
+        options.external_ip = Some("192.168.0.1".into());
+        let compose = docker_compose(&config, &options);
+        assert!(compose.contains("      - \"30302:30302/udp\"\n"));
+        assert!(compose.contains("      - \"8542:8542\"\n"));
+        assert!(compose.contains("      - \"8540:8540\"\n"));
+        assert!(!compose.contains("network_mode"));
+    }
+
+    #[test]
+    fn test_systemd_unit_runs_the_node_config() {
+        let config = generate(&GeneratorOptions::new(1, 1)).unwrap();
+        let unit = systemd_unit(&config.nodes[0], &OrchestrationOptions::default());
+        assert!(unit.contains(
+            "ExecStart=/usr/bin/openethereum --config /etc/openethereum/hbbft_validator_1.toml\n"
+        ));
+    }
+
+    #[test]
+    fn test_output_format_parsing() {
+        for name in OutputFormat::variants().iter() {
+            assert!(name.parse::<OutputFormat>().is_ok());
+        }
+        assert_eq!(
+            "Docker-Compose".parse::<OutputFormat>(),
+            Ok(OutputFormat::DockerCompose)
+        );
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! Bakes the initial validators and their key generation history into a chain spec.
+//!
+//! The hbbft system contracts are deployed in the genesis block as proxies, whose constructor
+//! takes the logic contract, the admin and the calldata of an initializer call. A template spec
+//! deploys them without an initializer call. [`bake_spec`] appends the `initialize` calls with
+//! the generated validators, Parts and Acks, so the chain starts with the generated key.
+
+use ethabi::{Contract, Token};
+use ethereum_types::U256;
+use keygen_history_helpers::KeyGenHistoryData;
+use parity_crypto::publickey::Address;
+use rustc_hex::ToHex;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// The validator set contract proxy.
+pub const VALIDATOR_SET_ADDRESS: &str = "1000000000000000000000000000000000000001";
+/// The staking contract proxy.
+pub const STAKING_CONTRACT_ADDRESS: &str = "1100000000000000000000000000000000000001";
+/// The block reward contract proxy, unless the engine parameters specify another one.
+pub const BLOCK_REWARD_ADDRESS: &str = "2000000000000000000000000000000000000001";
+/// The random contract proxy, unless the engine parameters specify another one.
+pub const RANDOM_ADDRESS: &str = "3000000000000000000000000000000000000001";
+/// The key generation history contract proxy.
+pub const KEYGEN_HISTORY_ADDRESS: &str = "7000000000000000000000000000000000000001";
+
+/// The initial staking parameters. The defaults suit short-lived test networks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StakingParams {
+    /// The minimum stake of a delegator, in wei.
+    pub delegator_min_stake: U256,
+    /// The minimum stake of a candidate, in wei.
+    pub candidate_min_stake: U256,
+    /// The duration of a staking epoch, in seconds.
+    pub fixed_epoch_duration: u64,
+    /// The duration of the key generation at the end of a staking epoch, in seconds.
+    pub transition_timeframe_length: u64,
+    /// The time before the end of a staking epoch in which withdrawals are disallowed, in seconds.
+    pub withdraw_disallow_period: u64,
+}
+
+impl Default for StakingParams {
+    fn default() -> Self {
+        let ether = U256::from(10).pow(18.into());
+        StakingParams {
+            delegator_min_stake: ether * U256::from(1_000),
+            candidate_min_stake: ether * U256::from(10_000),
+            fixed_epoch_duration: 120,
+            transition_timeframe_length: 30,
+            withdraw_disallow_period: 30,
+        }
+    }
+}
+
+fn address(s: &str) -> Address {
+    Address::from_str(s).expect("contract addresses are valid")
+}
+
+/// Returns the address of an engine parameter of the template, or the default.
+fn engine_param(spec: &Value, name: &str, default: &str) -> Result<Address, String> {
+    match spec["engine"]["hbbft"]["params"][name].as_str() {
+        Some(s) => Address::from_str(s.trim_start_matches("0x"))
+            .map_err(|_| format!("Invalid {} in the spec template: {}", name, s)),
+        None => Ok(address(default)),
+    }
+}
+
+fn addresses(addresses: &[Address]) -> Token {
+    Token::Array(addresses.iter().map(|a| Token::Address(*a)).collect())
+}
+
+fn initializer(abi: &[u8], tokens: &[Token]) -> Vec<u8> {
+    Contract::load(abi)
+        .and_then(|contract| contract.function("initialize")?.encode_input(tokens))
+        .expect("the initializer arguments match the contract ABI")
+}
+
+/// Appends the calldata of an initializer call to the constructor of a proxy deployed without one.
+fn initialize_proxy(constructor: &str, calldata: &[u8]) -> Option<String> {
+    let code = constructor.trim_start_matches("0x");
+    // The constructor arguments end with the offset and the zero length of the empty calldata.
+    let empty_calldata = format!("{:064x}{:064x}", 0x60, 0);
+    if !code.ends_with(&empty_calldata) {
+        return None;
+    }
+    let mut padded = calldata.to_vec();
+    padded.resize((calldata.len() + 31) / 32 * 32, 0);
+    Some(format!(
+        "0x{}{:064x}{}",
+        &code[..code.len() - 64],
+        calldata.len(),
+        padded.to_hex::<String>()
+    ))
+}
+
+fn initialize_account(
+    accounts: &mut Map<String, Value>,
+    address: &Address,
+    calldata: &[u8],
+) -> Result<(), String> {
+    let account = accounts
+        .iter_mut()
+        .find(|(key, _)| Address::from_str(key.trim_start_matches("0x")).ok() == Some(*address))
+        .map(|(_, account)| account)
+        .ok_or_else(|| format!("The spec template has no account {:?}", address))?;
+    let constructor = account["constructor"]
+        .as_str()
+        .and_then(|constructor| initialize_proxy(constructor, calldata))
+        .ok_or_else(|| format!("{:?} is not an uninitialized proxy contract", address))?;
+    account["constructor"] = Value::String(constructor);
+    Ok(())
+}
+
+/// Returns the template spec with the hbbft system contracts initialized with the given
+/// validators and their key generation history.
+pub fn bake_spec(
+    template: &str,
+    history: &KeyGenHistoryData,
+    staking: &StakingParams,
+) -> Result<String, String> {
+    let mut spec: Value = serde_json::from_str(template)
+        .map_err(|e| format!("The spec template is not valid JSON: {}", e))?;
+    let validator_set = address(VALIDATOR_SET_ADDRESS);
+    let staking_contract = address(STAKING_CONTRACT_ADDRESS);
+    let keygen_history = address(KEYGEN_HISTORY_ADDRESS);
+    let block_reward = engine_param(&spec, "blockRewardContractAddress", BLOCK_REWARD_ADDRESS)?;
+    let random = engine_param(&spec, "randomnessContractAddress", RANDOM_ADDRESS)?;
+
+    let validator_set_calldata = initializer(
+        include_bytes!("../../../../../res/contracts/validator_set_hbbft.json"),
+        &[
+            Token::Address(block_reward),
+            Token::Address(random),
+            Token::Address(staking_contract),
+            Token::Address(keygen_history),
+            addresses(&history.validators),
+            addresses(&history.staking_addresses),
+        ],
+    );
+    let public_keys = history
+        .public_keys
+        .iter()
+        .flat_map(|public| {
+            let (first, second) = public.as_bytes().split_at(32);
+            vec![
+                Token::FixedBytes(first.to_vec()),
+                Token::FixedBytes(second.to_vec()),
+            ]
+        })
+        .collect();
+    let ip_addresses = history
+        .ip_addresses
+        .iter()
+        .map(|ip| Token::FixedBytes(ip.as_bytes().to_vec()))
+        .collect();
+    let staking_calldata = initializer(
+        include_bytes!("../../../../../res/contracts/staking_contract.json"),
+        &[
+            Token::Address(validator_set),
+            addresses(&history.staking_addresses),
+            Token::Uint(staking.delegator_min_stake),
+            Token::Uint(staking.candidate_min_stake),
+            Token::Uint(staking.fixed_epoch_duration.into()),
+            Token::Uint(staking.transition_timeframe_length.into()),
+            Token::Uint(staking.withdraw_disallow_period.into()),
+            Token::Array(public_keys),
+            Token::Array(ip_addresses),
+        ],
+    );
+    let parts = history
+        .parts
+        .iter()
+        .map(|part| Token::Bytes(part.clone()))
+        .collect();
+    let acks = history
+        .acks
+        .iter()
+        .map(|acks| Token::Array(acks.iter().map(|ack| Token::Bytes(ack.clone())).collect()))
+        .collect();
+    let keygen_history_calldata = initializer(
+        include_bytes!("../../../../../res/contracts/key_history_contract.json"),
+        &[
+            Token::Address(validator_set),
+            addresses(&history.validators),
+            Token::Array(parts),
+            Token::Array(acks),
+        ],
+    );
+
+    let accounts = spec["accounts"]
+        .as_object_mut()
+        .ok_or("The spec template has no accounts")?;
+    initialize_account(accounts, &validator_set, &validator_set_calldata)?;
+    initialize_account(accounts, &staking_contract, &staking_calldata)?;
+    initialize_account(accounts, &keygen_history, &keygen_history_calldata)?;
+    Ok(serde_json::to_string_pretty(&spec).expect("a JSON value converts to a string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H128;
+    use parity_crypto::publickey::Public;
+    use serde_json::json;
+
+    const UNINITIALIZED_PROXY: &str = "0x6080\
+        0000000000000000000000000000000000000000000000000000000000000060\
+        0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn template() -> Value {
+        json!({
+            "engine": { "hbbft": { "params": {} } },
+            "accounts": {
+                "0x1000000000000000000000000000000000000001": { "constructor": UNINITIALIZED_PROXY },
+                "0x1100000000000000000000000000000000000001": { "constructor": UNINITIALIZED_PROXY },
+                "0x7000000000000000000000000000000000000001": { "constructor": UNINITIALIZED_PROXY },
+            }
+        })
+    }
+
+    #[test]
+    fn initializer_calls_are_appended_to_the_proxies() {
+        let history = KeyGenHistoryData {
+            validators: vec![Address::from_low_u64_be(10)],
+            staking_addresses: vec![Address::from_low_u64_be(1)],
+            public_keys: vec![Public::from_low_u64_be(2)],
+            ip_addresses: vec![H128::from_low_u64_be(3)],
+            parts: vec![vec![4; 40]],
+            acks: vec![vec![vec![5; 10]]],
+        };
+        let spec = bake_spec(&template().to_string(), &history, &StakingParams::default());
+        let spec: Value = serde_json::from_str(&spec.unwrap()).unwrap();
+        for address in &[VALIDATOR_SET_ADDRESS, KEYGEN_HISTORY_ADDRESS] {
+            let constructor = spec["accounts"][format!("0x{}", address)]["constructor"]
+                .as_str()
+                .unwrap();
+            let code = &constructor[2..];
+            assert!(code.starts_with(&UNINITIALIZED_PROXY[2..UNINITIALIZED_PROXY.len() - 64]));
+            let length = usize::from_str_radix(&code[68..132], 16).unwrap();
+            assert!(length > 0);
+            assert_eq!(code.len(), 132 + (length + 31) / 32 * 64);
+        }
+
+        let mut initialized = template();
+        initialized["accounts"]["0x1100000000000000000000000000000000000001"]["constructor"] =
+            json!("0x6080");
+        assert!(bake_spec(
+            &initialized.to_string(),
+            &history,
+            &StakingParams::default()
+        )
+        .is_err());
+    }
+}
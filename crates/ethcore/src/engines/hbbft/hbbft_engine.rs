@@ -1,48 +1,108 @@
 use std::{
     cmp::{max, min},
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet, VecDeque},
     convert::TryFrom,
-    ops::BitXor,
-    sync::{Arc, Weak},
-    time::Duration,
+    fmt::Debug,
+    iter,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-use super::block_reward_hbbft::BlockRewardContract;
+use super::block_reward_hbbft::{self, BlockRewardContract};
 use block::ExecutedBlock;
+use bytes::Bytes;
 use client::traits::{EngineClient, ForceUpdateSealing};
-use crypto::publickey::Signature;
+use crypto::publickey::{public_to_address, Signature};
 use engines::{
-    default_system_or_code_call, signer::EngineSigner, Engine, EngineError, ForkChoice, Seal,
-    SealingState,
+    authority_round::AuthorityRound, default_system_or_code_call, signer::EngineSigner,
+    ConstructedVerifier, Engine, EngineError, EpochChange, ForkChoice, Headers,
+    PendingTransitionStore, Seal, SealingState,
 };
 use error::{BlockError, Error};
-use ethereum_types::{H256, H512, U256};
+use ethcore_miner::pool::{Priority, ScoredTransaction, VerifiedTransaction};
+use ethereum_types::{Address, H256, H512, U256};
+use hash::KECCAK_NULL_RLP;
 use ethjson::spec::HbbftParams;
-use hbbft::{NetworkInfo, Target};
+use hbbft::{FaultLog, NetworkInfo, Target};
 use io::{IoContext, IoHandler, IoService, TimerToken};
-use itertools::Itertools;
-use machine::EthereumMachine;
+use lru_cache::LruCache;
+use machine::{AuxiliaryData, Call, EthereumMachine};
 use parking_lot::RwLock;
+use pod_state::PodState;
 use rlp;
 use serde::Deserialize;
 use serde_json;
+use stats::PrometheusRegistry;
 use types::{
+    ancestry_action::AncestryAction,
     header::{ExtendedHeader, Header},
     ids::BlockId,
-    transaction::{SignedTransaction, TypedTransaction},
+    transaction::{Action, SignedTransaction, TypedTransaction},
     BlockNumber,
 };
+use vm::EnvInfo;
 
 use super::{
+    activation,
+    batch_policy::{BatchPolicy, DefaultBatchPolicy},
+    api::{
+        key_share_proof_message, DebugState, EncryptionKey, FaultReport, ForkEvidence, HbbftNotify, HbbftStatus, HbbftValidator, KeyShareProof,
+        KeygenPhaseStatus, KeygenProgress, KeygenValidatorStatus, MessageKind, MessageLatency, PoolStatus, ResetScope, SealingStatus, ValidatorConnection,
+        ValidatorLiveness, ValidatorPeer, Withdrawal,
+    },
+    clock::{Clock, SystemClock},
     contracts::{
-        keygen_history::initialize_synckeygen,
-        staking::start_time_of_next_phase_transition,
-        validator_set::{get_pending_validators, is_pending_validator, ValidatorType},
+        block_gas_limit,
+        feature_registry::{
+            self, FEATURE_OBSERVERS, FEATURE_OFFLINE_NOTICE, FEATURE_WIRE_V1, FEATURE_WIRE_V2,
+            FEATURE_WIRE_V3, FEATURE_WIRE_V4,
+        },
+        gas_price,
+        keygen_history::{
+            has_acks_of_address_data, has_part_of_address_data, initialize_synckeygen,
+            is_validator_keygen_transaction, KeygenReadStats, KEYGEN_HISTORY_ADDRESS,
+        },
+        random_hbbft,
+        staking::{
+            claim_ordered_withdraw_call, claim_reward_call, get_posdao_epoch,
+            get_posdao_epoch_start, get_reward_amount, ordered_withdrawal, remove_my_pool_call,
+            reward_was_taken, stake_first_epoch, start_time_of_next_phase_transition,
+            STAKING_CONTRACT_ADDRESS,
+        },
+        validator_set::{
+            self, get_pending_validators, get_pool_status, get_validator_pubkeys,
+            is_pending_validator, staking_by_mining_address, ValidatorType,
+        },
+    },
+    contribution::{Contribution, ContributionLimits},
+    encryption::{self, Decryptions, EncryptedPool},
+    epoch_keys::{EpochKey, EpochKeyProof, EpochKeyRegistry},
+    extra_data, fork,
+    hbbft_state::{
+        encryption_schedule, Batch, HbMessage, HbbftState, HoneyBadgerStep,
+        DEFAULT_EPOCH_HANDOFF_BLOCKS, DEFAULT_MAX_FUTURE_MESSAGES,
+    },
+    keygen_gossip::{self, KeygenGossip, Received},
+    keygen_transactions::{
+        KeygenTimeout, KeygenTransactionSender, DEFAULT_KEYGEN_GAS_PRICE, DEFAULT_KEYGEN_TIMEOUT,
     },
-    contribution::{unix_now_millis, unix_now_secs},
-    hbbft_state::{Batch, HbMessage, HbbftState, HoneyBadgerStep},
-    keygen_transactions::KeygenTransactionSender,
-    sealing::{self, RlpSig, Sealing},
+    message_latency::{honey_badger_kind, MessageLatencies},
+    message_outbox::MessageOutbox,
+    metrics::HbbftMetrics,
+    observers::{ObserverRegistry, Subscription},
+    queue_trigger::{QueueTrigger, QueuedTransaction},
+    recovery,
+    reward_claims::RewardClaimer,
+    sealing::{self, FutureSealShares, RlpSig, SealShareFilter, Sealing},
+    spec_validation, transaction_order,
+    utils::bound_contract::CallError,
+    validator_peers::{validator_enodes, ValidatorPeers},
+    watchdog::{TickWatchdog, TimedRwLock},
+    wire::{self, Envelope, WireVersion, WireVersions},
     NodeId,
 };
 
@@ -55,6 +115,142 @@ enum Message {
     HoneyBadger(usize, HbMessage),
     /// A threshold signature share. The combined signature is used as the block seal.
     Sealing(BlockNumber, sealing::Message),
+    /// The Part of a pending validator for the key generation of the next POSDAO epoch.
+    Keygen(keygen_gossip::Message),
+    /// A decryption share of the encrypted transaction with the given index in a block's batch.
+    Decryption(BlockNumber, usize, encryption::Message),
+    /// The sender is shutting down, and will not take part in consensus until it restarts.
+    Offline,
+    /// The sender is not a validator, and subscribes to our broadcast Honey Badger messages.
+    Observe,
+}
+
+impl Message {
+    /// The part of the protocol this message belongs to, for latency statistics.
+    fn kind(&self) -> MessageKind {
+        match self {
+            Message::HoneyBadger(_, hb_msg) => honey_badger_kind(hb_msg),
+            Message::Sealing(..) => MessageKind::Sealing,
+            Message::Keygen(_) => MessageKind::Keygen,
+            Message::Decryption(..) => MessageKind::Decryption,
+            Message::Offline => MessageKind::Offline,
+            Message::Observe => MessageKind::Observe,
+        }
+    }
+}
+
+/// Decodes a packet of consensus messages as received from a peer, and returns the number of
+/// messages in it. Exposed to fuzz the decoding of untrusted input.
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn decode_packet(packet: &[u8]) -> Result<usize, String> {
+    wire::decode_messages::<Message>(packet)
+        .map(|(_, messages)| messages.len())
+        .map_err(|e| e.to_string())
+}
+
+/// Encodes an unsigned observer subscription in a packet with the given envelope.
+#[cfg(test)]
+pub fn subscription_packet(envelope: Envelope) -> Vec<u8> {
+    wire::encode_messages(&[Message::Observe], WireVersion::V3, envelope, None)
+        .expect("encoding a subscription must succeed")
+        .remove(0)
+}
+
+/// The ways a byzantine validator tampers with the consensus messages it sends, to test that
+/// honest validators tolerate and report them.
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tampering {
+    /// Truncates packets containing Honey Badger messages, so they can't be decoded.
+    GarbageHoneyBadger,
+    /// Sends signature shares as shares of the following block.
+    WrongBlockSealingShares,
+    /// Sends each key generation Part together with a different one.
+    DuplicateParts,
+}
+
+/// Tampers with a packet of consensus messages, and returns the packets to send instead.
+#[cfg(test)]
+pub fn tamper_packet(
+    packet: &[u8],
+    tampering: Tampering,
+    signer: Option<&dyn EngineSigner>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let version = wire::packet_version(packet).map_err(|e| e.to_string())?;
+    let (envelope, messages): (_, Vec<Message>) =
+        wire::decode_messages(packet).map_err(|e| e.to_string())?;
+    let messages: Vec<Message> = match tampering {
+        Tampering::GarbageHoneyBadger => {
+            let has_hb_messages = messages.iter().any(|message| match message {
+                Message::HoneyBadger(..) => true,
+                _ => false,
+            });
+            if has_hb_messages {
+                return Ok(vec![packet[..packet.len() / 2].to_vec()]);
+            }
+            messages
+        }
+        Tampering::WrongBlockSealingShares => messages
+            .into_iter()
+            .map(|message| match message {
+                Message::Sealing(block_num, share) => Message::Sealing(block_num + 1, share),
+                message => message,
+            })
+            .collect(),
+        Tampering::DuplicateParts => messages
+            .into_iter()
+            .flat_map(|message| match message {
+                Message::Keygen(part) => {
+                    let mut other = part.clone();
+                    other.part.push(0);
+                    vec![Message::Keygen(part), Message::Keygen(other)]
+                }
+                message => vec![message],
+            })
+            .collect(),
+    };
+    let envelope = envelope.unwrap_or(Envelope {
+        chain_id: 0,
+        posdao_epoch: 0,
+    });
+    wire::encode_messages(&messages, version, envelope, signer).map_err(|e| e.to_string())
+}
+
+/// Database key under which the last agreed batch is persisted.
+pub const LAST_BATCH_KEY: &[u8] = b"hbbft_last_batch";
+
+/// A batch agreed upon by the validators, persisted so its block can be recreated after a crash.
+#[derive(Deserialize, Serialize)]
+struct PersistedBatch {
+    /// The hash of the block the batch's block is built upon.
+    parent_hash: H256,
+    /// The hbbft epoch, equal to the number of the block to create.
+    epoch: u64,
+    /// The agreed contributions of the batch.
+    contributions: BTreeMap<NodeId, Contribution>,
+    /// The decrypted transactions of the batch.
+    #[serde(default)]
+    decrypted: Vec<Vec<u8>>,
+}
+
+/// Database key under which the mining address of a withdrawn validator is persisted, so it does
+/// not resume contributing after a restart.
+const WITHDRAWN_KEY: &[u8] = b"hbbft_withdrawn";
+
+/// Database key under which cached consensus messages are persisted, periodically and on
+/// shutdown.
+const FUTURE_MESSAGES_KEY: &[u8] = b"hbbft_future_messages";
+
+/// Consensus messages for future hbbft epochs, persisted so a restarted validator can resume
+/// an epoch without waiting for them to be sent again.
+#[derive(Deserialize, Serialize)]
+struct PersistedMessages {
+    /// The POSDAO epoch the messages belong to.
+    posdao_epoch: u64,
+    /// The counter of messages we sent.
+    message_counter: usize,
+    /// The cached messages, by hbbft epoch.
+    messages: BTreeMap<u64, Vec<(NodeId, HbMessage)>>,
 }
 
 /// The Honey Badger BFT Engine.
@@ -63,12 +259,75 @@ pub struct HoneyBadgerBFT {
     client: Arc<RwLock<Option<Weak<dyn EngineClient>>>>,
     signer: Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
     machine: EthereumMachine,
-    hbbft_state: RwLock<HbbftState>,
-    sealing: RwLock<BTreeMap<BlockNumber, Sealing>>,
+    hbbft_state: TimedRwLock<HbbftState>,
+    sealing: TimedRwLock<BTreeMap<BlockNumber, Sealing>>,
     params: HbbftParams,
+    /// The AuRa engine of the blocks before the activation block.
+    legacy_engine: Option<Arc<AuthorityRound>>,
     message_counter: RwLock<usize>,
     random_numbers: RwLock<BTreeMap<BlockNumber, U256>>,
+    contributors: RwLock<BTreeMap<BlockNumber, BTreeSet<Address>>>,
     keygen_transaction_sender: RwLock<KeygenTransactionSender>,
+    keygen_timeout: RwLock<KeygenTimeout>,
+    /// The upcoming POSDAO epoch and the numbers of missing Parts and Acks last logged.
+    keygen_logged: RwLock<Option<(u64, usize, usize)>>,
+    keygen_gossip: RwLock<KeygenGossip>,
+    reward_claimer: RwLock<Option<RewardClaimer>>,
+    isolated: AtomicBool,
+    notify: RwLock<Vec<Weak<dyn HbbftNotify>>>,
+    keygen_read_stats: Arc<KeygenReadStats>,
+    /// Our key generation progress, as of the block with the given hash.
+    keygen_progress: RwLock<Option<(H256, KeygenProgress)>>,
+    wire_versions: RwLock<WireVersions>,
+    message_latencies: RwLock<MessageLatencies>,
+    recovery_block: RwLock<Option<BlockNumber>>,
+    client_version: RwLock<String>,
+    features_announced_epoch: RwLock<Option<u64>>,
+    active_features: AtomicU64,
+    pool_status: RwLock<Option<PoolStatus>>,
+    pool_status_block: AtomicU64,
+    silent_validators: RwLock<BTreeSet<NodeId>>,
+    unreachable_validators: RwLock<BTreeSet<NodeId>>,
+    /// Validators that announced their shutdown and have not sent messages since.
+    offline_validators: RwLock<BTreeSet<NodeId>>,
+    outbox: RwLock<MessageOutbox>,
+    seal_failures: RwLock<BTreeMap<u64, u64>>,
+    seal_failure_alert: AtomicBool,
+    withdrawn: AtomicBool,
+    /// Set once the batch persisted before a restart was recreated or discarded.
+    batch_recovered: AtomicBool,
+    /// Set once the client shuts down, stops the engine's timer.
+    shutting_down: AtomicBool,
+    validator_peers: RwLock<ValidatorPeers>,
+    fork_evidence: RwLock<VecDeque<ForkEvidence>>,
+    epoch_keys: RwLock<EpochKeyRegistry>,
+    forks_detected: AtomicU64,
+    /// The faults of validators of the current POSDAO epoch, by validator.
+    fault_reports: RwLock<BTreeMap<NodeId, FaultReport>>,
+    batch_policy: RwLock<Arc<dyn BatchPolicy>>,
+    metrics: HbbftMetrics,
+    seal_shares: RwLock<SealShareFilter>,
+    future_seal_shares: RwLock<FutureSealShares>,
+    clock: RwLock<Arc<dyn Clock>>,
+    queue_trigger: RwLock<QueueTrigger>,
+    /// The limits of the contributions we propose and accept in agreed batches.
+    contribution_limits: ContributionLimits,
+    /// The generation of the future messages cache last persisted, and when it was persisted.
+    persisted_messages: RwLock<(u64, Instant)>,
+    /// Agreed batches whose block could not be created yet, by hbbft epoch.
+    deferred_batches: RwLock<BTreeMap<u64, BTreeMap<NodeId, Contribution>>>,
+    /// Encrypted transactions submitted to this validator, waiting to be proposed.
+    encrypted_pool: RwLock<EncryptedPool>,
+    /// The decryptions of the encrypted transactions of agreed batches.
+    decryptions: RwLock<Decryptions>,
+    /// The observers subscribed to our consensus messages.
+    observers: RwLock<ObserverRegistry>,
+    /// Our subscription to the validators' consensus messages, while we observe them.
+    observer_subscription: RwLock<Subscription>,
+    /// The block gas limits read from the governance contract, by parent block hash.
+    gas_limits: RwLock<LruCache<H256, Option<U256>>>,
+    /// The start of the running timer tick, watched for ticks that take too long.
+    tick_watchdog: TickWatchdog,
 }
 
 struct TransitionHandler {
@@ -78,6 +337,31 @@ struct TransitionHandler {
 
 const DEFAULT_DURATION: Duration = Duration::from_secs(1);
 
+/// How often changes to the cached consensus messages for future epochs are persisted, so they
+/// survive a crash as well.
+const FUTURE_MESSAGES_PERSIST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far in the future, in seconds, the timestamp of a recovery block may be.
+const MAX_RECOVERY_BLOCK_CLOCK_DRIFT: u64 = 15;
+
+/// The number of hbbft epochs without consensus messages after which a validator is reported as silent.
+const SILENT_EPOCHS_WARNING_THRESHOLD: u64 = 10;
+
+/// The number of blocks of a POSDAO epoch in a row with an invalid seal after which an alert is raised.
+const SEAL_FAILURE_ALERT_THRESHOLD: u64 = 5;
+
+/// The number of most recent competing block pairs kept as evidence.
+const MAX_FORK_EVIDENCE: usize = 16;
+
+/// The duration of a timer tick after which the engine's diagnostic state is logged.
+const SLOW_TICK_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// How often the watchdog thread checks whether the running timer tick is too slow.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of parent blocks whose block gas limit read from the governance contract is cached.
+const GAS_LIMIT_CACHE_CAPACITY: usize = 16;
+
 impl TransitionHandler {
     /// Returns the approximate time duration between the latest block and the given offset
     /// (is 0 if the offset was passed) or the default time duration of 1s.
@@ -87,7 +371,7 @@ impl TransitionHandler {
             let next_block_time = (block_header.timestamp() + offset) as u128 * 1000;
 
             // We get the current time in milliseconds to calculate the exact timer duration.
-            let now = unix_now_millis();
+            let now = self.engine.clock.read().now_millis();
 
             if now >= next_block_time {
                 // If the current time is already past the minimum time for the next block
@@ -136,7 +420,10 @@ impl IoHandler<()> for TransitionHandler {
     }
 
     fn timeout(&self, io: &IoContext<()>, timer: TimerToken) {
-        if timer == ENGINE_TIMEOUT_TOKEN {
+        // The timer is not registered again once the client shuts down.
+        if timer == ENGINE_TIMEOUT_TOKEN && !self.engine.shutting_down.load(Ordering::SeqCst) {
+            self.engine.tick_watchdog.tick_started(Instant::now());
+
             //trace!(target: "consensus", "Honey Badger IoHandler timeout called");
             // The block may be complete, but not have been ready to seal - trigger a new seal attempt.
             // TODO: In theory, that should not happen. The seal is ready exactly when the sealing entry is `Complete`.
@@ -148,6 +435,31 @@ impl IoHandler<()> for TransitionHandler {
 
             // Periodically allow messages received for future epochs to be processed.
             self.engine.replay_cached_messages();
+            self.engine.persist_changed_cached_messages();
+
+            // Create the blocks of batches agreed before their parent block was imported.
+            self.engine.create_deferred_block();
+
+            // Announce our supported features once per POSDAO epoch.
+            self.engine.announce_features();
+
+            // Make sure our pool has not been demoted on chain.
+            self.engine.check_pool_status();
+
+            // Claim our staker's rewards, if configured.
+            self.engine.claim_rewards();
+
+            // Report validators we have not heard from in a while.
+            self.engine.check_validator_liveness();
+
+            // Deliver messages to reconnected validators.
+            self.engine.flush_outbox();
+
+            // Renew our subscription to the validators' consensus messages, if we observe them.
+            self.engine.subscribe_as_observer();
+
+            // Keep reserved connections to the current and pending validators.
+            self.engine.update_validator_peers();
 
             // The client may not be registered yet on startup, we set the default duration.
             let mut timer_duration = DEFAULT_DURATION;
@@ -166,44 +478,136 @@ impl IoHandler<()> for TransitionHandler {
 
                         // If the maximum block time has been reached we trigger a new block in any case.
                         if self.max_block_time_remaining(c.clone()) == Duration::from_secs(0) {
-                            self.engine.start_hbbft_epoch(c);
+                            self.engine.start_hbbft_epoch(c.clone());
                         }
 
+                        // As a last resort, create a recovery block if consensus has stalled for too long.
+                        self.engine.try_create_recovery_block(c);
+
                         // Set timer duration to the default period (1s)
                         timer_duration = DEFAULT_DURATION;
                     }
 
-                    // The duration should be at least 1ms and at most self.engine.params.minimum_block_time
+                    // The duration should be at least 1ms and at most self.engine.params.minimum_block_time,
+                    // or the default period if there is no minimum block time.
                     timer_duration = max(timer_duration, Duration::from_millis(1));
-                    timer_duration = min(
-                        timer_duration,
-                        Duration::from_secs(self.engine.params.minimum_block_time),
-                    );
+                    if self.engine.params.minimum_block_time > 0 {
+                        timer_duration = min(
+                            timer_duration,
+                            Duration::from_secs(self.engine.params.minimum_block_time),
+                        );
+                    }
                 }
             }
 
+            self.engine.tick_watchdog.tick_finished();
+
             io.register_timer_once(ENGINE_TIMEOUT_TOKEN, timer_duration)
 				.unwrap_or_else(
 					|e| warn!(target: "consensus", "Failed to restart consensus step timer: {}.", e),
 				);
         }
     }
+
+    /// Sent when we switched to a new POSDAO epoch: messages cached for it can be processed
+    /// right away instead of waiting for the next timeout. Sent on shutdown to stop the timer.
+    fn message(&self, io: &IoContext<()>, _message: &()) {
+        if self.engine.shutting_down.load(Ordering::SeqCst) {
+            io.clear_timer(ENGINE_TIMEOUT_TOKEN).unwrap_or_else(
+                |e| warn!(target: "consensus", "Failed to stop consensus timer: {}.", e),
+            );
+            return;
+        }
+        self.engine.replay_cached_messages();
+    }
 }
 
 impl HoneyBadgerBFT {
     /// Creates an instance of the Honey Badger BFT Engine.
-    pub fn new(params: HbbftParams, machine: EthereumMachine) -> Result<Arc<Self>, Error> {
+    pub fn new(mut params: HbbftParams, machine: EthereumMachine) -> Result<Arc<Self>, Error> {
+        let keygen_read_stats = Arc::new(KeygenReadStats::default());
+        let wire_versions =
+            WireVersions::from_params(&params.wire_versions.clone().unwrap_or_default())
+                .map_err(EngineError::Custom)?;
+        spec_validation::validate_params(&params).map_err(EngineError::Custom)?;
+        spec_validation::validate_extra_data_size(
+            &params,
+            machine.params().maximum_extra_data_size,
+        )
+        .map_err(EngineError::Custom)?;
+        let legacy_engine = activation::legacy_engine(&mut params, &machine)?;
+        let keygen_timeout = params.keygen_timeout.unwrap_or(DEFAULT_KEYGEN_TIMEOUT);
+        let keygen_gas_price = params.keygen_gas_price;
+        let queue_trigger = QueueTrigger::new(&params);
+        let contribution_limits = ContributionLimits::from_params(&params);
+        let hbbft_state = HbbftState::new(
+            keygen_read_stats.clone(),
+            encryption_schedule(params.encryption_schedule),
+            params
+                .max_future_messages
+                .unwrap_or(DEFAULT_MAX_FUTURE_MESSAGES),
+            params
+                .epoch_handoff_blocks
+                .unwrap_or(DEFAULT_EPOCH_HANDOFF_BLOCKS),
+        );
         let engine = Arc::new(HoneyBadgerBFT {
             transition_service: IoService::<()>::start("Hbbft")?,
             client: Arc::new(RwLock::new(None)),
             signer: Arc::new(RwLock::new(None)),
             machine,
-            hbbft_state: RwLock::new(HbbftState::new()),
-            sealing: RwLock::new(BTreeMap::new()),
+            hbbft_state: TimedRwLock::new(hbbft_state),
+            sealing: TimedRwLock::new(BTreeMap::new()),
             params,
+            legacy_engine,
             message_counter: RwLock::new(0),
             random_numbers: RwLock::new(BTreeMap::new()),
-            keygen_transaction_sender: RwLock::new(KeygenTransactionSender::new()),
+            contributors: RwLock::new(BTreeMap::new()),
+            keygen_transaction_sender: RwLock::new(KeygenTransactionSender::new(keygen_gas_price)),
+            keygen_timeout: RwLock::new(KeygenTimeout::new(keygen_timeout)),
+            keygen_logged: RwLock::new(None),
+            keygen_gossip: RwLock::new(KeygenGossip::default()),
+            reward_claimer: RwLock::new(None),
+            isolated: AtomicBool::new(false),
+            notify: RwLock::new(Vec::new()),
+            keygen_read_stats,
+            keygen_progress: RwLock::new(None),
+            wire_versions: RwLock::new(wire_versions),
+            message_latencies: RwLock::new(MessageLatencies::default()),
+            recovery_block: RwLock::new(None),
+            client_version: RwLock::new(format!("ethcore/v{}", env!("CARGO_PKG_VERSION"))),
+            features_announced_epoch: RwLock::new(None),
+            active_features: AtomicU64::new(0),
+            pool_status: RwLock::new(None),
+            pool_status_block: AtomicU64::new(0),
+            silent_validators: RwLock::new(BTreeSet::new()),
+            unreachable_validators: RwLock::new(BTreeSet::new()),
+            offline_validators: RwLock::new(BTreeSet::new()),
+            outbox: RwLock::new(MessageOutbox::default()),
+            seal_failures: RwLock::new(BTreeMap::new()),
+            seal_failure_alert: AtomicBool::new(false),
+            withdrawn: AtomicBool::new(false),
+            batch_recovered: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            validator_peers: RwLock::new(ValidatorPeers::default()),
+            fork_evidence: RwLock::new(VecDeque::new()),
+            epoch_keys: RwLock::new(EpochKeyRegistry::default()),
+            forks_detected: AtomicU64::new(0),
+            fault_reports: RwLock::new(BTreeMap::new()),
+            batch_policy: RwLock::new(Arc::new(DefaultBatchPolicy)),
+            metrics: HbbftMetrics::default(),
+            seal_shares: RwLock::new(SealShareFilter::default()),
+            future_seal_shares: RwLock::new(FutureSealShares::default()),
+            clock: RwLock::new(Arc::new(SystemClock)),
+            queue_trigger: RwLock::new(queue_trigger),
+            contribution_limits,
+            persisted_messages: RwLock::new((0, Instant::now())),
+            deferred_batches: RwLock::new(BTreeMap::new()),
+            encrypted_pool: RwLock::new(EncryptedPool::default()),
+            decryptions: RwLock::new(Decryptions::default()),
+            observers: RwLock::new(ObserverRegistry::default()),
+            observer_subscription: RwLock::new(Subscription::default()),
+            gas_limits: RwLock::new(LruCache::new(GAS_LIMIT_CACHE_CAPACITY)),
+            tick_watchdog: TickWatchdog::default(),
         });
 
         if !engine.params.is_unit_test.unwrap_or(false) {
@@ -214,6 +618,7 @@ impl HoneyBadgerBFT {
             engine
                 .transition_service
                 .register_handler(Arc::new(handler))?;
+            HoneyBadgerBFT::spawn_tick_watchdog(Arc::downgrade(&engine));
         }
 
         Ok(engine)
@@ -225,68 +630,270 @@ impl HoneyBadgerBFT {
         output: Vec<Batch>,
         network_info: &NetworkInfo<NodeId>,
     ) {
-        // TODO: Multiple outputs are possible,
-        //       process all outputs, respecting their epoch context.
-        if output.len() > 1 {
-            error!(target: "consensus", "UNHANDLED EPOCH OUTPUTS!");
-            panic!("UNHANDLED EPOCH OUTPUTS!");
+        if output.is_empty() {
+            return;
         }
-        let batch = match output.first() {
-            None => return,
-            Some(batch) => batch,
+        for mut batch in output {
+            trace!(target: "consensus", "Batch received for epoch {}.", batch.epoch);
+            batch.contributions =
+                self.drop_oversized_contributions(batch.epoch, batch.contributions);
+            self.metrics.batch_agreed(
+                batch
+                    .contributions
+                    .values()
+                    .map(|c| c.transactions.iter().map(|t| t.len() as u64).sum::<u64>()),
+            );
+            self.notify(|n| n.batch_agreed(batch.epoch, batch.contributions.len()));
+            self.encrypted_pool
+                .write()
+                .remove_agreed(&batch.contributions);
+            self.deferred_batches
+                .write()
+                .insert(batch.epoch, batch.contributions);
+        }
+        self.create_next_block(client, network_info);
+    }
+
+    /// Removes the contributions exceeding the contribution limits from an agreed batch, and
+    /// reports their proposers.
+    ///
+    /// Every validator applies the same limits to the same agreed batch, so all of them create
+    /// the block from the same remaining contributions.
+    fn drop_oversized_contributions(
+        &self,
+        hbbft_epoch: u64,
+        contributions: BTreeMap<NodeId, Contribution>,
+    ) -> BTreeMap<NodeId, Contribution> {
+        // Only the size limits are checked, which do not depend on the parent block.
+        contributions
+            .into_iter()
+            .filter(|(node_id, contribution)| {
+                let reason = match self.contribution_limits.check(contribution) {
+                    Ok(()) => return true,
+                    Err(reason) => reason,
+                };
+                let proposer = public_to_address(&node_id.0);
+                self.report_fault(
+                    *node_id,
+                    format!(
+                        "its contribution to hbbft epoch {} was dropped: {}",
+                        hbbft_epoch, reason
+                    ),
+                );
+                self.metrics.contribution_rejected();
+                self.notify(|n| n.contribution_rejected(hbbft_epoch, proposer, &reason));
+                false
+            })
+            .collect()
+    }
+
+    /// Returns the limits of the contributions to the block following the given parent block.
+    ///
+    /// The minimum gas price of the gas price governance contract at the parent block takes
+    /// precedence over the `minimumGasPrice` parameter, which is only used if the contract is
+    /// not configured or cannot be read.
+    fn contribution_limits_at(
+        &self,
+        client: &dyn EngineClient,
+        parent: BlockId,
+    ) -> ContributionLimits {
+        let mut limits = self.contribution_limits;
+        if let Some(contract) = self.params.min_gas_price_contract_address {
+            match gas_price::minimum_gas_price(client, contract, parent) {
+                Ok(price) => limits.min_gas_price = price,
+                Err(e) => {
+                    warn!(target: "engine", "Could not read the minimum gas price at block {:?}, using the minimumGasPrice parameter: {:?}", parent, e)
+                }
+            }
+        }
+        limits
+    }
+
+    /// Creates the block following the latest block, if its batch was agreed.
+    ///
+    /// A validator catching up can agree on several hbbft epochs in one step, but a block can
+    /// only be created once its parent is imported. The batches of later epochs wait until then,
+    /// and batches of blocks imported in the meantime are discarded. If the batch proposes
+    /// encrypted transactions, the block is only created once they are decrypted.
+    fn create_next_block(
+        &self,
+        client: Arc<dyn EngineClient>,
+        network_info: &NetworkInfo<NodeId>,
+    ) -> Option<()> {
+        let epoch = client.block_number(BlockId::Latest)? + 1;
+        {
+            let mut batches = self.deferred_batches.write();
+            *batches = batches.split_off(&epoch);
+            if !batches.contains_key(&epoch) {
+                return None;
+            }
+        }
+        self.decryptions.write().prune(epoch - 1);
+        let decrypted = if self.decrypts_transactions(epoch) {
+            self.decrypted_transactions(&client, epoch, network_info)?
+        } else {
+            Vec::new()
         };
+        let contributions = self.deferred_batches.write().remove(&epoch)?;
+        trace!(target: "consensus", "Creating block #{} from the agreed batch.", epoch);
+
+        if self.params.persist_last_batch.unwrap_or(false) {
+            self.persist_batch(&*client, epoch, &contributions, &decrypted);
+        }
+        self.create_block(client, epoch, &contributions, &decrypted, network_info);
+        Some(())
+    }
+
+    /// Returns the decrypted transactions of the batch of the given block, or `None` while they
+    /// are still being decrypted. Starts their decryption if it was not started yet.
+    fn decrypted_transactions(
+        &self,
+        client: &Arc<dyn EngineClient>,
+        block_num: BlockNumber,
+        network_info: &NetworkInfo<NodeId>,
+    ) -> Option<Vec<Vec<u8>>> {
+        {
+            let decryptions = self.decryptions.read();
+            if decryptions.is_started(block_num) {
+                return decryptions.plaintexts(block_num);
+            }
+        }
+        let ciphertexts =
+            encryption::batch_ciphertexts(self.deferred_batches.read().get(&block_num)?);
+        if ciphertexts.is_empty() {
+            return Some(Vec::new());
+        }
+        trace!(target: "consensus", "Decrypting {} transactions of block #{}.", ciphertexts.len(), block_num);
+        let steps = self
+            .decryptions
+            .write()
+            .start(block_num, ciphertexts, network_info);
+        for (index, step) in steps {
+            self.process_decryption_step(client.clone(), step, block_num, index, network_info);
+        }
+        // The block is created once the last transaction is decrypted.
+        None
+    }
 
-        trace!(target: "consensus", "Batch received for epoch {}, creating new Block.", batch.epoch);
+    /// Creates the block of a batch that was agreed before its parent block was imported.
+    fn create_deferred_block(&self) -> Option<()> {
+        if self.deferred_batches.read().is_empty() {
+            return None;
+        }
+        let client = self.client_arc()?;
+        let epoch = client.block_number(BlockId::Latest)? + 1;
+        if !self.deferred_batches.read().contains_key(&epoch) {
+            return None;
+        }
+        let network_info =
+            self.hbbft_state
+                .write()
+                .network_info_for(client.clone(), &self.signer, epoch)?;
+        self.create_next_block(client, &network_info)
+    }
+
+    /// Creates the pending block for the given agreed contributions and signs it.
+    fn create_block(
+        &self,
+        client: Arc<dyn EngineClient>,
+        epoch: u64,
+        contributions: &BTreeMap<NodeId, Contribution>,
+        decrypted: &[Vec<u8>],
+        network_info: &NetworkInfo<NodeId>,
+    ) {
+        let policy = self.batch_policy.read().clone();
+        let limits = self.contribution_limits_at(&*client, BlockId::Number(epoch - 1));
 
         // Decode and de-duplicate transactions
-        let batch_txns: Vec<_> = batch
-            .contributions
-            .iter()
-            .flat_map(|(_, c)| &c.transactions)
-            .filter_map(|ser_txn| {
-                // TODO: Report proposers of malformed transactions.
-                TypedTransaction::decode(ser_txn).ok()
-            })
-            .unique()
+        let mut decoded_txns = Vec::new();
+        for (proposer, contribution) in contributions {
+            // Encrypted transactions are decoded once they are decrypted.
+            let entries = contribution
+                .transactions
+                .iter()
+                .filter(|entry| encryption::untag(entry).is_none());
+            let mut underpriced = 0;
+            for ser_txn in entries {
+                match TypedTransaction::decode(ser_txn) {
+                    Ok(txn) if !limits.is_priced_enough(&txn) => underpriced += 1,
+                    Ok(txn) => decoded_txns.push(txn),
+                    Err(e) => self.report_fault(
+                        *proposer,
+                        format!(
+                            "it proposed a malformed transaction in hbbft epoch {}: {:?}",
+                            epoch, e
+                        ),
+                    ),
+                }
+            }
+            if underpriced > 0 {
+                self.report_fault(
+                    *proposer,
+                    format!(
+                        "it proposed {} transactions below the minimum gas price in hbbft epoch {}",
+                        underpriced, epoch
+                    ),
+                );
+            }
+        }
+        decoded_txns.extend(
+            decrypted
+                .iter()
+                .filter_map(|ser_txn| TypedTransaction::decode(ser_txn).ok())
+                .filter(|txn| limits.is_priced_enough(txn)),
+        );
+        let proposed = decoded_txns.len();
+        let decoded_txns =
+            self.hbbft_state
+                .write()
+                .drop_included_transactions(&*client, epoch, decoded_txns);
+        if decoded_txns.len() < proposed {
+            debug!(target: "consensus", "Dropped {} transactions of hbbft epoch {} that were already included in a recent block.",
+                   proposed - decoded_txns.len(), epoch);
+        }
+        let batch_txns: Vec<_> = policy
+            .transactions(decoded_txns)
+            .into_iter()
             .filter_map(|txn| {
                 // TODO: Report proposers of invalidly signed transactions.
                 SignedTransaction::new(txn).ok()
             })
             .collect();
 
-        // We use the median of all contributions' timestamps
-        let timestamps = batch
-            .contributions
+        let timestamps = contributions
             .iter()
-            .map(|(_, c)| c.timestamp)
-            .sorted();
-
-        let timestamp = match timestamps.iter().nth(timestamps.len() / 2) {
-            Some(t) => t.clone(),
+            .map(|(n, c)| (*n, c.timestamp))
+            .collect();
+        let timestamp = match policy.timestamp(&timestamps) {
+            Some(t) => t,
             None => {
                 error!(target: "consensus", "Error calculating the block timestamp");
                 return;
             }
         };
 
-        let random_number = batch
-            .contributions
+        let random_data = contributions
             .iter()
-            .fold(U256::zero(), |acc, (n, c)| {
-                if c.random_data.len() >= 32 {
-                    U256::from(&c.random_data[0..32]).bitxor(acc)
-                } else {
-                    // TODO: Report malicious behavior by node!
-                    error!(target: "consensus", "Insufficient random data from node {}", n);
-                    acc
-                }
-            });
+            .map(|(n, c)| (*n, &c.random_data[..]))
+            .collect();
+        let random_number = policy.random_number(&random_data);
 
-        self.random_numbers
-            .write()
-            .insert(batch.epoch, random_number);
+        self.set_random_number(epoch, random_number);
+        self.set_contributors(
+            epoch,
+            contributions
+                .keys()
+                .map(|id| public_to_address(&id.0))
+                .collect(),
+        );
+
+        let batch_txns = if self.params.group_transactions_by_sender.unwrap_or(false) {
+            transaction_order::group_by_sender(batch_txns, &random_number)
+        } else {
+            batch_txns
+        };
 
-        if let Some(header) = client.create_pending_block_at(batch_txns, timestamp, batch.epoch) {
+        if let Some(header) = client.create_pending_block_at(batch_txns, timestamp, epoch) {
             let block_num = header.number();
             let hash = header.bare_hash();
             trace!(target: "consensus", "Sending signature share of {} for block {}", hash, block_num);
@@ -306,8 +913,182 @@ impl HoneyBadgerBFT {
             };
             self.process_seal_step(client, step, block_num, network_info);
         } else {
-            error!(target: "consensus", "Could not create pending block for hbbft epoch {}: ", batch.epoch);
+            error!(target: "consensus", "Could not create pending block for hbbft epoch {}: ", epoch);
+        }
+    }
+
+    /// Writes the agreed batch to disk, so its block can be recreated if we crash before importing it.
+    fn persist_batch(
+        &self,
+        client: &dyn EngineClient,
+        epoch: u64,
+        contributions: &BTreeMap<NodeId, Contribution>,
+        decrypted: &[Vec<u8>],
+    ) {
+        let parent_header = epoch
+            .checked_sub(1)
+            .and_then(|parent| client.block_header(BlockId::Number(parent)));
+        let parent_hash = match parent_header {
+            Some(header) => header.hash(),
+            None => {
+                error!(target: "consensus", "Could not persist batch for hbbft epoch {}: parent block not available.", epoch);
+                return;
+            }
+        };
+        let persisted = PersistedBatch {
+            parent_hash,
+            epoch,
+            contributions: contributions.clone(),
+            decrypted: decrypted.to_vec(),
+        };
+        match serde_json::to_vec(&persisted) {
+            Ok(bytes) => client.set_engine_data(LAST_BATCH_KEY, Some(bytes)),
+            Err(e) => error!(target: "consensus", "Could not serialize batch for hbbft epoch {}: {}", epoch, e),
+        }
+    }
+
+    /// Writes the batch of the next block to disk on shutdown, if it was agreed but its block
+    /// was not created yet, so it can be recreated after the restart.
+    ///
+    /// Batches whose block was created are already persisted when the block is created.
+    fn persist_pending_batch(&self) -> Option<()> {
+        if !self.params.persist_last_batch.unwrap_or(false) {
+            return None;
+        }
+        let client = self.client_arc()?;
+        let epoch = client.block_number(BlockId::Latest)? + 1;
+        let contributions = self.deferred_batches.read().get(&epoch)?.clone();
+        let decrypted = if self.decrypts_transactions(epoch) {
+            match self.decryptions.read().plaintexts(epoch) {
+                Some(decrypted) => decrypted,
+                None => {
+                    warn!(target: "consensus", "Not persisting the batch of hbbft epoch {}: its transactions are still being decrypted.", epoch);
+                    return None;
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        info!(target: "consensus", "Persisting the agreed batch of hbbft epoch {} on shutdown.", epoch);
+        self.persist_batch(&*client, epoch, &contributions, &decrypted);
+        Some(())
+    }
+
+    /// Writes the cached consensus messages for future epochs and the message counter to disk.
+    fn persist_cached_messages(&self) {
+        let client = match self.client_arc() {
+            Some(client) => client,
+            None => return,
+        };
+        let (generation, persisted) = {
+            let state = self.hbbft_state.read();
+            let persisted = PersistedMessages {
+                posdao_epoch: state.current_posdao_epoch(),
+                message_counter: *self.message_counter.read(),
+                messages: state.future_messages().clone(),
+            };
+            (state.future_messages_generation(), persisted)
+        };
+        *self.persisted_messages.write() = (generation, Instant::now());
+        match serde_json::to_vec(&persisted) {
+            Ok(bytes) => {
+                trace!(target: "consensus", "Persisting {} cached consensus messages.", persisted.messages.values().map(Vec::len).sum::<usize>());
+                client.set_engine_data(FUTURE_MESSAGES_KEY, Some(bytes));
+            }
+            Err(e) => error!(target: "consensus", "Could not serialize cached consensus messages: {}", e),
+        }
+    }
+
+    /// Persists the cached consensus messages for future epochs if they changed since they were
+    /// last persisted, at most once per `FUTURE_MESSAGES_PERSIST_INTERVAL`.
+    fn persist_changed_cached_messages(&self) {
+        let (generation, persisted_at) = *self.persisted_messages.read();
+        if persisted_at.elapsed() < FUTURE_MESSAGES_PERSIST_INTERVAL
+            || self.hbbft_state.read().future_messages_generation() == generation
+        {
+            return;
+        }
+        self.persist_cached_messages();
+    }
+
+    /// Restores the consensus messages and message counter persisted before a restart, if they
+    /// belong to the current POSDAO epoch.
+    fn restore_cached_messages(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        let bytes = client.engine_data(FUTURE_MESSAGES_KEY)?;
+        client.set_engine_data(FUTURE_MESSAGES_KEY, None);
+        let persisted: PersistedMessages = match serde_json::from_slice(&bytes) {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                warn!(target: "consensus", "Discarding unreadable persisted consensus messages: {}", e);
+                return None;
+            }
+        };
+
+        {
+            let mut counter = self.message_counter.write();
+            *counter = max(*counter, persisted.message_counter);
+        }
+
+        let mut state = self.hbbft_state.write();
+        if state.current_posdao_epoch() != persisted.posdao_epoch {
+            trace!(target: "consensus", "Discarding persisted consensus messages of POSDAO epoch {}.", persisted.posdao_epoch);
+            return None;
+        }
+        info!(target: "consensus", "Restored {} cached consensus messages.", persisted.messages.values().map(Vec::len).sum::<usize>());
+        state.cache_future_messages(persisted.messages);
+        Some(())
+    }
+
+    /// Recreates and signs the block of a batch persisted before a restart, if the chain head still matches.
+    ///
+    /// This is tried whenever the client or the signer is set, until the batch was recreated or
+    /// discarded once.
+    fn recover_persisted_batch(&self) -> Option<()> {
+        if !self.params.persist_last_batch.unwrap_or(false)
+            || self.batch_recovered.load(Ordering::SeqCst)
+        {
+            return None;
+        }
+        let client = self.client_arc()?;
+        let bytes = client.engine_data(LAST_BATCH_KEY)?;
+        let persisted: PersistedBatch = match serde_json::from_slice(&bytes) {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                warn!(target: "consensus", "Discarding unreadable persisted batch: {}", e);
+                client.set_engine_data(LAST_BATCH_KEY, None);
+                self.batch_recovered.store(true, Ordering::SeqCst);
+                return None;
+            }
+        };
+
+        if client.block_header(BlockId::Latest)?.hash() != persisted.parent_hash {
+            // The block was imported already or the chain moved on, the batch is obsolete.
+            trace!(target: "consensus", "Discarding obsolete persisted batch for hbbft epoch {}.", persisted.epoch);
+            client.set_engine_data(LAST_BATCH_KEY, None);
+            self.batch_recovered.store(true, Ordering::SeqCst);
+            return None;
+        }
+
+        // We can only sign the block once we are a validator of the batch's epoch.
+        let network_info = self.hbbft_state.write().network_info_for(
+            client.clone(),
+            &self.signer,
+            persisted.epoch,
+        )?;
+        if self.batch_recovered.swap(true, Ordering::SeqCst) {
+            return None;
         }
+
+        info!(target: "consensus", "Recreating block #{} from persisted batch.", persisted.epoch);
+        self.create_block(
+            client,
+            persisted.epoch,
+            &persisted.contributions,
+            &persisted.decrypted,
+            &network_info,
+        );
+        Some(())
     }
 
     fn process_hb_message(
@@ -340,10 +1121,15 @@ impl HoneyBadgerBFT {
     ) -> Result<(), EngineError> {
         let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
         trace!(target: "consensus", "Received sealing message  {:?} from {}", message, sender_id);
-        if let Some(latest) = client.block_number(BlockId::Latest) {
-            if latest >= block_num {
-                return Ok(()); // Message is obsolete.
-            }
+        let latest = client.block_number(BlockId::Latest);
+        if latest.map_or(false, |latest| latest >= block_num) {
+            self.answer_late_seal_share(&client, sender_id, block_num);
+            return Ok(()); // Message is obsolete.
+        }
+
+        if !self.seal_shares.read().is_new(block_num, &sender_id) {
+            trace!(target: "consensus", "Ignoring repeated or late signature share for block {} from {}", block_num, sender_id);
+            return Ok(());
         }
 
         let network_info = match self.hbbft_state.write().network_info_for(
@@ -353,6 +1139,16 @@ impl HoneyBadgerBFT {
         ) {
             Some(n) => n,
             None => {
+                // The share may have arrived before the block's parent was imported.
+                let cached = latest.map_or(false, |latest| {
+                    self.future_seal_shares
+                        .write()
+                        .insert(latest, block_num, sender_id, message)
+                });
+                if cached {
+                    trace!(target: "consensus", "Caching signature share for block #{} from {} until its network info is available.", block_num, sender_id);
+                    return Ok(());
+                }
                 error!(target: "consensus", "Sealing message for block #{} could not be processed due to missing/mismatching network info.", block_num);
                 return Err(EngineError::UnexpectedMessage);
             }
@@ -366,60 +1162,334 @@ impl HoneyBadgerBFT {
             .or_insert_with(|| self.new_sealing(&network_info))
             .handle_message(&sender_id, message);
         match step_result {
-            Ok(step) => self.process_seal_step(client, step, block_num, &network_info),
+            Ok(step) => {
+                self.seal_shares.write().insert(block_num, sender_id);
+                self.process_seal_step(client, step, block_num, &network_info)
+            }
             Err(err) => error!(target: "consensus", "Error on ThresholdSign step: {:?}", err), // TODO: Errors
         }
         Ok(())
     }
 
-    fn dispatch_messages<I>(
+    /// Answers a late signature share for one of the last blocks sealed by the previous POSDAO
+    /// epoch's validators with our own share, so a validator still sealing it can complete it.
+    fn answer_late_seal_share(
         &self,
         client: &Arc<dyn EngineClient>,
-        messages: I,
-        net_info: &NetworkInfo<NodeId>,
-    ) where
-        I: IntoIterator<Item = TargetedMessage>,
-    {
-        for m in messages {
-            let ser =
-                serde_json::to_vec(&m.message).expect("Serialization of consensus message failed");
-            match m.target {
-                Target::Nodes(set) => {
-                    trace!(target: "consensus", "Dispatching message {:?} to {:?}", m.message, set);
-                    for node_id in set.into_iter().filter(|p| p != net_info.our_id()) {
-                        trace!(target: "consensus", "Sending message to {}", node_id.0);
-                        client.send_consensus_message(ser.clone(), Some(node_id.0));
-                    }
-                }
-                Target::AllExcept(set) => {
-                    trace!(target: "consensus", "Dispatching exclusive message {:?} to all except {:?}", m.message, set);
-                    for node_id in net_info
-                        .all_ids()
-                        .filter(|p| (p != &net_info.our_id() && !set.contains(p)))
-                    {
-                        trace!(target: "consensus", "Sending exclusive message to {}", node_id.0);
-                        client.send_consensus_message(ser.clone(), Some(node_id.0));
-                    }
-                }
+        sender_id: NodeId,
+        block_num: BlockNumber,
+    ) {
+        let network_info = match self
+            .hbbft_state
+            .write()
+            .handoff_network_info(block_num, sender_id)
+        {
+            Some(network_info) => network_info,
+            None => return,
+        };
+        let hash = match client
+            .block_header(BlockId::Number(block_num))
+            .and_then(|header| header.decode().ok())
+        {
+            Some(header) => header.bare_hash(),
+            None => return,
+        };
+        match self.new_sealing(&network_info).sign(hash) {
+            Ok(step) => {
+                trace!(target: "consensus", "Answering the late signature share of {} for block {} of the previous POSDAO epoch.", sender_id, block_num);
+                let messages = step.messages.into_iter().map(|msg| TargetedMessage {
+                    target: Target::Nodes(iter::once(sender_id).collect()),
+                    message: Message::Sealing(block_num, msg.message),
+                });
+                self.dispatch_messages(client, messages, &network_info);
+            }
+            Err(err) => {
+                debug!(target: "consensus", "Could not create a signature share for block {}: {:?}", block_num, err)
             }
         }
     }
 
-    fn process_seal_step(
+    fn process_decryption_message(
+        &self,
+        message: encryption::Message,
+        sender_id: NodeId,
+        block_num: BlockNumber,
+        index: usize,
+    ) -> Result<(), EngineError> {
+        let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
+        trace!(target: "consensus", "Received decryption share of transaction {} of block {} from {}", index, block_num, sender_id);
+        let latest = client.block_number(BlockId::Latest);
+        if latest.map_or(false, |latest| latest >= block_num) {
+            return Ok(()); // Message is obsolete.
+        }
+
+        let step = match self
+            .decryptions
+            .write()
+            .handle_message(block_num, index, &sender_id, message)
+        {
+            Some(step) => step,
+            // The share is kept until the batch of the block is agreed.
+            None => return Ok(()),
+        };
+        let network_info = self
+            .hbbft_state
+            .write()
+            .network_info_for(client.clone(), &self.signer, block_num)
+            .ok_or(EngineError::UnexpectedMessage)?;
+        self.process_decryption_step(client, step, block_num, index, &network_info);
+        Ok(())
+    }
+
+    /// Sends the decryption shares of a step, and creates the block once all of its encrypted
+    /// transactions are decrypted.
+    fn process_decryption_step(
         &self,
         client: Arc<dyn EngineClient>,
-        step: sealing::Step,
+        step: encryption::Result<encryption::Step>,
         block_num: BlockNumber,
+        index: usize,
         network_info: &NetworkInfo<NodeId>,
     ) {
+        let step = match step {
+            Ok(step) => step,
+            Err(err) => {
+                error!(target: "consensus", "Error on ThresholdDecrypt step of transaction {} of block {}: {:?}", index, block_num, err);
+                return;
+            }
+        };
+        self.report_faults(step.fault_log);
         let messages = step
             .messages
             .into_iter()
-            .map(|msg| msg.map(|m| Message::Sealing(block_num, m)));
+            .map(|msg| msg.map(|m| Message::Decryption(block_num, index, m)));
         self.dispatch_messages(&client, messages, network_info);
-        if let Some(sig) = step.output.into_iter().next() {
-            trace!(target: "consensus", "Signature for block {} is ready", block_num);
-            let state = Sealing::Complete(sig);
+        if let Some(plaintext) = step.output.into_iter().next() {
+            let complete = {
+                let mut decryptions = self.decryptions.write();
+                decryptions.record_output(block_num, index, plaintext);
+                decryptions.plaintexts(block_num).is_some()
+            };
+            if complete {
+                trace!(target: "consensus", "Transactions of block {} are decrypted", block_num);
+                self.create_deferred_block();
+            }
+        }
+    }
+
+    /// Processes the cached signature shares of the next block, once its parent is imported.
+    fn replay_future_seal_shares(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        let latest = client.block_number(BlockId::Latest)?;
+        let shares = self.future_seal_shares.write().take_ready(latest);
+        for (block_num, sender_id, message) in shares {
+            trace!(target: "consensus", "Replaying cached signature share for block #{} from {}", block_num, sender_id);
+            if let Err(e) = self.process_sealing_message(message, sender_id, block_num) {
+                debug!(target: "consensus", "Cached signature share for block #{} from {} could not be processed: {:?}", block_num, sender_id, e);
+            }
+        }
+        Some(())
+    }
+
+    fn dispatch_messages<I>(
+        &self,
+        client: &Arc<dyn EngineClient>,
+        messages: I,
+        net_info: &NetworkInfo<NodeId>,
+    ) where
+        I: IntoIterator<Item = TargetedMessage>,
+    {
+        let posdao_epoch = self.hbbft_state.read().current_posdao_epoch();
+        let wire_version = self.wire_versions.read().for_epoch(posdao_epoch);
+        let envelope = self.envelope(posdao_epoch);
+        // Group the messages by recipient, so they can be sent in batches.
+        let messages: Vec<TargetedMessage> = messages.into_iter().collect();
+        let observers: Vec<NodeId> = self
+            .observers
+            .write()
+            .observers(Instant::now())
+            .into_iter()
+            .filter(|id| !net_info.all_ids().any(|validator| validator == id))
+            .collect();
+        let mut batches: BTreeMap<NodeId, Vec<&Message>> = BTreeMap::new();
+        for m in &messages {
+            match &m.target {
+                Target::Nodes(set) => {
+                    trace!(target: "consensus", "Dispatching message {:?} to {:?}", m.message, set);
+                    for node_id in set.iter().filter(|p| *p != net_info.our_id()) {
+                        batches.entry(*node_id).or_default().push(&m.message);
+                    }
+                }
+                Target::AllExcept(set) => {
+                    trace!(target: "consensus", "Dispatching exclusive message {:?} to all except {:?}", m.message, set);
+                    for node_id in net_info
+                        .all_ids()
+                        .filter(|p| (p != &net_info.our_id() && !set.contains(p)))
+                    {
+                        batches.entry(*node_id).or_default().push(&m.message);
+                    }
+                    // Observers only follow the broadcast parts of Honey Badger.
+                    if let Message::HoneyBadger(..) = m.message {
+                        for node_id in observers.iter().filter(|p| !set.contains(p)) {
+                            batches.entry(*node_id).or_default().push(&m.message);
+                        }
+                    }
+                }
+            }
+        }
+        // The network drops messages to nodes we have no session with, so keep them until the
+        // node reconnects.
+        let connected_peers = client.as_full_client().and_then(|c| c.connected_peers());
+        let now = Instant::now();
+        let signer = self.signer.read();
+        for (node_id, batch) in batches {
+            let signer = signer.as_ref().map(|signer| &**signer);
+            let packets = match wire::encode_messages(&batch, wire_version, envelope, signer) {
+                Ok(packets) => packets,
+                Err(e) => {
+                    error!(target: "consensus", "Could not encode {} messages for {}: {}", batch.len(), node_id.0, e);
+                    continue;
+                }
+            };
+            self.metrics.messages_sent(batch.len() as u64);
+            if let Some(ref peers) = connected_peers {
+                if !peers.contains(&node_id.0) && observers.contains(&node_id) {
+                    trace!(target: "consensus", "Dropping {} messages for disconnected observer {}", batch.len(), node_id.0);
+                    continue;
+                }
+                if !peers.contains(&node_id.0) {
+                    trace!(target: "consensus", "Queueing {} messages in {} packets for disconnected {}", batch.len(), packets.len(), node_id.0);
+                    let mut outbox = self.outbox.write();
+                    for packet in packets {
+                        outbox.push(node_id, packet, now);
+                    }
+                    continue;
+                }
+            }
+            trace!(target: "consensus", "Sending {} messages in {} packets to {}", batch.len(), packets.len(), node_id.0);
+            for packet in packets {
+                client.send_consensus_message(packet, Some(node_id.0));
+            }
+        }
+    }
+
+    /// The envelope binding the consensus messages we send to our chain and POSDAO epoch.
+    fn envelope(&self, posdao_epoch: u64) -> Envelope {
+        Envelope {
+            chain_id: self.machine.params().chain_id,
+            posdao_epoch,
+        }
+    }
+
+    /// Rejects packets sent on another chain or in an earlier POSDAO epoch, and packets without
+    /// an envelope once the wire version in use requires one.
+    ///
+    /// Packets from later POSDAO epochs are accepted: their sender already imported the epoch's
+    /// first block, and the messages are cached until we do. Packets from the previous POSDAO
+    /// epoch are accepted while its validators' state is retained for the handoff.
+    fn check_envelope(&self, envelope: Option<Envelope>) -> Result<(), EngineError> {
+        let (posdao_epoch, handoff_epoch) = {
+            let state = self.hbbft_state.read();
+            (state.current_posdao_epoch(), state.handoff_posdao_epoch())
+        };
+        let envelope = match envelope {
+            Some(envelope) => envelope,
+            None if self.wire_versions.read().for_epoch(posdao_epoch) >= WireVersion::V3 => {
+                return Err(EngineError::MalformedMessage(
+                    "Consensus messages without an envelope are not accepted any more.".into(),
+                ));
+            }
+            None => return Ok(()),
+        };
+        let chain_id = self.machine.params().chain_id;
+        if envelope.chain_id != chain_id {
+            return Err(EngineError::MalformedMessage(format!(
+                "Consensus messages were sent for chain ID {}, expected {}.",
+                envelope.chain_id, chain_id
+            )));
+        }
+        if envelope.posdao_epoch < posdao_epoch && Some(envelope.posdao_epoch) != handoff_epoch {
+            return Err(EngineError::MalformedMessage(format!(
+                "Consensus messages were sent in POSDAO epoch {}, we are in epoch {}.",
+                envelope.posdao_epoch, posdao_epoch
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects packets not signed by the node they were received from, and packets without a
+    /// signature once the wire version in use requires one.
+    ///
+    /// Packets with consensus messages other than Parts of pending validators and observer
+    /// subscriptions must also be signed by a validator of the POSDAO epoch in their envelope, if
+    /// we know its validators. Packets from later epochs are cached, their senders are checked
+    /// when the messages are handled. Observers without a signer subscribe with unsigned packets.
+    fn check_packet_signer(
+        &self,
+        signer: Option<H512>,
+        sender_id: NodeId,
+        envelope: Option<Envelope>,
+        messages: &[Message],
+    ) -> Result<(), EngineError> {
+        let state = self.hbbft_state.read();
+        let is_subscription = messages.iter().all(|message| match message {
+            Message::Observe => true,
+            _ => false,
+        });
+        let signer = match signer {
+            Some(signer) => signer,
+            None if is_subscription => return Ok(()),
+            None if self
+                .wire_versions
+                .read()
+                .for_epoch(state.current_posdao_epoch())
+                >= WireVersion::V4 =>
+            {
+                return Err(EngineError::MalformedMessage(
+                    "Unsigned consensus messages are not accepted any more.".into(),
+                ));
+            }
+            None => return Ok(()),
+        };
+        if signer != sender_id.0 {
+            return Err(EngineError::MalformedMessage(format!(
+                "Consensus messages received from {} were signed by {}.",
+                sender_id.0, signer
+            )));
+        }
+        let from_validator = messages.iter().any(|message| match message {
+            Message::Keygen(_) | Message::Observe => false,
+            _ => true,
+        });
+        let posdao_epoch = envelope.map(|envelope| envelope.posdao_epoch);
+        let is_validator = posdao_epoch.and_then(|epoch| state.is_validator_of(epoch, &sender_id));
+        if from_validator && is_validator == Some(false) {
+            return Err(EngineError::MalformedMessage(format!(
+                "{} is not a validator of POSDAO epoch {:?}.",
+                sender_id.0, posdao_epoch
+            )));
+        }
+        Ok(())
+    }
+
+    fn process_seal_step(
+        &self,
+        client: Arc<dyn EngineClient>,
+        step: sealing::Step,
+        block_num: BlockNumber,
+        network_info: &NetworkInfo<NodeId>,
+    ) {
+        self.report_faults(step.fault_log);
+        let messages = step
+            .messages
+            .into_iter()
+            .map(|msg| msg.map(|m| Message::Sealing(block_num, m)));
+        self.dispatch_messages(&client, messages, network_info);
+        if let Some(sig) = step.output.into_iter().next() {
+            trace!(target: "consensus", "Signature for block {} is ready", block_num);
+            self.metrics.block_sealed(block_num);
+            self.seal_shares.write().complete(block_num);
+            let state = Sealing::Complete(sig);
             self.sealing.write().insert(block_num, state);
             client.update_sealing(ForceUpdateSealing::No);
         }
@@ -431,6 +1501,7 @@ impl HoneyBadgerBFT {
         step: HoneyBadgerStep,
         network_info: &NetworkInfo<NodeId>,
     ) {
+        self.report_faults(step.fault_log);
         let mut message_counter = self.message_counter.write();
         let messages = step.messages.into_iter().map(|msg| {
             *message_counter += 1;
@@ -447,47 +1518,115 @@ impl HoneyBadgerBFT {
     /// contributions exceeds the maximum number of tolerated faulty nodes.
     fn join_hbbft_epoch(&self) -> Result<(), EngineError> {
         let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
-        if self.is_syncing(&client) {
+        if self.awaits_activation(&client)
+            || self.withdrawn.load(Ordering::SeqCst)
+            || self.is_syncing(&client)
+        {
             return Ok(());
         }
+        let limits = self.contribution_limits_at(&*client, BlockId::Latest);
         let step = self
             .hbbft_state
             .write()
-            .contribute_if_contribution_threshold_reached(client.clone(), &self.signer);
+            .contribute_if_contribution_threshold_reached(
+                client.clone(),
+                &self.signer,
+                self.now_secs(),
+                self.encrypted_entries(&client),
+                limits,
+            );
         if let Some((step, network_info)) = step {
+            self.contribution_sent();
             self.process_step(client, step, &network_info)
         }
         Ok(())
     }
 
     fn start_hbbft_epoch(&self, client: Arc<dyn EngineClient>) {
-        if self.is_syncing(&client) {
+        if self.awaits_activation(&client)
+            || self.withdrawn.load(Ordering::SeqCst)
+            || self.is_syncing(&client)
+            || !self.has_validator_connectivity(&client)
+        {
             return;
         }
-        let step = self
-            .hbbft_state
-            .write()
-            .try_send_contribution(client.clone(), &self.signer);
+        let encrypted = self.encrypted_entries(&client);
+        let limits = self.contribution_limits_at(&*client, BlockId::Latest);
+        let step = self.hbbft_state.write().try_send_contribution(
+            client.clone(),
+            &self.signer,
+            self.now_secs(),
+            encrypted,
+            limits,
+        );
         if let Some((step, network_info)) = step {
+            self.contribution_sent();
             self.process_step(client, step, &network_info)
         }
     }
 
+    fn contribution_sent(&self) {
+        if let Some(epoch) = self.hbbft_state.read().honey_badger_epoch() {
+            self.metrics.contribution_sent(epoch);
+        }
+    }
+
     fn transaction_queue_and_time_thresholds_reached(
         &self,
         client: &Arc<dyn EngineClient>,
     ) -> bool {
         if let Some(block_header) = client.block_header(BlockId::Latest) {
             let target_min_timestamp = block_header.timestamp() + self.params.minimum_block_time;
-            let now = unix_now_secs();
-            let queue_length = client.queued_transactions().len();
+            let now = self.now_secs();
+            let queued_transactions = client.queued_transactions();
+            // Key generation transactions must not wait for user traffic, otherwise the
+            // keygen phase could stall on chains with a high queue trigger.
+            let has_system_transactions =
+                self.has_validator_keygen_transactions(&**client, &queued_transactions);
+            let queued: Vec<_> = queued_transactions
+                .iter()
+                .map(|txn| QueuedTransaction {
+                    hash: txn.signed().hash(),
+                    gas: txn.signed().tx().gas,
+                    is_local: txn.priority() == Priority::Local,
+                })
+                .collect();
+            // The trigger tracks the age of the queued transactions, so it sees the queue even
+            // before the minimum block time passed.
+            let queue_triggered = self.queue_trigger.write().is_reached(&queued, now);
             (self.params.minimum_block_time == 0 || target_min_timestamp <= now)
-                && queue_length >= self.params.transaction_queue_size_trigger
+                && (has_system_transactions || queue_triggered)
         } else {
             false
         }
     }
 
+    /// Returns true if a current or pending validator queued a transaction to the key generation
+    /// history contract. The validator sets are only read if such transactions are queued.
+    fn has_validator_keygen_transactions(
+        &self,
+        client: &dyn EngineClient,
+        queued_transactions: &[Arc<VerifiedTransaction>],
+    ) -> bool {
+        let keygen_transactions: Vec<_> = queued_transactions
+            .iter()
+            .filter(|txn| txn.signed().tx().action == Action::Call(*KEYGEN_HISTORY_ADDRESS))
+            .collect();
+        if keygen_transactions.is_empty() {
+            return false;
+        }
+        let mut validators = BTreeSet::new();
+        for validator_type in vec![ValidatorType::Current, ValidatorType::Pending] {
+            match get_validator_pubkeys(client, BlockId::Latest, validator_type) {
+                Ok(pubkeys) => validators.extend(pubkeys.into_iter().map(|(address, _)| address)),
+                Err(err) => debug!(target: "consensus", "Failed to read the validators: {:?}", err),
+            }
+        }
+        keygen_transactions
+            .into_iter()
+            .any(|txn| is_validator_keygen_transaction(txn.signed(), &validators))
+    }
+
     fn new_sealing(&self, network_info: &NetworkInfo<NodeId>) -> Sealing {
         Sealing::new(network_info.clone())
     }
@@ -496,111 +1635,1727 @@ impl HoneyBadgerBFT {
         self.client.read().as_ref().and_then(Weak::upgrade)
     }
 
-    fn start_hbbft_epoch_if_next_phase(&self) {
-        match self.client_arc() {
-            None => return,
-            Some(client) => {
-                // Get the next phase start time
-                let genesis_transition_time = match start_time_of_next_phase_transition(&*client) {
-                    Ok(time) => time,
-                    Err(_) => return,
-                };
+    /// Returns the current UNIX time of the engine's clock, in seconds.
+    fn now_secs(&self) -> u64 {
+        self.clock.read().now_secs()
+    }
+
+    /// Replaces the engine's clock, so tests can control the passing of time.
+    #[cfg(test)]
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.write() = clock;
+    }
+
+    fn start_hbbft_epoch_if_next_phase(&self) {
+        match self.client_arc() {
+            None => return,
+            Some(client) => {
+                // Get the next phase start time
+                let genesis_transition_time = match start_time_of_next_phase_transition(&*client) {
+                    Ok(time) => time,
+                    Err(_) => return,
+                };
+
+                // If current time larger than phase start time, start a new block.
+                if genesis_transition_time.as_u64() < self.now_secs() {
+                    self.start_hbbft_epoch(client);
+                }
+            }
+        }
+    }
+
+    fn replay_cached_messages(&self) -> Option<()> {
+        self.replay_future_seal_shares();
+        let client = self.client_arc()?;
+        let steps = self
+            .hbbft_state
+            .write()
+            .replay_cached_messages(client.clone());
+        let mut processed_step = false;
+        if let Some((steps, network_info)) = steps {
+            for step in steps {
+                match step {
+                    Ok(step) => {
+                        trace!(target: "engine", "Processing cached message step");
+                        processed_step = true;
+                        self.process_step(client.clone(), step, &network_info)
+                    }
+                    Err(e) => error!(target: "engine", "Error handling replayed message: {}", e),
+                }
+            }
+        }
+
+        if processed_step {
+            if let Err(e) = self.join_hbbft_epoch() {
+                error!(target: "engine", "Error trying to join epoch: {}", e);
+            }
+        }
+
+        Some(())
+    }
+
+    /// Returns true if we are in the keygen phase and a new key has been generated.
+    fn do_keygen(&self) -> bool {
+        match self.client_arc() {
+            None => false,
+            Some(client) => {
+                // If we are not in key generation phase, return false.
+                match get_pending_validators(&*client) {
+                    Err(_) => return false,
+                    Ok(validators) => {
+                        // If the validator set is empty then we are not in the key generation phase.
+                        if validators.is_empty() {
+                            self.keygen_timeout.write().complete();
+                            return false;
+                        }
+                    }
+                }
+
+                // Check if a new key is ready to be generated, return true to switch to the new epoch in that case.
+                if let Ok(synckeygen) = initialize_synckeygen(
+                    &*client,
+                    &self.signer,
+                    BlockId::Latest,
+                    ValidatorType::Pending,
+                    &self.keygen_read_stats,
+                ) {
+                    if synckeygen.is_ready() {
+                        self.keygen_timeout.write().complete();
+                        return true;
+                    }
+                }
+                match self.read_keygen_phase(&*client) {
+                    Ok(status) => {
+                        self.check_keygen_timeout(&*client, &status);
+                        self.log_keygen_phase(&status);
+                    }
+                    Err(e) => {
+                        trace!(target: "engine", "Could not read the key generation state: {:?}", e)
+                    }
+                }
+
+                // Otherwise check if we are in the pending validator set and send Parts and Acks transactions.
+                // @todo send_keygen_transactions initializes another synckeygen structure, a potentially
+                //       time consuming process. Move sending of keygen transactions into a separate function
+                //       and call it periodically using timer events instead of on close block.
+                self.send_keygen_transactions(&*client);
+                false
+            }
+        }
+    }
+
+    /// Sends our Part and Acks transactions if we are a pending validator, and sends our Part
+    /// directly to the other pending validators once its transaction was sent.
+    fn send_keygen_transactions(&self, client: &dyn EngineClient) {
+        let address = match self.signer.read().as_ref() {
+            Some(signer) => signer.address(),
+            None => return,
+        };
+        if !is_pending_validator(client, &address).unwrap_or(false) {
+            return;
+        }
+        let gossip_part = self
+            .keygen_transaction_sender
+            .write()
+            .send_keygen_transactions(
+                client,
+                &self.signer,
+                &self.keygen_read_stats,
+                &self.wire_versions.read(),
+                &self.metrics,
+                &self.keygen_gossip.read(),
+            );
+        let message = match gossip_part {
+            Ok(Some(message)) => message,
+            Ok(None) | Err(_) => return,
+        };
+        let pending = match get_validator_pubkeys(client, BlockId::Latest, ValidatorType::Pending) {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!(target: "engine", "Could not send our Part to the pending validators: {:?}", e);
+                return;
+            }
+        };
+        let wire_version = self.wire_versions.read().for_epoch(message.epoch);
+        let envelope = self.envelope(self.hbbft_state.read().current_posdao_epoch());
+        let signer = self.signer.read();
+        let signer = signer.as_ref().map(|signer| &**signer);
+        let packets = match wire::encode_messages(
+            &[Message::Keygen(message)],
+            wire_version,
+            envelope,
+            signer,
+        ) {
+            Ok(packets) => packets,
+            Err(e) => {
+                warn!(target: "engine", "Could not encode our Part for the pending validators: {}", e);
+                return;
+            }
+        };
+        for (_, public) in pending.iter().filter(|(a, _)| **a != address) {
+            for packet in &packets {
+                client.send_consensus_message(packet.clone(), Some(*public));
+            }
+        }
+    }
+
+    /// Stores the Part a pending validator sent us, and acknowledges it if it is new.
+    fn process_keygen_message(
+        &self,
+        message: keygen_gossip::Message,
+        sender_id: NodeId,
+    ) -> Result<(), EngineError> {
+        let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
+        let sender = public_to_address(&sender_id.0);
+        if !is_pending_validator(&*client, &sender).unwrap_or(false) {
+            return Err(EngineError::UnexpectedMessage);
+        }
+        trace!(target: "engine", "Received the Part of {} for POSDAO epoch {}.", sender, message.epoch);
+        let epoch = message.epoch;
+        match self.keygen_gossip.write().insert(sender, message) {
+            Received::New => self.send_keygen_transactions(&*client),
+            Received::Ignored => {}
+            Received::Conflicting => self.report_fault(
+                sender_id,
+                format!("it sent conflicting Parts for POSDAO epoch {}", epoch),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Raises an alert if the key generation made no progress for longer than the timeout, and
+    /// reports the pending validators that keep it from completing.
+    fn check_keygen_timeout(&self, client: &dyn EngineClient, status: &KeygenPhaseStatus) {
+        let block_num = match client.block_number(BlockId::Latest) {
+            Some(block_num) => block_num,
+            None => return,
+        };
+        let missing_data = status.missing_parts() + status.missing_acks();
+        let stalled = self
+            .keygen_timeout
+            .write()
+            .incomplete(block_num, missing_data);
+        let blocks = match stalled {
+            Some(blocks) => blocks,
+            None => return,
+        };
+        let missing: Vec<Address> = status
+            .validators
+            .iter()
+            .filter(|v| !v.has_part || !v.has_acks)
+            .map(|v| v.mining_address)
+            .collect();
+        error!(target: "engine", "CRITICAL: The key generation for the next POSDAO epoch has made no progress for {} blocks. Pending validators without Part or Acks on chain: {:?}. The validator selection needs to be restarted.", blocks, missing);
+        self.notify(|n| n.keygen_stalled(blocks, &missing));
+        self.report_stalled_validators(client, block_num, &missing);
+    }
+
+    /// Reports the pending validators that keep the key generation from completing to the
+    /// validator set contract, proposing to remove them so the pending validators are selected
+    /// anew.
+    ///
+    /// Only validators of the current POSDAO epoch report, and the contract only removes a
+    /// validator once enough of them reported it.
+    fn report_stalled_validators(
+        &self,
+        client: &dyn EngineClient,
+        block_num: u64,
+        missing: &[Address],
+    ) {
+        if self.hbbft_state.read().network_info().is_none() {
+            return;
+        }
+        let reporter = match self.signer.read().as_ref() {
+            Some(signer) => signer.address(),
+            None => return,
+        };
+        let gas_price = max(
+            U256::from(DEFAULT_KEYGEN_GAS_PRICE),
+            self.contribution_limits_at(client, BlockId::Latest)
+                .min_gas_price,
+        );
+        for address in missing.iter().filter(|a| **a != reporter) {
+            match validator_set::report_malicious_callable(client, reporter, *address, block_num) {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!(target: "engine", "The validator set contract does not accept a report against pending validator {} at block {}.", address, block_num);
+                    continue;
+                }
+                Err(e) => {
+                    warn!(target: "engine", "Could not check whether pending validator {} can be reported: {:?}", address, e);
+                    continue;
+                }
+            }
+            match validator_set::report_malicious(client, *address, block_num, gas_price) {
+                Ok(()) => {
+                    info!(target: "engine", "Reported pending validator {} for stalling the key generation.", address)
+                }
+                Err(e) => {
+                    warn!(target: "engine", "Could not report pending validator {}: {:?}", address, e)
+                }
+            }
+        }
+    }
+
+    /// Logs which Parts and Acks the key generation is waiting for, whenever that changes.
+    fn log_keygen_phase(&self, status: &KeygenPhaseStatus) {
+        let progress = (
+            status.upcoming_epoch,
+            status.missing_parts(),
+            status.missing_acks(),
+        );
+        if *self.keygen_logged.read() == Some(progress) {
+            return;
+        }
+        *self.keygen_logged.write() = Some(progress);
+        let validators = status.validators.len();
+        info!(target: "engine", "Key generation for POSDAO epoch {}: waiting for {} of {} Parts and {} of {} Acks.",
+              status.upcoming_epoch, progress.1, validators, progress.2, validators);
+        if let Some(block) = status.part_in_flight {
+            info!(target: "engine", "Our Part transaction sent in block {} is not on chain yet.", block);
+        } else if let Some(block) = status.acks_in_flight {
+            info!(target: "engine", "Our Acks transactions sent in block {} are not on chain yet.", block);
+        }
+    }
+
+    fn check_for_epoch_change(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        if self.awaits_activation(&client) {
+            // The hbbft contracts may not be deployed yet.
+            return None;
+        }
+        let (previous_epoch, current_epoch) = {
+            let mut state = self.hbbft_state.write();
+            let previous_epoch = state.current_posdao_epoch();
+            if let None = state.update_honeybadger(client, &self.signer, BlockId::Latest, false) {
+                error!(target: "consensus", "Fatal: Updating Honey Badger instance failed!");
+            }
+            (previous_epoch, state.current_posdao_epoch())
+        };
+        if previous_epoch != current_epoch {
+            // The pooled transactions are encrypted with the key of the previous epoch.
+            self.encrypted_pool.write().clear();
+            self.fault_reports.write().clear();
+            self.update_active_features(&client, current_epoch);
+            // Subscribe to the new validators right away if we observe them.
+            self.observer_subscription.write().reset();
+            self.notify(|n| n.posdao_epoch_changed(current_epoch));
+            // Replay on the engine's IO thread: we may be called while a block is being closed.
+            if let Err(e) = self.transition_service.send_message(()) {
+                warn!(target: "consensus", "Could not schedule replaying cached messages: {}", e);
+            }
+        }
+        Some(())
+    }
+
+    /// Reads the features supported by enough validators of the new POSDAO epoch and switches
+    /// to them. The registry is read at the epoch's start block, so all nodes agree on the result.
+    fn update_active_features(&self, client: &Arc<dyn EngineClient>, posdao_epoch: u64) {
+        let registry = match self.params.feature_registry_contract_address {
+            Some(registry) => registry,
+            None => return,
+        };
+        let features = get_posdao_epoch_start(&**client, BlockId::Latest).and_then(|start| {
+            feature_registry::active_features(&**client, registry, BlockId::Number(start.low_u64()))
+        });
+        let features = match features {
+            Ok(features) => features,
+            Err(e) => {
+                error!(target: "engine", "Could not read the active features of POSDAO epoch {}: {:?}", posdao_epoch, e);
+                return;
+            }
+        };
+        if self.active_features.swap(features, Ordering::SeqCst) != features {
+            info!(target: "engine", "Active hbbft features in POSDAO epoch {}: {:#x}", posdao_epoch, features);
+        }
+        if features & FEATURE_WIRE_V4 != 0 {
+            self.wire_versions
+                .write()
+                .activate(posdao_epoch, WireVersion::V4);
+        } else if features & FEATURE_WIRE_V3 != 0 {
+            self.wire_versions
+                .write()
+                .activate(posdao_epoch, WireVersion::V3);
+        } else if features & FEATURE_WIRE_V2 != 0 {
+            self.wire_versions
+                .write()
+                .activate(posdao_epoch, WireVersion::V2);
+        } else if features & FEATURE_WIRE_V1 != 0 {
+            self.wire_versions
+                .write()
+                .activate(posdao_epoch, WireVersion::V1);
+        }
+    }
+
+    /// Announces our version and supported features to the registry contract, once per POSDAO
+    /// epoch, if we are a current or pending validator.
+    fn announce_features(&self) -> Option<()> {
+        let registry = self.params.feature_registry_contract_address?;
+        let client = self.client_arc()?;
+        if self.is_syncing(&client) {
+            return None;
+        }
+        let (posdao_epoch, is_validator) = {
+            let state = self.hbbft_state.read();
+            (state.current_posdao_epoch(), state.network_info().is_some())
+        };
+        if *self.features_announced_epoch.read() == Some(posdao_epoch) {
+            return None;
+        }
+        let address = self.signer.read().as_ref()?.address();
+        if !is_validator && !is_pending_validator(&*client, &address).ok()? {
+            return None;
+        }
+
+        let version = self.client_version.read().clone();
+        match feature_registry::announce_features(
+            &*client,
+            registry,
+            address,
+            &version,
+            self.params.keygen_gas_price,
+        ) {
+            Ok(sent) => {
+                if sent {
+                    info!(target: "engine", "Announced version {} and features {:#x} to the feature registry.", version, feature_registry::SUPPORTED_FEATURES);
+                }
+                *self.features_announced_epoch.write() = Some(posdao_epoch);
+            }
+            Err(e) => warn!(target: "engine", "Could not announce features to the feature registry: {:?}", e),
+        }
+        Some(())
+    }
+
+    /// Claims the rewards and the ordered withdrawal of our pool's staker every configured number
+    /// of POSDAO epochs, if a reward claimer is set.
+    fn claim_rewards(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        if self.is_syncing(&client) {
+            return None;
+        }
+        let full_client = client.as_full_client()?;
+        let posdao_epoch = self.hbbft_state.read().current_posdao_epoch();
+        let mut claimer_guard = self.reward_claimer.write();
+        let claimer = claimer_guard.as_mut()?;
+        if !claimer.is_due(posdao_epoch) {
+            return None;
+        }
+        let mining_address = self.signer.read().as_ref()?.address();
+        let staker = claimer.address();
+        let block_id = BlockId::Latest;
+        let read_error = |e: CallError| {
+            warn!(target: "engine", "Could not read the rewards of staker {}: {:?}", staker, e);
+        };
+        let pool = staking_by_mining_address(&*client, block_id, &mining_address)
+            .map_err(read_error)
+            .ok()?;
+        if pool.is_zero() {
+            warn!(target: "engine", "Cannot claim rewards: the mining address {} has no pool.", mining_address);
+            return None;
+        }
+
+        let mut calls = Vec::new();
+        let first_epoch = stake_first_epoch(&*client, block_id, pool, staker)
+            .map_err(read_error)
+            .ok()?;
+        let epochs = claimer
+            .unclaimed_epochs(first_epoch.low_u64(), posdao_epoch, |epoch| {
+                reward_was_taken(&*client, block_id, pool, staker, epoch.into())
+            })
+            .map_err(read_error)
+            .ok()?;
+        let epochs: Vec<U256> = epochs.into_iter().map(Into::into).collect();
+        if !epochs.is_empty() {
+            let reward = get_reward_amount(&*client, block_id, epochs.clone(), pool, staker)
+                .map_err(read_error)
+                .ok()?;
+            if !reward.is_zero() {
+                info!(target: "engine", "Claiming the reward of {} for {} staking epochs.", staker, epochs.len());
+                let gas = U256::from(200_000 + 50_000 * epochs.len());
+                calls.push((claim_reward_call(epochs, pool), gas));
+            }
+        }
+        let (withdrawal, ordered_epoch) = ordered_withdrawal(&*client, block_id, pool, staker)
+            .map_err(read_error)
+            .ok()?;
+        if !withdrawal.is_zero() && ordered_epoch < U256::from(posdao_epoch) {
+            info!(target: "engine", "Claiming the ordered withdrawal of {} wei of {}.", withdrawal, staker);
+            calls.push((claim_ordered_withdraw_call(pool), U256::from(200_000)));
+        }
+
+        let block_number = client.block_number(block_id)?;
+        let chain_id = self.machine.signing_chain_id(&EnvInfo {
+            number: block_number,
+            ..Default::default()
+        });
+        let mut nonce = full_client.next_nonce(&staker);
+        let mut transactions = Vec::new();
+        for (data, gas) in calls {
+            match claimer.sign_call(*STAKING_CONTRACT_ADDRESS, data, gas, nonce, chain_id) {
+                Ok(transaction) => transactions.push(transaction),
+                Err(e) => {
+                    warn!(target: "engine", "{}", e);
+                    break;
+                }
+            }
+            nonce = nonce + 1;
+        }
+        if !transactions.is_empty() {
+            full_client.queue_transactions(transactions, 0);
+        }
+        Some(())
+    }
+
+    /// Checks the on-chain standing of our pool once per block, and warns if it deviates from
+    /// an active and available pool, so operators learn about a removal or ban before we stop
+    /// being elected.
+    fn check_pool_status(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        if self.is_syncing(&client) {
+            return None;
+        }
+        let address = self.signer.read().as_ref()?.address();
+        let block_number = client.block_number(BlockId::Latest)?;
+        if self.pool_status_block.swap(block_number, Ordering::SeqCst) == block_number {
+            return None;
+        }
+        let status = match get_pool_status(&*client, BlockId::Number(block_number), &address) {
+            Ok(status) => status,
+            Err(e) => {
+                warn!(target: "engine", "Could not read the pool status of {}: {:?}", address, e);
+                return None;
+            }
+        };
+        if self.pool_status.write().replace(status) == Some(status) {
+            return Some(());
+        }
+        match status {
+            PoolStatus::Active => info!(target: "engine", "The pool of {} is active and available.", address),
+            PoolStatus::NoPool => warn!(target: "engine", "Mining address {} does not belong to a staking pool, it will not be elected as validator.", address),
+            PoolStatus::Inactive => warn!(target: "engine", "The pool of {} is inactive, it will not be elected as validator.", address),
+            PoolStatus::Banned => warn!(target: "engine", "Mining address {} is banned, it will not be elected as validator.", address),
+            PoolStatus::Unavailable => warn!(target: "engine", "Mining address {} is not marked as available, it will not be elected as validator.", address),
+        }
+        self.notify(|n| n.pool_status_changed(status));
+        Some(())
+    }
+
+    /// Sets the client version announced to the feature registry contract.
+    pub fn set_client_version(&self, version: String) {
+        *self.client_version.write() = version;
+    }
+
+    /// Observes the current validators' consensus while this node is not one of them.
+    pub fn set_observer(&self, enabled: bool) {
+        let mut state = self.hbbft_state.write();
+        state.set_observing(enabled);
+        self.observer_subscription.write().reset();
+        // A validator's state is kept, observing only starts when we are not a validator.
+        if state.network_info().is_some() {
+            return;
+        }
+        if let Some(client) = self.client_arc() {
+            if let None = state.update_honeybadger(client, &self.signer, BlockId::Latest, true) {
+                info!(target: "engine", "HoneyBadger Algorithm could not be created, Client possibly not set yet.");
+            }
+        }
+    }
+
+    /// Returns true if we are connected to enough of the other validators to reach agreement.
+    ///
+    /// Agreement requires all but `f` validators to take part, so we need to be connected to at
+    /// least `N - f - 1` of the others. Nodes which are not validators, or whose client does not
+    /// report its peers, are always considered connected.
+    fn has_validator_connectivity(&self, client: &Arc<dyn EngineClient>) -> bool {
+        let connected_peers: BTreeSet<H512> =
+            match client.as_full_client().and_then(|c| c.connected_peers()) {
+                Some(peers) => peers,
+                None => return true,
+            };
+        let (reachable, required) = match self.hbbft_state.read().network_info() {
+            Some(network_info) => {
+                let reachable = network_info
+                    .all_ids()
+                    .filter(|id| *id != network_info.our_id())
+                    .filter(|id| connected_peers.contains(&id.0))
+                    .count();
+                let required = network_info.num_nodes() - network_info.num_faulty() - 1;
+                (reachable, required)
+            }
+            None => return true,
+        };
+
+        let isolated = reachable < required;
+        if self.isolated.swap(isolated, Ordering::SeqCst) != isolated {
+            self.notify(|n| n.isolation_changed(isolated));
+            if isolated {
+                warn!(target: "consensus", "Connected to only {} of the {} validators required for consensus, pausing contributions.", reachable, required);
+            } else {
+                info!(target: "consensus", "Connected to {} validators, resuming contributions.", reachable);
+            }
+        }
+        !isolated
+    }
+
+    /// Delivers the queued messages of reconnected validators, and warns about validators of the
+    /// current POSDAO epoch without a peer session, once until they reconnect.
+    fn flush_outbox(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        let connected: BTreeSet<NodeId> = client
+            .as_full_client()?
+            .connected_peers()?
+            .into_iter()
+            .map(NodeId)
+            .collect();
+        let deliverable = self
+            .outbox
+            .write()
+            .take_deliverable(&connected, Instant::now());
+        for (node_id, packet) in deliverable {
+            client.send_consensus_message(packet, Some(node_id.0));
+        }
+
+        let unreachable: BTreeSet<NodeId> = match self.hbbft_state.read().network_info() {
+            Some(network_info) => network_info
+                .all_ids()
+                .filter(|id| *id != network_info.our_id() && !connected.contains(id))
+                .cloned()
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        let offline_validators = self.offline_validators.read();
+        let mut unreachable_validators = self.unreachable_validators.write();
+        for node_id in unreachable.difference(&unreachable_validators) {
+            if offline_validators.contains(node_id) {
+                info!(target: "consensus", "Validator {} shut down, queueing its consensus messages.", node_id.0);
+            } else {
+                warn!(target: "consensus", "Validator {} has no peer session with us, queueing its consensus messages.", node_id.0);
+            }
+        }
+        for node_id in unreachable_validators.difference(&unreachable) {
+            info!(target: "consensus", "Validator {} is connected again.", node_id.0);
+        }
+        *unreachable_validators = unreachable;
+        Some(())
+    }
+
+    /// Records a fault of the given validator, and notifies the listeners.
+    fn report_fault(&self, node_id: NodeId, fault: String) {
+        let validator = public_to_address(&node_id.0);
+        warn!(target: "consensus", "Validator {} misbehaved: {}.", validator, fault);
+        self.metrics.fault_reported();
+        {
+            let mut reports = self.fault_reports.write();
+            let report = reports.entry(node_id).or_insert_with(|| FaultReport {
+                node_id,
+                faults: 0,
+                last_fault: String::new(),
+            });
+            report.faults += 1;
+            report.last_fault = fault.clone();
+        }
+        self.notify(|n| n.validator_faulty(validator, &fault));
+    }
+
+    /// Records the faults Honey Badger, threshold signing or decryption detected in a step.
+    fn report_faults<F: Debug>(&self, fault_log: FaultLog<NodeId, F>) {
+        for fault in fault_log.0 {
+            self.report_fault(fault.node_id, format!("{:?}", fault.kind));
+        }
+    }
+
+    /// Returns the validators of the current POSDAO epoch that sent invalid consensus messages
+    /// or contributions.
+    pub fn fault_reports(&self) -> Vec<FaultReport> {
+        self.fault_reports.read().values().cloned().collect()
+    }
+
+    /// Adds the given entries to our next contribution, unchecked, to test how the other
+    /// validators handle malformed contributions.
+    #[cfg(test)]
+    pub fn inject_contribution_entries(&self, entries: Vec<Vec<u8>>) {
+        self.hbbft_state
+            .write()
+            .inject_contribution_entries(entries);
+    }
+
+    /// Processes a Honey Badger step that outputs the given batches in the given order, as a
+    /// validator catching up on several hbbft epochs at once does.
+    #[cfg(test)]
+    pub fn process_agreed_batches(
+        &self,
+        batches: Vec<(u64, BTreeMap<NodeId, Contribution>)>,
+    ) -> Option<()> {
+        let client = self.client_arc()?;
+        let epoch = client.block_number(BlockId::Latest)? + 1;
+        let network_info =
+            self.hbbft_state
+                .write()
+                .network_info_for(client.clone(), &self.signer, epoch)?;
+        let mut step = HoneyBadgerStep::default();
+        step.output = batches
+            .into_iter()
+            .map(|(epoch, contributions)| Batch {
+                epoch,
+                contributions,
+            })
+            .collect();
+        self.process_step(client, step, &network_info);
+        Some(())
+    }
+
+    /// Creates the block of a deferred batch, as the engine timer does on every tick.
+    #[cfg(test)]
+    pub fn tick_deferred_block(&self) {
+        self.create_deferred_block();
+    }
+
+    /// Tells the other validators that we are shutting down, once all validators understand the
+    /// notice.
+    fn announce_offline(&self) -> Option<()> {
+        if self.active_features.load(Ordering::SeqCst) & FEATURE_OFFLINE_NOTICE == 0 {
+            return None;
+        }
+        let client = self.client_arc()?;
+        let network_info = self.hbbft_state.read().network_info()?.clone();
+        if !network_info.is_validator() {
+            return None;
+        }
+        info!(target: "consensus", "Telling the other validators that we are shutting down.");
+        let message = TargetedMessage {
+            target: Target::AllExcept(BTreeSet::new()),
+            message: Message::Offline,
+        };
+        self.dispatch_messages(&client, iter::once(message), &network_info);
+        Some(())
+    }
+
+    fn process_offline_notice(&self, sender_id: NodeId) -> Result<(), EngineError> {
+        info!(target: "consensus", "Validator {} is shutting down.", sender_id.0);
+        self.offline_validators.write().insert(sender_id);
+        self.notify(|n| n.validator_offline(public_to_address(&sender_id.0)));
+        Ok(())
+    }
+
+    /// Subscribes to the current validators' consensus messages every
+    /// `observers::SUBSCRIPTION_INTERVAL`, while we observe their consensus and the validators
+    /// support observers.
+    fn subscribe_as_observer(&self) -> Option<()> {
+        if self.active_features.load(Ordering::SeqCst) & FEATURE_OBSERVERS == 0 {
+            return None;
+        }
+        let validators = self.hbbft_state.read().observed_validators()?;
+        if !self.observer_subscription.write().renew(Instant::now()) {
+            return None;
+        }
+        let client = self.client_arc()?;
+        let posdao_epoch = self.hbbft_state.read().current_posdao_epoch();
+        let wire_version = self.wire_versions.read().for_epoch(posdao_epoch);
+        let signer = self.signer.read();
+        let signer = signer.as_ref().map(|signer| &**signer);
+        let packets = match wire::encode_messages(
+            &[Message::Observe],
+            wire_version,
+            self.envelope(posdao_epoch),
+            signer,
+        ) {
+            Ok(packets) => packets,
+            Err(e) => {
+                error!(target: "consensus", "Could not encode the observer subscription: {}", e);
+                return None;
+            }
+        };
+        debug!(target: "consensus", "Subscribing to the consensus messages of {} validators of POSDAO epoch {}.", validators.len(), posdao_epoch);
+        for node_id in validators {
+            for packet in &packets {
+                client.send_consensus_message(packet.clone(), Some(node_id.0));
+            }
+        }
+        Some(())
+    }
+
+    /// Sends our broadcast Honey Badger messages to the sender from now on, until its
+    /// subscription expires, if it is not a validator itself.
+    fn process_observer_subscription(&self, sender_id: NodeId) -> Result<(), EngineError> {
+        let is_validator = {
+            let state = self.hbbft_state.read();
+            match state.network_info() {
+                Some(network_info) => network_info.all_ids().any(|id| *id == sender_id),
+                // Only validators send their consensus messages to observers.
+                None => return Ok(()),
+            }
+        };
+        if is_validator {
+            trace!(target: "consensus", "Ignoring the observer subscription of validator {}.", sender_id.0);
+        } else if self.observers.write().subscribe(sender_id, Instant::now()) {
+            trace!(target: "consensus", "Observer {} subscribed to our consensus messages.", sender_id.0);
+        } else {
+            debug!(target: "consensus", "Too many observers, ignoring the subscription of {}.", sender_id.0);
+        }
+        Ok(())
+    }
+
+    /// Warns about validators which have not sent consensus messages for
+    /// `SILENT_EPOCHS_WARNING_THRESHOLD` hbbft epochs, once until they are heard from again.
+    fn check_validator_liveness(&self) {
+        let liveness = self.hbbft_state.read().validator_liveness();
+        let mut silent_validators = self.silent_validators.write();
+        silent_validators.retain(|id| liveness.iter().any(|v| v.node_id == *id));
+        for validator in liveness {
+            if validator.silent_epochs >= SILENT_EPOCHS_WARNING_THRESHOLD {
+                if silent_validators.insert(validator.node_id) {
+                    warn!(target: "consensus", "Validator {} sent no consensus messages for {} hbbft epochs.", validator.node_id, validator.silent_epochs);
+                }
+            } else if silent_validators.remove(&validator.node_id) {
+                info!(target: "consensus", "Validator {} is sending consensus messages again.", validator.node_id);
+            }
+        }
+    }
+
+    /// Counts blocks with an invalid seal in a row, per POSDAO epoch of their parent, and raises
+    /// an alert once `SEAL_FAILURE_ALERT_THRESHOLD` is reached. A valid seal clears the alert.
+    fn record_seal_verification(
+        &self,
+        client: &Arc<dyn EngineClient>,
+        header: &Header,
+        valid: bool,
+    ) {
+        if valid {
+            self.seal_failures.write().clear();
+            if self.seal_failure_alert.swap(false, Ordering::SeqCst) {
+                info!(target: "engine", "Block #{} has a valid seal, clearing the seal verification alert.", header.number());
+            }
+            return;
+        }
+        let posdao_epoch = get_posdao_epoch(&**client, BlockId::Number(header.number() - 1))
+            .map(|epoch| epoch.low_u64())
+            .unwrap_or_else(|_| self.hbbft_state.read().current_posdao_epoch());
+        let failures = {
+            let mut seal_failures = self.seal_failures.write();
+            let failures = seal_failures.entry(posdao_epoch).or_insert(0);
+            *failures += 1;
+            *failures
+        };
+        warn!(target: "engine", "Invalid seal for block #{} of POSDAO epoch {}, {} in a row.", header.number(), posdao_epoch, failures);
+        if failures >= SEAL_FAILURE_ALERT_THRESHOLD
+            && !self.seal_failure_alert.swap(true, Ordering::SeqCst)
+        {
+            error!(target: "engine", "ALERT: {} blocks of POSDAO epoch {} in a row failed seal verification. The epoch keys may be out of sync, or peers are sending bogus blocks.", failures, posdao_epoch);
+        }
+    }
+
+    /// Returns true if too many blocks in a row failed seal verification.
+    pub fn has_seal_failure_alert(&self) -> bool {
+        self.seal_failure_alert.load(Ordering::SeqCst)
+    }
+
+    /// Checks the running timer tick from a separate thread, so a tick that is stuck, e.g. on a
+    /// lock, is reported while it is stuck. The thread stops once the engine is shut down or
+    /// dropped.
+    fn spawn_tick_watchdog(engine: Weak<HoneyBadgerBFT>) {
+        let spawned = thread::Builder::new()
+            .name("hbbft-watchdog".into())
+            .spawn(move || loop {
+                thread::sleep(WATCHDOG_INTERVAL);
+                let engine = match engine.upgrade() {
+                    Some(engine) => engine,
+                    None => return,
+                };
+                if engine.shutting_down.load(Ordering::SeqCst) {
+                    return;
+                }
+                let now = Instant::now();
+                if let Some(running) = engine.tick_watchdog.check(now, SLOW_TICK_THRESHOLD) {
+                    engine.log_diagnostics(running);
+                }
+            });
+        if let Err(e) = spawned {
+            warn!(target: "consensus", "Failed to start the timer tick watchdog: {}", e);
+        }
+    }
+
+    /// Logs the engine's lock hold times and state, to debug a timer tick that is taking too
+    /// long.
+    ///
+    /// The locks are not waited for: if one of them is still held, that is logged instead.
+    fn log_diagnostics(&self, tick_duration: Duration) {
+        let state_hold = self.hbbft_state.take_longest_hold();
+        let sealing_hold = self.sealing.take_longest_hold();
+        warn!(target: "consensus", "Timer tick running for {:?}. Longest lock holds since the last report: hbbft_state {:?}, sealing {:?}.", tick_duration, state_hold, sealing_hold);
+        match self.hbbft_state.try_read() {
+            Some(state) => {
+                let future_messages = state.future_messages();
+                let cached: usize = future_messages.values().map(Vec::len).sum();
+                warn!(target: "consensus", "POSDAO epoch: {}, hbbft epoch: {:?}, cached future messages: {} for {} epochs.", state.current_posdao_epoch(), state.honey_badger_epoch(), cached, future_messages.len());
+            }
+            None => warn!(target: "consensus", "The hbbft state is still locked."),
+        }
+        match self.sealing.try_read() {
+            Some(sealing) => {
+                let pending: Vec<_> = sealing.keys().collect();
+                warn!(target: "consensus", "Blocks pending sealing: {:?}", pending);
+            }
+            None => warn!(target: "consensus", "The sealing state is still locked."),
+        }
+        match (
+            self.random_numbers.try_read(),
+            self.seal_failures.try_read(),
+        ) {
+            (Some(random_numbers), Some(seal_failures)) => {
+                warn!(target: "consensus", "Cached random numbers: {}, POSDAO epochs with seal failures: {}.", random_numbers.len(), seal_failures.len());
+            }
+            _ => {
+                warn!(target: "consensus", "The random numbers or seal failures are still locked.")
+            }
+        }
+    }
+
+    /// Reports for how many hbbft epochs each of the other validators has been silent.
+    pub fn validator_liveness(&self) -> Vec<ValidatorLiveness> {
+        self.hbbft_state.read().validator_liveness()
+    }
+
+    /// Reports which of the other validators of the current POSDAO epoch are connected peers.
+    pub fn validator_connections(&self) -> Vec<ValidatorConnection> {
+        let client = match self.client_arc() {
+            Some(client) => client,
+            None => return Vec::new(),
+        };
+        let connected_peers = match client.as_full_client().and_then(|c| c.connected_peers()) {
+            Some(peers) => peers,
+            None => return Vec::new(),
+        };
+        let state = self.hbbft_state.read();
+        let network_info = match state.network_info() {
+            Some(network_info) => network_info,
+            None => return Vec::new(),
+        };
+        let outbox = self.outbox.read();
+        network_info
+            .all_ids()
+            .filter(|id| *id != network_info.our_id())
+            .map(|id| ValidatorConnection {
+                node_id: *id,
+                is_connected: connected_peers.contains(&id.0),
+                queued_packets: outbox.queued(id),
+            })
+            .collect()
+    }
+
+    /// Returns true if the last connectivity check found too few validators among our peers.
+    pub fn is_isolated(&self) -> bool {
+        self.isolated.load(Ordering::SeqCst)
+    }
+
+    /// Returns a snapshot of the consensus state.
+    pub fn status(&self) -> HbbftStatus {
+        let state = self.hbbft_state.read();
+        let progress = state.consensus_progress();
+        HbbftStatus {
+            posdao_epoch: state.current_posdao_epoch(),
+            hbbft_epoch: progress.map(|(epoch, _, _)| epoch),
+            is_validator: state.network_info().is_some(),
+            is_observer: state.is_observer(),
+            received_proposals: progress.map(|(_, received, _)| received),
+            proposal_threshold_reached: progress.map(|(_, _, reached)| reached),
+            network_size: state.network_info().map(|info| info.num_nodes()),
+            max_faulty: state.network_info().map(|info| info.num_faulty()),
+            pending_seals: self
+                .sealing
+                .read()
+                .values()
+                .filter(|sealing| match sealing {
+                    Sealing::Ongoing(_) => true,
+                    Sealing::Complete(_) => false,
+                })
+                .count(),
+            has_signer: self.signer.read().is_some(),
+            signer: self.signer.read().as_ref().map(|signer| signer.address()),
+            is_isolated: self.is_isolated(),
+            keygen_history_reads: self.keygen_read_stats.calls(),
+            keygen_history_read_bytes: self.keygen_read_stats.bytes(),
+            active_features: self.active_features.load(Ordering::SeqCst),
+            pool_status: *self.pool_status.read(),
+            seal_failure_alert: self.has_seal_failure_alert(),
+            withdrawn: self.withdrawn.load(Ordering::SeqCst),
+            forks_detected: self.forks_detected.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Returns the in-memory consensus state, for debugging.
+    pub fn debug_state(&self) -> DebugState {
+        let state = self.hbbft_state.read();
+        DebugState {
+            hbbft_epoch: state.honey_badger_epoch(),
+            received_proposals: state.received_proposals(),
+            sealing: self
+                .sealing
+                .read()
+                .iter()
+                .map(|(block_num, sealing)| {
+                    let status = match sealing {
+                        Sealing::Ongoing(_) => SealingStatus::InProgress,
+                        Sealing::Complete(_) => SealingStatus::Complete,
+                    };
+                    (*block_num, status)
+                })
+                .collect(),
+            future_messages: state
+                .future_messages()
+                .iter()
+                .map(|(epoch, messages)| (*epoch, messages.len()))
+                .collect(),
+        }
+    }
+
+    /// Returns the key of the current POSDAO epoch, signed by the engine signer and encoded as an
+    /// epoch transition proof.
+    pub fn epoch_key_proof(&self) -> Result<Vec<u8>, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        let (posdao_epoch, start_block, public_key) = {
+            let state = self.hbbft_state.read();
+            let public_key = state.public_master_key().ok_or_else(|| {
+                "The key of the current POSDAO epoch is not known yet.".to_owned()
+            })?;
+            (
+                state.current_posdao_epoch(),
+                state.posdao_epoch_start(),
+                public_key,
+            )
+        };
+        let validators = get_validator_pubkeys(
+            &*client,
+            BlockId::Number(start_block),
+            ValidatorType::Current,
+        )
+        .map_err(|e| format!("Could not read the validators: {:?}", e))?;
+        let key = EpochKey {
+            posdao_epoch,
+            start_block,
+            public_key,
+            validators,
+        };
+        let signature = self
+            .signer
+            .read()
+            .as_ref()
+            .ok_or_else(|| "The node has no engine signer to sign the epoch key.".to_owned())?
+            .sign(key.hash())
+            .map_err(|e| format!("Could not sign the epoch key: {}", e))?;
+        let proof = EpochKeyProof {
+            key,
+            signatures: vec![signature],
+        };
+        Ok(proof.encode())
+    }
+
+    /// Adds the signatures of an epoch key proof, for verifying seals without a full client.
+    /// Returns whether the key is registered, or still lacks signatures.
+    ///
+    /// With a full client, the key must also match the epoch's key set and validators on chain.
+    pub fn add_epoch_key_proof(&self, proof: &[u8]) -> Result<bool, String> {
+        let proof =
+            EpochKeyProof::decode(proof).map_err(|e| format!("Invalid epoch key proof: {}", e))?;
+        self.register_epoch_key_proof(proof)
+    }
+
+    /// Checks a decoded epoch key proof and adds its signatures, see `add_epoch_key_proof`.
+    fn register_epoch_key_proof(&self, proof: EpochKeyProof) -> Result<bool, String> {
+        let (posdao_epoch, start_block) = (proof.key.posdao_epoch, proof.key.start_block);
+        if let Some(client) = self.client_arc() {
+            if client.as_full_client().is_some() {
+                let public_key = self.hbbft_state.write().epoch_public_key(
+                    &*client,
+                    posdao_epoch,
+                    start_block,
+                )?;
+                if public_key != proof.key.public_key {
+                    return Err(format!(
+                        "The key does not match the key set of POSDAO epoch {}.",
+                        posdao_epoch
+                    ));
+                }
+                let validators = get_validator_pubkeys(
+                    &*client,
+                    BlockId::Number(start_block),
+                    ValidatorType::Current,
+                )
+                .map_err(|e| format!("Could not read the validators: {:?}", e))?;
+                if validators != proof.key.validators {
+                    return Err(format!(
+                        "The validators do not match the validators of POSDAO epoch {}.",
+                        posdao_epoch
+                    ));
+                }
+            }
+        }
+        let registered = self.epoch_keys.write().add_proof(proof)?;
+        if registered {
+            info!(target: "engine", "Registered the key of POSDAO epoch {}, starting at block #{}.", posdao_epoch, start_block);
+        }
+        Ok(registered)
+    }
+
+    /// Discards the given in-memory state and re-initializes it from the chain.
+    pub fn reset_state(&self, scope: ResetScope) -> Result<(), String> {
+        let all = scope == ResetScope::All;
+        warn!(target: "engine", "Resetting hbbft engine state: {:?}", scope);
+        if all || scope == ResetScope::FutureMessages {
+            self.hbbft_state.write().clear_future_messages();
+        }
+        if all || scope == ResetScope::Sealing {
+            self.sealing.write().clear();
+            *self.seal_shares.write() = SealShareFilter::default();
+            *self.future_seal_shares.write() = FutureSealShares::default();
+            *self.recovery_block.write() = None;
+            self.deferred_batches.write().clear();
+            self.decryptions.write().clear();
+        }
+        if all || scope == ResetScope::KeygenSender {
+            *self.keygen_transaction_sender.write() =
+                KeygenTransactionSender::new(self.params.keygen_gas_price);
+            *self.keygen_gossip.write() = KeygenGossip::default();
+            self.keygen_timeout.write().complete();
+        }
+        if all || scope == ResetScope::EpochKeys {
+            let client = self
+                .client_arc()
+                .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+            self.hbbft_state
+                .write()
+                .update_honeybadger(client, &self.signer, BlockId::Latest, true)
+                .ok_or_else(|| "Re-initializing the hbbft epoch keys failed.".to_owned())?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the genesis state contains the system contracts the engine relies on.
+    ///
+    /// Chains switching to hbbft at `activationBlock` deploy them later, so they are exempt.
+    pub fn verify_genesis(
+        &self,
+        genesis: &PodState,
+        constructors: &[(Address, Bytes)],
+    ) -> Result<(), Error> {
+        if self.params.activation_block.unwrap_or(0) > 0 {
+            return Ok(());
+        }
+        spec_validation::validate_genesis(genesis, constructors)
+            .map_err(|e| EngineError::Custom(e).into())
+    }
+
+    /// Returns the validators of the current POSDAO epoch, as of the latest block.
+    pub fn validator_set(&self) -> Result<Vec<HbbftValidator>, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        let validators = get_validator_pubkeys(&*client, BlockId::Latest, ValidatorType::Current)
+            .map_err(|e| format!("Could not read the validator set: {:?}", e))?;
+        let our_id = self.hbbft_state.read().our_node_id();
+        Ok(validators
+            .into_iter()
+            .map(|(mining_address, public)| {
+                let node_id = NodeId(public);
+                HbbftValidator {
+                    mining_address,
+                    node_id,
+                    is_self: our_id == Some(node_id),
+                }
+            })
+            .collect())
+    }
+
+    /// Stops contributing to new hbbft epochs and flushes the pending consensus state.
+    pub fn withdraw(&self) -> Result<Withdrawal, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        let address = match self.signer.read().as_ref() {
+            Some(signer) => signer.address(),
+            None => return Err("No signer is configured.".to_owned()),
+        };
+        let staking_address = staking_by_mining_address(&*client, BlockId::Latest, &address)
+            .map_err(|e| format!("Could not read the staking address: {:?}", e))?;
+        if staking_address.is_zero() {
+            return Err(format!("The mining address {} has no pool.", address));
+        }
+
+        warn!(target: "engine", "Withdrawing validator {}, no longer contributing to hbbft epochs.", address);
+        self.withdrawn.store(true, Ordering::SeqCst);
+        client.set_engine_data(WITHDRAWN_KEY, Some(address.as_bytes().to_vec()));
+        self.persist_cached_messages();
+        self.sealing.write().clear();
+        *self.seal_shares.write() = SealShareFilter::default();
+        *self.recovery_block.write() = None;
+        Ok(Withdrawal {
+            staking_address,
+            staking_contract: *STAKING_CONTRACT_ADDRESS,
+            call_data: remove_my_pool_call(),
+        })
+    }
+
+    /// Restores whether the current signer's mining address was withdrawn, before a restart or
+    /// before the signer was replaced.
+    fn restore_withdrawn(&self) {
+        let address = self.signer.read().as_ref().map(|signer| signer.address());
+        let withdrawn = match (self.client_arc(), address) {
+            (Some(client), Some(address)) => client
+                .engine_data(WITHDRAWN_KEY)
+                .map_or(false, |bytes| bytes == address.as_bytes()),
+            _ => false,
+        };
+        self.withdrawn.store(withdrawn, Ordering::SeqCst);
+    }
+
+    /// Reads the enodes of the other validators once per POSDAO epoch, and the ones of the
+    /// pending validators whenever they change, and reserves connections to them and to the
+    /// pinned enodes.
+    fn update_validator_peers(&self) -> Option<()> {
+        let client = self.client_arc()?;
+        if self.awaits_activation(&client) {
+            return None;
+        }
+        let (posdao_epoch, our_id) = {
+            let state = self.hbbft_state.read();
+            (state.current_posdao_epoch(), state.our_node_id())
+        };
+        if self.validator_peers.read().is_outdated(posdao_epoch) {
+            let validators = match get_validator_pubkeys(
+                &*client,
+                BlockId::Latest,
+                ValidatorType::Current,
+            ) {
+                Ok(validators) => validators,
+                Err(e) => {
+                    warn!(target: "engine", "Could not read the validators to connect to: {:?}", e);
+                    return None;
+                }
+            };
+            let ports = self.validator_peers.read().ports().clone();
+            let enodes = validator_enodes(&*client, BlockId::Latest, validators, our_id, &ports);
+            self.validator_peers
+                .write()
+                .set_validators(posdao_epoch, enodes);
+        }
+        let block_number = client.block_number(BlockId::Latest)?;
+        if self
+            .validator_peers
+            .read()
+            .is_pending_outdated(block_number)
+        {
+            let pending = match get_validator_pubkeys(
+                &*client,
+                BlockId::Number(block_number),
+                ValidatorType::Pending,
+            ) {
+                Ok(pending) => pending,
+                Err(e) => {
+                    warn!(target: "engine", "Could not read the pending validators to connect to: {:?}", e);
+                    return None;
+                }
+            };
+            let pending_ids = pending.values().map(|public| NodeId(*public)).collect();
+            if self
+                .validator_peers
+                .write()
+                .check_pending(block_number, pending_ids)
+            {
+                let ports = self.validator_peers.read().ports().clone();
+                let enodes = validator_enodes(
+                    &*client,
+                    BlockId::Number(block_number),
+                    pending,
+                    our_id,
+                    &ports,
+                );
+                self.validator_peers.write().set_pending_validators(enodes);
+            }
+        }
+        self.validator_peers.write().reconcile(&*client);
+        Some(())
+    }
+
+    /// Returns the validator and pinned enodes we keep reserved connections to.
+    pub fn validator_peers(&self) -> Vec<ValidatorPeer> {
+        self.validator_peers.read().peers()
+    }
+
+    /// Takes the ports of the other validators from the given configured enodes, e.g. the
+    /// reserved peers, instead of assuming the default port.
+    pub fn set_configured_enodes(&self, enodes: &[String]) {
+        self.validator_peers.write().set_configured_enodes(enodes);
+    }
+
+    /// Pins the given enode as a reserved peer.
+    pub fn pin_peer(&self, enode: String) -> Result<(), String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        self.validator_peers.write().pin(&*client, enode)
+    }
+
+    /// Removes the pin of the given enode.
+    pub fn unpin_peer(&self, enode: &str) -> Result<(), String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        self.validator_peers.write().unpin(&*client, enode)
+    }
+
+    /// Returns our progress in the key generation for the next POSDAO epoch.
+    ///
+    /// The progress is read from the chain at most once per block.
+    pub fn keygen_progress(&self) -> Result<KeygenProgress, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        let head = client
+            .block_header(BlockId::Latest)
+            .ok_or_else(|| "The latest block header is not available.".to_owned())?
+            .hash();
+        if let Some((hash, progress)) = *self.keygen_progress.read() {
+            if hash == head {
+                return Ok(progress);
+            }
+        }
+        let progress = self
+            .read_keygen_progress(&*client)
+            .map_err(|e| format!("Could not read the key generation state: {:?}", e))?;
+        *self.keygen_progress.write() = Some((head, progress));
+        Ok(progress)
+    }
+
+    fn read_keygen_progress(&self, client: &dyn EngineClient) -> Result<KeygenProgress, CallError> {
+        let address = match self.signer.read().as_ref() {
+            Some(signer) => signer.address(),
+            None => return Ok(KeygenProgress::default()),
+        };
+        let mut progress = KeygenProgress::default();
+        progress.is_pending_validator = is_pending_validator(client, &address)?;
+        if !progress.is_pending_validator {
+            return Ok(progress);
+        }
+        progress.part_confirmed =
+            has_part_of_address_data(client, address, &self.keygen_read_stats)?;
+        let validators = get_pending_validators(client)?.len();
+        progress.acks_confirmed =
+            has_acks_of_address_data(client, address, validators, &self.keygen_read_stats)?;
+        // Initializing the key generation reads all Parts and Acks, so only do it once ours are on chain.
+        if progress.part_confirmed && progress.acks_confirmed {
+            progress.key_generated = initialize_synckeygen(
+                client,
+                &self.signer,
+                BlockId::Latest,
+                ValidatorType::Pending,
+                &self.keygen_read_stats,
+            )?
+            .is_ready();
+        }
+        Ok(progress)
+    }
+
+    /// Returns which Parts and Acks of the key generation for the next POSDAO epoch are on chain.
+    pub fn keygen_phase(&self) -> Result<KeygenPhaseStatus, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        self.read_keygen_phase(&*client)
+            .map_err(|e| format!("Could not read the key generation state: {:?}", e))
+    }
+
+    fn read_keygen_phase(&self, client: &dyn EngineClient) -> Result<KeygenPhaseStatus, CallError> {
+        let mut status = KeygenPhaseStatus::default();
+        status.upcoming_epoch = get_posdao_epoch(client, BlockId::Latest)?.low_u64() + 1;
+        let pending_validators = get_pending_validators(client)?;
+        let stats = &self.keygen_read_stats;
+        for address in &pending_validators {
+            status.validators.push(KeygenValidatorStatus {
+                mining_address: *address,
+                has_part: has_part_of_address_data(client, *address, stats)?,
+                has_acks: has_acks_of_address_data(
+                    client,
+                    *address,
+                    pending_validators.len(),
+                    stats,
+                )?,
+            });
+        }
+        let address = self.signer.read().as_ref().map(|signer| signer.address());
+        let ours = status
+            .validators
+            .iter()
+            .find(|v| Some(v.mining_address) == address)
+            .cloned();
+        if let Some(ours) = ours {
+            let sender = self.keygen_transaction_sender.read();
+            status.part_in_flight = sender
+                .part_sent(status.upcoming_epoch)
+                .filter(|_| !ours.has_part);
+            status.acks_in_flight = sender
+                .acks_sent(status.upcoming_epoch)
+                .filter(|_| !ours.has_acks);
+        }
+        Ok(status)
+    }
+
+    /// Signs a proof of possession with our key share of the current POSDAO epoch.
+    pub fn key_share_proof(&self) -> Option<KeyShareProof> {
+        let state = self.hbbft_state.read();
+        let network_info = state.network_info()?;
+        let node_id = *network_info.our_id();
+        let posdao_epoch = state.current_posdao_epoch();
+        let message = key_share_proof_message(posdao_epoch, &node_id);
+        Some(KeyShareProof {
+            posdao_epoch,
+            node_id,
+            public_key_share: network_info.public_key_share(&node_id)?.clone(),
+            signature: network_info.secret_key_share()?.sign(message),
+        })
+    }
+
+    /// Returns the key to encrypt transactions with for the validators of the current POSDAO
+    /// epoch.
+    pub fn encryption_key(&self) -> Result<EncryptionKey, String> {
+        let state = self.hbbft_state.read();
+        let public_key = state
+            .public_master_key()
+            .ok_or_else(|| "The key of the current POSDAO epoch is not known yet.".to_owned())?;
+        Ok(EncryptionKey {
+            posdao_epoch: state.current_posdao_epoch(),
+            public_key,
+        })
+    }
+
+    /// Adds an encrypted transaction to be proposed in our next contributions.
+    pub fn submit_encrypted_transaction(&self, ciphertext: Vec<u8>) -> Result<H256, String> {
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine is not registered with a client.".to_owned())?;
+        let next_block = client
+            .block_number(BlockId::Latest)
+            .map_or(0, |latest| latest + 1);
+        if !self.decrypts_transactions(next_block) {
+            return Err("Encrypted transactions are not enabled yet.".to_owned());
+        }
+        if self.hbbft_state.read().network_info().is_none() {
+            return Err("This node is not a validator of the current POSDAO epoch.".to_owned());
+        }
+        if encryption::parse_ciphertext(&ciphertext).is_none() {
+            return Err("The ciphertext is malformed.".to_owned());
+        }
+        let hash = self.encrypted_pool.write().insert(ciphertext);
+        trace!(target: "consensus", "Pooled encrypted transaction {}.", hash);
+        Ok(hash)
+    }
+
+    /// Records competing blocks with valid seals and raises a critical alert.
+    fn record_fork(&self, chosen: &Header, rejected: &Header) {
+        let evidence = ForkEvidence {
+            chosen: chosen.clone(),
+            rejected: rejected.clone(),
+        };
+        {
+            let mut fork_evidence = self.fork_evidence.write();
+            if fork_evidence.contains(&evidence) {
+                return;
+            }
+            if fork_evidence.len() >= MAX_FORK_EVIDENCE {
+                fork_evidence.pop_front();
+            }
+            fork_evidence.push_back(evidence.clone());
+        }
+        self.forks_detected.fetch_add(1, Ordering::SeqCst);
+        error!(target: "consensus", "CRITICAL: Competing blocks #{} {} and {} both carry a valid seal. The epoch keys may be compromised. Choosing {}.", chosen.number(), chosen.hash(), rejected.hash(), chosen.hash());
+        error!(target: "consensus", "Chosen header: {:?}", chosen);
+        error!(target: "consensus", "Rejected header: {:?}", rejected);
+        self.notify(|n| n.fork_detected(&evidence));
+    }
+
+    /// Stores the random number of a block we are creating.
+    ///
+    /// The numbers of earlier blocks are dropped: they are either imported, and their number is
+    /// in the header's extra data, or they were superseded.
+    fn set_random_number(&self, block_num: BlockNumber, random_number: U256) {
+        let mut random_numbers = self.random_numbers.write();
+        *random_numbers = random_numbers.split_off(&block_num);
+        random_numbers.insert(block_num, random_number);
+    }
+
+    /// Stores the validators that contributed to the batch of a block we are creating.
+    fn set_contributors(&self, block_num: BlockNumber, contributors: BTreeSet<Address>) {
+        let mut all_contributors = self.contributors.write();
+        *all_contributors = all_contributors.split_off(&block_num);
+        all_contributors.insert(block_num, contributors);
+    }
+
+    /// Returns true if the block reward call of the given block passes the contributors.
+    fn reports_contributors(&self, block_num: BlockNumber) -> bool {
+        self.params
+            .block_reward_contributors_transition
+            .map_or(false, |transition| block_num >= transition)
+    }
+
+    /// Returns true if the batch of the given block may propose encrypted transactions.
+    fn decrypts_transactions(&self, block_num: BlockNumber) -> bool {
+        self.params
+            .encrypted_transactions_transition
+            .map_or(false, |transition| block_num >= transition)
+    }
+
+    /// Returns the contribution entries of the pooled encrypted transactions, if the next block
+    /// may propose them.
+    fn encrypted_entries(&self, client: &Arc<dyn EngineClient>) -> Vec<Vec<u8>> {
+        let next_block = client
+            .block_number(BlockId::Latest)
+            .map_or(0, |latest| latest + 1);
+        if !self.decrypts_transactions(next_block) {
+            return Vec::new();
+        }
+        self.encrypted_pool
+            .read()
+            .entries(encryption::MAX_ENCRYPTED_CONTRIBUTION_BYTES)
+    }
+
+    /// Returns the validators that could contribute to the given block's batch, in address order.
+    fn contributor_candidates(&self, header: &Header) -> Result<Vec<Address>, Error> {
+        let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
+        let validators = get_validator_pubkeys(
+            &*client,
+            BlockId::Hash(*header.parent_hash()),
+            ValidatorType::Current,
+        )
+        .map_err(|e| EngineError::Custom(format!("Could not read the validators: {:?}", e)))?;
+        Ok(validators.keys().cloned().collect())
+    }
+
+    /// Returns true if the engine itself finalizes validator set changes in the given block.
+    fn finalizes_change(&self, block_num: BlockNumber) -> bool {
+        self.params
+            .finalize_change_transition
+            .map_or(false, |transition| block_num >= transition)
+    }
+
+    /// Splits a block's extra data into the random number and the contributors bit field, see
+    /// `extra_data` for the layout.
+    fn split_extra_data<'a>(&self, header: &'a Header) -> (&'a [u8], &'a [u8]) {
+        extra_data::split(
+            header.extra_data(),
+            self.params.randomness_contract_address.is_some(),
+            self.reports_contributors(header.number()),
+        )
+    }
+
+    /// Returns the random number committed by the given block, or `None` if the block is
+    /// unknown.
+    ///
+    /// Fails if the chain has no randomness contract, as the blocks do not carry random numbers
+    /// then.
+    pub fn block_random(&self, block_num: BlockNumber) -> Result<Option<U256>, String> {
+        if self.params.randomness_contract_address.is_none() {
+            return Err(
+                "The chain does not commit random numbers: no randomness contract is configured."
+                    .into(),
+            );
+        }
+        if self.is_legacy_block(block_num) {
+            return Ok(None);
+        }
+        let client = self
+            .client_arc()
+            .ok_or_else(|| "The engine has no client yet.".to_string())?;
+        Ok(client
+            .block_header(BlockId::Number(block_num))
+            .and_then(|header| random_hbbft::decode_seed(self.split_extra_data(&header).0)))
+    }
+
+    /// Returns the evidence of the most recent competing blocks with valid seals.
+    pub fn fork_evidence(&self) -> Vec<ForkEvidence> {
+        self.fork_evidence.read().iter().cloned().collect()
+    }
+
+    /// Returns processing latency percentiles of received messages, per message kind and sender.
+    pub fn message_latencies(&self) -> Vec<MessageLatency> {
+        self.message_latencies.read().by_sender()
+    }
+
+    /// Claims the rewards of the given staker of our pool every `interval` POSDAO epochs.
+    pub fn set_reward_claimer(&self, signer: Box<dyn EngineSigner>, interval: u64) {
+        *self.reward_claimer.write() = Some(RewardClaimer::new(signer, interval));
+    }
+
+    /// Replaces the rules deriving a block from the agreed contributions of an hbbft epoch.
+    pub fn set_batch_policy(&self, policy: Arc<dyn BatchPolicy>) {
+        *self.batch_policy.write() = policy;
+    }
+
+    /// Adds a target to be notified about consensus events.
+    pub fn add_notify(&self, target: Arc<dyn HbbftNotify>) {
+        self.notify.write().push(Arc::downgrade(&target));
+    }
+
+    /// Calls `f` for every target that is still alive, and forgets the dropped ones.
+    fn notify<F>(&self, f: F)
+    where
+        F: Fn(&dyn HbbftNotify),
+    {
+        let targets: Vec<_> = {
+            let mut notify = self.notify.write();
+            notify.retain(|np| np.upgrade().is_some());
+            notify.iter().filter_map(Weak::upgrade).collect()
+        };
+        for n in targets {
+            f(&*n);
+        }
+    }
+
+    /// Creates a recovery block if recovery blocks are enabled, no block has been created for
+    /// `recovery_block_timeout` seconds and we are the designated proposer of the next block.
+    fn try_create_recovery_block(&self, client: Arc<dyn EngineClient>) -> Option<()> {
+        let timeout = self.params.recovery_block_timeout?;
+        let parent = client.block_header(BlockId::Latest)?;
+        let now = self.now_secs();
+        if !recovery::stall_exceeded(parent.timestamp(), now, timeout) || self.is_syncing(&client)
+        {
+            return None;
+        }
+        let block_num = parent.number() + 1;
+        if self.is_legacy_block(block_num) || *self.recovery_block.read() == Some(block_num) {
+            return None;
+        }
 
-                // If current time larger than phase start time, start a new block.
-                if genesis_transition_time.as_u64() < unix_now_secs() {
-                    self.start_hbbft_epoch(client);
-                }
+        if self.withdrawn.load(Ordering::SeqCst) {
+            return None;
+        }
+        let our_address = self.signer.read().as_ref()?.address();
+        let validators = match get_validator_pubkeys(
+            &*client,
+            BlockId::Hash(parent.hash()),
+            ValidatorType::Current,
+        ) {
+            Ok(validators) => validators,
+            Err(e) => {
+                error!(target: "consensus", "Could not read the validators for a recovery block: {:?}", e);
+                return None;
             }
+        };
+        if recovery::designated_proposer(&validators, block_num) != Some(&our_address) {
+            return None;
+        }
+
+        warn!(target: "consensus", "No block for {} seconds, creating recovery block #{} as its designated proposer.", now - parent.timestamp(), block_num);
+        *self.recovery_block.write() = Some(block_num);
+        // Recovery blocks are not based on agreed contributions, so there is no shared random number.
+        self.set_random_number(block_num, U256::from(parent.hash().as_bytes()));
+        self.set_contributors(block_num, BTreeSet::new());
+        if client
+            .create_pending_block_at(Vec::new(), now, block_num)
+            .is_none()
+        {
+            error!(target: "consensus", "Could not create pending recovery block #{}", block_num);
+            *self.recovery_block.write() = None;
+            return None;
         }
+        client.update_sealing(ForceUpdateSealing::No);
+        Some(())
     }
 
-    fn replay_cached_messages(&self) -> Option<()> {
-        let client = self.client_arc()?;
-        let steps = self
-            .hbbft_state
-            .write()
-            .replay_cached_messages(client.clone());
-        let mut processed_step = false;
-        if let Some((steps, network_info)) = steps {
-            for step in steps {
-                match step {
-                    Ok(step) => {
-                        trace!(target: "engine", "Processing cached message step");
-                        processed_step = true;
-                        self.process_step(client.clone(), step, &network_info)
-                    }
-                    Err(e) => error!(target: "engine", "Error handling replayed message: {}", e),
-                }
+    /// Verifies a block sealed with a recovery seal.
+    fn verify_recovery_block(
+        &self,
+        client: &Arc<dyn EngineClient>,
+        header: &Header,
+        parent: &Header,
+        signature: &Signature,
+    ) -> Result<(), Error> {
+        let timeout = match self.params.recovery_block_timeout {
+            Some(timeout) => timeout,
+            None => {
+                error!(target: "engine", "Recovery block #{} received, but recovery blocks are disabled.", header.number());
+                return Err(BlockError::InvalidSeal.into());
             }
+        };
+        if !recovery::stall_exceeded(parent.timestamp(), header.timestamp(), timeout)
+            || header.timestamp() > self.now_secs() + MAX_RECOVERY_BLOCK_CLOCK_DRIFT
+        {
+            error!(target: "engine", "Recovery block #{} has timestamp {}, parent timestamp is {}.", header.number(), header.timestamp(), parent.timestamp());
+            return Err(BlockError::InvalidSeal.into());
         }
-
-        if processed_step {
-            if let Err(e) = self.join_hbbft_epoch() {
-                error!(target: "engine", "Error trying to join epoch: {}", e);
+        if *header.transactions_root() != KECCAK_NULL_RLP {
+            error!(target: "engine", "Recovery block #{} contains transactions.", header.number());
+            return Err(BlockError::InvalidSeal.into());
+        }
+        // The proposer must not choose the random seed or the rewarded contributors.
+        if let Some(extra_data) = self.recovery_extra_data(header, parent)? {
+            if *header.extra_data() != extra_data {
+                error!(target: "engine", "Recovery block #{} carries a random seed or contributors of its own.", header.number());
+                return Err(BlockError::InvalidSeal.into());
             }
         }
-
-        Some(())
+        let validators = get_validator_pubkeys(
+            &**client,
+            BlockId::Hash(parent.hash()),
+            ValidatorType::Current,
+        )
+        .map_err(|e| EngineError::Custom(format!("Could not read validators: {:?}", e)))?;
+        if !recovery::is_signed_by_designated_proposer(
+            &validators,
+            header.number(),
+            &header.bare_hash(),
+            signature,
+        ) {
+            error!(target: "engine", "Recovery block #{} is not sealed by its designated proposer.", header.number());
+            return Err(BlockError::InvalidSeal.into());
+        }
+        warn!(target: "engine", "Accepting recovery block #{}.", header.number());
+        Ok(())
     }
 
-    /// Returns true if we are in the keygen phase and a new key has been generated.
-    fn do_keygen(&self) -> bool {
-        match self.client_arc() {
-            None => false,
-            Some(client) => {
-                // If we are not in key generation phase, return false.
-                match get_pending_validators(&*client) {
-                    Err(_) => return false,
-                    Ok(validators) => {
-                        // If the validator set is empty then we are not in the key generation phase.
-                        if validators.is_empty() {
-                            return false;
-                        }
-                    }
-                }
+    /// Returns the extra data of a recovery block: the parent hash as random seed and no
+    /// contributors, as `try_create_recovery_block` sets them. Returns `None` if the block's extra
+    /// data carries neither.
+    fn recovery_extra_data(
+        &self,
+        header: &Header,
+        parent: &Header,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let has_seed = self.params.randomness_contract_address.is_some();
+        let has_contributors = self.reports_contributors(header.number());
+        if !has_seed && !has_contributors {
+            return Ok(None);
+        }
+        let mut extra_data = Vec::new();
+        if has_seed {
+            extra_data = random_hbbft::encode_seed(&U256::from(parent.hash().as_bytes()));
+        }
+        if has_contributors {
+            extra_data.extend(block_reward_hbbft::encode_contributors(
+                &self.contributor_candidates(header)?,
+                &BTreeSet::new(),
+            ));
+        }
+        Ok(Some(extra_data))
+    }
 
-                // Check if a new key is ready to be generated, return true to switch to the new epoch in that case.
-                if let Ok(synckeygen) = initialize_synckeygen(
-                    &*client,
-                    &self.signer,
-                    BlockId::Latest,
-                    ValidatorType::Pending,
-                ) {
-                    if synckeygen.is_ready() {
-                        return true;
-                    }
-                }
+    /// Returns true if the block with the given number is sealed by the legacy engine.
+    fn is_legacy_block(&self, block_num: BlockNumber) -> bool {
+        block_num < self.params.activation_block.unwrap_or(0)
+    }
 
-                // Otherwise check if we are in the pending validator set and send Parts and Acks transactions.
-                // @todo send_keygen_transactions initializes another synckeygen structure, a potentially
-                //       time consuming process. Move sending of keygen transactions into a separate function
-                //       and call it periodically using timer events instead of on close block.
-                if let Some(signer) = self.signer.read().as_ref() {
-                    if let Ok(is_pending) = is_pending_validator(&*client, &signer.address()) {
-                        if is_pending {
-                            let _err = self
-                                .keygen_transaction_sender
-                                .write()
-                                .send_keygen_transactions(&*client, &self.signer);
-                        }
-                    }
-                }
-                false
-            }
+    /// Returns the AuRa engine of the block with the given number, if it is before the activation
+    /// block.
+    fn legacy_engine_for(&self, block_num: BlockNumber) -> Option<&AuthorityRound> {
+        if !self.is_legacy_block(block_num) {
+            return None;
         }
+        self.legacy_engine.as_ref().map(|engine| &**engine)
     }
 
-    fn check_for_epoch_change(&self) -> Option<()> {
-        let client = self.client_arc()?;
-        if let None = self.hbbft_state.write().update_honeybadger(
-            client,
-            &self.signer,
-            BlockId::Latest,
-            false,
-        ) {
-            error!(target: "consensus", "Fatal: Updating Honey Badger instance failed!");
-        }
-        Some(())
+    /// Returns true if the next block is still sealed by the legacy engine.
+    fn awaits_activation(&self, client: &Arc<dyn EngineClient>) -> bool {
+        let next_block = client.block_number(BlockId::Latest).map_or(0, |latest| latest + 1);
+        self.is_legacy_block(next_block)
     }
 
     fn is_syncing(&self, client: &Arc<dyn EngineClient>) -> bool {
@@ -622,7 +3377,24 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
     }
 
     fn fork_choice(&self, new: &ExtendedHeader, current: &ExtendedHeader) -> ForkChoice {
-        crate::engines::total_difficulty_fork_choice(new, current)
+        // Legacy engines may fork legitimately.
+        if let Some(legacy) = self.legacy_engine_for(new.header.number()) {
+            return legacy.fork_choice(new, current);
+        }
+        if !fork::is_competing(new, current) {
+            return crate::engines::total_difficulty_fork_choice(new, current);
+        }
+        let choice = fork::choose(new, current);
+        if !fork::is_evidence(new, current) {
+            // A recovery block competing with a late threshold-sealed block.
+            debug!(target: "consensus", "Competing recovery and threshold-sealed blocks #{}, choosing {:?}.", new.header.number(), choice);
+            return choice;
+        }
+        match choice {
+            ForkChoice::New => self.record_fork(&new.header, &current.header),
+            ForkChoice::Old => self.record_fork(&current.header, &new.header),
+        }
+        choice
     }
 
     fn verify_local_seal(&self, _header: &Header) -> Result<(), Error> {
@@ -631,19 +3403,29 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
     }
 
     /// Phase 1 Checks
-    fn verify_block_basic(&self, _header: &Header) -> Result<(), Error> {
-        Ok(())
+    fn verify_block_basic(&self, header: &Header) -> Result<(), Error> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.verify_block_basic(header),
+            None => Ok(()),
+        }
     }
 
     /// Pase 2 Checks
-    fn verify_block_unordered(&self, _header: &Header) -> Result<(), Error> {
-        Ok(())
+    fn verify_block_unordered(&self, header: &Header) -> Result<(), Error> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.verify_block_unordered(header),
+            None => Ok(()),
+        }
     }
 
     /// Phase 3 Checks
     /// We check the signature here since at this point the blocks are imported in-order.
     /// To verify the signature we need the parent block already imported on the chain.
-    fn verify_block_family(&self, header: &Header, _parent: &Header) -> Result<(), Error> {
+    fn verify_block_family(&self, header: &Header, parent: &Header) -> Result<(), Error> {
+        if let Some(legacy) = self.legacy_engine_for(header.number()) {
+            return legacy.verify_block_family(header, parent);
+        }
+
         let client = self.client_arc().ok_or(EngineError::RequiresClient)?;
 
         let latest_block_nr = client.block_number(BlockId::Latest).expect("must succeed");
@@ -657,27 +3439,103 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
             return Err(BlockError::InvalidSeal.into());
         }
 
-        let RlpSig(sig) = rlp::decode(header.seal().first().ok_or(BlockError::InvalidSeal)?)?;
-        if self
+        let seal = header.seal().first().ok_or(BlockError::InvalidSeal)?;
+        if let Some(signature) = recovery::decode_recovery_seal(seal) {
+            return self.verify_recovery_block(&client, header, parent, &signature);
+        }
+
+        let RlpSig(sig) = rlp::decode(seal)?;
+        let valid = self
             .hbbft_state
             .write()
-            .verify_seal(client, &self.signer, &sig, header)
-        {
+            .verify_seal(client.clone(), &self.signer, &sig, header);
+        self.record_seal_verification(&client, header, valid);
+        if valid {
             Ok(())
         } else {
-            error!(target: "engine", "Invalid seal for block #{}!", header.number());
             Err(BlockError::InvalidSeal.into())
         }
     }
 
     // Phase 4
-    fn verify_block_external(&self, _header: &Header) -> Result<(), Error> {
-        Ok(())
+    fn verify_block_external(&self, header: &Header) -> Result<(), Error> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.verify_block_external(header),
+            None => Ok(()),
+        }
+    }
+
+    fn genesis_epoch_data(&self, header: &Header, call: &Call) -> Result<Vec<u8>, String> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.genesis_epoch_data(header, call),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn signals_epoch_end<'a>(
+        &self,
+        header: &Header,
+        aux: AuxiliaryData<'a>,
+    ) -> EpochChange<EthereumMachine> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.signals_epoch_end(header, aux),
+            None => EpochChange::No,
+        }
+    }
+
+    fn is_epoch_end(
+        &self,
+        chain_head: &Header,
+        finalized: &[H256],
+        chain: &Headers<Header>,
+        transition_store: &PendingTransitionStore,
+    ) -> Option<Vec<u8>> {
+        self.legacy_engine_for(chain_head.number())?.is_epoch_end(
+            chain_head,
+            finalized,
+            chain,
+            transition_store,
+        )
+    }
+
+    fn epoch_verifier<'a>(
+        &self,
+        header: &Header,
+        proof: &'a [u8],
+    ) -> ConstructedVerifier<'a, EthereumMachine> {
+        if let Some(legacy) = self.legacy_engine_for(header.number()) {
+            return legacy.epoch_verifier(header, proof);
+        }
+        let proof = match EpochKeyProof::decode(proof) {
+            Ok(proof) => proof,
+            Err(e) => {
+                return ConstructedVerifier::Err(
+                    EngineError::MalformedMessage(format!("Invalid epoch key proof: {}", e)).into(),
+                )
+            }
+        };
+        // Only keys vouched for by enough validators are trusted.
+        let key = proof.key.clone();
+        match self.register_epoch_key_proof(proof) {
+            Ok(true) => ConstructedVerifier::Trusted(Box::new(key)),
+            Ok(false) => ConstructedVerifier::Err(
+                EngineError::InsufficientProof(format!(
+                    "The key of POSDAO epoch {} lacks validator signatures.",
+                    key.posdao_epoch
+                ))
+                .into(),
+            ),
+            Err(e) => ConstructedVerifier::Err(EngineError::InsufficientProof(e).into()),
+        }
     }
 
     fn register_client(&self, client: Weak<dyn EngineClient>) {
+        if let Some(legacy) = self.legacy_engine.as_ref() {
+            legacy.register_client(client.clone());
+        }
         *self.client.write() = Some(client.clone());
         if let Some(client) = self.client_arc() {
+            *self.validator_peers.write() = ValidatorPeers::restore(&*client);
             if let None = self.hbbft_state.write().update_honeybadger(
                 client,
                 &self.signer,
@@ -688,10 +3546,32 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
                 error!(target: "engine", "Error during HoneyBadger initialization!");
             }
         }
+        self.restore_withdrawn();
+        self.restore_cached_messages();
+        self.recover_persisted_batch();
+    }
+
+    fn on_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        if let Err(e) = self.transition_service.send_message(()) {
+            warn!(target: "consensus", "Failed to stop consensus timer: {}.", e);
+        }
+        self.persist_pending_batch();
+        self.persist_cached_messages();
+        self.announce_offline();
     }
 
     fn set_signer(&self, signer: Option<Box<dyn EngineSigner>>) {
         *self.signer.write() = signer;
+        self.restore_withdrawn();
+        // The pool status, the announced features and the sent keygen transactions belong to the
+        // previous signer's address.
+        *self.pool_status.write() = None;
+        self.pool_status_block.store(0, Ordering::SeqCst);
+        *self.features_announced_epoch.write() = None;
+        *self.keygen_progress.write() = None;
+        *self.keygen_transaction_sender.write() =
+            KeygenTransactionSender::new(self.params.keygen_gas_price);
         if let Some(client) = self.client_arc() {
             if let None = self.hbbft_state.write().update_honeybadger(
                 client,
@@ -702,6 +3582,11 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
                 info!(target: "engine", "HoneyBadger Algorithm could not be created, Client possibly not set yet.");
             }
         }
+        self.recover_persisted_batch();
+        // When the signer is replaced at runtime, don't wait for the next block to check the new
+        // address's pool and announce its features.
+        self.check_pool_status();
+        self.announce_features();
     }
 
     fn sign(&self, hash: H256) -> Result<Signature, Error> {
@@ -718,15 +3603,22 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
         block: &ExecutedBlock,
     ) -> Result<Vec<SignedTransaction>, Error> {
         self.check_for_epoch_change();
-        let _random_number = match self.random_numbers.read().get(&block.header.number()) {
-            None => {
-                return Err(EngineError::Custom(
-                    "No value available for calling randomness contract.".into(),
-                )
-                .into())
-            }
-            Some(r) => r,
-        };
+        // The finalization of validator set changes, the randomness commitment and the other
+        // epoch-end calls are not engine transactions: those would be signed by this node, but all
+        // validators must create the same block from the agreed batch. They are system calls in
+        // `on_close_block` instead, the random number is read from the block's extra data there.
+        if self.params.randomness_contract_address.is_some()
+            && !self
+                .random_numbers
+                .read()
+                .contains_key(&block.header.number())
+        {
+            return Err(EngineError::Custom(format!(
+                "No random number for the randomness contract in block #{}.",
+                block.header.number()
+            ))
+            .into());
+        }
         Ok(Vec::new())
     }
 
@@ -740,8 +3632,25 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
             None => return SealingState::NotReady,
             Some(block_num) => block_num + 1,
         };
+        if self.is_legacy_block(next_block) {
+            return SealingState::NotReady;
+        }
+        // Recovery blocks are sealed by us alone.
+        {
+            let mut recovery_block = self.recovery_block.write();
+            match *recovery_block {
+                Some(block_num) if block_num == next_block => return SealingState::Ready,
+                Some(block_num) if block_num < next_block => *recovery_block = None,
+                _ => {}
+            }
+        }
+        // An isolated validator cannot collect enough signature shares; back off until reconnected.
+        if !self.has_validator_connectivity(&client) {
+            return SealingState::NotReady;
+        }
         let mut sealing = self.sealing.write();
         *sealing = sealing.split_off(&next_block);
+        self.seal_shares.write().prune(next_block);
 
         // We are ready to seal if we have a valid signature for the next block.
         if let Some(next_seal) = sealing.get(&next_block) {
@@ -762,23 +3671,90 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
     }
 
     fn handle_message(&self, message: &[u8], node_id: Option<H512>) -> Result<(), EngineError> {
+        let received = Instant::now();
         self.check_for_epoch_change();
+        self.replay_future_seal_shares();
         let node_id = NodeId(node_id.ok_or(EngineError::UnexpectedMessage)?);
-        match serde_json::from_slice(message) {
-            Ok(Message::HoneyBadger(msg_idx, hb_msg)) => {
-                self.process_hb_message(msg_idx, hb_msg, node_id)
+        let (envelope, messages): (_, Vec<Message>) = match wire::decode_messages(message) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                let is_validator = self
+                    .hbbft_state
+                    .read()
+                    .network_info()
+                    .map_or(false, |n| n.all_ids().any(|id| *id == node_id));
+                if is_validator {
+                    self.report_fault(node_id, format!("it sent an undecodable packet: {}", e));
+                }
+                return Err(EngineError::MalformedMessage(format!(
+                    "Message decoding failed: {}",
+                    e
+                )));
             }
-            Ok(Message::Sealing(block_num, seal_msg)) => {
-                self.process_sealing_message(seal_msg, node_id, block_num)
+        };
+        self.check_envelope(envelope)?;
+        let signer = wire::packet_signer(message).map_err(|e| {
+            EngineError::MalformedMessage(format!("Message signature check failed: {}", e))
+        })?;
+        self.check_packet_signer(signer, node_id, envelope, &messages)?;
+        // A validator that announced its shutdown and sends other messages has restarted.
+        let online = messages.iter().any(|message| match message {
+            Message::Offline => false,
+            _ => true,
+        });
+        if online && self.offline_validators.read().contains(&node_id) {
+            self.offline_validators.write().remove(&node_id);
+        }
+        let mut result = Ok(());
+        for message in messages {
+            self.metrics.message_received();
+            let kind = message.kind();
+            let outcome = match message {
+                Message::HoneyBadger(msg_idx, hb_msg) => {
+                    self.process_hb_message(msg_idx, hb_msg, node_id)
+                }
+                Message::Sealing(block_num, seal_msg) => {
+                    self.process_sealing_message(seal_msg, node_id, block_num)
+                }
+                Message::Keygen(keygen_msg) => self.process_keygen_message(keygen_msg, node_id),
+                Message::Decryption(block_num, index, decryption_msg) => {
+                    self.process_decryption_message(decryption_msg, node_id, block_num, index)
+                }
+                Message::Offline => self.process_offline_notice(node_id),
+                Message::Observe => self.process_observer_subscription(node_id),
+            };
+            self.message_latencies
+                .write()
+                .record(kind, node_id, received.elapsed());
+            // Process the rest of the batch, but report the first failure.
+            if result.is_ok() {
+                result = outcome;
             }
-            Err(_) => Err(EngineError::MalformedMessage(
-                "Serde message decoding failed.".into(),
-            )),
         }
+        result
+    }
+
+    fn seal_fields(&self, header: &Header) -> usize {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.seal_fields(header),
+            None => 1,
+        }
+    }
+
+    fn maximum_uncle_count(&self, block: BlockNumber) -> usize {
+        self.legacy_engine_for(block)
+            .map_or(0, |legacy| legacy.maximum_uncle_count(block))
     }
 
-    fn seal_fields(&self, _header: &Header) -> usize {
-        1
+    fn ancestry_actions(
+        &self,
+        header: &Header,
+        ancestry: &mut dyn Iterator<Item = ExtendedHeader>,
+    ) -> Vec<AncestryAction> {
+        match self.legacy_engine_for(header.number()) {
+            Some(legacy) => legacy.ancestry_actions(header, ancestry),
+            None => Vec::new(),
+        }
     }
 
     fn generate_seal(&self, block: &ExecutedBlock, _parent: &Header) -> Seal {
@@ -788,6 +3764,27 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
         };
 
         let block_num = block.header.number();
+        if self.is_legacy_block(block_num) {
+            error!(target: "consensus", "generate_seal: Block #{} is before the hbbft activation block.", block_num);
+            return Seal::None;
+        }
+        if *self.recovery_block.read() == Some(block_num) {
+            if !block.transactions.is_empty() {
+                error!(target: "consensus", "generate_seal: Recovery block #{} must not contain transactions.", block_num);
+                return Seal::None;
+            }
+            return match self.sign(block.header.bare_hash()) {
+                Ok(signature) => {
+                    trace!(target: "consensus", "Returning recovery seal for block {}.", block_num);
+                    Seal::Regular(vec![recovery::encode_recovery_seal(&signature)])
+                }
+                Err(e) => {
+                    error!(target: "consensus", "generate_seal: Could not sign recovery block #{}: {}", block_num, e);
+                    Seal::None
+                }
+            };
+        }
+
         let sealing = self.sealing.read();
         let sig = match sealing.get(&block_num).and_then(Sealing::signature) {
             None => return Seal::None,
@@ -809,16 +3806,228 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
         false
     }
 
+    fn populate_from_parent(&self, header: &mut Header, parent: &Header) {
+        if let Some(legacy) = self.legacy_engine_for(header.number()) {
+            return legacy.populate_from_parent(header, parent);
+        }
+        if let Some(gas_limit) = self.gas_limit_override(header) {
+            if gas_limit != *parent.gas_limit() {
+                info!(target: "engine", "Block gas limit was changed from {} to {}.", parent.gas_limit(), gas_limit);
+            }
+            header.set_gas_limit(gas_limit);
+        }
+    }
+
+    fn gas_limit_override(&self, header: &Header) -> Option<U256> {
+        if let Some(legacy) = self.legacy_engine_for(header.number()) {
+            return legacy.gas_limit_override(header);
+        }
+        let contract = self.params.block_gas_limit_contract_address?;
+        let parent_hash = *header.parent_hash();
+        if let Some(gas_limit) = self.gas_limits.write().get_mut(&parent_hash) {
+            return *gas_limit;
+        }
+        let client = self.client_arc()?;
+        // Failed reads are not cached: the parent block may not be imported yet.
+        let parent = BlockId::Hash(parent_hash);
+        let gas_limit = match block_gas_limit::block_gas_limit(&*client, contract, parent) {
+            Ok(gas_limit) if gas_limit.is_zero() => None,
+            Ok(gas_limit) => Some(gas_limit),
+            Err(e) => {
+                // Expected for queued blocks whose parent is not imported yet.
+                debug!(target: "engine", "Could not read the gas limit of block {}: {:?}", header.number(), e);
+                return None;
+            }
+        };
+        trace!(target: "engine", "Gas limit of block {}: {:?}", header.number(), gas_limit);
+        self.gas_limits.write().insert(parent_hash, gas_limit);
+        gas_limit
+    }
+
+    fn min_gas_price_override(&self, header: &Header) -> Option<U256> {
+        let contract = self.params.min_gas_price_contract_address?;
+        let client = self.client_arc()?;
+        match gas_price::minimum_gas_price(&*client, contract, BlockId::Hash(header.hash())) {
+            Ok(price) => {
+                trace!(target: "engine", "Minimum gas price at block {}: {}", header.number(), price);
+                Some(price)
+            }
+            Err(e) => {
+                warn!(target: "engine", "Could not read the minimum gas price at block {}: {:?}", header.number(), e);
+                None
+            }
+        }
+    }
+
     fn use_block_author(&self) -> bool {
         false
     }
 
+    fn prometheus_metrics(&self, r: &mut PrometheusRegistry) {
+        r.register_counter(
+            "hbbft_keygen_history_reads",
+            "Calls made by the engine to the key generation history contract",
+            self.keygen_read_stats.calls() as i64,
+        );
+        r.register_counter(
+            "hbbft_keygen_history_read_bytes",
+            "Bytes of Parts and Acks read from the key generation history contract",
+            self.keygen_read_stats.bytes() as i64,
+        );
+        r.register_counter(
+            "hbbft_forks_detected",
+            "Competing blocks with valid seals seen since startup",
+            self.forks_detected.load(Ordering::SeqCst) as i64,
+        );
+        r.register_gauge(
+            "hbbft_keygen_incomplete_blocks",
+            "Blocks the key generation for the next POSDAO epoch has been incomplete for",
+            self.keygen_timeout.read().incomplete_blocks() as i64,
+        );
+        r.register_counter(
+            "hbbft_synckeygen_initializations",
+            "Key generation states reconstructed from contract data",
+            self.keygen_read_stats.synckeygen_initializations() as i64,
+        );
+        if let Some(state) = self.hbbft_state.try_read() {
+            let cached: usize = state.future_messages().values().map(Vec::len).sum();
+            r.register_gauge(
+                "hbbft_cached_future_messages",
+                "Consensus messages cached for future hbbft epochs",
+                cached as i64,
+            );
+            r.register_counter(
+                "hbbft_obsolete_messages",
+                "Consensus messages dropped because their hbbft epoch was over",
+                state.obsolete_messages() as i64,
+            );
+            r.register_counter(
+                "hbbft_evicted_future_messages",
+                "Consensus messages evicted from the full cache for future hbbft epochs",
+                state.evicted_messages() as i64,
+            );
+            r.register_counter(
+                "hbbft_handoff_messages",
+                "Consensus messages of the previous POSDAO epoch handled after a validator set change",
+                state.handoff_messages() as i64,
+            );
+        }
+        self.metrics.register(r);
+
+        for latency in self.message_latencies.read().by_kind() {
+            let kind = format!("{:?}", latency.kind).to_lowercase();
+            for (percentile, value) in &[
+                ("p50", latency.p50_micros),
+                ("p90", latency.p90_micros),
+                ("p99", latency.p99_micros),
+            ] {
+                r.register_gauge(
+                    &format!("hbbft_message_latency_{}_{}", kind, percentile),
+                    &format!(
+                        "{} percentile of the {} message processing time in microseconds",
+                        percentile, kind
+                    ),
+                    *value as i64,
+                );
+            }
+        }
+    }
+
+    fn on_new_block(
+        &self,
+        block: &mut ExecutedBlock,
+        epoch_begin: bool,
+        ancestry: &mut dyn Iterator<Item = ExtendedHeader>,
+    ) -> Result<(), Error> {
+        if let Some(legacy) = self.legacy_engine_for(block.header.number()) {
+            return legacy.on_new_block(block, epoch_begin, ancestry);
+        }
+        // Store the agreed random number and the contributors in blocks we create, so importing
+        // nodes can make the same calls. Imported blocks keep their own extra data, it is set
+        // again after this call.
+        let block_num = block.header.number();
+        let mut extra_data = Vec::new();
+        if self.params.randomness_contract_address.is_some() {
+            if let Some(random_number) = self.random_numbers.read().get(&block_num) {
+                extra_data = random_hbbft::encode_seed(random_number);
+            }
+        }
+        if self.reports_contributors(block_num) {
+            if let Some(contributors) = self.contributors.read().get(&block_num) {
+                let validators = self.contributor_candidates(&block.header)?;
+                extra_data.extend(block_reward_hbbft::encode_contributors(
+                    &validators,
+                    contributors,
+                ));
+            }
+        }
+        let maximum_extra_data_size = self.machine.params().maximum_extra_data_size;
+        if extra_data.len() > maximum_extra_data_size {
+            return Err(EngineError::Custom(format!(
+                "Block #{} needs {} bytes of extra data, but maximumExtraDataSize is {}.",
+                block_num,
+                extra_data.len(),
+                maximum_extra_data_size
+            ))
+            .into());
+        }
+        if !extra_data.is_empty() {
+            block.header.set_extra_data(extra_data);
+        }
+        Ok(())
+    }
+
     fn on_close_block(&self, block: &mut ExecutedBlock) -> Result<(), Error> {
+        if let Some(legacy) = self.legacy_engine_for(block.header.number()) {
+            return legacy.on_close_block(block);
+        }
         self.check_for_epoch_change();
+        let block_num = block.header.number();
+        let (seed, contributor_bits) = self.split_extra_data(&block.header);
+        let seed = random_hbbft::decode_seed(seed);
+        let contributors = if self.reports_contributors(block_num) {
+            let validators = self.contributor_candidates(&block.header)?;
+            let contributors =
+                block_reward_hbbft::decode_contributors(&validators, contributor_bits);
+            if contributors.is_none() {
+                return Err(EngineError::Custom(format!(
+                    "Block #{} carries no valid contributors for the block reward contract.",
+                    block_num
+                ))
+                .into());
+            }
+            contributors
+        } else {
+            None
+        };
+        if let Some(address) = self.params.randomness_contract_address {
+            let seed = seed.ok_or_else(|| {
+                EngineError::Custom(format!(
+                    "Block #{} carries no random number for the randomness contract.",
+                    block_num
+                ))
+            })?;
+            let mut call = default_system_or_code_call(&self.machine, block);
+            random_hbbft::set_current_seed(&mut call, address, seed)?;
+        }
+        let finalizes_change = self.finalizes_change(block_num);
+        let is_epoch_end = (finalizes_change
+            || self.params.block_reward_contract_address.is_some())
+            && self.do_keygen();
+        if finalizes_change && is_epoch_end {
+            info!(target: "engine", "Finalizing the validator set change in block #{}.", block_num);
+            let mut call = default_system_or_code_call(&self.machine, block);
+            validator_set::finalize_change(&mut call)?;
+        }
         if let Some(address) = self.params.block_reward_contract_address {
             let mut call = default_system_or_code_call(&self.machine, block);
             let contract = BlockRewardContract::new_from_address(address);
-            let _total_reward = contract.reward(&mut call, self.do_keygen())?;
+            let _total_reward = match contributors {
+                Some(contributors) => {
+                    contract.reward_contributors(&mut call, is_epoch_end, contributors)?
+                }
+                None => contract.reward(&mut call, is_epoch_end)?,
+            };
         }
         Ok(())
     }
@@ -826,15 +4035,27 @@ impl Engine<EthereumMachine> for HoneyBadgerBFT {
 
 #[cfg(test)]
 mod tests {
-    use super::super::{contribution::Contribution, test::create_transactions::create_transaction};
+    use super::{
+        super::{
+            api::MessageKind,
+            contribution::Contribution,
+            test::create_transactions::create_transaction,
+            wire::{self, Envelope, WireVersion},
+            NodeId,
+        },
+        Message,
+    };
     use crypto::publickey::{Generator, Random};
-    use ethereum_types::U256;
+    use ethereum_types::{H512, U256};
     use hbbft::{
         honey_badger::{HoneyBadger, HoneyBadgerBuilder},
-        NetworkInfo,
+        NetworkInfo, Target,
     };
     use rand_065;
-    use std::sync::Arc;
+    use std::{
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        sync::Arc,
+    };
     use types::transaction::SignedTransaction;
 
     #[test]
@@ -855,7 +4076,7 @@ mod tests {
         let mut pending: Vec<SignedTransaction> = Vec::new();
         let keypair = Random.generate();
         pending.push(create_transaction(&keypair, &U256::from(1)));
-        let input_contribution = Contribution::new(&pending);
+        let input_contribution = Contribution::new(&pending, 0);
 
         let step = honey_badger
             .propose(&input_contribution, &mut rng)
@@ -868,4 +4089,100 @@ mod tests {
         assert_eq!(out.contributions.len(), 1);
         assert_eq!(out.contributions.get(&0).unwrap(), &input_contribution);
     }
+
+    /// Runs an hbbft epoch of four validators contributing ten transactions each, and calls
+    /// `observe` with every message sent and its number of recipients.
+    fn run_epoch<F: FnMut(&Message, usize)>(mut observe: F) {
+        let mut rng = rand_065::thread_rng();
+        let ids = (0..4).map(|i| NodeId(H512::from_low_u64_be(i)));
+        let net_infos = NetworkInfo::generate_map(ids, &mut rng)
+            .expect("NetworkInfo generation is expected to always succeed");
+        let mut nodes: BTreeMap<NodeId, HoneyBadger<Contribution, NodeId>> = net_infos
+            .into_iter()
+            .map(|(id, net_info)| (id, HoneyBadger::builder(Arc::new(net_info)).build()))
+            .collect();
+        let mut queue = VecDeque::new();
+        for (id, node) in &mut nodes {
+            let keypair = Random.generate();
+            let txns: Vec<_> = (0..10u64)
+                .map(|nonce| create_transaction(&keypair, &U256::from(nonce)))
+                .collect();
+            let step = node
+                .propose(&Contribution::new(&txns, 0), &mut rng)
+                .expect("proposing must succeed");
+            queue.extend(step.messages.into_iter().map(|m| (*id, m)));
+        }
+
+        while let Some((sender, targeted)) = queue.pop_front() {
+            let recipients: Vec<NodeId> = match &targeted.target {
+                Target::Nodes(set) => set.iter().cloned().collect(),
+                Target::AllExcept(set) => nodes
+                    .keys()
+                    .filter(|id| **id != sender && !set.contains(id))
+                    .cloned()
+                    .collect(),
+            };
+            let message = Message::HoneyBadger(0, targeted.message);
+            observe(&message, recipients.len());
+            let hb_message = match message {
+                Message::HoneyBadger(_, hb_message) => hb_message,
+                _ => unreachable!(),
+            };
+            for recipient in recipients {
+                let step = nodes
+                    .get_mut(&recipient)
+                    .expect("recipients are validators")
+                    .handle_message(&sender, hb_message.clone(), &mut rng)
+                    .expect("handling a validator's message must succeed");
+                queue.extend(step.messages.into_iter().map(|m| (recipient, m)));
+            }
+        }
+    }
+
+    /// Returns the number of bytes sent in an epoch if every message is encoded with the given
+    /// wire version.
+    fn epoch_traffic(version: WireVersion) -> usize {
+        let envelope = Envelope {
+            chain_id: 1,
+            posdao_epoch: 0,
+        };
+        let mut bytes = 0;
+        run_epoch(|message, recipients| {
+            let packet_len: usize = wire::encode_messages(&[message], version, envelope, None)
+                .expect("encoding must succeed")
+                .iter()
+                .map(Vec::len)
+                .sum();
+            bytes += packet_len * recipients;
+        });
+        bytes
+    }
+
+    #[test]
+    fn honey_badger_message_kinds_are_recognized() {
+        let mut kinds = BTreeSet::new();
+        run_epoch(|message, _| {
+            kinds.insert(message.kind());
+        });
+        let expected = vec![
+            MessageKind::Broadcast,
+            MessageKind::Agreement,
+            MessageKind::Decryption,
+        ];
+        assert_eq!(kinds.into_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(Message::Offline.kind(), MessageKind::Offline);
+        assert_eq!(Message::Observe.kind(), MessageKind::Observe);
+    }
+
+    #[test]
+    fn binary_wire_format_reduces_epoch_traffic() {
+        let json = epoch_traffic(WireVersion::V1);
+        let binary = epoch_traffic(WireVersion::V2);
+        println!(
+            "Traffic of an hbbft epoch: {} bytes as JSON, {} bytes as compressed bincode.",
+            json, binary
+        );
+        // Threshold crypto payloads are byte arrays, which JSON encodes as lists of numbers.
+        assert!(binary * 2 < json);
+    }
 }
@@ -1,21 +1,33 @@
 use client::traits::EngineClient;
 use engines::signer::EngineSigner;
+use ethjson::spec::HbbftEncryptionSchedule;
 use hbbft::{
     crypto::{PublicKey, Signature},
-    honey_badger::{self, HoneyBadgerBuilder},
+    honey_badger::{self, EncryptionSchedule, HoneyBadgerBuilder},
     Epoched, NetworkInfo,
 };
 use parking_lot::RwLock;
-use std::{collections::BTreeMap, sync::Arc};
-use types::{header::Header, ids::BlockId};
+use std::{
+    cmp::{max, min},
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use types::{header::Header, ids::BlockId, transaction::UnverifiedTransaction, BlockNumber};
 
 use super::{
+    api::ValidatorLiveness,
     contracts::{
-        keygen_history::{initialize_synckeygen, synckeygen_to_network_info},
+        keygen_history::{
+            initialize_synckeygen, synckeygen_to_network_info, synckeygen_to_observer_network_info,
+            KeygenReadStats,
+        },
         staking::{get_posdao_epoch, get_posdao_epoch_start},
         validator_set::ValidatorType,
     },
-    contribution::Contribution,
+    contribution::{select_transactions, Contribution, ContributionLimits},
+    key_cache::PublicKeyCache,
+    recent_transactions::RecentTransactions,
+    rng::engine_rng,
     NodeId,
 };
 
@@ -25,28 +37,160 @@ pub(crate) type Batch = honey_badger::Batch<Contribution, NodeId>;
 pub(crate) type HoneyBadgerStep = honey_badger::Step<Contribution, NodeId>;
 pub(crate) type HoneyBadgerResult = honey_badger::Result<HoneyBadgerStep>;
 
+/// The default number of consensus messages cached for future hbbft epochs.
+pub const DEFAULT_MAX_FUTURE_MESSAGES: usize = 100_000;
+
+/// How many hbbft epochs ahead of the current one a message may be to count as a sign of its
+/// sender's liveness. Equal to the number of future epochs HoneyBadger handles messages for.
+pub const MAX_FUTURE_EPOCHS: u64 = 3;
+
+/// The default number of blocks of a new POSDAO epoch after which the previous validators'
+/// consensus state is dropped.
+pub const DEFAULT_EPOCH_HANDOFF_BLOCKS: u64 = 1;
+
+/// Removes messages of the furthest epochs until at most `max` messages are cached. Returns the
+/// number of removed messages.
+///
+/// Messages for the nearest epochs are needed first, while a peer flooding the cache is most
+/// likely to send messages for epochs far ahead.
+fn evict_furthest<T>(cache: &mut BTreeMap<u64, Vec<T>>, max: usize) -> usize {
+    let mut count: usize = cache.values().map(Vec::len).sum();
+    let mut evicted = 0;
+    while count > max {
+        let furthest = match cache.keys().next_back() {
+            Some(epoch) => *epoch,
+            None => break,
+        };
+        let messages = cache
+            .get_mut(&furthest)
+            .expect("the key was just read; qed");
+        let remove = min(count - max, messages.len());
+        messages.truncate(messages.len() - remove);
+        if messages.is_empty() {
+            cache.remove(&furthest);
+        }
+        count -= remove;
+        evicted += remove;
+    }
+    evicted
+}
+
+/// Returns the encryption schedule configured by the `encryptionSchedule` engine parameter.
+pub fn encryption_schedule(schedule: Option<HbbftEncryptionSchedule>) -> EncryptionSchedule {
+    match schedule {
+        None | Some(HbbftEncryptionSchedule::Always) => EncryptionSchedule::Always,
+        Some(HbbftEncryptionSchedule::Never) => EncryptionSchedule::Never,
+        Some(HbbftEncryptionSchedule::EveryNthEpoch(n)) => EncryptionSchedule::EveryNthEpoch(n),
+        Some(HbbftEncryptionSchedule::TickTock(on, off)) => EncryptionSchedule::TickTock(on, off),
+    }
+}
+
+/// The consensus state of the previous POSDAO epoch's validators, retained after a validator set
+/// change to handle their in-flight messages for the last blocks they sealed.
+struct PreviousEpoch {
+    posdao_epoch: u64,
+    /// The last block sealed by the previous validators: the block the new epoch started in.
+    last_block: u64,
+    network_info: NetworkInfo<NodeId>,
+    honey_badger: HoneyBadger,
+    /// The blocks and senders whose late signature shares were already answered.
+    answered_seals: BTreeSet<(u64, NodeId)>,
+}
+
+/// The consensus state of a node observing the current validators' consensus without taking part.
+struct Observer {
+    network_info: NetworkInfo<NodeId>,
+    honey_badger: HoneyBadger,
+}
+
 pub(crate) struct HbbftState {
     network_info: Option<NetworkInfo<NodeId>>,
     honey_badger: Option<HoneyBadger>,
     public_master_key: Option<PublicKey>,
     current_posdao_epoch: u64,
+    /// The block the current POSDAO epoch started in.
+    posdao_epoch_start: u64,
     future_messages_cache: BTreeMap<u64, Vec<(NodeId, HbMessage)>>,
+    /// Incremented whenever the future messages cache changes, so it is only persisted if needed.
+    future_messages_generation: u64,
+    /// The maximum number of messages in the future messages cache.
+    max_future_messages: usize,
+    /// The number of messages dropped because their hbbft epoch was over.
+    obsolete_messages: u64,
+    /// The number of messages evicted from the full future messages cache.
+    evicted_messages: u64,
+    keygen_read_stats: Arc<KeygenReadStats>,
+    /// The latest hbbft epoch we received a message for, by sender, in the current POSDAO epoch.
+    message_epochs: BTreeMap<NodeId, u64>,
+    /// The first hbbft epoch of the current POSDAO epoch we took part in.
+    first_hbbft_epoch: Option<u64>,
+    /// The public key sets of POSDAO epochs, for verifying seals.
+    public_keys: PublicKeyCache,
+    /// The hbbft epochs whose contributions are threshold encrypted until they are agreed on.
+    encryption_schedule: EncryptionSchedule,
+    /// The previous POSDAO epoch's state, until the handoff to the current validators is over.
+    previous: Option<PreviousEpoch>,
+    /// The number of blocks of a new POSDAO epoch after which the previous state is dropped.
+    /// The handoff is disabled if 0.
+    handoff_blocks: u64,
+    /// The number of messages of the previous POSDAO epoch handled during handoffs.
+    handoff_messages: u64,
+    /// The transactions included in the most recent blocks.
+    recent_transactions: RecentTransactions,
+    /// Whether to observe the current validators' consensus while we are not one of them.
+    observing: bool,
+    /// The observed consensus state, if we are observing.
+    observer: Option<Observer>,
+    /// Entries added to our next contribution, to test how the other validators handle
+    /// malformed ones.
+    #[cfg(test)]
+    injected_entries: Vec<Vec<u8>>,
 }
 
 impl HbbftState {
-    pub fn new() -> Self {
+    pub fn new(
+        keygen_read_stats: Arc<KeygenReadStats>,
+        encryption_schedule: EncryptionSchedule,
+        max_future_messages: usize,
+        handoff_blocks: u64,
+    ) -> Self {
         HbbftState {
             network_info: None,
             honey_badger: None,
             public_master_key: None,
             current_posdao_epoch: 0,
+            posdao_epoch_start: 0,
             future_messages_cache: BTreeMap::new(),
+            future_messages_generation: 0,
+            max_future_messages,
+            obsolete_messages: 0,
+            evicted_messages: 0,
+            keygen_read_stats,
+            message_epochs: BTreeMap::new(),
+            first_hbbft_epoch: None,
+            public_keys: PublicKeyCache::default(),
+            encryption_schedule,
+            previous: None,
+            handoff_blocks,
+            handoff_messages: 0,
+            recent_transactions: RecentTransactions::default(),
+            observing: false,
+            observer: None,
+            #[cfg(test)]
+            injected_entries: Vec::new(),
         }
     }
 
+    /// Adds the given entries to our next contribution, unchecked.
+    #[cfg(test)]
+    pub fn inject_contribution_entries(&mut self, entries: Vec<Vec<u8>>) {
+        self.injected_entries.extend(entries);
+    }
+
     fn new_honey_badger(&self, network_info: NetworkInfo<NodeId>) -> Option<HoneyBadger> {
         let mut builder: HoneyBadgerBuilder<Contribution, _> =
             HoneyBadger::builder(Arc::new(network_info));
+        builder.encryption_schedule(self.encryption_schedule);
         return Some(builder.build());
     }
 
@@ -70,19 +214,50 @@ impl HbbftState {
             signer,
             BlockId::Number(posdao_epoch_start.low_u64()),
             ValidatorType::Current,
+            &self.keygen_read_stats,
         )
         .ok()?;
         assert!(synckeygen.is_ready());
 
         let (pks, sks) = synckeygen.generate().ok()?;
         self.public_master_key = Some(pks.public_key());
+        match client.block_header(BlockId::Number(posdao_epoch_start.low_u64())) {
+            Some(start) => {
+                self.public_keys
+                    .insert(&*client, target_posdao_epoch, start.hash(), &pks)
+            }
+            None => {
+                warn!(target: "engine", "The start block of POSDAO epoch {} is missing, not caching its public key set.", target_posdao_epoch)
+            }
+        }
+        // Keep the previous validators' state until the new validators finalized their first block,
+        // so messages still in flight for the last blocks of the previous epoch are not lost.
+        if target_posdao_epoch > self.current_posdao_epoch && self.handoff_blocks > 0 {
+            self.previous = self.take_previous_epoch(posdao_epoch_start.low_u64());
+        }
         // Clear network info and honey badger instance, since we may not be in this POSDAO epoch any more.
         self.network_info = None;
         self.honey_badger = None;
+        self.observer = None;
+        self.message_epochs.clear();
+        self.first_hbbft_epoch = None;
         // Set the current POSDAO epoch #
         self.current_posdao_epoch = target_posdao_epoch;
+        self.posdao_epoch_start = posdao_epoch_start.low_u64();
         trace!(target: "engine", "Switched hbbft state to epoch {}.", self.current_posdao_epoch);
         if sks.is_none() {
+            if self.observing {
+                let network_info = synckeygen_to_observer_network_info(&synckeygen, pks);
+                if !network_info.is_validator() {
+                    let honey_badger = self.new_honey_badger(network_info.clone())?;
+                    self.observer = Some(Observer {
+                        network_info,
+                        honey_badger,
+                    });
+                    trace!(target: "engine", "We are not part of the HoneyBadger validator set - observing its consensus.");
+                    return Some(());
+                }
+            }
             trace!(target: "engine", "We are not part of the HoneyBadger validator set - running as regular node.");
             return Some(());
         }
@@ -95,6 +270,31 @@ impl HbbftState {
         Some(())
     }
 
+    /// Moves the current validators' state into a `PreviousEpoch`, if we are one of them.
+    fn take_previous_epoch(&mut self, last_block: u64) -> Option<PreviousEpoch> {
+        let network_info = self.network_info.take()?;
+        let honey_badger = self.honey_badger.take()?;
+        trace!(target: "consensus", "Retaining the consensus state of POSDAO epoch {} for the handoff to the new validators.", self.current_posdao_epoch);
+        Some(PreviousEpoch {
+            posdao_epoch: self.current_posdao_epoch,
+            last_block,
+            network_info,
+            honey_badger,
+            answered_seals: BTreeSet::new(),
+        })
+    }
+
+    /// Drops the previous validators' state once the handoff is over.
+    fn drop_previous_epoch(&mut self, latest_block: u64) {
+        let is_over = self.previous.as_ref().map_or(false, |previous| {
+            latest_block >= previous.last_block + self.handoff_blocks
+        });
+        if is_over {
+            trace!(target: "consensus", "Dropping the consensus state of the previous POSDAO epoch at block {}.", latest_block);
+            self.previous = None;
+        }
+    }
+
     // Call periodically to assure cached messages will eventually be delivered.
     pub fn replay_cached_messages(
         &mut self,
@@ -148,6 +348,7 @@ impl HbbftState {
         self.future_messages_cache = self
             .future_messages_cache
             .split_off(&(honey_badger.epoch() + 1));
+        self.future_messages_generation += 1;
 
         Some((all_steps, network_info))
     }
@@ -168,15 +369,21 @@ impl HbbftState {
             BlockId::Number(latest_block_number),
             false,
         );
+        self.drop_previous_epoch(latest_block_number);
+
+        let next_block = latest_block_number + 1;
+        if let Some(observer) = self.observer.as_mut() {
+            observer.honey_badger.skip_to_epoch(next_block);
+        }
 
         // If honey_badger is None we are not a validator, nothing to do.
         let honey_badger = self.honey_badger.as_mut()?;
 
-        let next_block = latest_block_number + 1;
         if next_block != honey_badger.epoch() {
             trace!(target: "consensus", "Skipping honey_badger forward to epoch(block) {}, was at epoch(block) {}.", next_block, honey_badger.epoch());
         }
         honey_badger.skip_to_epoch(next_block);
+        self.first_hbbft_epoch.get_or_insert(next_block);
 
         Some(())
     }
@@ -188,9 +395,20 @@ impl HbbftState {
         sender_id: NodeId,
         message: HbMessage,
     ) -> Option<(HoneyBadgerStep, NetworkInfo<NodeId>)> {
-        self.skip_to_current_epoch(client, signer)?;
+        let is_validator = self.skip_to_current_epoch(client, signer);
+
+        // Messages for the blocks sealed by the previous validators go to their retained state.
+        let last_block = self.previous.as_ref().map(|previous| previous.last_block);
+        if last_block.map_or(false, |last_block| message.epoch() <= last_block) {
+            return self.process_previous_epoch_message(sender_id, message);
+        }
+        if is_validator.is_none() {
+            return self.process_observed_message(sender_id, message);
+        }
 
         // If honey_badger is None we are not a validator, nothing to do.
+        let current_epoch = self.honey_badger.as_ref()?.epoch();
+        self.record_message_epoch(sender_id, message.epoch(), current_epoch);
         let honey_badger = self.honey_badger.as_mut()?;
 
         // Note that if the message is for a future epoch we do not know if the current honey_badger
@@ -202,6 +420,15 @@ impl HbbftState {
                 .entry(message.epoch())
                 .or_default()
                 .push((sender_id, message));
+            self.future_messages_generation += 1;
+            self.evict_future_messages();
+            return None;
+        }
+
+        // HoneyBadger would reject messages for epochs that are over, don't bother it with them.
+        if message.epoch() < honey_badger.epoch() {
+            trace!(target: "consensus", "Dropping message for hbbft epoch {} from {}, the current hbbft epoch is {}.", message.epoch(), sender_id, honey_badger.epoch());
+            self.obsolete_messages += 1;
             return None;
         }
 
@@ -216,25 +443,192 @@ impl HbbftState {
         }
     }
 
+    /// Records that the sender sent a message for the given hbbft epoch, unless it is more than
+    /// `MAX_FUTURE_EPOCHS` ahead of the current epoch: otherwise a peer could fake its liveness,
+    /// or hide that it stalled, by sending a message for a made-up epoch.
+    fn record_message_epoch(&mut self, sender_id: NodeId, epoch: u64, current_epoch: u64) {
+        if epoch > current_epoch.saturating_add(MAX_FUTURE_EPOCHS) {
+            trace!(target: "consensus", "Not counting the message for hbbft epoch {} from {} towards its liveness, the current hbbft epoch is {}.", epoch, sender_id, current_epoch);
+            return;
+        }
+        let last_epoch = self.message_epochs.entry(sender_id).or_insert(epoch);
+        *last_epoch = max(*last_epoch, epoch);
+    }
+
+    /// Handles a message for one of the last blocks sealed by the previous POSDAO epoch's
+    /// validators. These blocks are already imported, so the step's output is discarded, but its
+    /// messages help validators that are still agreeing on them.
+    fn process_previous_epoch_message(
+        &mut self,
+        sender_id: NodeId,
+        message: HbMessage,
+    ) -> Option<(HoneyBadgerStep, NetworkInfo<NodeId>)> {
+        let previous = self.previous.as_mut()?;
+        if message.epoch() < previous.honey_badger.epoch() {
+            trace!(target: "consensus", "Dropping message for hbbft epoch {} of the previous POSDAO epoch from {}.", message.epoch(), sender_id);
+            self.obsolete_messages += 1;
+            return None;
+        }
+        match previous.honey_badger.handle_message(&sender_id, message) {
+            Ok(mut step) => {
+                self.handoff_messages += 1;
+                step.output.clear();
+                Some((step, previous.network_info.clone()))
+            }
+            Err(err) => {
+                debug!(target: "consensus", "Error on handling HoneyBadger message of the previous POSDAO epoch: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Handles a message of the current validators' consensus, if we observe it. The step's faults
+    /// are reported like a validator's, but its output is discarded: observers import the blocks
+    /// the validators create.
+    fn process_observed_message(
+        &mut self,
+        sender_id: NodeId,
+        message: HbMessage,
+    ) -> Option<(HoneyBadgerStep, NetworkInfo<NodeId>)> {
+        let observer = self.observer.as_mut()?;
+        if message.epoch() < observer.honey_badger.epoch() {
+            trace!(target: "consensus", "Dropping observed message for hbbft epoch {} from {}, the current hbbft epoch is {}.", message.epoch(), sender_id, observer.honey_badger.epoch());
+            self.obsolete_messages += 1;
+            return None;
+        }
+        match observer.honey_badger.handle_message(&sender_id, message) {
+            Ok(mut step) => {
+                for batch in step.output.drain(..) {
+                    debug!(target: "consensus", "Observed the validators agree on {} contributions in hbbft epoch {}.", batch.contributions.len(), batch.epoch);
+                }
+                Some((step, observer.network_info.clone()))
+            }
+            Err(err) => {
+                debug!(target: "consensus", "Error on handling an observed HoneyBadger message: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Observes the current validators' consensus while we are not one of them. Takes effect
+    /// with the next forced update of the hbbft state.
+    pub fn set_observing(&mut self, observing: bool) {
+        self.observing = observing;
+        if !observing {
+            self.observer = None;
+        }
+    }
+
+    /// Whether we observe the current validators' consensus.
+    pub fn is_observer(&self) -> bool {
+        self.observer.is_some()
+    }
+
+    /// The validators whose consensus we observe.
+    pub fn observed_validators(&self) -> Option<Vec<NodeId>> {
+        let observer = self.observer.as_ref()?;
+        Some(observer.network_info.all_ids().cloned().collect())
+    }
+
+    /// The current hbbft epoch, the number of contributions received in it and whether enough
+    /// contributions were received for all validators to contribute, if we are a validator or
+    /// an observer.
+    pub fn consensus_progress(&self) -> Option<(u64, usize, bool)> {
+        let (network_info, honey_badger) = match (&self.network_info, &self.honey_badger) {
+            (Some(network_info), Some(honey_badger)) => (network_info, honey_badger),
+            _ => {
+                let observer = self.observer.as_ref()?;
+                (&observer.network_info, &observer.honey_badger)
+            }
+        };
+        let received = honey_badger.received_proposals();
+        Some((
+            honey_badger.epoch(),
+            received,
+            received > network_info.num_faulty(),
+        ))
+    }
+
+    /// Returns the previous validators' network info if a late signature share of the sender for
+    /// the given block should be answered with ours: the block is one of the last blocks they
+    /// sealed, and the sender was not answered yet.
+    pub fn handoff_network_info(
+        &mut self,
+        block_nr: u64,
+        sender_id: NodeId,
+    ) -> Option<NetworkInfo<NodeId>> {
+        let handoff_blocks = self.handoff_blocks;
+        let previous = self.previous.as_mut()?;
+        if block_nr > previous.last_block
+            || block_nr + handoff_blocks <= previous.last_block
+            || !previous.network_info.all_ids().any(|id| *id == sender_id)
+            || !previous.answered_seals.insert((block_nr, sender_id))
+        {
+            return None;
+        }
+        self.handoff_messages += 1;
+        Some(previous.network_info.clone())
+    }
+
+    /// The POSDAO epoch whose validators' state is retained for the handoff, if any.
+    pub fn handoff_posdao_epoch(&self) -> Option<u64> {
+        self.previous.as_ref().map(|previous| previous.posdao_epoch)
+    }
+
+    /// Returns whether the given node is a validator of the given POSDAO epoch, or `None` if we
+    /// don't know that epoch's validators: it is neither the current epoch, in which we are a
+    /// validator or an observer, nor the previous epoch during a handoff.
+    pub fn is_validator_of(&self, posdao_epoch: u64, node_id: &NodeId) -> Option<bool> {
+        let network_info = if posdao_epoch == self.current_posdao_epoch {
+            match (&self.network_info, &self.observer) {
+                (Some(network_info), _) => network_info,
+                (None, Some(observer)) => &observer.network_info,
+                (None, None) => return None,
+            }
+        } else {
+            &self
+                .previous
+                .as_ref()
+                .filter(|previous| previous.posdao_epoch == posdao_epoch)?
+                .network_info
+        };
+        Some(network_info.all_ids().any(|id| id == node_id))
+    }
+
+    /// The number of messages of the previous POSDAO epoch handled during handoffs.
+    pub fn handoff_messages(&self) -> u64 {
+        self.handoff_messages
+    }
+
     pub fn contribute_if_contribution_threshold_reached(
         &mut self,
         client: Arc<dyn EngineClient>,
         signer: &Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
+        timestamp: u64,
+        encrypted: Vec<Vec<u8>>,
+        limits: ContributionLimits,
     ) -> Option<(HoneyBadgerStep, NetworkInfo<NodeId>)> {
         // If honey_badger is None we are not a validator, nothing to do.
         let honey_badger = self.honey_badger.as_mut()?;
         let network_info = self.network_info.as_ref()?;
 
         if honey_badger.received_proposals() > network_info.num_faulty() {
-            return self.try_send_contribution(client, signer);
+            return self.try_send_contribution(client, signer, timestamp, encrypted, limits);
         }
         None
     }
 
+    /// Proposes a contribution with the queued transactions and the given timestamp, in seconds.
+    ///
+    /// The `encrypted` entries, proposing threshold encrypted transactions, are added to the
+    /// contribution and count towards the given `limits`.
     pub fn try_send_contribution(
         &mut self,
         client: Arc<dyn EngineClient>,
         signer: &Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
+        timestamp: u64,
+        encrypted: Vec<Vec<u8>>,
+        limits: ContributionLimits,
     ) -> Option<(HoneyBadgerStep, NetworkInfo<NodeId>)> {
         // Make sure we are in the most current epoch.
         self.skip_to_current_epoch(client.clone(), signer)?;
@@ -263,16 +657,28 @@ impl HbbftState {
         trace!(target: "consensus", "Writing contribution for hbbft epoch(block) {}.", honey_badger.epoch());
 
         // Now we can select the transactions to include in our contribution.
+        // The snapshot is read atomically, so replaced or dropped transactions are never mixed in.
         // TODO: Select a random *subset* of transactions to propose
-        let input_contribution = Contribution::new(
-            &client
-                .queued_transactions()
-                .iter()
-                .map(|txn| txn.signed().clone())
-                .collect(),
-        );
+        // A block cannot hold more gas than its limit, so neither should a single contribution.
+        let snapshot = client.queued_transactions_snapshot();
+        let max_gas = client.block_header(BlockId::Latest)?.gas_limit();
+        let queued: Vec<_> = snapshot
+            .transactions
+            .iter()
+            .map(|txn| txn.signed().clone())
+            .collect();
+        let limits = limits.without(&encrypted);
+        let selected = select_transactions(&queued, max_gas, &limits);
+        debug!(target: "consensus", "Contributing {} of {} transactions of queue state {} and {} encrypted transactions to hbbft epoch {}.",
+               selected.len(), queued.len(), snapshot.sequence, encrypted.len(), honey_badger.epoch());
+        let mut input_contribution = Contribution::new(&selected, timestamp);
+        input_contribution.transactions.extend(encrypted);
+        #[cfg(test)]
+        input_contribution
+            .transactions
+            .extend(self.injected_entries.drain(..));
 
-        let mut rng = rand_065::thread_rng();
+        let mut rng = engine_rng();
         let step = honey_badger.propose(&input_contribution, &mut rng);
         match step {
             Ok(step) => Some((step, network_info)),
@@ -284,6 +690,30 @@ impl HbbftState {
         }
     }
 
+    /// Removes the transactions that were already included in one of the recent blocks before the
+    /// given one.
+    ///
+    /// Nothing is removed if the recent blocks cannot be read in full.
+    pub fn drop_included_transactions(
+        &mut self,
+        client: &dyn EngineClient,
+        block_num: BlockNumber,
+        txns: Vec<UnverifiedTransaction>,
+    ) -> Vec<UnverifiedTransaction> {
+        match client.as_full_client() {
+            Some(full_client) => self.recent_transactions.update(full_client, block_num - 1),
+            None => self.recent_transactions.invalidate(),
+        }
+        let recent = &self.recent_transactions;
+        if !recent.is_valid() {
+            debug!(target: "consensus", "The transactions of the blocks before block {} are unknown, not dropping included transactions.", block_num);
+            return txns;
+        }
+        txns.into_iter()
+            .filter(|txn| !recent.contains(&txn.hash()))
+            .collect()
+    }
+
     pub fn verify_seal(
         &mut self,
         client: Arc<dyn EngineClient>,
@@ -304,8 +734,6 @@ impl HbbftState {
             }
         };
         if self.current_posdao_epoch != target_posdao_epoch {
-            trace!(target: "consensus", "verify_seal - hbbft state epoch does not match epoch at the header's parent, attempting to reconstruct the appropriate public key share from scratch.");
-            // If the requested block nr is already imported we try to generate the public master key from scratch.
             let posdao_epoch_start = match get_posdao_epoch_start(
                 &*client,
                 BlockId::Number(parent_block_nr),
@@ -316,35 +744,17 @@ impl HbbftState {
                     return false;
                 }
             };
-
-            let synckeygen = match initialize_synckeygen(
+            return match self.past_public_key(
                 &*client,
-                &Arc::new(RwLock::new(Option::None)),
-                BlockId::Number(posdao_epoch_start.low_u64()),
-                ValidatorType::Current,
+                target_posdao_epoch,
+                posdao_epoch_start.low_u64(),
             ) {
-                Ok(synckeygen) => synckeygen,
+                Ok(key) => key.verify(signature, header.bare_hash()),
                 Err(e) => {
-                    error!(target: "consensus", "Synckeygen failed with error: {:?}", e);
-                    return false;
+                    error!(target: "consensus", "Failed to verify seal - {}", e);
+                    false
                 }
             };
-
-            if !synckeygen.is_ready() {
-                error!(target: "consensus", "Synckeygen not ready when it sohuld be!");
-                return false;
-            }
-
-            let pks = match synckeygen.generate() {
-                Ok((pks, _)) => pks,
-                Err(e) => {
-                    error!(target: "consensus", "Generating of public key share failed with error: {:?}", e);
-                    return false;
-                }
-            };
-
-            trace!(target: "consensus", "verify_seal - successfully reconstructed public key share of past posdao epoch.");
-            return pks.public_key().verify(signature, header.bare_hash());
         }
 
         match self.public_master_key {
@@ -356,6 +766,171 @@ impl HbbftState {
         }
     }
 
+    /// Returns the public master key of the given POSDAO epoch, which started at the given block.
+    pub fn epoch_public_key(
+        &mut self,
+        client: &dyn EngineClient,
+        posdao_epoch: u64,
+        start_block: u64,
+    ) -> Result<PublicKey, String> {
+        if posdao_epoch == self.current_posdao_epoch && start_block == self.posdao_epoch_start {
+            return self
+                .public_master_key
+                .ok_or_else(|| "The key of the current POSDAO epoch is not known yet.".to_owned());
+        }
+        self.past_public_key(client, posdao_epoch, start_block)
+    }
+
+    /// Returns the public master key of an epoch other than the current one, from the cache or
+    /// by replaying the epoch's key generation.
+    fn past_public_key(
+        &mut self,
+        client: &dyn EngineClient,
+        posdao_epoch: u64,
+        start_block: u64,
+    ) -> Result<PublicKey, String> {
+        let start_hash = client
+            .block_header(BlockId::Number(start_block))
+            .map(|start| start.hash())
+            .ok_or_else(|| {
+                format!(
+                    "the start block of POSDAO epoch {} is missing.",
+                    posdao_epoch
+                )
+            })?;
+        if let Some(pks) = self.public_keys.get(client, posdao_epoch, start_hash) {
+            trace!(target: "consensus", "Using the cached public key set of POSDAO epoch {}.", posdao_epoch);
+            return Ok(pks.public_key());
+        }
+        trace!(target: "consensus", "Reconstructing the public key set of POSDAO epoch {} from scratch.", posdao_epoch);
+        let synckeygen = initialize_synckeygen(
+            client,
+            &Arc::new(RwLock::new(Option::None)),
+            BlockId::Number(start_block),
+            ValidatorType::Current,
+            &self.keygen_read_stats,
+        )
+        .map_err(|e| format!("Synckeygen failed with error: {:?}", e))?;
+        if !synckeygen.is_ready() {
+            return Err("Synckeygen not ready when it should be!".into());
+        }
+        let (pks, _) = synckeygen
+            .generate()
+            .map_err(|e| format!("Generating of public key share failed with error: {:?}", e))?;
+        trace!(target: "consensus", "Successfully reconstructed the public key set of POSDAO epoch {}.", posdao_epoch);
+        self.public_keys
+            .insert(client, posdao_epoch, start_hash, &pks);
+        Ok(pks.public_key())
+    }
+
+    /// The POSDAO epoch the current validator set belongs to.
+    pub fn current_posdao_epoch(&self) -> u64 {
+        self.current_posdao_epoch
+    }
+
+    /// The block the current POSDAO epoch started in.
+    pub fn posdao_epoch_start(&self) -> u64 {
+        self.posdao_epoch_start
+    }
+
+    /// The public master key of the current POSDAO epoch's validators, once it is known.
+    pub fn public_master_key(&self) -> Option<PublicKey> {
+        self.public_master_key
+    }
+
+    /// The current hbbft epoch, if we are one of the validators.
+    pub fn honey_badger_epoch(&self) -> Option<u64> {
+        self.honey_badger.as_ref().map(|hb| hb.epoch())
+    }
+
+    /// The number of contributions received in the current hbbft epoch, if we are a validator.
+    pub fn received_proposals(&self) -> Option<usize> {
+        self.honey_badger.as_ref().map(|hb| hb.received_proposals())
+    }
+
+    /// The network info of the current POSDAO epoch, if we are one of its validators.
+    pub fn network_info(&self) -> Option<&NetworkInfo<NodeId>> {
+        self.network_info.as_ref()
+    }
+
+    /// Messages received for future hbbft epochs, by epoch.
+    pub fn future_messages(&self) -> &BTreeMap<u64, Vec<(NodeId, HbMessage)>> {
+        &self.future_messages_cache
+    }
+
+    /// Changes whenever the messages cached for future hbbft epochs change.
+    pub fn future_messages_generation(&self) -> u64 {
+        self.future_messages_generation
+    }
+
+    /// Discards all messages cached for future hbbft epochs.
+    pub fn clear_future_messages(&mut self) {
+        self.future_messages_cache.clear();
+        self.future_messages_generation += 1;
+    }
+
+    /// Adds messages for future hbbft epochs to the cache, e.g. after restoring them on startup.
+    pub fn cache_future_messages(&mut self, messages: BTreeMap<u64, Vec<(NodeId, HbMessage)>>) {
+        for (epoch, mut messages) in messages {
+            self.future_messages_cache
+                .entry(epoch)
+                .or_default()
+                .append(&mut messages);
+        }
+        self.future_messages_generation += 1;
+        self.evict_future_messages();
+    }
+
+    /// Keeps the future messages cache within its configured size.
+    fn evict_future_messages(&mut self) {
+        let evicted = evict_furthest(&mut self.future_messages_cache, self.max_future_messages);
+        if evicted > 0 {
+            debug!(target: "consensus", "The future messages cache is full, evicted {} messages of the furthest hbbft epochs.", evicted);
+            self.evicted_messages += evicted as u64;
+        }
+    }
+
+    /// The number of messages dropped because their hbbft epoch was over.
+    pub fn obsolete_messages(&self) -> u64 {
+        self.obsolete_messages
+    }
+
+    /// The number of messages evicted from the full future messages cache.
+    pub fn evicted_messages(&self) -> u64 {
+        self.evicted_messages
+    }
+
+    /// Reports for how many hbbft epochs each of the other validators has been silent.
+    ///
+    /// Only messages received in the current POSDAO epoch count. Returns an empty list if we
+    /// are not a validator.
+    pub fn validator_liveness(&self) -> Vec<ValidatorLiveness> {
+        let (network_info, honey_badger) = match (&self.network_info, &self.honey_badger) {
+            (Some(network_info), Some(honey_badger)) => (network_info, honey_badger),
+            _ => return Vec::new(),
+        };
+        let current_epoch = honey_badger.epoch();
+        let first_epoch = self.first_hbbft_epoch.unwrap_or(current_epoch);
+        network_info
+            .all_ids()
+            .filter(|id| *id != network_info.our_id())
+            .map(|id| {
+                let last_message_epoch = self.message_epochs.get(id).cloned();
+                ValidatorLiveness {
+                    node_id: *id,
+                    last_message_epoch,
+                    silent_epochs: current_epoch
+                        .saturating_sub(last_message_epoch.unwrap_or(first_epoch)),
+                }
+            })
+            .collect()
+    }
+
+    /// Our node ID, if we are a validator of the current POSDAO epoch.
+    pub fn our_node_id(&self) -> Option<NodeId> {
+        self.network_info.as_ref().map(|n| *n.our_id())
+    }
+
     pub fn network_info_for(
         &mut self,
         client: Arc<dyn EngineClient>,
@@ -369,6 +944,13 @@ impl HbbftState {
             .low_u64();
 
         if self.current_posdao_epoch != posdao_epoch {
+            if let Some(previous) = self
+                .previous
+                .as_ref()
+                .filter(|previous| previous.posdao_epoch == posdao_epoch)
+            {
+                return Some(previous.network_info.clone());
+            }
             error!(target: "consensus", "Trying to get the network info from a different epoch. Current epoch: {}, Requested epoch: {}",
 				   self.current_posdao_epoch, posdao_epoch);
             return None;
@@ -377,3 +959,100 @@ impl HbbftState {
         self.network_info.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+    use rand_065;
+
+    #[test]
+    fn furthest_epochs_are_evicted_first() {
+        let mut cache: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+        cache.insert(5, vec![1, 2]);
+        cache.insert(6, vec![3]);
+        cache.insert(9, vec![4, 5, 6]);
+        assert_eq!(evict_furthest(&mut cache, 6), 0);
+        assert_eq!(evict_furthest(&mut cache, 4), 2);
+        assert_eq!(cache.get(&9), Some(&vec![4]));
+        assert_eq!(evict_furthest(&mut cache, 2), 2);
+        assert_eq!(cache.keys().collect::<Vec<_>>(), vec![&5]);
+        assert_eq!(evict_furthest(&mut cache, 0), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn only_near_message_epochs_count_towards_liveness() {
+        let ids: Vec<NodeId> = (0..4).map(|i| NodeId(H512::from_low_u64_be(i))).collect();
+        let netinfos = NetworkInfo::generate_map(ids.clone(), &mut rand_065::thread_rng())
+            .expect("NetworkInfo generation is expected to always succeed");
+        let network_info = netinfos[&ids[0]].clone();
+        let mut state = HbbftState::new(
+            Arc::new(KeygenReadStats::default()),
+            EncryptionSchedule::Always,
+            DEFAULT_MAX_FUTURE_MESSAGES,
+            DEFAULT_EPOCH_HANDOFF_BLOCKS,
+        );
+        state.honey_badger = state.new_honey_badger(network_info.clone());
+        state.network_info = Some(network_info);
+        state
+            .honey_badger
+            .as_mut()
+            .expect("The honey badger was just created")
+            .skip_to_epoch(10);
+        state.first_hbbft_epoch = Some(10);
+
+        state.record_message_epoch(ids[1], 10 + MAX_FUTURE_EPOCHS, 10);
+        // A made-up epoch far ahead neither counts as a message nor as progress.
+        state.record_message_epoch(ids[2], u64::max_value(), 10);
+        state.record_message_epoch(ids[1], 10 + MAX_FUTURE_EPOCHS + 1, 10);
+        state.record_message_epoch(ids[3], 8, 10);
+
+        let liveness: BTreeMap<NodeId, (Option<u64>, u64)> = state
+            .validator_liveness()
+            .into_iter()
+            .map(|v| (v.node_id, (v.last_message_epoch, v.silent_epochs)))
+            .collect();
+        assert_eq!(liveness[&ids[1]], (Some(10 + MAX_FUTURE_EPOCHS), 0));
+        assert_eq!(liveness[&ids[2]], (None, 0));
+        assert_eq!(liveness[&ids[3]], (Some(8), 2));
+    }
+
+    #[test]
+    fn previous_epoch_is_handed_off() {
+        let ids = (0..4).map(|i| NodeId(H512::from_low_u64_be(i)));
+        let netinfos = NetworkInfo::generate_map(ids, &mut rand_065::thread_rng())
+            .expect("NetworkInfo generation is expected to always succeed");
+        let (_, network_info) = netinfos.into_iter().next().unwrap();
+        let mut state = HbbftState::new(
+            Arc::new(KeygenReadStats::default()),
+            EncryptionSchedule::Always,
+            DEFAULT_MAX_FUTURE_MESSAGES,
+            2,
+        );
+        state.current_posdao_epoch = 4;
+        state.honey_badger = state.new_honey_badger(network_info.clone());
+        state.network_info = Some(network_info);
+        state.previous = state.take_previous_epoch(100);
+        assert_eq!(state.handoff_posdao_epoch(), Some(4));
+        assert!(state.network_info.is_none());
+
+        let sender = NodeId(H512::from_low_u64_be(3));
+        assert!(state.handoff_network_info(100, sender).is_some());
+        // Every sender is answered once per block.
+        assert!(state.handoff_network_info(100, sender).is_none());
+        assert!(state.handoff_network_info(99, sender).is_some());
+        // Only the last blocks of the previous epoch and its validators are answered.
+        assert!(state.handoff_network_info(98, sender).is_none());
+        assert!(state.handoff_network_info(101, sender).is_none());
+        let stranger = NodeId(H512::from_low_u64_be(9));
+        assert!(state.handoff_network_info(100, stranger).is_none());
+        assert_eq!(state.handoff_messages(), 2);
+
+        // The state is kept until the new validators finalized their first blocks.
+        state.drop_previous_epoch(101);
+        assert_eq!(state.handoff_posdao_epoch(), Some(4));
+        state.drop_previous_epoch(102);
+        assert_eq!(state.handoff_posdao_epoch(), None);
+    }
+}
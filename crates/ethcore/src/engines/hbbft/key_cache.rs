@@ -0,0 +1,162 @@
+//! Public key sets of POSDAO epochs, cached for seal verification.
+//!
+//! Verifying the seal of a block needs the public key set of the POSDAO epoch of its parent.
+//! Reconstructing the key set of a past epoch replays its key generation from the key generation
+//! history contract, which is expensive. Key sets are therefore kept in memory and persisted in
+//! the client database, so nodes without a signer can verify the seals of old blocks, e.g. while
+//! syncing, without repeating the key generation, even across restarts. The least recently used
+//! key sets are evicted from memory first, so each epoch's key set is reconstructed at most once.
+//!
+//! Key sets are identified by the epoch number and the hash of the epoch's start block: after a
+//! reorganization, the key set of an epoch that started on the abandoned fork is not reused.
+
+use client::traits::EngineClient;
+use ethereum_types::H256;
+use hbbft::crypto::PublicKeySet;
+use lru_cache::LruCache;
+
+/// Prefix of the database keys under which key sets are persisted, followed by the epoch number
+/// and the hash of its start block.
+const KEY_SET_KEY_PREFIX: &[u8] = b"hbbft_public_key_set_";
+
+/// The maximum number of key sets kept in memory.
+const MAX_CACHED_KEY_SETS: usize = 16;
+
+fn db_key(posdao_epoch: u64, start_hash: &H256) -> Vec<u8> {
+    let mut key = KEY_SET_KEY_PREFIX.to_vec();
+    key.extend_from_slice(&posdao_epoch.to_be_bytes());
+    key.extend_from_slice(start_hash.as_bytes());
+    key
+}
+
+/// Public key sets by POSDAO epoch and epoch start block hash, backed by the client database.
+pub struct PublicKeyCache {
+    key_sets: LruCache<(u64, H256), PublicKeySet>,
+}
+
+impl Default for PublicKeyCache {
+    fn default() -> Self {
+        PublicKeyCache {
+            key_sets: LruCache::new(MAX_CACHED_KEY_SETS),
+        }
+    }
+}
+
+impl PublicKeyCache {
+    /// Returns the key set of the given POSDAO epoch, which started in the block with the given
+    /// hash, if it was cached before.
+    pub fn get(
+        &mut self,
+        client: &dyn EngineClient,
+        posdao_epoch: u64,
+        start_hash: H256,
+    ) -> Option<PublicKeySet> {
+        if let Some(key_set) = self.key_sets.get_mut(&(posdao_epoch, start_hash)) {
+            return Some(key_set.clone());
+        }
+        let bytes = client.engine_data(&db_key(posdao_epoch, &start_hash))?;
+        match bincode::deserialize::<PublicKeySet>(&bytes) {
+            Ok(key_set) => {
+                self.key_sets
+                    .insert((posdao_epoch, start_hash), key_set.clone());
+                Some(key_set)
+            }
+            Err(e) => {
+                warn!(target: "engine", "Discarding unreadable public key set of POSDAO epoch {}: {}", posdao_epoch, e);
+                client.set_engine_data(&db_key(posdao_epoch, &start_hash), None);
+                None
+            }
+        }
+    }
+
+    /// Caches and persists the key set of the given POSDAO epoch, which started in the block with
+    /// the given hash.
+    pub fn insert(
+        &mut self,
+        client: &dyn EngineClient,
+        posdao_epoch: u64,
+        start_hash: H256,
+        key_set: &PublicKeySet,
+    ) {
+        let id = (posdao_epoch, start_hash);
+        if self.key_sets.get_mut(&id).map(|cached| &*cached) == Some(key_set) {
+            return;
+        }
+        match bincode::serialize(key_set) {
+            Ok(bytes) => client.set_engine_data(&db_key(posdao_epoch, &start_hash), Some(bytes)),
+            Err(e) => {
+                error!(target: "engine", "Could not serialize public key set of POSDAO epoch {}: {}", posdao_epoch, e)
+            }
+        }
+        self.key_sets.insert(id, key_set.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::TestBlockChainClient;
+    use engines::hbbft::rng::engine_rng;
+    use hbbft::crypto::SecretKeySet;
+
+    fn random_key_set() -> PublicKeySet {
+        SecretKeySet::random(1, &mut engine_rng()).public_keys()
+    }
+
+    fn hash(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    #[test]
+    fn key_sets_are_persisted() {
+        let client = TestBlockChainClient::new();
+        let key_set = random_key_set();
+        PublicKeyCache::default().insert(&client, 3, hash(1), &key_set);
+
+        let mut restarted = PublicKeyCache::default();
+        assert_eq!(restarted.get(&client, 3, hash(1)), Some(key_set));
+        assert_eq!(restarted.get(&client, 4, hash(1)), None);
+    }
+
+    #[test]
+    fn key_sets_of_forks_are_not_reused() {
+        let client = TestBlockChainClient::new();
+        let mut cache = PublicKeyCache::default();
+        let key_set = random_key_set();
+        cache.insert(&client, 3, hash(1), &key_set);
+
+        // The same epoch started in a different block after a reorganization.
+        assert_eq!(cache.get(&client, 3, hash(2)), None);
+        assert_eq!(PublicKeyCache::default().get(&client, 3, hash(2)), None);
+        let other = random_key_set();
+        cache.insert(&client, 3, hash(2), &other);
+        assert_eq!(cache.get(&client, 3, hash(1)), Some(key_set));
+        assert_eq!(cache.get(&client, 3, hash(2)), Some(other));
+    }
+
+    #[test]
+    fn unreadable_key_sets_are_discarded() {
+        let client = TestBlockChainClient::new();
+        client.set_engine_data(&db_key(5, &hash(1)), Some(vec![1, 2, 3]));
+        assert_eq!(PublicKeyCache::default().get(&client, 5, hash(1)), None);
+        assert_eq!(client.engine_data(&db_key(5, &hash(1))), None);
+    }
+
+    #[test]
+    fn least_recently_used_key_sets_are_evicted() {
+        let client = TestBlockChainClient::new();
+        let mut cache = PublicKeyCache::default();
+        for epoch in 0..(MAX_CACHED_KEY_SETS as u64) {
+            cache.insert(&client, epoch, hash(epoch), &random_key_set());
+        }
+        // Using the key set of epoch 0 makes epoch 1 the least recently used one.
+        assert!(cache.get(&client, 0, hash(0)).is_some());
+        let last = MAX_CACHED_KEY_SETS as u64;
+        cache.insert(&client, last, hash(last), &random_key_set());
+        assert_eq!(cache.key_sets.len(), MAX_CACHED_KEY_SETS);
+        assert!(cache.key_sets.contains_key(&(0, hash(0))));
+        assert!(!cache.key_sets.contains_key(&(1, hash(1))));
+        // Evicted key sets are still read from the database.
+        assert!(cache.get(&client, 1, hash(1)).is_some());
+    }
+}
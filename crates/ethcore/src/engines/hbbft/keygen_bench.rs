@@ -0,0 +1,132 @@
+//! Key generation data for the `hbbft_keygen` benchmarks.
+//!
+//! Builds the Parts and Acks a network of new validators writes to the key generation history
+//! contract, so `SyncKeyGen` initialization can be measured without a client.
+
+use crypto::publickey::{Generator, KeyPair, Public, Random};
+use engines::signer::{from_keypair, EngineSigner};
+use ethereum_types::Address;
+use hbbft::sync_key_gen::SyncKeyGen;
+use parking_lot::RwLock;
+use std::{collections::BTreeMap, sync::Arc};
+
+use super::{
+    contracts::keygen_history::{
+        decode_acks, decode_part, engine_signer_to_synckeygen, handle_acks, handle_keygen_data,
+        handle_part, PublicWrapper, SerializedKeygenData,
+    },
+    wire::{encode_ack, encode_part, WireVersion},
+};
+
+type Signer = Arc<RwLock<Option<Box<dyn EngineSigner>>>>;
+
+/// The complete key generation data of a network, from the point of view of one validator.
+pub struct KeygenFixture {
+    signer: Signer,
+    vmap: BTreeMap<Address, Public>,
+    keygen_data: Vec<SerializedKeygenData>,
+}
+
+impl KeygenFixture {
+    /// Generates the Parts and Acks of all validators of a network of the given size.
+    pub fn new(num_validators: usize) -> Self {
+        let mut keys: Vec<KeyPair> = (0..num_validators).map(|_| Random.generate()).collect();
+        keys.sort_by_key(KeyPair::address);
+        let vmap: BTreeMap<Address, Public> = keys
+            .iter()
+            .map(|key| (key.address(), *key.public()))
+            .collect();
+        let mut signers: Vec<Signer> = keys
+            .into_iter()
+            .map(|key| Arc::new(RwLock::new(Some(from_keypair(key)))))
+            .collect();
+
+        let mut keygens = Vec::new();
+        let mut parts = Vec::new();
+        for signer in &signers {
+            let (synckeygen, part) = engine_signer_to_synckeygen(signer, pub_keys(&vmap))
+                .expect("the fixture's keys are valid");
+            keygens.push(synckeygen);
+            parts.push(part.expect("every validator creates a Part"));
+        }
+
+        let mut acks = vec![Vec::new(); num_validators];
+        for (address, part) in vmap.keys().zip(&parts) {
+            for (synckeygen, acks) in keygens.iter_mut().zip(&mut acks) {
+                let ack = handle_part(*address, part.clone(), &vmap, synckeygen)
+                    .expect("the fixture's Parts are valid")
+                    .expect("every validator acknowledges the Parts");
+                acks.push(encode_ack(&ack, WireVersion::V1).expect("Acks can be encoded"));
+            }
+        }
+
+        let keygen_data = vmap
+            .keys()
+            .zip(parts)
+            .zip(acks)
+            .map(|((address, part), acks)| {
+                let part = encode_part(&part, WireVersion::V1).expect("Parts can be encoded");
+                (*address, part, acks)
+            })
+            .collect();
+        KeygenFixture {
+            signer: signers.remove(0),
+            vmap,
+            keygen_data,
+        }
+    }
+
+    /// Handles all Parts and Acks as `initialize_synckeygen` does, and returns whether the
+    /// key generation is complete.
+    pub fn initialize(&self) -> bool {
+        let mut synckeygen = self.synckeygen();
+        handle_keygen_data(&self.keygen_data, &self.vmap, &mut synckeygen)
+            .expect("the fixture's data is valid");
+        synckeygen.is_ready()
+    }
+
+    /// Decodes and handles all Parts and Acks one validator after another, and returns whether
+    /// the key generation is complete.
+    pub fn initialize_sequentially(&self) -> bool {
+        let mut synckeygen = self.synckeygen();
+        for (address, serialized_part, _) in &self.keygen_data {
+            let part =
+                decode_part(*address, serialized_part).expect("the fixture's Parts are valid");
+            handle_part(*address, part, &self.vmap, &mut synckeygen)
+                .expect("the fixture's Parts are valid");
+        }
+        for (address, _, serialized_acks) in &self.keygen_data {
+            let acks =
+                decode_acks(*address, serialized_acks).expect("the fixture's Acks are valid");
+            handle_acks(*address, acks, &self.vmap, &mut synckeygen)
+                .expect("the fixture's Acks are valid");
+        }
+        synckeygen.is_ready()
+    }
+
+    fn synckeygen(&self) -> SyncKeyGen<Public, PublicWrapper> {
+        engine_signer_to_synckeygen(&self.signer, pub_keys(&self.vmap))
+            .expect("the fixture's keys are valid")
+            .0
+    }
+}
+
+fn pub_keys(vmap: &BTreeMap<Address, Public>) -> Arc<BTreeMap<Public, PublicWrapper>> {
+    Arc::new(
+        vmap.values()
+            .map(|public| (*public, PublicWrapper { inner: *public }))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeygenFixture;
+
+    #[test]
+    fn concurrent_decoding_completes_the_key_generation() {
+        let fixture = KeygenFixture::new(4);
+        assert!(fixture.initialize());
+        assert!(fixture.initialize_sequentially());
+    }
+}
@@ -0,0 +1,111 @@
+//! Direct exchange of key generation Parts between pending validators.
+//!
+//! A pending validator can only acknowledge the Parts of the others once it knows them. Reading
+//! them from the key generation history contract means waiting for the blocks that include them,
+//! which takes long on chains with long block times. Pending validators therefore also send the
+//! serialized Part they write to the contract directly to each other, and acknowledge a Part as
+//! soon as it arrives either way. The contract stays authoritative: a Part found on chain always
+//! takes precedence over a received one, and the key itself is only ever derived from chain data.
+//!
+//! A validator writes the same Part for an epoch on every attempt, so a received Part matches the
+//! one that ends up on chain.
+
+use ethereum_types::Address;
+use std::collections::BTreeMap;
+
+/// The serialized Part a pending validator wrote for the key generation of a POSDAO epoch.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Message {
+    /// The POSDAO epoch the key is generated for.
+    pub epoch: u64,
+    /// The Part, encoded as in the key generation history contract.
+    pub part: Vec<u8>,
+}
+
+/// What became of a received Part.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Received {
+    /// The Part is new, and was stored.
+    New,
+    /// The Part was known already, is empty or belongs to an earlier epoch.
+    Ignored,
+    /// The sender sent a different Part for the same epoch before. The first one is kept.
+    Conflicting,
+}
+
+/// The Parts received from pending validators for the upcoming POSDAO epoch.
+#[derive(Default)]
+pub struct KeygenGossip {
+    epoch: u64,
+    parts: BTreeMap<Address, Vec<u8>>,
+}
+
+impl KeygenGossip {
+    /// Stores a Part received from the given pending validator. Parts for an earlier epoch than
+    /// the latest one seen are ignored, and a newer epoch discards the stored Parts.
+    ///
+    /// A validator writes the same Part on every attempt, so a different Part for the same epoch
+    /// is not stored.
+    pub fn insert(&mut self, sender: Address, message: Message) -> Received {
+        if message.epoch < self.epoch || message.part.is_empty() {
+            return Received::Ignored;
+        }
+        if message.epoch > self.epoch {
+            self.epoch = message.epoch;
+            self.parts.clear();
+        }
+        match self.parts.get(&sender) {
+            None => {
+                self.parts.insert(sender, message.part);
+                Received::New
+            }
+            Some(part) if *part == message.part => Received::Ignored,
+            Some(_) => Received::Conflicting,
+        }
+    }
+
+    /// The serialized Part the given validator sent for the given epoch, if any.
+    pub fn part(&self, epoch: u64, address: &Address) -> Option<&[u8]> {
+        if epoch != self.epoch {
+            return None;
+        }
+        self.parts.get(address).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(epoch: u64, part: &[u8]) -> Message {
+        Message {
+            epoch,
+            part: part.to_vec(),
+        }
+    }
+
+    #[test]
+    fn only_parts_of_the_latest_epoch_are_kept() {
+        let (a, b) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let mut gossip = KeygenGossip::default();
+        assert_eq!(gossip.insert(a, message(5, b"a5")), Received::New);
+        assert_eq!(gossip.insert(a, message(5, b"a5")), Received::Ignored);
+        assert_eq!(gossip.insert(b, message(5, b"")), Received::Ignored);
+        assert_eq!(gossip.part(5, &a), Some(&b"a5"[..]));
+        assert_eq!(gossip.part(4, &a), None);
+
+        assert_eq!(gossip.insert(b, message(6, b"b6")), Received::New);
+        assert_eq!(gossip.part(6, &a), None);
+        assert_eq!(gossip.insert(a, message(5, b"a5")), Received::Ignored);
+        assert_eq!(gossip.part(6, &b), Some(&b"b6"[..]));
+    }
+
+    #[test]
+    fn conflicting_parts_are_detected() {
+        let a = Address::from_low_u64_be(1);
+        let mut gossip = KeygenGossip::default();
+        assert_eq!(gossip.insert(a, message(5, b"a5")), Received::New);
+        assert_eq!(gossip.insert(a, message(5, b"x5")), Received::Conflicting);
+        assert_eq!(gossip.part(5, &a), Some(&b"a5"[..]));
+    }
+}
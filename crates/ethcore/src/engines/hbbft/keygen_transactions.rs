@@ -1,39 +1,217 @@
-use client::traits::{EngineClient, TransactionRequest};
+use client::traits::{BlockChainClient, EngineClient, TransactionRequest};
 use engines::{
     hbbft::{
         contracts::{
             keygen_history::{
-                engine_signer_to_synckeygen, has_acks_of_address_data, has_part_of_address_data,
-                key_history_contract, part_of_address, PublicWrapper, KEYGEN_HISTORY_ADDRESS,
+                acks_count_of_address, decode_part, engine_signer_to_synckeygen, handle_part,
+                has_part_of_address_data, key_history_contract, serialized_part_of_address,
+                KeygenReadStats, PublicWrapper, KEYGEN_HISTORY_ADDRESS,
             },
             staking::get_posdao_epoch,
             validator_set::{get_validator_pubkeys, ValidatorType},
         },
+        keygen_gossip::{self, KeygenGossip},
+        metrics::HbbftMetrics,
         utils::bound_contract::CallError,
+        wire::{self, WireVersions},
     },
     signer::EngineSigner,
 };
 use ethereum_types::U256;
+use ethjson::spec::HbbftKeygenGasPrice;
 use itertools::Itertools;
 use parking_lot::RwLock;
 use std::{collections::BTreeMap, sync::Arc};
 use types::ids::BlockId;
 
+/// The number of blocks the key generation may stay incomplete before an alert is raised, if
+/// the spec does not set `keygenTimeout`.
+pub const DEFAULT_KEYGEN_TIMEOUT: u64 = 100;
+
+/// The gas price of the Part and Acks transactions if the spec does not set `keygenGasPrice`,
+/// and if the median gas price of recent blocks is unknown: 10 gwei.
+pub const DEFAULT_KEYGEN_GAS_PRICE: u64 = 10_000_000_000;
+
+/// The number of recent blocks whose transactions the median gas price is computed over.
+const GAS_PRICE_SAMPLE_BLOCKS: usize = 100;
+
+/// Returns the gas price of the Part and Acks transactions under the given strategy.
+///
+/// `median` returns the median gas price of recent blocks, and is only called if needed.
+fn keygen_gas_price<F>(strategy: Option<HbbftKeygenGasPrice>, median: F) -> U256
+where
+    F: FnOnce() -> Option<U256>,
+{
+    match strategy.unwrap_or(HbbftKeygenGasPrice::Fixed(DEFAULT_KEYGEN_GAS_PRICE)) {
+        HbbftKeygenGasPrice::Fixed(price) => price.into(),
+        HbbftKeygenGasPrice::MedianPercent(percent) => match median() {
+            Some(median) => median.saturating_mul(percent.into()) / 100,
+            // There were no transactions since genesis.
+            None => DEFAULT_KEYGEN_GAS_PRICE.into(),
+        },
+        HbbftKeygenGasPrice::Zero => U256::zero(),
+    }
+}
+
+/// Returns the gas price of the node's service transactions, like the Part and Acks or the
+/// feature announcements, under the given `keygenGasPrice` strategy.
+pub fn service_gas_price(
+    strategy: Option<HbbftKeygenGasPrice>,
+    client: &dyn BlockChainClient,
+) -> U256 {
+    keygen_gas_price(strategy, || {
+        client
+            .gas_price_corpus(GAS_PRICE_SAMPLE_BLOCKS)
+            .median()
+            .cloned()
+    })
+}
+
+/// Returns the gas of a `writeAcks` transaction with the given total size of Acks, in bytes.
+///
+/// The required gas values have been approximated by experimenting and it's a very rough
+/// estimation. It can be further fine tuned to be just above the real consumption.
+fn acks_gas(bytes: usize) -> U256 {
+    U256::from(bytes * 800 + 200_000)
+}
+
+/// Splits the serialized Acks into consecutive chunks, each written by a transaction with at
+/// most `max_gas` gas. An Ack exceeding it on its own gets a chunk of its own.
+fn chunk_acks(acks: &[Vec<u8>], max_gas: U256) -> Vec<&[Vec<u8>]> {
+    let mut chunks = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+    for (index, ack) in acks.iter().enumerate() {
+        if index > start && acks_gas(bytes + ack.len()) > max_gas {
+            chunks.push(&acks[start..index]);
+            start = index;
+            bytes = 0;
+        }
+        bytes += ack.len();
+    }
+    if start < acks.len() {
+        chunks.push(&acks[start..]);
+    }
+    chunks
+}
+
+/// Tracks for how many blocks the key generation for the next POSDAO epoch has been incomplete,
+/// and for how many of them it made no progress.
+///
+/// A pending validator that never writes its Part or Acks keeps the key generation from ever
+/// completing. Once no Part or Acks were written for longer than the timeout, an alert is due
+/// once per timeout period, until the key generation progresses, completes or the pending
+/// validator set is cleared.
+pub struct KeygenTimeout {
+    timeout: u64,
+    started: Option<u64>,
+    incomplete_blocks: u64,
+    /// The block of the last progress, and the number of Parts and Acks missing since then.
+    last_progress: Option<(u64, usize)>,
+    next_alert: u64,
+}
+
+impl KeygenTimeout {
+    pub fn new(timeout: u64) -> Self {
+        KeygenTimeout {
+            timeout: timeout.max(1),
+            started: None,
+            incomplete_blocks: 0,
+            last_progress: None,
+            next_alert: timeout.max(1),
+        }
+    }
+
+    /// Records that the key generation is incomplete at the given block, with `missing` Parts
+    /// and Acks not on chain yet. Returns the number of blocks without progress if an alert is
+    /// due.
+    pub fn incomplete(&mut self, block_number: u64, missing: usize) -> Option<u64> {
+        let started = *self.started.get_or_insert(block_number);
+        self.incomplete_blocks = block_number.saturating_sub(started);
+        let progressed = match self.last_progress {
+            Some((_, last_missing)) => missing < last_missing,
+            None => true,
+        };
+        if progressed {
+            self.last_progress = Some((block_number, missing));
+            self.next_alert = self.timeout;
+        }
+        let last_progress = self.last_progress.map_or(block_number, |(block, _)| block);
+        let stalled_blocks = block_number.saturating_sub(last_progress);
+        if stalled_blocks < self.next_alert {
+            return None;
+        }
+        self.next_alert = stalled_blocks + self.timeout;
+        Some(stalled_blocks)
+    }
+
+    /// Records that no key generation is in progress.
+    pub fn complete(&mut self) {
+        *self = KeygenTimeout::new(self.timeout);
+    }
+
+    /// The number of blocks the current key generation has been incomplete for.
+    pub fn incomplete_blocks(&self) -> u64 {
+        self.incomplete_blocks
+    }
+}
+
+/// Sends the Part and Acks transactions of a pending validator, and resends them if they are not
+/// on chain after `resend_delay` blocks.
+///
+/// All state belongs to the key generation for one POSDAO epoch and is discarded once the key
+/// for another epoch is generated.
 pub struct KeygenTransactionSender {
+    /// The POSDAO epoch the key is generated for.
+    epoch: u64,
     last_part_sent: u64,
     last_acks_sent: u64,
     resend_delay: u64,
+    /// The serialized Part written in this epoch, reused on every attempt so the Part other
+    /// validators received directly matches the one on chain.
+    part: Option<Vec<u8>>,
+    /// The serialized Acks written in this epoch, reused on every attempt so Acks written in
+    /// several transactions belong together.
+    acks: Option<Vec<Vec<u8>>>,
+    /// The number of our Acks found on chain at the last attempt.
+    acks_written: usize,
+    /// The strategy for the transactions' gas price. A fixed price of 10 gwei if not set.
+    gas_price: Option<HbbftKeygenGasPrice>,
 }
 
 impl KeygenTransactionSender {
-    pub fn new() -> Self {
+    pub fn new(gas_price: Option<HbbftKeygenGasPrice>) -> Self {
         KeygenTransactionSender {
+            epoch: 0,
             last_part_sent: 0,
             last_acks_sent: 0,
             resend_delay: 10,
+            part: None,
+            acks: None,
+            acks_written: 0,
+            gas_price,
+        }
+    }
+
+    /// Starts the key generation for the given POSDAO epoch, unless it is already in progress.
+    fn enter_epoch(&mut self, epoch: u64) {
+        if epoch != self.epoch {
+            *self = KeygenTransactionSender {
+                epoch,
+                ..KeygenTransactionSender::new(self.gas_price)
+            };
         }
     }
 
+    /// Returns the block our Part transaction for the given POSDAO epoch was last sent in.
+    pub fn part_sent(&self, epoch: u64) -> Option<u64> {
+        Some(self.last_part_sent).filter(|sent| self.epoch == epoch && *sent > 0)
+    }
+
+    /// Returns the block our Acks transactions for the given POSDAO epoch were last sent in.
+    pub fn acks_sent(&self, epoch: u64) -> Option<u64> {
+        Some(self.last_acks_sent).filter(|sent| self.epoch == epoch && *sent > 0)
+    }
+
     fn part_threshold_reached(&self, block_number: u64) -> bool {
         self.last_part_sent == 0 || block_number > (self.last_part_sent + self.resend_delay)
     }
@@ -44,11 +222,18 @@ impl KeygenTransactionSender {
 
     /// Returns a collection of transactions the pending validator has to submit in order to
     /// complete the keygen history contract data necessary to generate the next key and switch to the new validator set.
+    ///
+    /// Parts not yet on chain are taken from the ones received from the other pending validators.
+    /// Returns our Part, to be sent to them, if its transaction was sent.
     pub fn send_keygen_transactions(
         &mut self,
         client: &dyn EngineClient,
         signer: &Arc<RwLock<Option<Box<dyn EngineSigner>>>>,
-    ) -> Result<(), CallError> {
+        stats: &KeygenReadStats,
+        wire_versions: &WireVersions,
+        metrics: &HbbftMetrics,
+        gossip: &KeygenGossip,
+    ) -> Result<Option<keygen_gossip::Message>, CallError> {
         // If we have no signer there is nothing for us to send.
         let address = match signer.read().as_ref() {
             Some(signer) => signer.address(),
@@ -59,7 +244,7 @@ impl KeygenTransactionSender {
 
         // If the chain is still syncing, do not send Parts or Acks.
         if full_client.is_major_syncing() {
-            return Ok(());
+            return Ok(None);
         }
 
         let vmap = get_validator_pubkeys(&*client, BlockId::Latest, ValidatorType::Pending)?;
@@ -80,19 +265,32 @@ impl KeygenTransactionSender {
         };
 
         let upcoming_epoch = get_posdao_epoch(client, BlockId::Latest)? + 1;
+        let wire_version = wire_versions.for_epoch(upcoming_epoch.low_u64());
         let cur_block = client
             .block_number(BlockId::Latest)
             .ok_or(CallError::ReturnValueInvalid)?;
 
+        let epoch = upcoming_epoch.low_u64();
+        self.enter_epoch(epoch);
+        let gas_price_strategy = self.gas_price;
+        let gas_price = || service_gas_price(gas_price_strategy, full_client);
+        let mut gossip_part = None;
+
         // Check if we already sent our part.
-        if self.part_threshold_reached(cur_block) && !has_part_of_address_data(client, address)? {
-            let serialized_part = match bincode::serialize(&part_data) {
-                Ok(part) => part,
-                Err(_) => return Err(CallError::ReturnValueInvalid),
+        if self.part_threshold_reached(cur_block) && !has_part_of_address_data(client, address, stats)? {
+            let serialized_part = match self.part {
+                Some(ref part) => part.clone(),
+                None => match wire::encode_part(&part_data, wire_version) {
+                    Ok(part) => part,
+                    Err(_) => return Err(CallError::ReturnValueInvalid),
+                },
             };
+            self.part = Some(serialized_part.clone());
             let serialized_part_len = serialized_part.len();
-            let write_part_data =
-                key_history_contract::functions::write_part::call(upcoming_epoch, serialized_part);
+            let write_part_data = key_history_contract::functions::write_part::call(
+                upcoming_epoch,
+                serialized_part.clone(),
+            );
 
             // the required gas values have been approximated by
             // experimenting and it's a very rough estimation.
@@ -107,58 +305,191 @@ impl KeygenTransactionSender {
                 TransactionRequest::call(*KEYGEN_HISTORY_ADDRESS, write_part_data.0)
                     .gas(U256::from(gas))
                     .nonce(full_client.nonce(&address, BlockId::Latest).unwrap())
-                    .gas_price(U256::from(10000000000u64));
+                    .gas_price(gas_price());
             full_client
                 .transact_silently(part_transaction)
                 .map_err(|_| CallError::ReturnValueInvalid)?;
+            if self.last_part_sent != 0 {
+                metrics.keygen_transaction_retried();
+            }
             self.last_part_sent = cur_block;
+            gossip_part = Some(keygen_gossip::Message {
+                epoch,
+                part: serialized_part,
+            });
         }
 
-        // Return if any Part is missing.
+        // Return if any Part is missing, both on chain and among the received ones.
         let mut acks = Vec::new();
         for v in vmap.keys().sorted() {
-            acks.push(
-                match part_of_address(&*client, *v, &vmap, &mut synckeygen, BlockId::Latest)? {
-                    Some(ack) => ack,
-                    None => return Err(CallError::ReturnValueInvalid),
-                },
-            );
+            let mut serialized_part =
+                serialized_part_of_address(&*client, *v, BlockId::Latest, stats)?;
+            if serialized_part.is_empty() {
+                match gossip.part(epoch, v) {
+                    Some(part) => serialized_part = part.to_vec(),
+                    None => return Ok(gossip_part),
+                }
+            }
+            let part = decode_part(*v, &serialized_part)?;
+            acks.push(match handle_part(*v, part, &vmap, &mut synckeygen)? {
+                Some(ack) => ack,
+                None => return Err(CallError::ReturnValueInvalid),
+            });
         }
 
         // Now we are sure all parts are ready, let's check if we sent our Acks.
-        if self.acks_threshold_reached(cur_block) && !has_acks_of_address_data(client, address)? {
-            let mut serialized_acks = Vec::new();
-            let mut total_bytes_for_acks = 0;
+        if !self.acks_threshold_reached(cur_block) {
+            return Ok(gossip_part);
+        }
+        let written = acks_count_of_address(client, address, stats)?;
+        if written >= acks.len() {
+            return Ok(gossip_part);
+        }
+        if written > self.acks_written {
+            debug!(target: "engine", "{} of our {} Acks for POSDAO epoch {} are on chain.", written, acks.len(), epoch);
+        }
+        self.acks_written = written;
 
-            for ack in acks {
-                let ack_to_push = match bincode::serialize(&ack) {
-                    Ok(serialized_ack) => serialized_ack,
-                    Err(_) => return Err(CallError::ReturnValueInvalid),
-                };
-                total_bytes_for_acks += ack_to_push.len();
-                serialized_acks.push(ack_to_push);
+        if self.acks.is_none() {
+            let serialized_acks = acks
+                .iter()
+                .map(|ack| wire::encode_ack(ack, wire_version))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| CallError::ReturnValueInvalid)?;
+            self.acks = Some(serialized_acks);
+        }
+        let serialized_acks = self.acks.as_ref().expect("the Acks were set above; qed");
+
+        // A transaction exceeding the block gas limit is never mined, so the Acks of large
+        // validator sets are written in several transactions, each appending to the ones on
+        // chain. The remaining ones are sent with consecutive nonces.
+        let max_gas = client
+            .block_header(BlockId::Latest)
+            .ok_or(CallError::ReturnValueInvalid)?
+            .gas_limit();
+        let nonce = full_client
+            .nonce(&address, BlockId::Latest)
+            .ok_or(CallError::ReturnValueInvalid)?;
+        let chunks = chunk_acks(&serialized_acks[written..], max_gas);
+        for (index, chunk) in chunks.iter().enumerate() {
+            let bytes: usize = chunk.iter().map(Vec::len).sum();
+            let gas = acks_gas(bytes);
+            trace!(target: "engine", "Hbbft acks transaction {} of {}: acks-len: {} gas: {}", index + 1, chunks.len(), bytes, gas);
+            if gas > max_gas {
+                warn!(target: "engine", "An Ack of {} bytes exceeds the block gas limit {}, its transaction cannot be mined.", bytes, max_gas);
             }
 
             let write_acks_data =
-                key_history_contract::functions::write_acks::call(upcoming_epoch, serialized_acks);
-
-            // the required gas values have been approximated by
-            // experimenting and it's a very rough estimation.
-            // it can be further fine tuned to be just above the real consumption.
-            let gas = total_bytes_for_acks * 800 + 200_000;
-            trace!(target: "engine","acks-len: {} gas: {}", total_bytes_for_acks, gas);
-
+                key_history_contract::functions::write_acks::call(upcoming_epoch, chunk.to_vec());
             let acks_transaction =
                 TransactionRequest::call(*KEYGEN_HISTORY_ADDRESS, write_acks_data.0)
-                    .gas(U256::from(gas))
-                    .nonce(full_client.nonce(&address, BlockId::Latest).unwrap())
-                    .gas_price(U256::from(10000000000u64));
+                    .gas(gas)
+                    .nonce(nonce + index)
+                    .gas_price(gas_price());
             full_client
                 .transact_silently(acks_transaction)
                 .map_err(|_| CallError::ReturnValueInvalid)?;
-            self.last_acks_sent = cur_block;
         }
+        if self.last_acks_sent != 0 {
+            metrics.keygen_transaction_retried();
+        }
+        self.last_acks_sent = cur_block;
+
+        Ok(gossip_part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stalled_key_generation_is_reported_once_per_timeout() {
+        let mut timeout = KeygenTimeout::new(10);
+        assert_eq!(timeout.incomplete(100, 4), None);
+        assert_eq!(timeout.incomplete(109, 4), None);
+        assert_eq!(timeout.incomplete(110, 4), Some(10));
+        assert_eq!(timeout.incomplete(111, 4), None);
+        assert_eq!(timeout.incomplete(120, 4), Some(20));
+        assert_eq!(timeout.incomplete_blocks(), 20);
+
+        // A written Part or Ack restarts the timeout, but not the count of incomplete blocks.
+        assert_eq!(timeout.incomplete(125, 3), None);
+        assert_eq!(timeout.incomplete(134, 3), None);
+        assert_eq!(timeout.incomplete(135, 3), Some(10));
+        assert_eq!(timeout.incomplete_blocks(), 35);
+
+        timeout.complete();
+        assert_eq!(timeout.incomplete_blocks(), 0);
+        assert_eq!(timeout.incomplete(200, 4), None);
+        assert_eq!(timeout.incomplete(210, 4), Some(10));
+    }
+
+    #[test]
+    fn keygen_gas_price_follows_strategy() {
+        let median = || Some(U256::from(2_000));
+        assert_eq!(
+            keygen_gas_price(None, median),
+            U256::from(DEFAULT_KEYGEN_GAS_PRICE)
+        );
+        assert_eq!(
+            keygen_gas_price(Some(HbbftKeygenGasPrice::Fixed(5)), median),
+            U256::from(5)
+        );
+        assert_eq!(
+            keygen_gas_price(Some(HbbftKeygenGasPrice::MedianPercent(150)), median),
+            U256::from(3_000)
+        );
+        assert_eq!(
+            keygen_gas_price(Some(HbbftKeygenGasPrice::MedianPercent(150)), || None),
+            U256::from(DEFAULT_KEYGEN_GAS_PRICE)
+        );
+        assert_eq!(
+            keygen_gas_price(Some(HbbftKeygenGasPrice::Zero), median),
+            U256::zero()
+        );
+    }
+
+    #[test]
+    fn acks_are_split_to_fit_the_gas_limit() {
+        let acks = vec![vec![0; 100], vec![0; 100], vec![0; 100]];
+        let chunks = chunk_acks(&acks, acks_gas(300));
+        assert_eq!(chunks, vec![&acks[..]]);
+
+        let chunks = chunk_acks(&acks, acks_gas(250));
+        assert_eq!(chunks, vec![&acks[..2], &acks[2..]]);
+
+        // An Ack exceeding the limit on its own is not merged with others.
+        let chunks = chunk_acks(&acks, acks_gas(50));
+        assert_eq!(chunks, vec![&acks[..1], &acks[1..2], &acks[2..]]);
+
+        assert!(chunk_acks(&[], acks_gas(50)).is_empty());
+    }
+
+    #[test]
+    fn resend_state_is_kept_per_epoch() {
+        let mut sender = KeygenTransactionSender::new(None);
+        sender.enter_epoch(3);
+        sender.last_part_sent = 100;
+        sender.last_acks_sent = 101;
+        sender.part = Some(vec![1]);
+        sender.acks = Some(vec![vec![2]]);
+        assert!(!sender.part_threshold_reached(105));
+        assert!(!sender.acks_threshold_reached(105));
+        assert_eq!(sender.part_sent(3), Some(100));
+        assert_eq!(sender.acks_sent(3), Some(101));
+        assert_eq!(sender.part_sent(4), None);
+
+        sender.enter_epoch(3);
+        assert_eq!(sender.part, Some(vec![1]));
+        assert!(!sender.part_threshold_reached(105));
 
-        Ok(())
+        // The key generation for the next epoch must not wait for the previous resend delay.
+        sender.enter_epoch(4);
+        assert_eq!(sender.part, None);
+        assert_eq!(sender.acks, None);
+        assert!(sender.part_threshold_reached(105));
+        assert!(sender.acks_threshold_reached(105));
+        assert_eq!(sender.part_sent(4), None);
     }
 }
@@ -0,0 +1,180 @@
+//! Processing latency of received consensus messages, by message kind and sender.
+
+use stats::Corpus;
+use std::{
+    cmp::min,
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    time::Duration,
+};
+
+use super::{
+    api::{MessageKind, MessageLatency},
+    hbbft_state::HbMessage,
+    NodeId,
+};
+
+/// The number of most recent samples kept per message kind and sender.
+const SAMPLES_PER_KEY: usize = 256;
+
+/// The offset of the variant index of a Honey Badger message's content in its bincode encoding,
+/// after the `u64` epoch.
+const HB_CONTENT_OFFSET: usize = 8;
+
+/// The offset of the variant index of a subset message's content, after the Honey Badger
+/// content's variant index and the proposer ID, a length prefixed `0x` hex string of 128 digits.
+const SUBSET_CONTENT_OFFSET: usize = HB_CONTENT_OFFSET + 4 + 8 + 130;
+
+/// The length of the encoding prefix needed to classify a Honey Badger message.
+const PREFIX_LEN: usize = SUBSET_CONTENT_OFFSET + 4;
+
+/// Keeps the first `PREFIX_LEN` bytes written to it, and fails once they are complete so the
+/// serialization of the rest of the message is skipped.
+struct PrefixWriter {
+    prefix: [u8; PREFIX_LEN],
+    len: usize,
+}
+
+impl Write for PrefixWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = min(buf.len(), PREFIX_LEN - self.len);
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        self.prefix[self.len..self.len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl PrefixWriter {
+    /// The enum variant index encoded at the given offset, if the prefix reaches that far.
+    fn variant(&self, offset: usize) -> Option<u32> {
+        if self.len < offset + 4 {
+            return None;
+        }
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.prefix[offset..offset + 4]);
+        Some(u32::from_le_bytes(bytes))
+    }
+}
+
+/// Returns the kind of a Honey Badger message.
+///
+/// The hbbft crate does not expose the message content, so the kind is read from the variant
+/// indices in the message's bincode encoding: `Subset` or `DecryptionShare`, and for subset
+/// messages `Broadcast` or `BinaryAgreement`. Only the encoding's prefix is kept, on the stack.
+pub fn honey_badger_kind(message: &HbMessage) -> MessageKind {
+    let mut writer = PrefixWriter {
+        prefix: [0; PREFIX_LEN],
+        len: 0,
+    };
+    // Fails with `WriteZero` once the prefix is complete.
+    let _ = bincode::serialize_into(&mut writer, message);
+    match writer.variant(HB_CONTENT_OFFSET) {
+        Some(0) => match writer.variant(SUBSET_CONTENT_OFFSET) {
+            Some(0) => MessageKind::Broadcast,
+            Some(1) => MessageKind::Agreement,
+            _ => MessageKind::Unknown,
+        },
+        Some(1) => MessageKind::Decryption,
+        _ => MessageKind::Unknown,
+    }
+}
+
+/// Recent processing times of consensus messages.
+#[derive(Default)]
+pub struct MessageLatencies {
+    samples: BTreeMap<(MessageKind, NodeId), VecDeque<u64>>,
+}
+
+impl MessageLatencies {
+    /// Records the time it took to process a message.
+    pub fn record(&mut self, kind: MessageKind, sender: NodeId, elapsed: Duration) {
+        let samples = self.samples.entry((kind, sender)).or_default();
+        if samples.len() == SAMPLES_PER_KEY {
+            samples.pop_front();
+        }
+        samples.push_back(elapsed.as_micros() as u64);
+    }
+
+    /// Latency percentiles per message kind and sender.
+    pub fn by_sender(&self) -> Vec<MessageLatency> {
+        self.samples
+            .iter()
+            .filter_map(|((kind, sender), samples)| {
+                latency(*kind, Some(*sender), samples.iter().cloned().collect())
+            })
+            .collect()
+    }
+
+    /// Latency percentiles per message kind, over all senders.
+    pub fn by_kind(&self) -> Vec<MessageLatency> {
+        let mut merged: BTreeMap<MessageKind, Vec<u64>> = BTreeMap::new();
+        for ((kind, _), samples) in &self.samples {
+            merged.entry(*kind).or_default().extend(samples.iter());
+        }
+        merged
+            .into_iter()
+            .filter_map(|(kind, samples)| latency(kind, None, samples.into()))
+            .collect()
+    }
+}
+
+fn latency(
+    kind: MessageKind,
+    sender: Option<NodeId>,
+    corpus: Corpus<u64>,
+) -> Option<MessageLatency> {
+    Some(MessageLatency {
+        kind,
+        sender,
+        samples: corpus.len(),
+        p50_micros: *corpus.median()?,
+        p90_micros: *corpus.percentile(90).or_else(|| corpus.last())?,
+        p99_micros: *corpus.percentile(99).or_else(|| corpus.last())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+
+    #[test]
+    fn percentiles_per_kind_and_sender() {
+        let first = NodeId(H512::from_low_u64_be(1));
+        let second = NodeId(H512::from_low_u64_be(2));
+        let mut latencies = MessageLatencies::default();
+        for i in 1..=100 {
+            latencies.record(MessageKind::Agreement, first, Duration::from_micros(i));
+        }
+        latencies.record(MessageKind::Agreement, second, Duration::from_micros(1000));
+
+        let by_sender = latencies.by_sender();
+        assert_eq!(by_sender.len(), 2);
+        assert_eq!(by_sender[0].sender, Some(first));
+        assert_eq!(by_sender[0].p50_micros, 51);
+        assert_eq!(by_sender[0].p90_micros, 90);
+        assert_eq!(by_sender[1].p99_micros, 1000);
+
+        let by_kind = latencies.by_kind();
+        assert_eq!(by_kind.len(), 1);
+        assert_eq!(by_kind[0].samples, 101);
+        assert_eq!(by_kind[0].sender, None);
+    }
+
+    #[test]
+    fn only_recent_samples_are_kept() {
+        let sender = NodeId(H512::from_low_u64_be(1));
+        let mut latencies = MessageLatencies::default();
+        for i in 0..(2 * SAMPLES_PER_KEY as u64) {
+            latencies.record(MessageKind::Sealing, sender, Duration::from_micros(i));
+        }
+        assert_eq!(latencies.by_kind()[0].samples, SAMPLES_PER_KEY);
+    }
+}
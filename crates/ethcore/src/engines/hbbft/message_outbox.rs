@@ -0,0 +1,91 @@
+//! Consensus messages for validators that are temporarily not connected.
+//!
+//! The network drops messages to nodes without a peer session, and a validator that misses
+//! messages can fall behind in the current hbbft epoch. Packets to disconnected validators are
+//! therefore kept until the validator reconnects, or until they are too old to still matter.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use super::NodeId;
+
+/// How long packets are kept for a disconnected validator.
+pub const MESSAGE_TTL: Duration = Duration::from_secs(30);
+
+/// The maximum number of packets kept per validator. The oldest ones are dropped first.
+const MAX_PACKETS_PER_NODE: usize = 1024;
+
+/// Packets waiting for their recipients to reconnect.
+#[derive(Default)]
+pub struct MessageOutbox {
+    packets: BTreeMap<NodeId, VecDeque<(Instant, Vec<u8>)>>,
+}
+
+impl MessageOutbox {
+    /// Keeps a packet until the recipient reconnects.
+    pub fn push(&mut self, node_id: NodeId, packet: Vec<u8>, now: Instant) {
+        let packets = self.packets.entry(node_id).or_default();
+        if packets.len() == MAX_PACKETS_PER_NODE {
+            packets.pop_front();
+        }
+        packets.push_back((now, packet));
+    }
+
+    /// Drops expired packets, and removes and returns the packets of the connected nodes.
+    pub fn take_deliverable(
+        &mut self,
+        connected: &BTreeSet<NodeId>,
+        now: Instant,
+    ) -> Vec<(NodeId, Vec<u8>)> {
+        let mut deliverable = Vec::new();
+        self.packets.retain(|node_id, packets| {
+            packets.retain(|(queued, _)| now.duration_since(*queued) < MESSAGE_TTL);
+            if connected.contains(node_id) {
+                deliverable.extend(packets.drain(..).map(|(_, packet)| (*node_id, packet)));
+            }
+            !packets.is_empty()
+        });
+        deliverable
+    }
+
+    /// The number of packets waiting for the given node.
+    pub fn queued(&self, node_id: &NodeId) -> usize {
+        self.packets.get(node_id).map_or(0, VecDeque::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+
+    #[test]
+    fn packets_are_delivered_on_reconnect_until_they_expire() {
+        let (a, b) = (
+            NodeId(H512::from_low_u64_be(1)),
+            NodeId(H512::from_low_u64_be(2)),
+        );
+        let start = Instant::now();
+        let mut outbox = MessageOutbox::default();
+        outbox.push(a, vec![1], start);
+        outbox.push(b, vec![2], start);
+        outbox.push(b, vec![3], start + MESSAGE_TTL / 2);
+        assert_eq!(outbox.queued(&b), 2);
+
+        let connected: BTreeSet<NodeId> = vec![a].into_iter().collect();
+        assert_eq!(
+            outbox.take_deliverable(&connected, start),
+            vec![(a, vec![1])]
+        );
+        assert_eq!(outbox.queued(&a), 0);
+
+        let connected: BTreeSet<NodeId> = vec![a, b].into_iter().collect();
+        assert_eq!(
+            outbox.take_deliverable(&connected, start + MESSAGE_TTL),
+            vec![(b, vec![3])]
+        );
+        assert_eq!(outbox.queued(&b), 0);
+    }
+}
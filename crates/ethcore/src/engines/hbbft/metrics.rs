@@ -0,0 +1,214 @@
+//! Consensus metrics exported through the node's Prometheus endpoint.
+
+use parking_lot::Mutex;
+use stats::PrometheusRegistry;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+use types::BlockNumber;
+
+/// Counts observations in buckets with fixed upper bounds.
+pub struct Histogram {
+    bounds: &'static [u64],
+    /// The number of observations per bucket, and above the largest bound.
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Histogram {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Returns the upper bounds with the cumulative number of observations up to them, and the
+    /// total number of observations.
+    fn buckets(&self) -> (Vec<(f64, u64)>, u64) {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound as f64, cumulative)
+            })
+            .collect();
+        let total = cumulative + self.counts[self.bounds.len()].load(Ordering::Relaxed);
+        (buckets, total)
+    }
+
+    pub fn register(&self, r: &mut PrometheusRegistry, name: &str, help: &str) {
+        let (buckets, count) = self.buckets();
+        let sum = self.sum.load(Ordering::Relaxed) as f64;
+        r.register_histogram(name, help, &buckets, count, sum);
+    }
+}
+
+/// The consensus activity of the engine.
+pub struct HbbftMetrics {
+    messages_received: AtomicU64,
+    messages_sent: AtomicU64,
+    epoch_messages_received: AtomicU64,
+    epoch_messages_sent: AtomicU64,
+    keygen_transaction_retries: AtomicU64,
+    contributions_rejected: AtomicU64,
+    faults_reported: AtomicU64,
+    messages_received_per_epoch: Histogram,
+    messages_sent_per_epoch: Histogram,
+    contribution_bytes: Histogram,
+    contribution_to_seal_millis: Histogram,
+    /// The block we contributed to last, and when we did.
+    contribution_sent: Mutex<Option<(BlockNumber, Instant)>>,
+}
+
+impl Default for HbbftMetrics {
+    fn default() -> Self {
+        const MESSAGES: &[u64] = &[10, 50, 100, 500, 1_000, 5_000, 10_000];
+        const BYTES: &[u64] = &[1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+        const MILLIS: &[u64] = &[100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000];
+        HbbftMetrics {
+            messages_received: AtomicU64::new(0),
+            messages_sent: AtomicU64::new(0),
+            epoch_messages_received: AtomicU64::new(0),
+            epoch_messages_sent: AtomicU64::new(0),
+            keygen_transaction_retries: AtomicU64::new(0),
+            contributions_rejected: AtomicU64::new(0),
+            faults_reported: AtomicU64::new(0),
+            messages_received_per_epoch: Histogram::new(MESSAGES),
+            messages_sent_per_epoch: Histogram::new(MESSAGES),
+            contribution_bytes: Histogram::new(BYTES),
+            contribution_to_seal_millis: Histogram::new(MILLIS),
+            contribution_sent: Mutex::new(None),
+        }
+    }
+}
+
+impl HbbftMetrics {
+    pub fn message_received(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.epoch_messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn messages_sent(&self, count: u64) {
+        self.messages_sent.fetch_add(count, Ordering::Relaxed);
+        self.epoch_messages_sent.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records the sizes of an agreed batch's contributions, and closes the epoch's message
+    /// counts.
+    pub fn batch_agreed<I: IntoIterator<Item = u64>>(&self, contribution_sizes: I) {
+        for size in contribution_sizes {
+            self.contribution_bytes.observe(size);
+        }
+        self.messages_received_per_epoch
+            .observe(self.epoch_messages_received.swap(0, Ordering::Relaxed));
+        self.messages_sent_per_epoch
+            .observe(self.epoch_messages_sent.swap(0, Ordering::Relaxed));
+    }
+
+    pub fn keygen_transaction_retried(&self) {
+        self.keygen_transaction_retries
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn contribution_rejected(&self) {
+        self.contributions_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn fault_reported(&self) {
+        self.faults_reported.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn contribution_sent(&self, block_num: BlockNumber) {
+        *self.contribution_sent.lock() = Some((block_num, Instant::now()));
+    }
+
+    /// Records the time since our contribution, if the sealed block is the one we contributed to.
+    pub fn block_sealed(&self, block_num: BlockNumber) {
+        let mut contribution_sent = self.contribution_sent.lock();
+        if let Some((contributed, sent)) = *contribution_sent {
+            if contributed == block_num {
+                self.contribution_to_seal_millis
+                    .observe(sent.elapsed().as_millis() as u64);
+                *contribution_sent = None;
+            }
+        }
+    }
+
+    pub fn register(&self, r: &mut PrometheusRegistry) {
+        r.register_counter(
+            "hbbft_messages_received",
+            "Consensus messages received since startup",
+            self.messages_received.load(Ordering::Relaxed) as i64,
+        );
+        r.register_counter(
+            "hbbft_messages_sent",
+            "Consensus messages sent since startup",
+            self.messages_sent.load(Ordering::Relaxed) as i64,
+        );
+        r.register_counter(
+            "hbbft_keygen_transaction_retries",
+            "Part and Acks transactions sent again because they were not included in time",
+            self.keygen_transaction_retries.load(Ordering::Relaxed) as i64,
+        );
+        r.register_counter(
+            "hbbft_contributions_rejected",
+            "Agreed contributions dropped for exceeding the contribution limits",
+            self.contributions_rejected.load(Ordering::Relaxed) as i64,
+        );
+        r.register_counter(
+            "hbbft_faults_reported",
+            "Invalid consensus messages and contributions received from validators",
+            self.faults_reported.load(Ordering::Relaxed) as i64,
+        );
+        self.messages_received_per_epoch.register(
+            r,
+            "hbbft_epoch_messages_received",
+            "Consensus messages received per hbbft epoch",
+        );
+        self.messages_sent_per_epoch.register(
+            r,
+            "hbbft_epoch_messages_sent",
+            "Consensus messages sent per hbbft epoch",
+        );
+        self.contribution_bytes.register(
+            r,
+            "hbbft_contribution_bytes",
+            "Size of the agreed contributions' transactions in bytes",
+        );
+        self.contribution_to_seal_millis.register(
+            r,
+            "hbbft_contribution_to_seal_millis",
+            "Time from sending our contribution until the block's seal is complete",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[10, 100]);
+        for value in &[1, 10, 11, 1000] {
+            histogram.observe(*value);
+        }
+        assert_eq!(histogram.buckets(), (vec![(10.0, 2), (100.0, 3)], 4));
+    }
+}
@@ -1,15 +1,43 @@
+mod activation;
+mod batch_policy;
+pub mod api;
 mod block_reward_hbbft;
+mod clock;
 mod contracts;
 mod contribution;
+mod encryption;
+mod epoch_keys;
+mod extra_data;
+mod fork;
 mod hbbft_engine;
 mod hbbft_state;
+mod key_cache;
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod keygen_bench;
+mod keygen_gossip;
 mod keygen_transactions;
+mod message_latency;
+mod message_outbox;
+mod metrics;
+mod observers;
+mod queue_trigger;
+mod recent_transactions;
+mod recovery;
+mod reward_claims;
+mod rng;
 mod sealing;
+mod spec_validation;
 #[cfg(test)]
 mod test;
+mod transaction_order;
 mod utils;
+mod validator_peers;
+mod watchdog;
+mod wire;
 
 pub use self::hbbft_engine::HoneyBadgerBFT;
+#[cfg(any(test, feature = "test-helpers"))]
+pub use self::hbbft_engine::decode_packet;
 
 use crypto::publickey::Public;
 use std::fmt;
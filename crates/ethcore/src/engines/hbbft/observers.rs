@@ -0,0 +1,114 @@
+//! Nodes following the validators' consensus without taking part in it.
+//!
+//! Consensus messages are only sent to validators, so an observer subscribes by sending an
+//! `Observe` message to the current validators every `SUBSCRIPTION_INTERVAL`. Until the
+//! subscription expires, the validators also send it their broadcast Honey Badger messages.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::NodeId;
+
+/// The maximum number of observers a validator sends its consensus messages to.
+pub const MAX_OBSERVERS: usize = 16;
+
+/// How often observers renew their subscription.
+pub const SUBSCRIPTION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a subscription lasts without being renewed.
+pub const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The observers subscribed to our consensus messages, with the time of their last subscription.
+#[derive(Default)]
+pub struct ObserverRegistry {
+    subscriptions: BTreeMap<NodeId, Instant>,
+}
+
+impl ObserverRegistry {
+    /// Adds or renews the subscription of the given node. Returns false if there are already
+    /// `MAX_OBSERVERS` other observers.
+    pub fn subscribe(&mut self, node_id: NodeId, now: Instant) -> bool {
+        self.expire(now);
+        if self.subscriptions.len() >= MAX_OBSERVERS && !self.subscriptions.contains_key(&node_id) {
+            return false;
+        }
+        self.subscriptions.insert(node_id, now);
+        true
+    }
+
+    /// The observers whose subscription has not expired.
+    pub fn observers(&mut self, now: Instant) -> Vec<NodeId> {
+        self.expire(now);
+        self.subscriptions.keys().cloned().collect()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.subscriptions.retain(|_, subscribed| {
+            now.saturating_duration_since(*subscribed) < SUBSCRIPTION_TIMEOUT
+        });
+    }
+}
+
+/// When we last subscribed to the validators' consensus messages, as an observer.
+#[derive(Default)]
+pub struct Subscription {
+    last: Option<Instant>,
+}
+
+impl Subscription {
+    /// Returns true if the subscription needs to be renewed, and records the renewal.
+    pub fn renew(&mut self, now: Instant) -> bool {
+        let due = self.last.map_or(true, |last| {
+            now.saturating_duration_since(last) >= SUBSCRIPTION_INTERVAL
+        });
+        if due {
+            self.last = Some(now);
+        }
+        due
+    }
+
+    /// Makes the next call to `renew` subscribe right away, e.g. after the validators changed.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_types::H512;
+
+    fn node(id: u64) -> NodeId {
+        NodeId(H512::from_low_u64_be(id))
+    }
+
+    #[test]
+    fn subscriptions_are_limited_and_expire() {
+        let start = Instant::now();
+        let mut registry = ObserverRegistry::default();
+        for id in 0..MAX_OBSERVERS as u64 {
+            assert!(registry.subscribe(node(id), start));
+        }
+        assert!(!registry.subscribe(node(MAX_OBSERVERS as u64), start));
+        // Renewing an existing subscription is always possible.
+        let later = start + SUBSCRIPTION_INTERVAL;
+        assert!(registry.subscribe(node(0), later));
+
+        let expired = start + SUBSCRIPTION_TIMEOUT;
+        assert_eq!(registry.observers(expired), vec![node(0)]);
+        assert!(registry.subscribe(node(MAX_OBSERVERS as u64), expired));
+    }
+
+    #[test]
+    fn subscription_is_renewed_periodically() {
+        let start = Instant::now();
+        let mut subscription = Subscription::default();
+        assert!(subscription.renew(start));
+        assert!(!subscription.renew(start + SUBSCRIPTION_INTERVAL / 2));
+        assert!(subscription.renew(start + SUBSCRIPTION_INTERVAL));
+        subscription.reset();
+        assert!(subscription.renew(start + SUBSCRIPTION_INTERVAL));
+    }
+}
@@ -0,0 +1,152 @@
+//! When the transaction queue warrants a new block.
+//!
+//! Once the minimum block time passed, a validator proposes its contribution for the next hbbft
+//! epoch if the transaction queue triggers a block. Triggering on the number of queued
+//! transactions alone makes low-traffic networks wait for the maximum block time, and lets
+//! high-traffic networks create many small blocks. The cumulative gas of the queue, the age of
+//! the oldest queued transaction and pending local transactions can trigger a block as well.
+
+use ethereum_types::{H256, U256};
+use ethjson::spec::HbbftParams;
+use std::collections::HashMap;
+
+/// The properties of a queued transaction the trigger looks at.
+pub struct QueuedTransaction {
+    /// The transaction hash.
+    pub hash: H256,
+    /// The transaction's gas limit.
+    pub gas: U256,
+    /// Whether the transaction was submitted to this node directly.
+    pub is_local: bool,
+}
+
+/// Decides whether the transaction queue warrants a new block.
+pub struct QueueTrigger {
+    /// The number of queued transactions that triggers a block.
+    size: usize,
+    /// The cumulative gas of the queued transactions that triggers a block.
+    gas: Option<U256>,
+    /// The number of seconds a transaction may wait in the queue before it triggers a block.
+    max_age: Option<u64>,
+    /// Whether a queued local transaction triggers a block.
+    local: bool,
+    /// The time each queued transaction was first seen, in UNIX seconds.
+    first_seen: HashMap<H256, u64>,
+}
+
+impl QueueTrigger {
+    /// Creates the trigger configured in the engine parameters.
+    pub fn new(params: &HbbftParams) -> Self {
+        QueueTrigger {
+            size: params.transaction_queue_size_trigger,
+            gas: params.transaction_queue_gas_trigger.map(Into::into),
+            max_age: params.transaction_queue_age_trigger,
+            local: params.local_transactions_trigger.unwrap_or(false),
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the queued transactions warrant a new block.
+    ///
+    /// The age of a transaction is measured from the first call that saw it in the queue, so this
+    /// should be called periodically.
+    pub fn is_reached(&mut self, queued: &[QueuedTransaction], now: u64) -> bool {
+        let first_seen = queued
+            .iter()
+            .map(|txn| {
+                let seen = self.first_seen.get(&txn.hash).cloned().unwrap_or(now);
+                (txn.hash, seen)
+            })
+            .collect();
+        self.first_seen = first_seen;
+
+        if queued.len() >= self.size {
+            return true;
+        }
+        if queued.is_empty() {
+            return false;
+        }
+        if self.local && queued.iter().any(|txn| txn.is_local) {
+            return true;
+        }
+        if let Some(gas) = self.gas {
+            let queued_gas = queued
+                .iter()
+                .fold(U256::zero(), |sum, txn| sum.saturating_add(txn.gas));
+            if queued_gas >= gas {
+                return true;
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            let oldest = self.first_seen.values().min().cloned().unwrap_or(now);
+            if now.saturating_sub(oldest) >= max_age {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(size: usize) -> QueueTrigger {
+        QueueTrigger {
+            size,
+            gas: None,
+            max_age: None,
+            local: false,
+            first_seen: HashMap::new(),
+        }
+    }
+
+    fn txn(n: u64, gas: u64, is_local: bool) -> QueuedTransaction {
+        QueuedTransaction {
+            hash: H256::from_low_u64_be(n),
+            gas: gas.into(),
+            is_local,
+        }
+    }
+
+    #[test]
+    fn queue_size_triggers() {
+        let mut trigger = trigger(2);
+        assert!(!trigger.is_reached(&[txn(1, 21_000, false)], 0));
+        assert!(trigger.is_reached(&[txn(1, 21_000, false), txn(2, 21_000, false)], 0));
+    }
+
+    #[test]
+    fn queued_gas_triggers() {
+        let mut trigger = QueueTrigger {
+            gas: Some(100_000.into()),
+            ..trigger(10)
+        };
+        assert!(!trigger.is_reached(&[txn(1, 60_000, false)], 0));
+        assert!(trigger.is_reached(&[txn(1, 60_000, false), txn(2, 40_000, false)], 0));
+    }
+
+    #[test]
+    fn oldest_transaction_triggers() {
+        let mut trigger = QueueTrigger {
+            max_age: Some(5),
+            ..trigger(10)
+        };
+        assert!(!trigger.is_reached(&[txn(1, 21_000, false)], 100));
+        assert!(!trigger.is_reached(&[txn(1, 21_000, false), txn(2, 21_000, false)], 104));
+        assert!(trigger.is_reached(&[txn(1, 21_000, false), txn(2, 21_000, false)], 105));
+        // Transactions that left the queue are forgotten.
+        assert!(!trigger.is_reached(&[txn(2, 21_000, false)], 106));
+        assert!(!trigger.is_reached(&[], 200));
+    }
+
+    #[test]
+    fn local_transactions_trigger() {
+        let mut trigger = QueueTrigger {
+            local: true,
+            ..trigger(10)
+        };
+        assert!(!trigger.is_reached(&[txn(1, 21_000, false)], 0));
+        assert!(trigger.is_reached(&[txn(1, 21_000, false), txn(2, 21_000, true)], 0));
+    }
+}
@@ -0,0 +1,177 @@
+//! Hashes of the transactions included in the most recent blocks.
+//!
+//! Validators propose transactions from their own queues, which may still contain transactions
+//! that were just included in a block. Such transactions would only be rejected for their nonce
+//! when the block is executed, after taking up space in the batch. The window only depends on the
+//! finalized chain, so all validators drop the same transactions from an agreed batch.
+//!
+//! A window missing some of its blocks would drop some of the included transactions but not
+//! others. If a block cannot be read, the window is invalid and drops nothing until all of its
+//! blocks were read again.
+
+use client::traits::{BlockChainClient, BlockInfo};
+use ethereum_types::H256;
+use std::collections::{HashSet, VecDeque};
+use types::{ids::BlockId, BlockNumber};
+
+/// The number of most recent blocks whose transactions are remembered.
+pub const RECENT_TRANSACTION_BLOCKS: u64 = 64;
+
+/// The transaction hashes of a window of consecutive blocks.
+pub struct RecentTransactions {
+    max_blocks: u64,
+    /// The number and transaction hashes of each block in the window, oldest first.
+    blocks: VecDeque<(BlockNumber, Vec<H256>)>,
+    hashes: HashSet<H256>,
+    /// Whether the window contains all of its blocks.
+    valid: bool,
+}
+
+impl Default for RecentTransactions {
+    fn default() -> Self {
+        RecentTransactions::new(RECENT_TRANSACTION_BLOCKS)
+    }
+}
+
+impl RecentTransactions {
+    pub fn new(max_blocks: u64) -> Self {
+        RecentTransactions {
+            max_blocks,
+            blocks: VecDeque::new(),
+            hashes: HashSet::new(),
+            valid: false,
+        }
+    }
+
+    /// Moves the window to end with the given block, reading the blocks missing from the client.
+    ///
+    /// If a block is missing, the window is invalidated, and read from scratch on the next update.
+    pub fn update(&mut self, client: &dyn BlockChainClient, latest: BlockNumber) {
+        let first = (latest + 1).saturating_sub(self.max_blocks);
+        let next = match self.blocks.back() {
+            Some((number, _)) if self.valid && *number + 1 >= first && *number <= latest => {
+                *number + 1
+            }
+            _ => {
+                // The window is invalid, empty, too old or ahead of the chain: read it from scratch.
+                self.invalidate();
+                first
+            }
+        };
+        for number in next..=latest {
+            match client.block(BlockId::Number(number)) {
+                Some(block) => self.insert_block(number, block.transaction_hashes()),
+                None => {
+                    warn!(target: "consensus", "Block {} is missing, cannot remember its transactions. No included transactions are dropped until it can be read.", number);
+                    self.invalidate();
+                    return;
+                }
+            }
+        }
+        self.valid = true;
+    }
+
+    /// Forgets all blocks, until the window is read from scratch.
+    pub fn invalidate(&mut self) {
+        self.blocks.clear();
+        self.hashes.clear();
+        self.valid = false;
+    }
+
+    /// Returns whether the window contains all of its blocks. If not, it must not be used to drop
+    /// transactions.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Adds the transactions of the block following the window, and evicts the oldest block if
+    /// the window is full.
+    fn insert_block(&mut self, number: BlockNumber, hashes: Vec<H256>) {
+        self.hashes.extend(hashes.iter().cloned());
+        self.blocks.push_back((number, hashes));
+        while self.blocks.len() as u64 > self.max_blocks {
+            if let Some((_, evicted)) = self.blocks.pop_front() {
+                for hash in evicted {
+                    self.hashes.remove(&hash);
+                }
+            }
+        }
+    }
+
+    /// Returns whether the transaction was included in one of the blocks of the window.
+    pub fn contains(&self, hash: &H256) -> bool {
+        self.hashes.contains(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::{EachBlockWith, TestBlockChainClient};
+
+    fn hashes(client: &TestBlockChainClient, number: BlockNumber) -> Vec<H256> {
+        client
+            .block(BlockId::Number(number))
+            .expect("the block was added")
+            .transaction_hashes()
+    }
+
+    #[test]
+    fn contains_the_transactions_of_the_latest_blocks() {
+        let client = TestBlockChainClient::new();
+        client.add_blocks(5, EachBlockWith::Transactions(2));
+        let mut recent = RecentTransactions::new(2);
+
+        recent.update(&client, 3);
+        assert!(hashes(&client, 1).iter().all(|h| !recent.contains(h)));
+        assert!(hashes(&client, 2).iter().all(|h| recent.contains(h)));
+        assert!(hashes(&client, 3).iter().all(|h| recent.contains(h)));
+
+        recent.update(&client, 4);
+        assert!(hashes(&client, 2).iter().all(|h| !recent.contains(h)));
+        assert!(hashes(&client, 4).iter().all(|h| recent.contains(h)));
+    }
+
+    #[test]
+    fn skips_blocks_older_than_the_window() {
+        let client = TestBlockChainClient::new();
+        client.add_blocks(5, EachBlockWith::Transactions(1));
+        let mut recent = RecentTransactions::new(2);
+
+        recent.update(&client, 1);
+        recent.update(&client, 5);
+        assert!(hashes(&client, 1).iter().all(|h| !recent.contains(h)));
+        assert!(hashes(&client, 3).iter().all(|h| !recent.contains(h)));
+        assert!(hashes(&client, 4).iter().all(|h| recent.contains(h)));
+        assert!(hashes(&client, 5).iter().all(|h| recent.contains(h)));
+    }
+
+    #[test]
+    fn is_invalid_until_no_block_is_missing() {
+        let client = TestBlockChainClient::new();
+        client.add_blocks(5, EachBlockWith::Transactions(1));
+        let mut recent = RecentTransactions::new(3);
+        assert!(!recent.is_valid());
+
+        recent.update(&client, 3);
+        assert!(recent.is_valid());
+
+        // Block 4 is missing: the window must not keep blocks 2 and 3 alone.
+        let hash = client
+            .numbers
+            .write()
+            .remove(&4)
+            .expect("the block was added");
+        recent.update(&client, 5);
+        assert!(!recent.is_valid());
+        assert!(hashes(&client, 3).iter().all(|h| !recent.contains(h)));
+        assert!(hashes(&client, 5).iter().all(|h| !recent.contains(h)));
+
+        // Once the block can be read, the whole window is read again.
+        client.numbers.write().insert(4, hash);
+        recent.update(&client, 5);
+        assert!(recent.is_valid());
+        assert!(hashes(&client, 2).iter().all(|h| !recent.contains(h)));
+        assert!((3..=5).all(|n| hashes(&client, n).iter().all(|h| recent.contains(h))));
+    }
+}
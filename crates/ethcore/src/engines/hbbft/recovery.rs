@@ -0,0 +1,137 @@
+//! Recovery blocks: a last-resort liveness fallback for prolonged consensus outages.
+//!
+//! If no block has been created for `recoveryBlockTimeout` seconds, a single designated
+//! validator may create an empty block sealed with its own ECDSA signature instead of the
+//! validators' threshold signature, created with its mining key. The designated validator
+//! rotates with the block number over the validator set of the parent block, sorted by mining
+//! address.
+//!
+//! A recovery block carries weaker guarantees than a regular block: it is only backed by one
+//! validator. It is therefore only accepted if the feature is enabled in the spec, the parent is
+//! at least the timeout old, the block contains no transactions and is sealed by the designated
+//! validator. It lets the chain advance past a stalled hbbft epoch, so the validators can start
+//! over with a fresh one.
+
+use crypto::publickey::{public_to_address, recover, Public, Signature};
+use ethereum_types::{Address, H256};
+use rlp::{Rlp, RlpStream};
+use std::collections::BTreeMap;
+
+/// Marks the seal of a recovery block. Regular seals are a single RLP string, recovery seals
+/// are a list starting with this marker.
+const RECOVERY_SEAL_MARKER: &[u8] = b"hbbft-recovery";
+
+/// Encodes the seal of a recovery block signed with the given ECDSA signature.
+pub fn encode_recovery_seal(signature: &Signature) -> Vec<u8> {
+    let mut s = RlpStream::new_list(2);
+    s.append(&RECOVERY_SEAL_MARKER.to_vec());
+    s.append(&signature.to_vec());
+    s.out()
+}
+
+/// Returns the signature if the given seal field is a recovery seal.
+pub fn decode_recovery_seal(seal: &[u8]) -> Option<Signature> {
+    let rlp = Rlp::new(seal);
+    if !rlp.is_list() || rlp.item_count().ok()? != 2 {
+        return None;
+    }
+    if rlp.at(0).ok()?.data().ok()? != RECOVERY_SEAL_MARKER {
+        return None;
+    }
+    let data = rlp.at(1).ok()?.data().ok()?;
+    if data.len() != 65 {
+        return None;
+    }
+    let mut bytes = [0u8; 65];
+    bytes.copy_from_slice(data);
+    Some(Signature::from(bytes))
+}
+
+/// Returns true if the given seal field is a recovery seal.
+pub fn is_recovery_seal(seal: &[u8]) -> bool {
+    decode_recovery_seal(seal).is_some()
+}
+
+/// Returns the mining address of the validator allowed to create a recovery block with the
+/// given number, given the validators of its parent block.
+pub fn designated_proposer(
+    validators: &BTreeMap<Address, Public>,
+    block_number: u64,
+) -> Option<&Address> {
+    if validators.is_empty() {
+        return None;
+    }
+    let index = (block_number % validators.len() as u64) as usize;
+    validators.keys().nth(index)
+}
+
+/// Returns true if a block with the given timestamp may be a recovery block for its parent.
+pub fn stall_exceeded(parent_timestamp: u64, timestamp: u64, timeout: u64) -> bool {
+    timestamp >= parent_timestamp.saturating_add(timeout)
+}
+
+/// Returns true if the signature over the block's bare hash was created by the designated proposer.
+pub fn is_signed_by_designated_proposer(
+    validators: &BTreeMap<Address, Public>,
+    block_number: u64,
+    bare_hash: &H256,
+    signature: &Signature,
+) -> bool {
+    match (
+        designated_proposer(validators, block_number),
+        recover(signature, bare_hash),
+    ) {
+        (Some(designated), Ok(signer)) => *designated == public_to_address(&signer),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{sign, Generator, KeyPair, Random};
+    use rlp;
+
+    fn validators(n: usize) -> (Vec<KeyPair>, BTreeMap<Address, Public>) {
+        let keys: Vec<KeyPair> = (0..n).map(|_| Random.generate()).collect();
+        let map = keys
+            .iter()
+            .map(|k| (public_to_address(k.public()), *k.public()))
+            .collect();
+        (keys, map)
+    }
+
+    #[test]
+    fn recovery_seal_roundtrip() {
+        let key = Random.generate();
+        let signature = sign(key.secret(), &H256::from_low_u64_be(7)).unwrap();
+        let seal = encode_recovery_seal(&signature);
+        assert_eq!(decode_recovery_seal(&seal), Some(signature));
+        assert!(!is_recovery_seal(&rlp::encode(&vec![1u8; 96])));
+    }
+
+    #[test]
+    fn only_the_designated_proposer_is_accepted() {
+        let (keys, validators) = validators(4);
+        let hash = H256::from_low_u64_be(42);
+        for block_number in 10..14 {
+            let designated = designated_proposer(&validators, block_number).unwrap();
+            let accepted: Vec<_> = keys
+                .iter()
+                .filter(|k| {
+                    let signature = sign(k.secret(), &hash).unwrap();
+                    is_signed_by_designated_proposer(&validators, block_number, &hash, &signature)
+                })
+                .collect();
+            assert_eq!(accepted.len(), 1);
+            assert_eq!(accepted[0].address(), *designated);
+        }
+        assert_eq!(designated_proposer(&BTreeMap::new(), 1), None);
+    }
+
+    #[test]
+    fn stall_must_reach_the_timeout() {
+        assert!(!stall_exceeded(100, 159, 60));
+        assert!(stall_exceeded(100, 160, 60));
+    }
+}
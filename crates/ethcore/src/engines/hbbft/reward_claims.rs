@@ -0,0 +1,143 @@
+//! Automatic claiming of the staking rewards and ordered withdrawals of the node's pool.
+//!
+//! Rewards are paid to the staking address, not to the mining address the engine signs with, so
+//! the claim transactions are signed with a separately configured key of the staking address.
+
+use engines::signer::EngineSigner;
+use ethereum_types::{Address, U256};
+use types::transaction::{Action, Transaction, TypedTransaction};
+
+/// The maximum number of staking epochs claimed in one transaction, to bound its gas usage.
+pub const MAX_CLAIMED_EPOCHS: u64 = 32;
+
+/// The gas price of claim transactions, as used for the key generation transactions.
+const CLAIM_GAS_PRICE: u64 = 10_000_000_000;
+
+/// Claims the rewards of a pool's staker every `interval` POSDAO epochs.
+pub struct RewardClaimer {
+    signer: Box<dyn EngineSigner>,
+    interval: u64,
+    last_claim: Option<u64>,
+    /// The first staking epoch not known to be claimed already.
+    next_unclaimed: Option<u64>,
+}
+
+impl RewardClaimer {
+    pub fn new(signer: Box<dyn EngineSigner>, interval: u64) -> Self {
+        RewardClaimer {
+            signer,
+            interval: interval.max(1),
+            last_claim: None,
+            next_unclaimed: None,
+        }
+    }
+
+    /// The staking address the rewards are claimed for.
+    pub fn address(&self) -> Address {
+        self.signer.address()
+    }
+
+    /// Returns whether claiming is due in the given POSDAO epoch, and records the attempt.
+    pub fn is_due(&mut self, posdao_epoch: u64) -> bool {
+        match self.last_claim {
+            Some(last) if posdao_epoch < last + self.interval => false,
+            _ => {
+                self.last_claim = Some(posdao_epoch);
+                true
+            }
+        }
+    }
+
+    /// Returns up to `MAX_CLAIMED_EPOCHS` finished staking epochs before `posdao_epoch` whose
+    /// reward is not claimed yet, starting at `first_epoch` or after the previously claimed ones.
+    pub fn unclaimed_epochs<E, F>(
+        &mut self,
+        first_epoch: u64,
+        posdao_epoch: u64,
+        mut was_taken: F,
+    ) -> Result<Vec<u64>, E>
+    where
+        F: FnMut(u64) -> Result<bool, E>,
+    {
+        let start = self.next_unclaimed.unwrap_or(0).max(first_epoch);
+        let mut epochs = Vec::new();
+        let mut next_unclaimed = None;
+        for epoch in start..posdao_epoch {
+            if was_taken(epoch)? {
+                continue;
+            }
+            next_unclaimed.get_or_insert(epoch);
+            if epochs.len() as u64 == MAX_CLAIMED_EPOCHS {
+                break;
+            }
+            epochs.push(epoch);
+        }
+        self.next_unclaimed = Some(next_unclaimed.unwrap_or(posdao_epoch));
+        Ok(epochs)
+    }
+
+    /// Signs a call of the staking contract, and returns the RLP encoded transaction.
+    pub fn sign_call(
+        &self,
+        contract: Address,
+        data: Vec<u8>,
+        gas: U256,
+        nonce: U256,
+        chain_id: Option<u64>,
+    ) -> Result<Vec<u8>, String> {
+        let transaction = TypedTransaction::Legacy(Transaction {
+            nonce,
+            gas_price: CLAIM_GAS_PRICE.into(),
+            gas,
+            action: Action::Call(contract),
+            value: U256::zero(),
+            data,
+        });
+        let signature = self
+            .signer
+            .sign(transaction.signature_hash(chain_id))
+            .map_err(|e| format!("Could not sign the claim transaction: {}", e))?;
+        Ok(transaction.with_signature(signature, chain_id).encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{Generator, Random};
+    use engines::signer::from_keypair;
+    use std::convert::Infallible;
+
+    fn new_claimer(interval: u64) -> RewardClaimer {
+        RewardClaimer::new(from_keypair(Random.generate()), interval)
+    }
+
+    #[test]
+    fn claims_are_due_every_interval() {
+        let mut claimer = new_claimer(3);
+        assert!(claimer.is_due(10));
+        assert!(!claimer.is_due(10));
+        assert!(!claimer.is_due(12));
+        assert!(claimer.is_due(13));
+    }
+
+    #[test]
+    fn claimed_epochs_are_skipped() {
+        let mut claimer = new_claimer(1);
+        let taken = |epoch| Ok::<_, Infallible>(epoch % 2 == 0);
+        assert_eq!(claimer.unclaimed_epochs(3, 10, taken), Ok(vec![3, 5, 7, 9]));
+        // Epochs sent in a claim transaction are checked again, in case it failed.
+        assert_eq!(
+            claimer.unclaimed_epochs(3, 12, |_| Ok::<_, Infallible>(false)),
+            Ok(vec![3, 4, 5, 6, 7, 8, 9, 10, 11])
+        );
+
+        let mut claimer = new_claimer(1);
+        let epochs = claimer.unclaimed_epochs(0, 100, |_| Ok::<_, Infallible>(false));
+        assert_eq!(epochs.unwrap().len() as u64, MAX_CLAIMED_EPOCHS);
+        assert_eq!(
+            claimer.unclaimed_epochs(0, 100, |epoch| Ok::<_, Infallible>(epoch < 50)),
+            Ok((50..82).collect())
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! The engine's single source of randomness.
+//!
+//! The hbbft crate expects generators implementing the `rand` 0.6 traits, while the rest of
+//! `ethcore` uses `rand` 0.7. All randomness of the engine - the random data of contributions,
+//! threshold encryption and key generation - is drawn through [`engine_rng`], which implements
+//! the `RngCore` traits of both versions on top of the same generator.
+//!
+//! By default that generator is `rand_065::thread_rng`, a cryptographically secure generator
+//! seeded from, and periodically reseeded with, operating system entropy. Tests can replace it
+//! for the current thread with a deterministic one using [`seed_thread_rng`].
+
+use rand;
+use rand_065::{
+    self,
+    rngs::{StdRng, ThreadRng},
+    CryptoRng, RngCore, SeedableRng,
+};
+use std::{cell::RefCell, marker::PhantomData};
+
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<StdRng>> = RefCell::new(None);
+}
+
+/// A handle to the engine's random number generator on the current thread.
+///
+/// It is neither `Send` nor `Sync`: the generator is thread local, like `rand`'s `ThreadRng`.
+pub struct EngineRng {
+    thread_rng: ThreadRng,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Returns the engine's random number generator for the current thread.
+pub fn engine_rng() -> EngineRng {
+    EngineRng {
+        thread_rng: rand_065::thread_rng(),
+        _not_send: PhantomData,
+    }
+}
+
+/// Makes the engine's randomness on the current thread deterministic, for tests.
+#[cfg(test)]
+pub fn seed_thread_rng(seed: u64) {
+    SEEDED_RNG.with(|rng| *rng.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+}
+
+impl EngineRng {
+    fn with_rng<T, F: FnOnce(&mut dyn RngCore) -> T>(&mut self, f: F) -> T {
+        let thread_rng = &mut self.thread_rng;
+        SEEDED_RNG.with(|seeded| match seeded.borrow_mut().as_mut() {
+            Some(rng) => f(rng),
+            None => f(thread_rng),
+        })
+    }
+}
+
+impl RngCore for EngineRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with_rng(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with_rng(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with_rng(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_065::Error> {
+        self.with_rng(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+impl CryptoRng for EngineRng {}
+
+impl rand::RngCore for EngineRng {
+    fn next_u32(&mut self) -> u32 {
+        RngCore::next_u32(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RngCore::fill_bytes(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RngCore::fill_bytes(self, dest);
+        Ok(())
+    }
+}
+
+impl rand::CryptoRng for EngineRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    fn draw() -> (u64, u64) {
+        let mut rng = engine_rng();
+        (
+            RngCore::next_u64(&mut rng),
+            rand::RngCore::next_u64(&mut rng),
+        )
+    }
+
+    #[test]
+    fn seeded_randomness_is_deterministic() {
+        seed_thread_rng(42);
+        let first = draw();
+        seed_thread_rng(42);
+        assert_eq!(draw(), first);
+        seed_thread_rng(43);
+        assert_ne!(draw(), first);
+    }
+
+    #[test]
+    fn both_rand_versions_share_the_stream() {
+        seed_thread_rng(7);
+        let (a, b) = draw();
+        seed_thread_rng(7);
+        let mut rng = engine_rng();
+        assert_eq!(rand::RngCore::next_u64(&mut rng), a);
+        assert_eq!(RngCore::next_u64(&mut rng), b);
+    }
+}
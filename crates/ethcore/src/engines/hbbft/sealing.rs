@@ -1,7 +1,12 @@
 use super::NodeId;
 use hbbft::{crypto::Signature, threshold_sign::ThresholdSign, NetworkInfo};
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
-use std::{result, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    mem, result,
+    sync::Arc,
+};
+use types::BlockNumber;
 
 pub use hbbft::threshold_sign::{Message, Result};
 
@@ -48,6 +53,99 @@ impl Sealing {
     }
 }
 
+/// The number of most recently completed seals whose late shares are ignored.
+const MAX_COMPLETED_SEALS: usize = 32;
+
+/// Filters signature shares that need no processing: repeated shares of a sender, and shares
+/// arriving after the seal was already complete.
+#[derive(Default)]
+pub struct SealShareFilter {
+    senders: BTreeMap<BlockNumber, BTreeSet<NodeId>>,
+    completed: VecDeque<BlockNumber>,
+}
+
+impl SealShareFilter {
+    /// Returns true if the sender's share for the block needs to be processed.
+    pub fn is_new(&self, block_num: BlockNumber, sender_id: &NodeId) -> bool {
+        !self.completed.contains(&block_num)
+            && !self
+                .senders
+                .get(&block_num)
+                .map_or(false, |senders| senders.contains(sender_id))
+    }
+
+    /// Records that the sender's share for the block was processed.
+    pub fn insert(&mut self, block_num: BlockNumber, sender_id: NodeId) {
+        self.senders.entry(block_num).or_default().insert(sender_id);
+    }
+
+    /// Records that the block's seal is complete.
+    pub fn complete(&mut self, block_num: BlockNumber) {
+        self.senders.remove(&block_num);
+        if self.completed.contains(&block_num) {
+            return;
+        }
+        if self.completed.len() >= MAX_COMPLETED_SEALS {
+            self.completed.pop_front();
+        }
+        self.completed.push_back(block_num);
+    }
+
+    /// Forgets the senders of blocks below the given one, which are no longer sealed.
+    pub fn prune(&mut self, block_num: BlockNumber) {
+        self.senders = self.senders.split_off(&block_num);
+    }
+}
+
+/// The number of blocks after the next one whose signature shares are cached.
+const MAX_FUTURE_SEAL_BLOCKS: u64 = 2;
+
+/// Signature shares that arrived before the parent of their block was imported.
+///
+/// The validators sealing a block are only known once its parent is imported, so a fast proposer's
+/// shares can arrive too early to be processed. They are kept until the parent is imported, at
+/// most one per sender and block.
+#[derive(Default)]
+pub struct FutureSealShares {
+    shares: BTreeMap<BlockNumber, BTreeMap<NodeId, Message>>,
+}
+
+impl FutureSealShares {
+    /// Caches a share, unless its block is too far ahead of the latest block.
+    ///
+    /// Returns whether the share was cached.
+    pub fn insert(
+        &mut self,
+        latest: BlockNumber,
+        block_num: BlockNumber,
+        sender_id: NodeId,
+        message: Message,
+    ) -> bool {
+        if block_num > latest + 1 + MAX_FUTURE_SEAL_BLOCKS {
+            return false;
+        }
+        self.shares
+            .entry(block_num)
+            .or_default()
+            .insert(sender_id, message);
+        true
+    }
+
+    /// Removes and returns the shares of the block after the latest one. Shares of blocks that
+    /// are already imported are dropped.
+    pub fn take_ready(&mut self, latest: BlockNumber) -> Vec<(BlockNumber, NodeId, Message)> {
+        let future = self.shares.split_off(&(latest + 2));
+        let mut ready = mem::replace(&mut self.shares, future);
+        let block_num = latest + 1;
+        ready
+            .remove(&block_num)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(sender_id, message)| (block_num, sender_id, message))
+            .collect()
+    }
+}
+
 /// Wrapper for `Signature` to simplify RLP encoding and decoding.
 #[derive(PartialEq, Debug)]
 pub struct RlpSig<T>(pub T);
@@ -62,8 +160,12 @@ const RLP_ERR: &str = "RLP bytes don't encode a valid signature";
 
 impl Decodable for RlpSig<Signature> {
     fn decode(rlp: &Rlp) -> result::Result<Self, DecoderError> {
+        let data = rlp.data()?;
+        if data.len() != 96 {
+            return Err(DecoderError::RlpInvalidLength);
+        }
         let mut seal_bytes = [0u8; 96];
-        seal_bytes.copy_from_slice(rlp.data()?);
+        seal_bytes.copy_from_slice(data);
         let sig = Signature::from_bytes(seal_bytes).map_err(|_| DecoderError::Custom(RLP_ERR))?;
         Ok(RlpSig(sig))
     }
@@ -72,9 +174,50 @@ impl Decodable for RlpSig<Signature> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crypto::publickey::Public;
+    use hbbft::crypto::SignatureShare;
     use rand_065;
     use rlp;
 
+    #[test]
+    fn repeated_and_late_shares_are_filtered() {
+        let sender = NodeId::default();
+        let mut filter = SealShareFilter::default();
+        assert!(filter.is_new(5, &sender));
+        filter.insert(5, sender);
+        assert!(!filter.is_new(5, &sender));
+        assert!(filter.is_new(6, &sender));
+
+        filter.complete(6);
+        assert!(!filter.is_new(6, &sender));
+        for block_num in 7..(7 + MAX_COMPLETED_SEALS as u64) {
+            filter.complete(block_num);
+        }
+        assert!(filter.is_new(6, &sender));
+
+        filter.prune(6);
+        assert!(filter.is_new(5, &sender));
+    }
+
+    #[test]
+    fn future_shares_are_taken_once_their_parent_is_imported() {
+        let share = || Message(SignatureShare(rand_065::random()));
+        let (a, b) = (NodeId::default(), NodeId(Public::from_low_u64_be(1)));
+        let mut shares = FutureSealShares::default();
+        assert!(shares.insert(10, 12, a, share()));
+        assert!(shares.insert(10, 12, b, share()));
+        assert!(shares.insert(10, 13, a, share()));
+        assert!(!shares.insert(10, 14, a, share()));
+
+        assert!(shares.take_ready(10).is_empty());
+        let ready = shares.take_ready(11);
+        assert_eq!(ready.len(), 2);
+        assert!(ready.iter().all(|(block_num, _, _)| *block_num == 12));
+        assert!(shares.take_ready(11).is_empty());
+        // The shares of block 13 are dropped once it is imported.
+        assert!(shares.take_ready(13).is_empty());
+    }
+
     #[test]
     fn test_rlp_signature() {
         let sig: Signature = rand_065::random();
@@ -0,0 +1,242 @@
+//! Consistency checks of the engine parameters and the genesis state.
+//!
+//! A misconfigured chain spec otherwise only shows up at runtime, e.g. as a node that never
+//! creates a block, or as failing contract calls during the first key generation.
+
+use bytes::Bytes;
+use ethereum_types::Address;
+use ethjson::spec::{HbbftKeygenGasPrice, HbbftParams};
+use pod_state::PodState;
+
+use super::{
+    contracts::{
+        keygen_history::KEYGEN_HISTORY_ADDRESS, staking::STAKING_CONTRACT_ADDRESS,
+        validator_set::VALIDATOR_SET_ADDRESS,
+    },
+    extra_data,
+    keygen_transactions::DEFAULT_KEYGEN_GAS_PRICE,
+};
+
+/// The default maximum number of validators, matching the validator set contract's
+/// `MAX_VALIDATORS`.
+pub const DEFAULT_MAX_VALIDATORS: usize = 25;
+
+/// Returns an error describing the first inconsistency found in the engine parameters.
+pub fn validate_params(params: &HbbftParams) -> Result<(), String> {
+    if params.maximum_block_time < params.minimum_block_time {
+        return Err(format!(
+            "maximumBlockTime ({}) must not be less than minimumBlockTime ({}).",
+            params.maximum_block_time, params.minimum_block_time
+        ));
+    }
+    if params.minimum_block_time == 0 && params.transaction_queue_size_trigger == 0 {
+        return Err(
+            "transactionQueueSizeTrigger must be greater than 0 if minimumBlockTime is 0.".into(),
+        );
+    }
+    for (name, address) in &[
+        (
+            "blockRewardContractAddress",
+            params.block_reward_contract_address,
+        ),
+        (
+            "randomnessContractAddress",
+            params.randomness_contract_address,
+        ),
+        (
+            "featureRegistryContractAddress",
+            params.feature_registry_contract_address,
+        ),
+        (
+            "minGasPriceContractAddress",
+            params.min_gas_price_contract_address,
+        ),
+        (
+            "blockGasLimitContractAddress",
+            params.block_gas_limit_contract_address,
+        ),
+    ] {
+        if *address == Some(Address::zero()) {
+            return Err(format!("{} must not be the zero address.", name));
+        }
+    }
+    if params.max_contribution_transactions == Some(0) || params.max_contribution_bytes == Some(0) {
+        return Err("maxContributionTransactions and maxContributionBytes must not be 0.".into());
+    }
+    if let Some(minimum) = params.minimum_gas_price {
+        // Part and Acks transactions below the minimum would never be included.
+        let below_minimum = match params
+            .keygen_gas_price
+            .unwrap_or(HbbftKeygenGasPrice::Fixed(DEFAULT_KEYGEN_GAS_PRICE))
+        {
+            HbbftKeygenGasPrice::Fixed(price) => price < minimum,
+            HbbftKeygenGasPrice::MedianPercent(percent) => percent < 100,
+            HbbftKeygenGasPrice::Zero => minimum > 0,
+        };
+        if below_minimum {
+            return Err(format!(
+                "keygenGasPrice must not be below minimumGasPrice ({}).",
+                minimum
+            ));
+        }
+    }
+    if params.activation_block.unwrap_or(0) > 0 && params.legacy_authority_round.is_none() {
+        return Err(
+            "legacyAuthorityRound must be set to verify the blocks before activationBlock.".into(),
+        );
+    }
+    Ok(())
+}
+
+/// Returns an error if the extra data of a block with the largest validator set would exceed
+/// `maximumExtraDataSize`, see `extra_data` for its layout.
+pub fn validate_extra_data_size(
+    params: &HbbftParams,
+    maximum_extra_data_size: usize,
+) -> Result<(), String> {
+    let max_validators = params.max_validators.unwrap_or(DEFAULT_MAX_VALIDATORS);
+    let required = extra_data::size(
+        params.randomness_contract_address.is_some(),
+        params.block_reward_contributors_transition.is_some(),
+        max_validators,
+    );
+    if required > maximum_extra_data_size {
+        return Err(format!(
+            "maximumExtraDataSize ({}) must be at least {} to fit the random seed and the \
+             contributor bits of {} validators.",
+            maximum_extra_data_size, required, max_validators
+        ));
+    }
+    Ok(())
+}
+
+/// Returns an error naming the hbbft system contracts missing from the genesis state.
+pub fn validate_genesis(
+    genesis: &PodState,
+    constructors: &[(Address, Bytes)],
+) -> Result<(), String> {
+    let missing: Vec<String> = [
+        ("validator set", *VALIDATOR_SET_ADDRESS),
+        ("staking", *STAKING_CONTRACT_ADDRESS),
+        ("key generation history", *KEYGEN_HISTORY_ADDRESS),
+    ]
+    .iter()
+    .filter(|(_, address)| !has_code(genesis, constructors, address))
+    .map(|(name, address)| format!("the {} contract at {:?}", name, address))
+    .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The genesis state lacks {}, which the hbbft engine requires.",
+            missing.join(", ")
+        ))
+    }
+}
+
+fn has_code(genesis: &PodState, constructors: &[(Address, Bytes)], address: &Address) -> bool {
+    constructors.iter().any(|(a, _)| a == address)
+        || genesis
+            .get()
+            .get(address)
+            .and_then(|account| account.code.as_ref())
+            .map_or(false, |code| !code.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn params(json: &str) -> HbbftParams {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn inconsistent_params_are_rejected() {
+        let valid =
+            r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1}"#;
+        assert_eq!(validate_params(&params(valid)), Ok(()));
+
+        let slower_max =
+            r#"{"minimumBlockTime": 10, "maximumBlockTime": 5, "transactionQueueSizeTrigger": 1}"#;
+        assert!(validate_params(&params(slower_max))
+            .unwrap_err()
+            .starts_with("maximumBlockTime"));
+
+        let no_trigger =
+            r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 0}"#;
+        assert!(validate_params(&params(no_trigger))
+            .unwrap_err()
+            .starts_with("transactionQueueSizeTrigger"));
+
+        let zero_reward = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "blockRewardContractAddress": "0x0000000000000000000000000000000000000000"}"#;
+        assert!(validate_params(&params(zero_reward))
+            .unwrap_err()
+            .starts_with("blockRewardContractAddress"));
+        let zero_gas_limit = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "blockGasLimitContractAddress": "0x0000000000000000000000000000000000000000"}"#;
+        assert!(validate_params(&params(zero_gas_limit))
+            .unwrap_err()
+            .starts_with("blockGasLimitContractAddress"));
+
+        let cheap_keygen = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "minimumGasPrice": 20000000000}"#;
+        assert!(validate_params(&params(cheap_keygen))
+            .unwrap_err()
+            .starts_with("keygenGasPrice"));
+        let keygen_above_minimum = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "minimumGasPrice": 20000000000, "keygenGasPrice": { "medianPercent": 150 }}"#;
+        assert_eq!(validate_params(&params(keygen_above_minimum)), Ok(()));
+
+        let no_legacy_engine = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "activationBlock": 1000}"#;
+        assert!(validate_params(&params(no_legacy_engine))
+            .unwrap_err()
+            .starts_with("legacyAuthorityRound"));
+        let legacy_engine = r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "activationBlock": 1000, "legacyAuthorityRound": {"stepDuration": 5,
+            "validators": {"list": ["0x4000000000000000000000000000000000000001"]}}}"#;
+        assert_eq!(validate_params(&params(legacy_engine)), Ok(()));
+    }
+
+    #[test]
+    fn extra_data_must_fit_seed_and_contributors() {
+        let seed_only = params(
+            r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "randomnessContractAddress": "0x3000000000000000000000000000000000000001"}"#,
+        );
+        assert_eq!(validate_extra_data_size(&seed_only, 32), Ok(()));
+
+        let seed_and_contributors = params(
+            r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "randomnessContractAddress": "0x3000000000000000000000000000000000000001",
+            "blockRewardContributorsTransition": 0}"#,
+        );
+        assert!(validate_extra_data_size(&seed_and_contributors, 32)
+            .unwrap_err()
+            .starts_with("maximumExtraDataSize"));
+        // 25 validators need 4 bytes of contributor bits.
+        assert_eq!(validate_extra_data_size(&seed_and_contributors, 36), Ok(()));
+
+        let more_validators = params(
+            r#"{"minimumBlockTime": 0, "maximumBlockTime": 600, "transactionQueueSizeTrigger": 1,
+            "randomnessContractAddress": "0x3000000000000000000000000000000000000001",
+            "blockRewardContributorsTransition": 0, "maxValidators": 100}"#,
+        );
+        assert!(validate_extra_data_size(&more_validators, 36).is_err());
+        assert_eq!(validate_extra_data_size(&more_validators, 45), Ok(()));
+    }
+
+    #[test]
+    fn missing_system_contracts_are_named() {
+        let constructors = vec![
+            (*VALIDATOR_SET_ADDRESS, vec![1]),
+            (*STAKING_CONTRACT_ADDRESS, vec![1]),
+        ];
+        let error = validate_genesis(&PodState::new(), &constructors).unwrap_err();
+        assert!(error.contains("key generation history"));
+        assert!(!error.contains("staking"));
+    }
+}
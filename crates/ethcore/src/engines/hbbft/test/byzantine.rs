@@ -0,0 +1,216 @@
+//! Byzantine validators tampering with the consensus messages and contributions they send.
+//!
+//! Each test switches to four validators, one of them byzantine, and checks that the honest
+//! validators keep finalizing blocks and report the byzantine one as faulty.
+
+use super::{
+    super::{
+        api::HbbftNotify,
+        hbbft_engine::{tamper_packet, Tampering},
+    },
+    hbbft_test_client::{hbbft_engine, HbbftTestClient},
+    network_simulator::crank_byzantine_network,
+    posdao_epoch::{crank_until_with, switch_to_validators_with},
+};
+use crypto::publickey::{KeyPair, Public};
+use engines::signer::from_keypair;
+use ethereum_types::Address;
+use parking_lot::{Mutex, RwLock};
+use std::sync::Arc;
+
+/// The number of validators, of which one may be faulty.
+const NUM_VALIDATORS: usize = 4;
+
+/// The number of blocks the honest validators have to finalize.
+const BLOCKS: u64 = 3;
+
+/// The number of network cranks finalizing the blocks may take.
+const MAX_CRANKS: usize = 100;
+
+/// The index of the byzantine validator's client. The master of ceremonies comes first.
+const BYZANTINE: usize = 1;
+
+/// A validator of the simulated network that tampers with what it sends.
+pub struct MaliciousHbbftTestClient {
+    /// The index of the validator's client in the network.
+    index: usize,
+    /// How the validator tampers with its consensus messages, if at all.
+    tampering: Option<Tampering>,
+}
+
+impl MaliciousHbbftTestClient {
+    pub fn new(index: usize, tampering: Option<Tampering>) -> Self {
+        MaliciousHbbftTestClient { index, tampering }
+    }
+
+    fn keypair(&self, clients: &Vec<RwLock<HbbftTestClient>>) -> KeyPair {
+        clients[self.index].read().keypair.clone()
+    }
+
+    pub fn address(&self, clients: &Vec<RwLock<HbbftTestClient>>) -> Address {
+        self.keypair(clients).address()
+    }
+
+    /// Makes the validator propose the given entries in its next contribution, although they
+    /// are no transactions.
+    pub fn propose_malformed_entries(
+        &self,
+        clients: &Vec<RwLock<HbbftTestClient>>,
+        entries: Vec<Vec<u8>>,
+    ) {
+        hbbft_engine(clients[self.index].read().client.engine())
+            .inject_contribution_entries(entries);
+    }
+
+    /// Cranks the network, tampering with the consensus messages the validator sends.
+    pub fn crank(&self, clients: &Vec<RwLock<HbbftTestClient>>) {
+        let keypair = self.keypair(clients);
+        let node_id: Public = keypair.public().clone();
+        let signer = from_keypair(keypair);
+        crank_byzantine_network(clients, &node_id, |packet| match self.tampering {
+            Some(tampering) => tamper_packet(&packet, tampering, Some(&*signer))
+                .expect("The validator's own packets must decode"),
+            None => vec![packet],
+        });
+    }
+}
+
+/// Records the validators an engine reports as faulty.
+#[derive(Default)]
+struct FaultRecorder {
+    faulty: Mutex<Vec<Address>>,
+}
+
+impl HbbftNotify for FaultRecorder {
+    fn validator_faulty(&self, validator: Address, _fault: &str) {
+        self.faulty.lock().push(validator);
+    }
+}
+
+/// Registers a fault recorder with each client's engine.
+fn record_faults(clients: &Vec<RwLock<HbbftTestClient>>) -> Vec<Arc<FaultRecorder>> {
+    clients
+        .iter()
+        .map(|c| {
+            let recorder = Arc::new(FaultRecorder::default());
+            hbbft_engine(c.read().client.engine()).add_notify(recorder.clone());
+            recorder
+        })
+        .collect()
+}
+
+/// Asserts that every honest validator reported the byzantine one.
+fn assert_reported(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    recorders: &[Arc<FaultRecorder>],
+    malicious: &MaliciousHbbftTestClient,
+) {
+    let address = malicious.address(clients);
+    for (index, recorder) in recorders.iter().enumerate().skip(1) {
+        if index != malicious.index {
+            assert!(
+                recorder.faulty.lock().contains(&address),
+                "Validator {} did not report the byzantine validator",
+                index
+            );
+        }
+    }
+}
+
+/// Switches to the validators, with the byzantine one tampering from the start, and returns
+/// the clients, the transactor and the fault recorders of all clients.
+fn switch_with_byzantine_validator(
+    malicious: &MaliciousHbbftTestClient,
+) -> (
+    Vec<RwLock<HbbftTestClient>>,
+    KeyPair,
+    Vec<Arc<FaultRecorder>>,
+) {
+    let mut recorders = Vec::new();
+    let (clients, transactor) = switch_to_validators_with(NUM_VALIDATORS, |clients| {
+        if recorders.is_empty() {
+            recorders = record_faults(clients);
+        }
+        malicious.crank(clients);
+    });
+    (clients, transactor, recorders)
+}
+
+/// Cranks the network until all clients imported `BLOCKS` more blocks.
+fn finalize_blocks(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    transactor: &KeyPair,
+    malicious: &MaliciousHbbftTestClient,
+    phase: &str,
+) {
+    let start = clients[0].read().client.chain().best_block_number();
+    crank_until_with(
+        clients,
+        transactor,
+        phase,
+        MAX_CRANKS,
+        |clients| malicious.crank(clients),
+        |c| c.client.chain().best_block_number() >= start + BLOCKS,
+    );
+}
+
+#[test]
+fn malformed_contributions_are_reported() {
+    let malicious = MaliciousHbbftTestClient::new(BYZANTINE, None);
+    let (clients, transactor, recorders) = switch_with_byzantine_validator(&malicious);
+
+    malicious.propose_malformed_entries(&clients, vec![b"not a transaction".to_vec()]);
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &malicious,
+        "Consensus with a malformed contribution",
+    );
+    assert_reported(&clients, &recorders, &malicious);
+}
+
+#[test]
+fn garbage_honey_badger_messages_are_reported() {
+    let malicious = MaliciousHbbftTestClient::new(BYZANTINE, None);
+    let (clients, transactor, recorders) = switch_with_byzantine_validator(&malicious);
+
+    let malicious = MaliciousHbbftTestClient::new(BYZANTINE, Some(Tampering::GarbageHoneyBadger));
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &malicious,
+        "Consensus with garbage Honey Badger messages",
+    );
+    assert_reported(&clients, &recorders, &malicious);
+}
+
+#[test]
+fn sealing_shares_for_the_wrong_block_are_reported() {
+    let malicious = MaliciousHbbftTestClient::new(BYZANTINE, None);
+    let (clients, transactor, recorders) = switch_with_byzantine_validator(&malicious);
+
+    let malicious =
+        MaliciousHbbftTestClient::new(BYZANTINE, Some(Tampering::WrongBlockSealingShares));
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &malicious,
+        "Consensus with sealing shares for the wrong block",
+    );
+    assert_reported(&clients, &recorders, &malicious);
+}
+
+#[test]
+fn duplicate_parts_are_reported() {
+    // The Parts are sent during the key generation of the switch itself.
+    let malicious = MaliciousHbbftTestClient::new(BYZANTINE, Some(Tampering::DuplicateParts));
+    let (clients, transactor, recorders) = switch_with_byzantine_validator(&malicious);
+
+    assert_reported(&clients, &recorders, &malicious);
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &malicious,
+        "Consensus after duplicate Parts",
+    );
+}
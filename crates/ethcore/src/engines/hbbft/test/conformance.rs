@@ -0,0 +1,111 @@
+//! Conformance checks of the engine against data recorded on real networks.
+//!
+//! Every JSON file in `res/hbbft_conformance` is a fixture containing the chain spec of a
+//! recorded network and the RLP encoded blocks of a few POSDAO epochs, together with the
+//! decisions the engine is expected to reproduce for each of them: whether the block's seal
+//! is accepted and which POSDAO epoch the chain is in after importing it. Hand-made fixtures
+//! cover blocks that must be rejected on any network, e.g. a forged block on an unknown fork.
+
+use super::super::contracts::staking::get_posdao_epoch;
+use client::{BlockChainClient, Client, ImportBlock};
+use engines::hbbft::test::hbbft_test_client::{hbbft_client_with_spec, hbbft_spec_from_json};
+use rustc_hex::FromHex;
+use serde_json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use types::ids::BlockId;
+use verification::queue::kind::blocks::Unverified;
+
+/// A recorded network and the decisions the engine made for its blocks.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fixture {
+    /// The chain spec of the recorded network.
+    spec: serde_json::Value,
+    /// The recorded blocks, in import order.
+    blocks: Vec<RecordedBlock>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordedBlock {
+    /// The hex encoded RLP of the block.
+    rlp: String,
+    /// Whether the block's seal was accepted on the recorded network.
+    seal_valid: bool,
+    /// The POSDAO epoch after importing the block, if it was accepted.
+    posdao_epoch: Option<u64>,
+}
+
+fn fixture_paths() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/hbbft_conformance");
+    let entries = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: fixtures not readable: {}", dir.display(), e));
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn run_fixture(path: &Path) {
+    let name = path.display();
+    let fixture: Fixture = serde_json::from_slice(
+        &fs::read(path).unwrap_or_else(|e| panic!("{}: fixture not readable: {}", name, e)),
+    )
+    .unwrap_or_else(|e| panic!("{}: fixture malformed: {}", name, e));
+
+    let spec = fixture.spec;
+    let client: Arc<Client> = hbbft_client_with_spec(|| hbbft_spec_from_json(&spec));
+    client
+        .engine()
+        .register_client(Arc::downgrade(&client) as _);
+
+    for (index, recorded) in fixture.blocks.iter().enumerate() {
+        let bytes: Vec<u8> = recorded
+            .rlp
+            .trim_start_matches("0x")
+            .from_hex()
+            .unwrap_or_else(|e| panic!("{}: block #{} is not valid hex: {}", name, index, e));
+        let block = Unverified::from_rlp(bytes)
+            .unwrap_or_else(|e| panic!("{}: block #{} is not valid RLP: {}", name, index, e));
+        let hash = block.header.hash();
+        let number = block.header.number();
+
+        // Blocks with invalid seals may already be rejected at the queue.
+        let _ = client.import_block(block);
+        client.flush_queue();
+
+        let accepted = client.block(BlockId::Hash(hash)).is_some();
+        assert_eq!(
+            accepted, recorded.seal_valid,
+            "{}: seal decision for block #{} ({}) differs from the recorded network",
+            name, number, hash
+        );
+
+        if let Some(expected_epoch) = recorded.posdao_epoch {
+            let epoch = get_posdao_epoch(client.as_ref(), BlockId::Hash(hash))
+                .unwrap_or_else(|e| panic!("{}: reading POSDAO epoch failed: {:?}", name, e));
+            assert_eq!(
+                epoch.low_u64(),
+                expected_epoch,
+                "{}: POSDAO epoch after block {} differs from the recorded network",
+                name,
+                number
+            );
+        }
+    }
+}
+
+#[test]
+fn recorded_networks_conformance() {
+    let paths = fixture_paths();
+    assert!(!paths.is_empty(), "No conformance fixtures found.");
+    for path in paths {
+        run_fixture(&path);
+    }
+}
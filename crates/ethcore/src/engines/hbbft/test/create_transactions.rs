@@ -14,6 +14,24 @@ pub fn create_transaction(keypair: &KeyPair, nonce: &U256) -> SignedTransaction
     .sign(keypair.secret(), None)
 }
 
+/// Creates a transaction with the given gas price, e.g. zero like the service transactions of
+/// certified addresses.
+pub fn create_transaction_with_gas_price(
+    keypair: &KeyPair,
+    nonce: &U256,
+    gas_price: U256,
+) -> SignedTransaction {
+    TypedTransaction::Legacy(Transaction {
+        action: Action::Call(Address::from_low_u64_be(5798439875)),
+        value: U256::zero(),
+        data: vec![],
+        gas: U256::from(100_000),
+        gas_price,
+        nonce: *nonce,
+    })
+    .sign(keypair.secret(), None)
+}
+
 pub fn create_transfer(
     keypair: &KeyPair,
     receiver: &Address,
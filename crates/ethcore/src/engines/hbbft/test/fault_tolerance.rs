@@ -0,0 +1,120 @@
+//! Consensus among validators whose messages are lost, delayed, reordered or partitioned.
+//!
+//! Each test switches to four validators, which tolerate one faulty validator, and checks that
+//! blocks are still finalized while the network injects faults into the consensus messages.
+
+use super::{
+    hbbft_test_client::HbbftTestClient,
+    network_simulator::{crank_faulty_network, FaultModel, FaultyNetwork},
+    posdao_epoch::{crank_until_with, switch_to_validators},
+};
+use crypto::publickey::{KeyPair, Public};
+use parking_lot::RwLock;
+use std::collections::BTreeSet;
+
+/// The number of validators, of which one may be faulty.
+const NUM_VALIDATORS: usize = 4;
+
+/// The number of blocks to finalize in each phase of a test.
+const BLOCKS_PER_PHASE: u64 = 3;
+
+/// The number of network cranks a phase may take. Lost and delayed messages slow down
+/// consensus, so this is more generous than for a reliable network.
+const MAX_CRANKS: usize = 300;
+
+/// The seed of the faulty network's random decisions.
+const SEED: u64 = 42;
+
+/// Returns the node ID of the validator with the given index, starting at 0.
+fn validator(clients: &Vec<RwLock<HbbftTestClient>>, index: usize) -> Public {
+    // The master of ceremonies comes first, and is no validator any more.
+    clients[index + 1].read().keypair.public().clone()
+}
+
+/// Cranks the faulty network until all clients imported `BLOCKS_PER_PHASE` more blocks.
+fn finalize_blocks(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    transactor: &KeyPair,
+    network: &mut FaultyNetwork,
+    phase: &str,
+) {
+    let start = clients[0].read().client.chain().best_block_number();
+    crank_until_with(
+        clients,
+        transactor,
+        phase,
+        MAX_CRANKS,
+        |clients| crank_faulty_network(clients, network),
+        |c| c.client.chain().best_block_number() >= start + BLOCKS_PER_PHASE,
+    );
+}
+
+#[test]
+fn blocks_are_finalized_with_a_partitioned_validator() {
+    let (clients, transactor) = switch_to_validators(NUM_VALIDATORS);
+    let mut model = FaultModel::default();
+    model
+        .partitions
+        .push(vec![validator(&clients, 0)].into_iter().collect());
+    let mut network = FaultyNetwork::new(model, SEED);
+
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &mut network,
+        "Consensus with a partitioned validator",
+    );
+}
+
+#[test]
+fn blocks_are_finalized_with_a_lossy_validator_and_reordered_messages() {
+    let (clients, transactor) = switch_to_validators(NUM_VALIDATORS);
+    let mut model = FaultModel::default();
+    model.drop_rate = 0.5;
+    model.lossy.insert(validator(&clients, 0));
+    model.max_jitter = 2;
+    let mut network = FaultyNetwork::new(model, SEED);
+
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &mut network,
+        "Consensus with lost and reordered messages",
+    );
+}
+
+#[test]
+fn partitioned_validator_rejoins_consensus_after_healing() {
+    let (clients, transactor) = switch_to_validators(NUM_VALIDATORS);
+    let mut network = FaultyNetwork::new(FaultModel::default(), SEED);
+    let first: BTreeSet<Public> = vec![validator(&clients, 0)].into_iter().collect();
+    network.partition_for(first, 20);
+
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &mut network,
+        "Consensus with a partitioned validator",
+    );
+    while network.is_partitioned() {
+        crank_faulty_network(&clients, &mut network);
+    }
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &mut network,
+        "Consensus after the partition healed",
+    );
+
+    // Without the healed validator, the remaining two could not finalize blocks.
+    network
+        .model
+        .partitions
+        .push(vec![validator(&clients, 1)].into_iter().collect());
+    finalize_blocks(
+        &clients,
+        &transactor,
+        &mut network,
+        "Consensus with the healed validator",
+    );
+}
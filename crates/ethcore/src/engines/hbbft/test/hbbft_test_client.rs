@@ -4,12 +4,17 @@ use client::{
     BlockChainClient, ChainSyncing, Client, ImportExportBlocks,
 };
 use crypto::publickey::{Generator, KeyPair, Random};
-use engines::signer::from_keypair;
+use engines::hbbft::{
+    clock::{Clock, SystemClock, VirtualClock},
+    HoneyBadgerBFT,
+};
+use engines::{signer::from_keypair, EthEngine};
 use ethereum_types::{Address, U256};
 use miner::{Miner, MinerService};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde_json;
 use spec::Spec;
-use std::{ops::Deref, sync::Arc};
+use std::{ops::Deref, sync::Arc, time::Duration};
 use test_helpers::{generate_dummy_client_with_spec, TestNotify};
 use types::{data_format::DataFormat, ids::BlockId};
 
@@ -28,18 +33,64 @@ impl ChainSyncing for SyncProviderWrapper {
     }
 }
 
+/// Loads a chain spec from JSON, forcing the engine into unit test mode to avoid starting timers.
+pub fn hbbft_spec_from_json(spec: &serde_json::Value) -> Spec {
+    let mut spec = spec.clone();
+    spec["engine"]["hbbft"]["params"]["isUnitTest"] = serde_json::Value::Bool(true);
+    let bytes = serde_json::to_vec(&spec).expect("Chain spec serialization must succeed.");
+    Spec::load(&::std::env::temp_dir(), &bytes[..]).expect("Chain spec is invalid.")
+}
+
+/// Loads the test chain spec with the engine's timer running, as outside of unit tests.
+pub fn hbbft_spec_with_timers() -> Spec {
+    let mut spec: serde_json::Value = serde_json::from_slice(include_bytes!(
+        "../../../../res/chainspec/honey_badger_bft.json"
+    ))
+    .expect("Chain spec must be valid JSON.");
+    spec["engine"]["hbbft"]["params"]["isUnitTest"] = serde_json::Value::Bool(false);
+    let bytes = serde_json::to_vec(&spec).expect("Chain spec serialization must succeed.");
+    Spec::load(&::std::env::temp_dir(), &bytes[..]).expect("Chain spec is invalid.")
+}
+
+/// Returns the given engine as the hbbft engine of the test chain spec.
+pub fn hbbft_engine(engine: &dyn EthEngine) -> &HoneyBadgerBFT {
+    engine
+        .as_any()
+        .downcast_ref::<HoneyBadgerBFT>()
+        .expect("The test chain spec uses the hbbft engine.")
+}
+
 pub fn hbbft_client() -> std::sync::Arc<Client> {
-    let client = generate_dummy_client_with_spec(hbbft_spec);
+    hbbft_client_with_spec(hbbft_spec)
+}
+
+pub fn hbbft_client_with_spec<F>(spec: F) -> std::sync::Arc<Client>
+where
+    F: Fn() -> Spec,
+{
+    let client = generate_dummy_client_with_spec(spec);
     client.set_sync_provider(Box::new(SyncProviderWrapper()));
     client
 }
 
+/// Blocks received from another client, waiting for the simulated import latency to pass.
+struct PendingImport {
+    /// The UNIX time the blocks are imported at, in milliseconds.
+    due: u128,
+    last_block: u64,
+    blocks: Vec<u8>,
+}
+
 #[derive(Clone)]
 pub struct HbbftTestClient {
     pub client: Arc<Client>,
     pub notify: Arc<TestNotify>,
     pub miner: Arc<Miner>,
     pub keypair: KeyPair,
+    import_latency: Duration,
+    /// The clock the import latency passes by.
+    clock: Arc<dyn Clock>,
+    pending_imports: Arc<Mutex<Vec<PendingImport>>>,
 }
 
 impl HbbftTestClient {
@@ -89,9 +140,52 @@ impl HbbftTestClient {
         self.keypair.address()
     }
 
+    /// Delays the import of blocks synced from other clients by the given duration, simulating
+    /// a slow disk or expensive block execution. Consensus messages are still handled immediately.
+    pub fn set_import_latency(&mut self, latency: Duration) {
+        self.import_latency = latency;
+    }
+
+    /// Lets the import latency pass by the given clock instead of the system clock.
+    pub fn set_import_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Returns true if synced blocks are waiting for the import latency to pass.
+    pub fn has_pending_imports(&self) -> bool {
+        !self.pending_imports.lock().is_empty()
+    }
+
+    /// Imports the synced blocks whose import latency has passed.
+    pub fn import_due_blocks(&mut self) {
+        let now = self.clock.now_millis();
+        let due: Vec<_> = {
+            let mut pending = self.pending_imports.lock();
+            let split = pending
+                .iter()
+                .position(|p| p.due > now)
+                .unwrap_or(pending.len());
+            pending.drain(..split).collect()
+        };
+        for import in due {
+            self.client
+                .import_blocks(Box::new(&*import.blocks), Some(DataFormat::Binary))
+                .unwrap();
+        }
+    }
+
+    /// The best block number, including blocks still waiting to be imported.
+    fn best_known_block_number(&self) -> u64 {
+        let imported = self.client.chain().best_block_number();
+        self.pending_imports
+            .lock()
+            .last()
+            .map_or(imported, |p| imported.max(p.last_block))
+    }
+
     pub fn sync_blocks_to(&self, other: &mut Self) {
         let self_block_nr = self.client.chain().best_block_number();
-        let other_block_nr = other.client.chain().best_block_number();
+        let other_block_nr = other.best_known_block_number();
 
         if self_block_nr <= other_block_nr {
             return;
@@ -107,10 +201,18 @@ impl HbbftTestClient {
             )
             .unwrap();
 
-        other
-            .client
-            .import_blocks(Box::new(&*out), Some(DataFormat::Binary))
-            .unwrap();
+        if other.import_latency == Duration::from_secs(0) {
+            other
+                .client
+                .import_blocks(Box::new(&*out), Some(DataFormat::Binary))
+                .unwrap();
+        } else {
+            other.pending_imports.lock().push(PendingImport {
+                due: other.clock.now_millis() + other.import_latency.as_millis(),
+                last_block: self_block_nr,
+                blocks: out,
+            });
+        }
     }
 
     pub fn sync_transactions_to(&self, other: &mut Self) {
@@ -129,7 +231,32 @@ impl HbbftTestClient {
 }
 
 pub fn create_hbbft_client(keypair: KeyPair) -> HbbftTestClient {
-    let client = hbbft_client();
+    init_hbbft_client(hbbft_client(), keypair)
+}
+
+/// Creates a client of the given chain spec.
+pub fn create_hbbft_client_with_spec<F>(keypair: KeyPair, spec: F) -> HbbftTestClient
+where
+    F: Fn() -> Spec,
+{
+    init_hbbft_client(hbbft_client_with_spec(spec), keypair)
+}
+
+/// Creates a client whose engine runs its timer on its own thread, reading the given clock.
+///
+/// The timer ticks in real time, but block times only pass when the clock is advanced.
+pub fn create_hbbft_client_with_clock(
+    keypair: KeyPair,
+    clock: Arc<VirtualClock>,
+) -> HbbftTestClient {
+    let client = hbbft_client_with_spec(hbbft_spec_with_timers);
+    hbbft_engine(client.engine()).set_clock(clock.clone());
+    let mut test_client = init_hbbft_client(client, keypair);
+    test_client.set_import_clock(clock);
+    test_client
+}
+
+fn init_hbbft_client(client: Arc<Client>, keypair: KeyPair) -> HbbftTestClient {
     let miner = client.miner();
     let engine = client.engine();
     let signer = from_keypair(keypair.clone());
@@ -143,6 +270,9 @@ pub fn create_hbbft_client(keypair: KeyPair) -> HbbftTestClient {
         notify,
         miner,
         keypair,
+        import_latency: Duration::from_secs(0),
+        clock: Arc::new(SystemClock),
+        pending_imports: Arc::new(Mutex::new(Vec::new())),
     }
 }
 
@@ -158,3 +288,20 @@ pub fn create_hbbft_clients(
 
     clients
 }
+
+/// Creates a network like `create_hbbft_clients`, with all engines' timers reading the given clock.
+pub fn create_hbbft_clients_with_clock(
+    moc: HbbftTestClient,
+    num_clients: u32,
+    clock: Arc<VirtualClock>,
+) -> Vec<RwLock<HbbftTestClient>> {
+    let mut clients = vec![RwLock::new(moc)];
+    for _ in 0..num_clients {
+        clients.push(RwLock::new(create_hbbft_client_with_clock(
+            Random.generate(),
+            clock.clone(),
+        )));
+    }
+
+    clients
+}
@@ -1,23 +1,49 @@
 use super::{
+    clock::VirtualClock,
     contracts::{
+        random_hbbft::encode_seed,
         staking::{
-            get_posdao_epoch, start_time_of_next_phase_transition,
-            tests::{create_staker, is_pool_active},
+            get_posdao_epoch, is_pool_active, start_time_of_next_phase_transition,
+            tests::create_staker,
         },
         validator_set::{is_pending_validator, mining_by_staking_address},
     },
-    contribution::unix_now_secs,
-    test::hbbft_test_client::{create_hbbft_client, create_hbbft_clients},
+    contribution::{unix_now_secs, Contribution},
+    epoch_keys::EpochKeyProof,
+    hbbft_engine::{subscription_packet, LAST_BATCH_KEY},
+    recovery::encode_recovery_seal,
+    test::{
+        create_transactions::{create_transaction, create_transaction_with_gas_price},
+        hbbft_test_client::{
+            create_hbbft_client, create_hbbft_client_with_clock, create_hbbft_client_with_spec,
+            create_hbbft_clients, create_hbbft_clients_with_clock, hbbft_engine,
+            hbbft_spec_from_json, HbbftTestClient,
+        },
+        network_simulator::crank_network_until,
+    },
+    wire::Envelope,
+    NodeId,
 };
 use client::traits::BlockInfo;
-use crypto::publickey::{Generator, KeyPair, Random, Secret};
-use ethereum_types::{Address, U256};
-use std::str::FromStr;
-use types::ids::BlockId;
-
+use crypto::publickey::{sign, Generator, KeyPair, Random, Secret};
+use engines::{signer::from_keypair, EngineError};
+use error::{BlockError, Error, ErrorKind};
+use ethereum_types::{Address, H512, U256};
+use parking_lot::RwLock;
+use serde_json;
+use spec::Spec;
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Duration};
+use test_helpers::create_test_block;
+use types::{header::Header, ids::BlockId};
+use verification::{queue::kind::blocks::Unverified, verify_block_basic};
+
+mod byzantine;
+mod conformance;
 pub mod create_transactions;
+mod fault_tolerance;
 pub mod hbbft_test_client;
 pub mod network_simulator;
+mod posdao_epoch;
 
 lazy_static! {
     static ref MASTER_OF_CEREMONIES_KEYPAIR: KeyPair = KeyPair::from_secret(
@@ -48,6 +74,328 @@ fn test_miner_transaction_injection() {
     assert_eq!(block.transactions_count(), 1);
 }
 
+#[test]
+fn batches_of_one_step_are_sealed_in_epoch_order() {
+    let moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    let engine = moc.client.engine_arc();
+    let hbbft = hbbft_engine(&*engine);
+    let node_id = NodeId(*moc.keypair.public());
+    let now = unix_now_secs();
+    // Each transaction is only valid in the block of its epoch, after the previous ones.
+    let txns: Vec<_> = (0..3u64)
+        .map(|nonce| create_transaction(&moc.keypair, &U256::from(nonce)))
+        .collect();
+    let batch = |epoch: u64| -> (u64, BTreeMap<NodeId, Contribution>) {
+        let contribution = Contribution::new(&vec![txns[epoch as usize - 1].clone()], now + epoch);
+        (epoch, vec![(node_id, contribution)].into_iter().collect())
+    };
+    hbbft
+        .process_agreed_batches(vec![batch(3), batch(1), batch(2)])
+        .expect("The master of ceremonies is a validator.");
+
+    // Only the block following the latest block is created right away, the later ones wait for
+    // their parent.
+    assert_eq!(moc.client.chain().best_block_number(), 1);
+    hbbft.tick_deferred_block();
+    assert_eq!(moc.client.chain().best_block_number(), 2);
+    hbbft.tick_deferred_block();
+    assert_eq!(moc.client.chain().best_block_number(), 3);
+    hbbft.tick_deferred_block();
+    assert_eq!(moc.client.chain().best_block_number(), 3);
+
+    for (index, txn) in txns.iter().enumerate() {
+        let block = moc
+            .client
+            .block(BlockId::Number(index as u64 + 1))
+            .expect("The blocks of all batches must exist");
+        assert!(block.transaction_hashes().contains(&txn.hash()));
+    }
+}
+
+#[test]
+fn underpriced_transactions_of_agreed_batches_are_dropped() {
+    // Without a governance contract, the `minimumGasPrice` parameter is the minimum.
+    assert_underpriced_transactions_are_dropped(
+        || spec_with_params(vec![("minimumGasPrice", 10_000_000_000u64.into())]),
+        U256::from(10_000_000_000u64),
+    );
+    // The governance contract's minimum takes precedence, even if it is higher.
+    assert_underpriced_transactions_are_dropped(
+        || {
+            let mut spec = hbbft_spec_json();
+            let contract = "0x6000000000000000000000000000000000000002";
+            spec["engine"]["hbbft"]["params"]["minimumGasPrice"] = 1_000_000_000u64.into();
+            spec["engine"]["hbbft"]["params"]["minGasPriceContractAddress"] = contract.into();
+            // PUSH5 20000000000, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+            spec["accounts"][contract] = serde_json::from_str(
+                r#"{ "balance": "0", "code": "0x6404a817c80060005260206000f3" }"#,
+            )
+            .expect("Account must be valid JSON.");
+            hbbft_spec_from_json(&spec)
+        },
+        U256::from(20_000_000_000u64),
+    );
+}
+
+/// Asserts that the transactions of an agreed batch paying less than `minimum` are dropped and
+/// their proposer is reported.
+fn assert_underpriced_transactions_are_dropped<F: Fn() -> Spec>(spec: F, minimum: U256) {
+    let moc = create_hbbft_client_with_spec(MASTER_OF_CEREMONIES_KEYPAIR.clone(), spec);
+    let engine = moc.client.engine_arc();
+    let hbbft = hbbft_engine(&*engine);
+    let node_id = NodeId(*moc.keypair.public());
+    let priced = create_transaction_with_gas_price(&moc.keypair, &U256::from(0), minimum);
+    let underpriced =
+        create_transaction_with_gas_price(&moc.keypair, &U256::from(1), minimum - U256::one());
+    // Zero gas price service transactions are dropped as well.
+    let unpriced = create_transaction_with_gas_price(&moc.keypair, &U256::from(2), U256::zero());
+    let contribution = Contribution::new(
+        &vec![priced.clone(), underpriced, unpriced],
+        unix_now_secs(),
+    );
+    hbbft
+        .process_agreed_batches(vec![(
+            1,
+            vec![(node_id, contribution)].into_iter().collect(),
+        )])
+        .expect("The master of ceremonies is a validator.");
+
+    let block = moc
+        .client
+        .block(BlockId::Number(1))
+        .expect("Block 1 must exist");
+    assert_eq!(block.transaction_hashes(), vec![priced.hash()]);
+    let reports = hbbft.fault_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].node_id, node_id);
+    assert_eq!(reports[0].faults, 1);
+    assert!(reports[0]
+        .last_fault
+        .contains("2 transactions below the minimum gas price"));
+}
+
+/// The test chain spec with a block gas limit contract always returning 8000000.
+fn block_gas_limit_spec() -> Spec {
+    let mut spec = hbbft_spec_json();
+    let contract = "0x6000000000000000000000000000000000000001";
+    spec["engine"]["hbbft"]["params"]["blockGasLimitContractAddress"] = contract.into();
+    // PUSH4 8000000, PUSH1 0, MSTORE, PUSH1 32, PUSH1 0, RETURN
+    spec["accounts"][contract] =
+        serde_json::from_str(r#"{ "balance": "0", "code": "0x63007a120060005260206000f3" }"#)
+            .expect("Account must be valid JSON.");
+    hbbft_spec_from_json(&spec)
+}
+
+#[test]
+fn block_gas_limit_is_read_from_the_contract() {
+    let mut moc =
+        create_hbbft_client_with_spec(MASTER_OF_CEREMONIES_KEYPAIR.clone(), block_gas_limit_spec);
+    let genesis = moc.client.chain().genesis_header();
+    let contract_gas_limit = U256::from(8_000_000);
+    assert_ne!(genesis.gas_limit(), contract_gas_limit);
+
+    // Imported blocks must have the gas limit read at their parent.
+    let mut header = Header::new();
+    header.set_number(1);
+    header.set_parent_hash(genesis.hash());
+    header.set_timestamp(genesis.timestamp() + 1);
+    header.set_gas_limit(genesis.gas_limit());
+    let block = |header: &Header| {
+        Unverified::from_rlp(create_test_block(header)).expect("Test block must be valid RLP.")
+    };
+    match verify_block_basic(&block(&header), moc.client.engine(), false) {
+        Err(Error(ErrorKind::Block(BlockError::InvalidGasLimit(_)), _)) => (),
+        other => panic!("Expected InvalidGasLimit, got {:?}", other),
+    }
+    header.set_gas_limit(contract_gas_limit);
+    assert!(verify_block_basic(&block(&header), moc.client.engine(), false).is_ok());
+
+    // Created blocks use it as well.
+    moc.create_some_transaction(None);
+    let header = moc
+        .client
+        .block_header(BlockId::Number(1))
+        .expect("Block 1 must exist");
+    assert_eq!(header.gas_limit(), contract_gas_limit);
+}
+
+#[test]
+fn recovery_blocks_must_not_choose_their_seed() {
+    let moc = create_hbbft_client_with_spec(MASTER_OF_CEREMONIES_KEYPAIR.clone(), || {
+        spec_with_params(vec![
+            ("recoveryBlockTimeout", 60u64.into()),
+            (
+                "randomnessContractAddress",
+                "0x3000000000000000000000000000000000000001".into(),
+            ),
+        ])
+    });
+    let engine = moc.client.engine();
+    let genesis = moc
+        .client
+        .chain()
+        .genesis_header()
+        .decode()
+        .expect("The genesis header must be valid.");
+    let recovery_block = |seed: U256| {
+        let mut header = Header::new();
+        header.set_number(1);
+        header.set_parent_hash(genesis.hash());
+        header.set_timestamp(unix_now_secs());
+        header.set_gas_limit(*genesis.gas_limit());
+        header.set_extra_data(encode_seed(&seed));
+        let signature = sign(moc.keypair.secret(), &header.bare_hash())
+            .expect("Signing the recovery block must succeed.");
+        header.set_seal(vec![encode_recovery_seal(&signature)]);
+        header
+    };
+
+    // The master of ceremonies is the only validator, and the designated proposer.
+    let seed = U256::from(genesis.hash().as_bytes());
+    assert!(engine
+        .verify_block_family(&recovery_block(seed), &genesis)
+        .is_ok());
+    match engine.verify_block_family(&recovery_block(seed ^ U256::one()), &genesis) {
+        Err(Error(ErrorKind::Block(BlockError::InvalidSeal), _)) => (),
+        other => panic!("Expected InvalidSeal, got {:?}", other),
+    }
+}
+
+#[test]
+fn epoch_verifier_only_trusts_signed_epoch_keys() {
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    moc.create_some_transaction(None);
+    let engine = moc.client.engine_arc();
+    let proof = hbbft_engine(&*engine)
+        .epoch_key_proof()
+        .expect("The master of ceremonies can sign the key of POSDAO epoch 0.");
+    let block = moc
+        .client
+        .block_header(BlockId::Number(1))
+        .expect("Block 1 must exist")
+        .decode()
+        .expect("Block 1 must be valid.");
+
+    // A proof signed by someone else than the validators is rejected.
+    let mut forged = EpochKeyProof::decode(&proof).expect("The proof must be valid.");
+    let outsider = Random.generate();
+    forged.signatures =
+        vec![sign(outsider.secret(), &forged.key.hash())
+            .expect("Signing the epoch key must succeed.")];
+    assert!(engine
+        .epoch_verifier(&block, &forged.encode())
+        .known_confirmed()
+        .is_err());
+
+    // The validators' proof yields a verifier checking seals from headers alone.
+    let verifier = engine
+        .epoch_verifier(&block, &proof)
+        .known_confirmed()
+        .expect("The proof is signed by the only validator.");
+    assert!(verifier.verify_light(&block).is_ok());
+    let mut tampered = block.clone();
+    tampered.set_timestamp(block.timestamp() + 1);
+    assert!(verifier.verify_light(&tampered).is_err());
+}
+
+#[test]
+fn withdrawn_validator_stops_contributing() {
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    moc.create_some_transaction(None);
+    assert_eq!(moc.client.chain().best_block_number(), 1);
+
+    let engine = moc.client.engine_arc();
+    let hbbft = hbbft_engine(&*engine);
+    hbbft
+        .withdraw()
+        .expect("The master of ceremonies has a pool.");
+    moc.create_some_transaction(None);
+    assert_eq!(moc.client.chain().best_block_number(), 1);
+
+    // The withdrawal is persisted: setting the signer and registering the client again, as on a
+    // restart, does not resume contributing.
+    engine.set_signer(Some(from_keypair(moc.keypair.clone())));
+    engine.register_client(Arc::downgrade(&moc.client) as _);
+    assert!(hbbft.status().withdrawn);
+    moc.create_some_transaction(None);
+    assert_eq!(moc.client.chain().best_block_number(), 1);
+
+    // Another mining address is not withdrawn.
+    engine.set_signer(Some(from_keypair(Random.generate())));
+    assert!(!hbbft.status().withdrawn);
+}
+
+/// The test chain spec as JSON, to be modified by tests.
+fn hbbft_spec_json() -> serde_json::Value {
+    serde_json::from_slice(include_bytes!(
+        "../../../../res/chainspec/honey_badger_bft.json"
+    ))
+    .expect("Chain spec must be valid JSON.")
+}
+
+/// The test chain spec with the given engine parameters.
+fn spec_with_params(params: Vec<(&str, serde_json::Value)>) -> Spec {
+    let mut spec = hbbft_spec_json();
+    for (name, value) in params {
+        spec["engine"]["hbbft"]["params"][name] = value;
+    }
+    hbbft_spec_from_json(&spec)
+}
+
+#[test]
+fn persisted_batch_is_recovered_once() {
+    // Imported here, its `block_header` is ambiguous with `BlockInfo`'s.
+    use client::traits::EngineClient;
+
+    let mut moc = create_hbbft_client_with_spec(MASTER_OF_CEREMONIES_KEYPAIR.clone(), || {
+        spec_with_params(vec![("persistLastBatch", true.into())])
+    });
+    moc.create_some_transaction(None);
+    assert_eq!(moc.client.chain().best_block_number(), 1);
+    let persisted = moc
+        .client
+        .engine_data(LAST_BATCH_KEY)
+        .expect("The batch of block 1 must be persisted.");
+
+    // A node that crashed before importing the block recreates it from the persisted batch.
+    let restarted = create_hbbft_client_with_spec(MASTER_OF_CEREMONIES_KEYPAIR.clone(), || {
+        spec_with_params(vec![("persistLastBatch", true.into())])
+    });
+    restarted
+        .client
+        .set_engine_data(LAST_BATCH_KEY, Some(persisted));
+    let engine = restarted.client.engine();
+    engine.register_client(Arc::downgrade(&restarted.client) as _);
+    assert_eq!(restarted.client.chain().best_block_number(), 1);
+    let original = moc
+        .client
+        .block(BlockId::Number(1))
+        .expect("Block 1 must exist");
+    let recreated = restarted
+        .client
+        .block(BlockId::Number(1))
+        .expect("Block 1 must be recreated");
+    assert_eq!(
+        recreated.transaction_hashes(),
+        original.transaction_hashes()
+    );
+    assert_eq!(
+        recreated.header_view().timestamp(),
+        original.header_view().timestamp()
+    );
+
+    // Setting the signer again does not look at the batch anymore. Otherwise it would be
+    // discarded as obsolete now that its block is imported.
+    engine.set_signer(Some(from_keypair(restarted.keypair.clone())));
+    assert!(restarted.client.engine_data(LAST_BATCH_KEY).is_some());
+
+    // Without a recovery since the start, an obsolete batch is discarded.
+    moc.client
+        .engine()
+        .register_client(Arc::downgrade(&moc.client) as _);
+    assert!(moc.client.engine_data(LAST_BATCH_KEY).is_none());
+}
+
 #[test]
 fn test_staking_account_creation() {
     // Create Master of Ceremonies
@@ -99,7 +447,7 @@ fn test_staking_account_creation() {
 
     // Check if the staking pool is active.
     assert_eq!(
-        is_pool_active(moc.client.as_ref(), staker_1.address())
+        is_pool_active(moc.client.as_ref(), BlockId::Latest, staker_1.address())
             .expect("Pool active query must succeed."),
         true
     );
@@ -168,6 +516,82 @@ fn test_epoch_transition() {
     moc.create_some_transaction(Some(&transactor));
 }
 
+#[test]
+fn keygen_progress_is_read_once_per_block() {
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    let transactor: KeyPair = Random.generate();
+    let engine = moc.client.engine_arc();
+    let hbbft = hbbft_engine(&*engine);
+    assert!(hbbft
+        .keygen_progress()
+        .expect("Reading the keygen progress must succeed")
+        .is_ready());
+
+    // The first block triggers the phase transition, electing us for the next POSDAO epoch.
+    moc.transfer_to(&transactor.address(), &U256::from(9000000000000000000u64));
+    let progress = hbbft
+        .keygen_progress()
+        .expect("Reading the keygen progress must succeed");
+    assert!(progress.is_pending_validator);
+    assert!(!progress.part_confirmed);
+    assert!(!progress.is_ready());
+    let reads = hbbft.status().keygen_history_reads;
+    assert_eq!(hbbft.keygen_progress(), Ok(progress));
+    assert_eq!(hbbft.status().keygen_history_reads, reads);
+
+    // Once our Part and Acks are on chain, the new POSDAO epoch starts.
+    for _ in 0..5 {
+        moc.create_some_transaction(Some(&transactor));
+    }
+    assert!(hbbft
+        .keygen_progress()
+        .expect("Reading the keygen progress must succeed")
+        .is_ready());
+}
+
+#[test]
+fn packets_of_other_chains_and_stale_posdao_epochs_are_rejected() {
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    let transactor: KeyPair = Random.generate();
+    moc.transfer_to(&transactor.address(), &U256::from(9000000000000000000u64));
+    // Completes the key generation, and lets the new validators finalize their first blocks,
+    // ending the handoff from the first POSDAO epoch.
+    for _ in 0..7 {
+        moc.create_some_transaction(Some(&transactor));
+    }
+    assert_eq!(
+        get_posdao_epoch(moc.client.as_ref(), BlockId::Latest).expect("Constant call must succeed"),
+        U256::from(1)
+    );
+
+    let engine = moc.client.engine();
+    let chain_id = engine.params().chain_id;
+    let observer = Some(H512::from_low_u64_be(1));
+    let packet = |chain_id, posdao_epoch| {
+        subscription_packet(Envelope {
+            chain_id,
+            posdao_epoch,
+        })
+    };
+    assert!(engine
+        .handle_message(&packet(chain_id, 1), observer)
+        .is_ok());
+    // Packets from later POSDAO epochs are cached until we reach them.
+    assert!(engine
+        .handle_message(&packet(chain_id, 2), observer)
+        .is_ok());
+    match engine.handle_message(&packet(chain_id + 1, 1), observer) {
+        Err(EngineError::MalformedMessage(msg)) => assert!(msg.contains("chain ID"), "{}", msg),
+        other => panic!("Expected a chain ID mismatch, got {:?}", other),
+    }
+    match engine.handle_message(&packet(chain_id, 0), observer) {
+        Err(EngineError::MalformedMessage(msg)) => {
+            assert!(msg.contains("POSDAO epoch 0"), "{}", msg)
+        }
+        other => panic!("Expected a stale POSDAO epoch, got {:?}", other),
+    }
+}
+
 #[test]
 fn sync_two_validators() {
     // Create the MOC client
@@ -207,6 +631,90 @@ fn sync_two_validators() {
     moc.sync_transactions_to(&mut validator_1);
 }
 
+#[test]
+fn sync_with_import_latency() {
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+    let transactor: KeyPair = Random.generate();
+    let transaction_funds = U256::from(9000000000000000000u64);
+
+    let mut validator_1 = create_hbbft_client(Random.generate());
+    let clock = Arc::new(VirtualClock::new(unix_now_secs()));
+    let latency = Duration::from_secs(1);
+    validator_1.set_import_clock(clock.clone());
+    validator_1.set_import_latency(latency);
+
+    // The first block is received, but its import takes longer than the next block's creation.
+    moc.transfer_to(&transactor.address(), &transaction_funds);
+    moc.sync_blocks_to(&mut validator_1);
+    clock.advance(latency / 2);
+    moc.create_some_transaction(Some(&transactor));
+    moc.sync_blocks_to(&mut validator_1);
+
+    // Nothing is imported before the latency has passed, and no block is received twice.
+    validator_1.import_due_blocks();
+    assert!(validator_1.has_pending_imports());
+    assert_eq!(validator_1.client.chain().best_block_number(), 0);
+
+    // Only the first block is due.
+    clock.advance(latency / 2);
+    validator_1.import_due_blocks();
+    assert!(validator_1.has_pending_imports());
+    assert_eq!(validator_1.client.chain().best_block_number(), 1);
+
+    clock.advance(latency / 2);
+    validator_1.import_due_blocks();
+    assert!(!validator_1.has_pending_imports());
+    assert_eq!(validator_1.client.chain().best_block_number(), 2);
+    assert_eq!(
+        validator_1.balance(&transactor.address()),
+        transaction_funds
+    );
+
+    // The engine follows the imported chain into the creator's POSDAO epoch, with the creator
+    // elected for the next one, but does not seal blocks since it is no validator.
+    let status = |client: &HbbftTestClient| hbbft_engine(client.client.engine()).status();
+    assert_eq!(status(&validator_1).posdao_epoch, status(&moc).posdao_epoch);
+    assert!(!status(&validator_1).is_validator);
+    assert_eq!(status(&validator_1).pending_seals, 0);
+    assert!(
+        is_pending_validator(validator_1.client.as_ref(), &moc.address())
+            .expect("Constant call must succeed")
+    );
+}
+
+#[test]
+fn timer_starts_epoch_after_maximum_block_time() {
+    // The genesis block is at time 0, and the test chain spec's maximum block time is 600s.
+    let clock = Arc::new(VirtualClock::new(0));
+    let moc = create_hbbft_client_with_clock(MASTER_OF_CEREMONIES_KEYPAIR.clone(), clock.clone());
+    let clients = create_hbbft_clients_with_clock(moc, 1, clock.clone());
+    let has_block = |clients: &Vec<RwLock<HbbftTestClient>>| {
+        clients
+            .iter()
+            .all(|c| c.read().client.chain().best_block_number() >= 1)
+    };
+
+    // The timers tick, but no block time passes on the virtual clock.
+    assert!(!crank_network_until(
+        &clients,
+        Duration::from_secs(3),
+        has_block
+    ));
+
+    clock.advance(Duration::from_secs(600));
+    assert!(crank_network_until(
+        &clients,
+        Duration::from_secs(30),
+        has_block
+    ));
+    let header = clients[1]
+        .read()
+        .client
+        .block_header(BlockId::Number(1))
+        .expect("Block 1 must exist");
+    assert_eq!(header.timestamp(), 600);
+}
+
 #[test]
 fn test_moc_to_first_validator() {
     // Create MOC client
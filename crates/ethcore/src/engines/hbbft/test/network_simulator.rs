@@ -1,8 +1,18 @@
+use bytes::Bytes;
+use crypto::publickey::Public;
 use engines::hbbft::test::hbbft_test_client::HbbftTestClient;
 use parking_lot::RwLock;
-use std::collections::BTreeMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    thread,
+    time::{Duration, Instant},
+};
 
 pub fn crank_network(clients: &Vec<RwLock<HbbftTestClient>>) {
+    // import blocks whose simulated import latency has passed
+    import_due_blocks(clients);
+
     // sync blocks
     sync_blocks(clients);
 
@@ -13,6 +23,33 @@ pub fn crank_network(clients: &Vec<RwLock<HbbftTestClient>>) {
     sync_consensus_messages(clients);
 }
 
+/// Cranks the network until the condition holds, while the engines' timers run on their own
+/// threads. Returns false if the condition does not hold within the given real time.
+pub fn crank_network_until<F>(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    timeout: Duration,
+    condition: F,
+) -> bool
+where
+    F: Fn(&Vec<RwLock<HbbftTestClient>>) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+    while !condition(clients) {
+        if Instant::now() > deadline {
+            return false;
+        }
+        crank_network(clients);
+        thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+fn import_due_blocks(clients: &Vec<RwLock<HbbftTestClient>>) {
+    for c in clients {
+        c.write().import_due_blocks();
+    }
+}
+
 fn sync_blocks(clients: &Vec<RwLock<HbbftTestClient>>) {
     // Find client with most blocks.
     let best_client = clients
@@ -51,6 +88,208 @@ fn sync_transactions(clients: &Vec<RwLock<HbbftTestClient>>) {
     }
 }
 
+/// Cranks the network like `crank_network`, but delivers the consensus messages through the
+/// given faulty network.
+pub fn crank_faulty_network(clients: &Vec<RwLock<HbbftTestClient>>, network: &mut FaultyNetwork) {
+    import_due_blocks(clients);
+    sync_blocks(clients);
+    sync_transactions(clients);
+
+    let clients_map = clients
+        .iter()
+        .map(|c| (c.read().keypair.public().clone(), c))
+        .collect::<BTreeMap<_, _>>();
+
+    for (from, n) in &clients_map {
+        for m in n.read().notify.targeted_messages.write().drain(..) {
+            let to = m.1.expect("The Message target node id must be set");
+            network.send(*from, to, m.0);
+        }
+    }
+    for message in network.crank() {
+        clients_map
+            .get(&message.to)
+            .expect("Message target not found in nodes map")
+            .read()
+            .client
+            .engine()
+            .handle_message(&message.data, Some(message.from))
+            .expect("Message handling to succeed");
+    }
+}
+
+/// Cranks the network like `crank_network`, but passes the consensus messages sent by the given
+/// byzantine node through `tamper`. Honest nodes may reject the tampered messages, but have to
+/// handle all others.
+pub fn crank_byzantine_network<F>(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    byzantine: &Public,
+    tamper: F,
+) where
+    F: Fn(Bytes) -> Vec<Bytes>,
+{
+    import_due_blocks(clients);
+    sync_blocks(clients);
+    sync_transactions(clients);
+
+    let clients_map = clients
+        .iter()
+        .map(|c| (c.read().keypair.public().clone(), c))
+        .collect::<BTreeMap<_, _>>();
+
+    for (from, n) in &clients_map {
+        for m in n.read().notify.targeted_messages.write().drain(..) {
+            let target = clients_map
+                .get(&m.1.expect("The Message target node id must be set"))
+                .expect("Message target not found in nodes map")
+                .read();
+            if from != byzantine {
+                target
+                    .client
+                    .engine()
+                    .handle_message(&m.0, Some(*from))
+                    .expect("Message handling to succeed");
+                continue;
+            }
+            for packet in tamper(m.0) {
+                // Rejecting a tampered message is fine, as long as nothing breaks.
+                let _ = target.client.engine().handle_message(&packet, Some(*from));
+            }
+        }
+    }
+}
+
+/// The faults injected into the delivery of consensus messages.
+///
+/// Blocks and transactions are still synced perfectly: the faults target the consensus, which
+/// has to tolerate them on its own.
+#[derive(Clone, Debug, Default)]
+pub struct FaultModel {
+    /// The probability of a message being lost, between 0 and 1.
+    pub drop_rate: f64,
+    /// The nodes whose sent and received messages are lost at the drop rate. If empty, messages
+    /// between all nodes are.
+    pub lossy: BTreeSet<Public>,
+    /// The number of cranks messages from the first to the second node are delayed by.
+    /// Messages on other links are delivered in the crank they are sent in.
+    pub latencies: BTreeMap<(Public, Public), u64>,
+    /// The maximum number of cranks a message is delayed by in addition to its link's latency,
+    /// chosen at random for each message, so later messages can overtake earlier ones.
+    pub max_jitter: u64,
+    /// Groups of nodes cut off from the rest of the network. Messages between a node inside and
+    /// a node outside of a group are lost.
+    pub partitions: Vec<BTreeSet<Public>>,
+}
+
+impl FaultModel {
+    /// Returns true if no partition separates the given nodes.
+    pub fn is_reachable(&self, from: &Public, to: &Public) -> bool {
+        self.partitions
+            .iter()
+            .all(|partition| partition.contains(from) == partition.contains(to))
+    }
+
+    /// Returns true if messages between the given nodes are lost at the drop rate.
+    pub fn is_lossy(&self, from: &Public, to: &Public) -> bool {
+        self.drop_rate > 0.0
+            && (self.lossy.is_empty() || self.lossy.contains(from) || self.lossy.contains(to))
+    }
+}
+
+/// A consensus message on its way through the simulated network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InFlightMessage {
+    /// The crank the message is delivered in.
+    pub due: u64,
+    /// The sender's node ID.
+    pub from: Public,
+    /// The recipient's node ID.
+    pub to: Public,
+    pub data: Bytes,
+}
+
+/// A network dropping and delaying messages according to a fault model.
+///
+/// All random decisions are made by an RNG with the given seed, so a failing run can be
+/// reproduced exactly.
+pub struct FaultyNetwork {
+    /// The faults to inject. Can be changed between cranks, e.g. to heal a partition.
+    pub model: FaultModel,
+    rng: StdRng,
+    crank: u64,
+    in_flight: Vec<InFlightMessage>,
+    /// Partitions that heal on their own, with the crank they heal in.
+    temporary_partitions: Vec<(u64, BTreeSet<Public>)>,
+}
+
+impl FaultyNetwork {
+    pub fn new(model: FaultModel, seed: u64) -> Self {
+        FaultyNetwork {
+            model,
+            rng: StdRng::seed_from_u64(seed),
+            crank: 0,
+            in_flight: Vec::new(),
+            temporary_partitions: Vec::new(),
+        }
+    }
+
+    /// Cuts the given nodes off from the rest of the network for the given number of cranks.
+    /// Messages already in flight are still delivered.
+    pub fn partition_for(&mut self, nodes: BTreeSet<Public>, cranks: u64) {
+        self.temporary_partitions.push((self.crank + cranks, nodes));
+    }
+
+    /// Returns true if a temporary partition is in place.
+    pub fn is_partitioned(&self) -> bool {
+        !self.temporary_partitions.is_empty()
+    }
+
+    /// Sends a message, unless the fault model loses it.
+    pub fn send(&mut self, from: Public, to: Public, data: Bytes) {
+        let partitioned = self
+            .temporary_partitions
+            .iter()
+            .any(|(_, nodes)| nodes.contains(&from) != nodes.contains(&to));
+        if partitioned || !self.model.is_reachable(&from, &to) {
+            return;
+        }
+        if self.model.is_lossy(&from, &to) && self.rng.gen_bool(self.model.drop_rate) {
+            return;
+        }
+        let mut latency = self.model.latencies.get(&(from, to)).cloned().unwrap_or(0);
+        if self.model.max_jitter > 0 {
+            latency += self.rng.gen_range(0, self.model.max_jitter + 1);
+        }
+        self.in_flight.push(InFlightMessage {
+            due: self.crank + latency,
+            from,
+            to,
+            data,
+        });
+    }
+
+    /// Returns the messages due in the current crank, in the order they were sent, and advances
+    /// to the next crank.
+    pub fn crank(&mut self) -> Vec<InFlightMessage> {
+        let crank = self.crank;
+        let (due, in_flight): (Vec<_>, Vec<_>) = self
+            .in_flight
+            .drain(..)
+            .partition(|message| message.due <= crank);
+        self.in_flight = in_flight;
+        self.crank += 1;
+        let crank = self.crank;
+        self.temporary_partitions
+            .retain(|(heals_at, _)| *heals_at > crank);
+        due
+    }
+
+    /// Returns the number of messages sent but not delivered yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
 fn sync_consensus_messages(clients: &Vec<RwLock<HbbftTestClient>>) {
     let clients_map = clients
         .iter()
@@ -70,3 +309,92 @@ fn sync_consensus_messages(clients: &Vec<RwLock<HbbftTestClient>>) {
         }
     }
 }
+
+fn node(n: u64) -> Public {
+    Public::from_low_u64_be(n)
+}
+
+#[test]
+fn messages_are_delayed_and_partitioned() {
+    let mut model = FaultModel::default();
+    model.latencies.insert((node(1), node(2)), 2);
+    model.partitions.push(vec![node(3)].into_iter().collect());
+    let mut network = FaultyNetwork::new(model, 0);
+
+    network.send(node(1), node(2), vec![1]);
+    network.send(node(2), node(1), vec![2]);
+    network.send(node(1), node(3), vec![3]);
+    assert_eq!(network.in_flight(), 2);
+    let delivered = network.crank();
+    assert_eq!(delivered.len(), 1);
+    assert_eq!(delivered[0].data, vec![2]);
+    assert!(network.crank().is_empty());
+    assert_eq!(network.crank()[0].data, vec![1]);
+
+    network.model.partitions.clear();
+    network.send(node(1), node(3), vec![3]);
+    assert_eq!(network.crank().len(), 1);
+}
+
+#[test]
+fn message_loss_is_reproducible() {
+    let delivered = |seed| {
+        let mut model = FaultModel::default();
+        model.drop_rate = 0.5;
+        let mut network = FaultyNetwork::new(model, seed);
+        for i in 0..100u8 {
+            network.send(node(1), node(2), vec![i]);
+        }
+        network.crank()
+    };
+    let first = delivered(7);
+    assert!(first.len() > 10 && first.len() < 90);
+    assert_eq!(first, delivered(7));
+}
+
+#[test]
+fn only_messages_of_lossy_nodes_are_lost() {
+    let mut model = FaultModel::default();
+    model.drop_rate = 1.0;
+    model.lossy.insert(node(3));
+    let mut network = FaultyNetwork::new(model, 0);
+
+    network.send(node(1), node(2), vec![1]);
+    network.send(node(1), node(3), vec![2]);
+    network.send(node(3), node(2), vec![3]);
+    let delivered = network.crank();
+    assert_eq!(delivered.len(), 1);
+    assert_eq!(delivered[0].data, vec![1]);
+}
+
+#[test]
+fn jitter_reorders_messages() {
+    let mut model = FaultModel::default();
+    model.max_jitter = 5;
+    let mut network = FaultyNetwork::new(model, 3);
+    for i in 0..50u8 {
+        network.send(node(1), node(2), vec![i]);
+    }
+    let mut received = Vec::new();
+    for _ in 0..6 {
+        received.extend(network.crank().into_iter().map(|m| m.data[0]));
+    }
+    assert_eq!(received.len(), 50);
+    assert!(received.windows(2).any(|pair| pair[0] > pair[1]));
+}
+
+#[test]
+fn temporary_partitions_heal() {
+    let mut network = FaultyNetwork::new(FaultModel::default(), 0);
+    network.partition_for(vec![node(3)].into_iter().collect(), 2);
+
+    network.send(node(1), node(3), vec![1]);
+    network.send(node(1), node(2), vec![2]);
+    assert_eq!(network.crank().len(), 1);
+    network.send(node(3), node(1), vec![3]);
+    assert!(network.crank().is_empty());
+    assert!(!network.is_partitioned());
+
+    network.send(node(3), node(1), vec![4]);
+    assert_eq!(network.crank()[0].data, vec![4]);
+}
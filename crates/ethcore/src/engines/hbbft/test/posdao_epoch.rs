@@ -0,0 +1,185 @@
+//! A full POSDAO epoch switch from the master of ceremonies to a set of dynamic validators.
+//!
+//! The harness registers a pool for each of N fresh clients, drives the key generation across
+//! the simulated network until the validator set switches, and checks that the new validators
+//! seal blocks with their threshold signature.
+
+use super::{
+    super::contracts::{
+        staking::{get_posdao_epoch, tests::create_staker},
+        validator_set::{get_pending_validators, get_validator_pubkeys, ValidatorType},
+    },
+    hbbft_test_client::{create_hbbft_client, HbbftTestClient},
+    network_simulator::crank_network,
+    MASTER_OF_CEREMONIES_KEYPAIR,
+};
+use client::traits::BlockInfo;
+use crypto::publickey::{Generator, KeyPair, Random};
+use ethereum_types::{Address, U256};
+use miner::MinerService;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use types::ids::BlockId;
+
+/// The number of network cranks a phase of the epoch switch may take.
+const MAX_CRANKS: usize = 100;
+
+/// Cranks the network until the condition holds for all clients.
+fn crank_until<F>(clients: &Vec<RwLock<HbbftTestClient>>, transactor: &KeyPair, phase: &str, f: F)
+where
+    F: Fn(&HbbftTestClient) -> bool,
+{
+    crank_until_with(clients, transactor, phase, MAX_CRANKS, crank_network, f);
+}
+
+/// Cranks the network with the given function until the condition holds for all clients.
+///
+/// Each crank a transaction is created on the master of ceremonies, unless the previous one is
+/// still pending, so there is always a reason to create the next block.
+pub(super) fn crank_until_with<C, F>(
+    clients: &Vec<RwLock<HbbftTestClient>>,
+    transactor: &KeyPair,
+    phase: &str,
+    max_cranks: usize,
+    mut crank: C,
+    f: F,
+) where
+    C: FnMut(&Vec<RwLock<HbbftTestClient>>),
+    F: Fn(&HbbftTestClient) -> bool,
+{
+    for _ in 0..max_cranks {
+        if clients.iter().all(|c| f(&c.read())) {
+            return;
+        }
+        let mut moc = clients[0].write();
+        let is_pending = moc
+            .miner
+            .queued_transactions()
+            .iter()
+            .any(|tx| tx.signed().sender() == transactor.address());
+        if !is_pending {
+            moc.create_some_transaction(Some(transactor));
+        }
+        drop(moc);
+        crank(clients);
+    }
+    panic!("{} did not complete within {} cranks", phase, max_cranks);
+}
+
+fn posdao_epoch(client: &HbbftTestClient) -> U256 {
+    get_posdao_epoch(client.client.as_ref(), BlockId::Latest).expect("Constant call must succeed")
+}
+
+/// Switches the validator set from the master of ceremonies to `num_validators` new pools.
+///
+/// Returns the clients, the master of ceremonies first, and the funded account creating the
+/// transactions.
+pub(super) fn switch_to_validators(
+    num_validators: usize,
+) -> (Vec<RwLock<HbbftTestClient>>, KeyPair) {
+    switch_to_validators_with(num_validators, crank_network)
+}
+
+/// Switches the validator set like `switch_to_validators`, cranking the network with the given
+/// function.
+pub(super) fn switch_to_validators_with<C>(
+    num_validators: usize,
+    mut crank: C,
+) -> (Vec<RwLock<HbbftTestClient>>, KeyPair)
+where
+    C: FnMut(&Vec<RwLock<HbbftTestClient>>),
+{
+    let mut moc = create_hbbft_client(MASTER_OF_CEREMONIES_KEYPAIR.clone());
+
+    // To avoid performing external transactions with the MoC we create and fund a random address.
+    let transactor: KeyPair = Random.generate();
+    moc.transfer_to(
+        &transactor.address(),
+        &U256::from_dec_str("1000000000000000000000000").unwrap(),
+    );
+
+    // Fund each validator for its key generation transactions, and register its pool.
+    let validators: Vec<HbbftTestClient> = (0..num_validators)
+        .map(|_| create_hbbft_client(Random.generate()))
+        .collect();
+    let transaction_funds = U256::from(9000000000000000000u64);
+    for validator in &validators {
+        moc.transfer(&transactor, &validator.address(), &transaction_funds);
+        create_staker(&mut moc, &transactor, validator, transaction_funds);
+    }
+    let expected: BTreeMap<Address, _> = validators
+        .iter()
+        .map(|v| (v.address(), v.keypair.public().clone()))
+        .collect();
+
+    let clients: Vec<RwLock<HbbftTestClient>> = Some(moc)
+        .into_iter()
+        .chain(validators)
+        .map(RwLock::new)
+        .collect();
+
+    // The next phase transition selects all pools as pending validators.
+    crank_until_with(
+        &clients,
+        &transactor,
+        "Pending validator selection",
+        MAX_CRANKS,
+        &mut crank,
+        |c| {
+            let pending =
+                get_pending_validators(c.client.as_ref()).expect("Constant call must succeed");
+            expected.keys().all(|address| pending.contains(address))
+        },
+    );
+
+    // The pending validators write their Parts and Acks, and the contracts switch to them.
+    crank_until_with(
+        &clients,
+        &transactor,
+        "Key generation",
+        MAX_CRANKS,
+        &mut crank,
+        |c| posdao_epoch(c) == U256::from(1),
+    );
+    for client in &clients {
+        let current = get_validator_pubkeys(
+            client.read().client.as_ref(),
+            BlockId::Latest,
+            ValidatorType::Current,
+        )
+        .expect("Constant call must succeed");
+        assert_eq!(current, expected);
+    }
+    (clients, transactor)
+}
+
+fn run_posdao_epoch_switch(num_validators: usize) {
+    let (clients, transactor) = switch_to_validators(num_validators);
+
+    // The new validators agree on blocks, which every client accepts with the new set's
+    // threshold signature. The master of ceremonies is no longer a validator.
+    let switch_block = clients[0].read().client.chain().best_block_number();
+    crank_until(
+        &clients,
+        &transactor,
+        "Sealing by the new validators",
+        |c| c.client.chain().best_block_number() > switch_block,
+    );
+    let moc = clients[0].read();
+    let header = moc
+        .client
+        .block_header(BlockId::Number(switch_block + 1))
+        .expect("Block must exist");
+    assert_eq!(header.seal().len(), 1);
+    assert_eq!(posdao_epoch(&moc), U256::from(1));
+}
+
+#[test]
+fn posdao_epoch_switch_to_two_validators() {
+    run_posdao_epoch_switch(2);
+}
+
+#[test]
+fn posdao_epoch_switch_to_four_validators() {
+    run_posdao_epoch_switch(4);
+}
@@ -0,0 +1,95 @@
+//! Deterministic ordering of the transactions of an agreed batch.
+//!
+//! By default, a block contains the transactions of the batch's contributions in the order of
+//! the contributing validators. A sender's transactions can then be interleaved with others, or
+//! appear out of nonce order if different validators contributed them, in which case the ones
+//! with a too high nonce are dropped from the block. With `groupTransactionsBySender` enabled,
+//! each sender's transactions are placed next to each other, ordered by nonce.
+
+use ethereum_types::{Address, H256, U256};
+use hash::keccak;
+use std::collections::BTreeMap;
+use types::transaction::SignedTransaction;
+
+/// Orders the transactions grouped by sender, and by nonce within each group.
+///
+/// The groups are ordered by the hash of the batch's random number and the sender's address:
+/// all validators assemble the same block, but no sender can make sure to be placed first.
+pub fn group_by_sender(
+    transactions: Vec<SignedTransaction>,
+    random_number: &U256,
+) -> Vec<SignedTransaction> {
+    let mut by_sender: BTreeMap<Address, Vec<SignedTransaction>> = BTreeMap::new();
+    for transaction in transactions {
+        by_sender
+            .entry(transaction.sender())
+            .or_default()
+            .push(transaction);
+    }
+
+    let mut seed = [0u8; 32];
+    random_number.to_big_endian(&mut seed);
+    let mut groups: Vec<(H256, Vec<SignedTransaction>)> = by_sender
+        .into_iter()
+        .map(|(sender, mut transactions)| {
+            // The sort is stable, so transactions with the same nonce keep their batch order.
+            transactions.sort_by_key(|transaction| transaction.tx().nonce);
+            let key = keccak([&seed[..], sender.as_bytes()].concat());
+            (key, transactions)
+        })
+        .collect();
+    groups.sort_by_key(|(key, _)| *key);
+    groups
+        .into_iter()
+        .flat_map(|(_, transactions)| transactions)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{Generator, Random};
+    use engines::hbbft::test::create_transactions::create_transaction;
+
+    fn interleaved_batch() -> Vec<SignedTransaction> {
+        let senders: Vec<_> = (0..4).map(|_| Random.generate()).collect();
+        // Nonces in descending order, alternating between senders.
+        (0..5u64)
+            .rev()
+            .flat_map(|nonce| {
+                senders
+                    .iter()
+                    .map(move |sender| create_transaction(sender, &U256::from(nonce)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nonces_increase_within_contiguous_sender_groups() {
+        let ordered = group_by_sender(interleaved_batch(), &U256::from(7));
+        assert_eq!(ordered.len(), 20);
+        for group in ordered.chunks(5) {
+            assert!(group.iter().all(|tx| tx.sender() == group[0].sender()));
+            let nonces: Vec<_> = group.iter().map(|tx| tx.tx().nonce.low_u64()).collect();
+            assert_eq!(nonces, vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn order_depends_only_on_the_random_number() {
+        let batch = interleaved_batch();
+        let mut reversed = batch.clone();
+        reversed.reverse();
+        let ordered = group_by_sender(batch.clone(), &U256::from(7));
+        assert_eq!(group_by_sender(reversed, &U256::from(7)), ordered);
+
+        let senders = |txs: &[SignedTransaction]| -> Vec<Address> {
+            txs.iter().step_by(5).map(|tx| tx.sender()).collect()
+        };
+        let other_orders = (8..16)
+            .map(|seed| senders(&group_by_sender(batch.clone(), &U256::from(seed))))
+            .filter(|order| *order != senders(&ordered))
+            .count();
+        assert!(other_orders > 0);
+    }
+}
@@ -0,0 +1,402 @@
+//! Reserved peer connections to the other validators.
+//!
+//! Once per POSDAO epoch, the engine reserves connections to the nodes of the epoch's other
+//! validators, and drops the reservations of nodes that are no longer validators. Once the next
+//! epoch's validators are elected, their nodes are reserved as well, so that the key generation
+//! messages reach them and the new validator set is fully connected when the epoch starts. A validator's
+//! enode is made of its node ID and the IP address its pool announced in the staking contract.
+//! The contract does not store ports: a validator's port is taken from an enode with the same
+//! node ID in the node's configuration, e.g. its reserved peers, and is the default port
+//! otherwise. Operators can pin additional enodes, which stay reserved regardless of the
+//! validator set. Pins are persisted in the client database.
+
+use client::traits::EngineClient;
+use crypto::publickey::Public;
+use ethereum_types::{Address, H512};
+use serde_json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::Ipv6Addr,
+    str::FromStr,
+};
+use types::ids::BlockId;
+
+use super::{
+    api::ValidatorPeer,
+    contracts::{staking::get_pool_internet_address, validator_set::staking_by_mining_address},
+    NodeId,
+};
+
+/// The port of validators whose enode is not configured.
+pub const DEFAULT_PORT: u16 = 30303;
+
+/// The database key the pinned enodes are persisted under.
+const PINNED_PEERS_KEY: &[u8] = b"hbbft_pinned_peers";
+
+/// The devp2p ports of the nodes whose enodes are configured.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidatorPorts {
+    ports: BTreeMap<NodeId, u16>,
+}
+
+impl ValidatorPorts {
+    /// Collects the ports of the given enodes, skipping the ones that cannot be parsed.
+    pub fn from_enodes<'a, I: IntoIterator<Item = &'a String>>(enodes: I) -> Self {
+        let ports = enodes
+            .into_iter()
+            .filter_map(|enode| {
+                let parsed = parse_enode(enode);
+                if parsed.is_none() {
+                    debug!(target: "engine", "Ignoring unparsable enode {}.", enode);
+                }
+                parsed
+            })
+            .collect();
+        ValidatorPorts { ports }
+    }
+
+    /// The port of the given node: the configured one, or the default port.
+    pub fn port(&self, node_id: &NodeId) -> u16 {
+        self.ports.get(node_id).cloned().unwrap_or(DEFAULT_PORT)
+    }
+}
+
+/// Returns the node ID and the port of an enode of the form `enode://<id>@<host>:<port>`.
+fn parse_enode(enode: &str) -> Option<(NodeId, u16)> {
+    let rest = enode.trim().strip_prefix("enode://")?;
+    let at = rest.find('@')?;
+    let node_id = H512::from_str(&rest[..at]).ok()?;
+    let address = rest[at + 1..].split('?').next()?;
+    let port = address[address.rfind(':')? + 1..].parse().ok()?;
+    Some((NodeId(node_id), port))
+}
+
+/// Returns the enode of a validator node listening on the given port, or `None` if its pool
+/// announced no IP address.
+///
+/// IPv4 addresses are expected as IPv4-mapped or IPv4-compatible IPv6 addresses.
+pub fn validator_enode(node_id: &NodeId, internet_address: [u8; 16], port: u16) -> Option<String> {
+    let ip = Ipv6Addr::from(internet_address);
+    if ip.is_unspecified() {
+        return None;
+    }
+    let host = match ip.to_ipv4() {
+        Some(ipv4) => ipv4.to_string(),
+        None => format!("[{}]", ip),
+    };
+    Some(format!("enode://{:x}@{}:{}", node_id.0, host, port))
+}
+
+/// Returns the enodes of the given validators other than ourselves, skipping the ones whose
+/// pool announced no IP address.
+pub fn validator_enodes(
+    client: &dyn EngineClient,
+    block_id: BlockId,
+    validators: BTreeMap<Address, Public>,
+    our_id: Option<NodeId>,
+    ports: &ValidatorPorts,
+) -> BTreeSet<String> {
+    validators
+        .into_iter()
+        .filter(|(_, public)| our_id != Some(NodeId(*public)))
+        .filter_map(|(mining, public)| {
+            let staking = staking_by_mining_address(client, block_id, &mining).ok()?;
+            let ip = get_pool_internet_address(client, block_id, staking).ok()?;
+            let node_id = NodeId(public);
+            let enode = validator_enode(&node_id, ip, ports.port(&node_id));
+            if enode.is_none() {
+                debug!(target: "engine", "Validator {} announced no IP address.", mining);
+            }
+            enode
+        })
+        .collect()
+}
+
+/// The validator and pinned enodes, and the ones reserved with the network.
+#[derive(Default)]
+pub struct ValidatorPeers {
+    posdao_epoch: Option<u64>,
+    validators: BTreeSet<String>,
+    pending_block: Option<u64>,
+    pending_ids: BTreeSet<NodeId>,
+    pending: BTreeSet<String>,
+    pinned: BTreeSet<String>,
+    reserved: BTreeSet<String>,
+    ports: ValidatorPorts,
+}
+
+impl ValidatorPeers {
+    /// Loads the pinned enodes from the client database.
+    pub fn restore(client: &dyn EngineClient) -> Self {
+        let mut peers = ValidatorPeers::default();
+        if let Some(bytes) = client.engine_data(PINNED_PEERS_KEY) {
+            match serde_json::from_slice(&bytes) {
+                Ok(pinned) => peers.pinned = pinned,
+                Err(e) => warn!(target: "engine", "Discarding unreadable pinned peers: {}", e),
+            }
+        }
+        peers
+    }
+
+    /// Takes the validators' ports from the given configured enodes, and makes the validator and
+    /// pending enodes outdated, to be rebuilt with them.
+    pub fn set_configured_enodes(&mut self, enodes: &[String]) {
+        self.ports = ValidatorPorts::from_enodes(enodes);
+        self.posdao_epoch = None;
+        self.pending_block = None;
+        self.pending_ids.clear();
+    }
+
+    /// The ports of the validators whose enodes are configured.
+    pub fn ports(&self) -> &ValidatorPorts {
+        &self.ports
+    }
+
+    /// Returns true if the validator enodes were not set for the given POSDAO epoch yet.
+    pub fn is_outdated(&self, posdao_epoch: u64) -> bool {
+        self.posdao_epoch != Some(posdao_epoch)
+    }
+
+    /// Sets the enodes of the validators of the given POSDAO epoch.
+    pub fn set_validators(&mut self, posdao_epoch: u64, validators: BTreeSet<String>) {
+        self.posdao_epoch = Some(posdao_epoch);
+        self.validators = validators;
+    }
+
+    /// Returns true if the pending validators were not checked at the given block yet.
+    pub fn is_pending_outdated(&self, block_number: u64) -> bool {
+        self.pending_block != Some(block_number)
+    }
+
+    /// Records the node IDs of the pending validators at the given block, and returns whether
+    /// they differ from the ones the pending enodes were set for.
+    pub fn check_pending(&mut self, block_number: u64, pending_ids: BTreeSet<NodeId>) -> bool {
+        self.pending_block = Some(block_number);
+        if self.pending_ids == pending_ids {
+            return false;
+        }
+        self.pending_ids = pending_ids;
+        true
+    }
+
+    /// Sets the enodes of the validators elected for the next POSDAO epoch.
+    pub fn set_pending_validators(&mut self, pending: BTreeSet<String>) {
+        self.pending = pending;
+    }
+
+    /// Reserves the given enode until it is unpinned, and persists the pin.
+    pub fn pin(&mut self, client: &dyn EngineClient, enode: String) -> Result<(), String> {
+        if !self.reserved.contains(&enode) {
+            client.add_reserved_peer(enode.clone())?;
+            self.reserved.insert(enode.clone());
+        }
+        self.pinned.insert(enode);
+        self.persist(client);
+        Ok(())
+    }
+
+    /// Removes the pin of the given enode. It stays reserved if it belongs to a current or
+    /// pending validator.
+    pub fn unpin(&mut self, client: &dyn EngineClient, enode: &str) -> Result<(), String> {
+        if !self.pinned.remove(enode) {
+            return Err(format!("{} is not pinned.", enode));
+        }
+        self.persist(client);
+        self.reconcile(client);
+        Ok(())
+    }
+
+    /// Reserves the current and pending validator enodes and the pinned ones, and drops all other
+    /// reservations.
+    ///
+    /// Failures are retried on the next call.
+    pub fn reconcile(&mut self, client: &dyn EngineClient) {
+        let wanted: BTreeSet<String> = self
+            .validators
+            .iter()
+            .chain(&self.pending)
+            .chain(&self.pinned)
+            .cloned()
+            .collect();
+        for enode in wanted
+            .difference(&self.reserved)
+            .cloned()
+            .collect::<Vec<_>>()
+        {
+            match client.add_reserved_peer(enode.clone()) {
+                Ok(()) => {
+                    self.reserved.insert(enode);
+                }
+                Err(e) => debug!(target: "engine", "Could not reserve peer {}: {}", enode, e),
+            }
+        }
+        for enode in self
+            .reserved
+            .difference(&wanted)
+            .cloned()
+            .collect::<Vec<_>>()
+        {
+            match client.remove_reserved_peer(enode.clone()) {
+                Ok(()) => {
+                    self.reserved.remove(&enode);
+                }
+                Err(e) => debug!(target: "engine", "Could not unreserve peer {}: {}", enode, e),
+            }
+        }
+    }
+
+    /// Returns the current and pending validator enodes and the pinned ones.
+    pub fn peers(&self) -> Vec<ValidatorPeer> {
+        let enodes: BTreeSet<&String> = self
+            .validators
+            .iter()
+            .chain(&self.pending)
+            .chain(&self.pinned)
+            .collect();
+        enodes
+            .into_iter()
+            .map(|enode| ValidatorPeer {
+                enode: enode.clone(),
+                is_validator: self.validators.contains(enode),
+                is_pending_validator: self.pending.contains(enode),
+                is_pinned: self.pinned.contains(enode),
+                is_reserved: self.reserved.contains(enode),
+            })
+            .collect()
+    }
+
+    fn persist(&self, client: &dyn EngineClient) {
+        match serde_json::to_vec(&self.pinned) {
+            Ok(bytes) => client.set_engine_data(PINNED_PEERS_KEY, Some(bytes)),
+            Err(e) => error!(target: "engine", "Could not serialize pinned peers: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::TestBlockChainClient;
+    use ethereum_types::H512;
+    use std::net::Ipv4Addr;
+
+    fn enode(n: u64) -> String {
+        validator_enode(&NodeId(H512::from_low_u64_be(n)), [10; 16], DEFAULT_PORT).unwrap()
+    }
+
+    fn validators(ns: &[u64]) -> BTreeSet<String> {
+        ns.iter().map(|n| enode(*n)).collect()
+    }
+
+    #[test]
+    fn enodes_are_built_from_the_announced_address() {
+        let node_id = NodeId(H512::from_low_u64_be(1));
+        let ipv4 = Ipv4Addr::new(192, 168, 0, 1).to_ipv6_mapped().octets();
+        let enode = validator_enode(&node_id, ipv4, DEFAULT_PORT).unwrap();
+        assert!(enode.starts_with("enode://0000"));
+        assert!(enode.ends_with("01@192.168.0.1:30303"));
+        assert_eq!(validator_enode(&node_id, [0; 16], DEFAULT_PORT), None);
+    }
+
+    #[test]
+    fn ports_are_taken_from_generated_configs() {
+        // The config generator's reserved peers: node `i` listens on the base port plus `i`,
+        // 30300 by default or set with `--port-base`.
+        let ids: Vec<NodeId> = (1..=3).map(|n| NodeId(H512::from_low_u64_be(n))).collect();
+        for base_port in &[30300u16, 40000] {
+            let reserved: Vec<String> = ids
+                .iter()
+                .enumerate()
+                .map(|(idx, id)| {
+                    format!("enode://{:x}@127.0.0.1:{}", id.0, *base_port as usize + idx)
+                })
+                .collect();
+            let ports = ValidatorPorts::from_enodes(&reserved);
+            for (idx, id) in ids.iter().enumerate() {
+                assert_eq!(ports.port(id) as usize, *base_port as usize + idx);
+            }
+            let ip = Ipv4Addr::new(127, 0, 0, 1).to_ipv6_mapped().octets();
+            assert_eq!(
+                validator_enode(&ids[2], ip, ports.port(&ids[2])).as_ref(),
+                Some(&reserved[2])
+            );
+        }
+
+        let unknown = NodeId(H512::from_low_u64_be(9));
+        let ports = ValidatorPorts::from_enodes(&vec![
+            "enode://nonsense@127.0.0.1:1".to_owned(),
+            format!("enode://{:x}@[::1]:30305?discport=30306", ids[0].0),
+        ]);
+        assert_eq!(ports.port(&ids[0]), 30305);
+        assert_eq!(ports.port(&unknown), DEFAULT_PORT);
+    }
+
+    #[test]
+    fn reservations_follow_the_validator_set() {
+        let client = TestBlockChainClient::new();
+        let mut peers = ValidatorPeers::default();
+        assert!(peers.is_outdated(1));
+        peers.set_validators(1, validators(&[1, 2]));
+        peers.reconcile(&client);
+        assert_eq!(*client.reserved_peers.read(), validators(&[1, 2]));
+
+        peers.set_validators(2, validators(&[2, 3]));
+        assert!(!peers.is_outdated(2));
+        peers.reconcile(&client);
+        assert_eq!(*client.reserved_peers.read(), validators(&[2, 3]));
+    }
+
+    #[test]
+    fn pending_validators_are_reserved_until_they_leave() {
+        let client = TestBlockChainClient::new();
+        let mut peers = ValidatorPeers::default();
+        peers.set_validators(1, validators(&[1, 2]));
+        let ids = |ns: &[u64]| -> BTreeSet<NodeId> {
+            ns.iter()
+                .map(|n| NodeId(H512::from_low_u64_be(*n)))
+                .collect()
+        };
+
+        assert!(peers.is_pending_outdated(10));
+        assert!(!peers.check_pending(10, ids(&[])));
+        assert!(!peers.is_pending_outdated(10));
+        assert!(peers.check_pending(11, ids(&[2, 3])));
+        peers.set_pending_validators(validators(&[2, 3]));
+        assert!(!peers.check_pending(12, ids(&[2, 3])));
+        peers.reconcile(&client);
+        assert_eq!(*client.reserved_peers.read(), validators(&[1, 2, 3]));
+        let pending: Vec<bool> = peers
+            .peers()
+            .iter()
+            .map(|p| p.is_pending_validator)
+            .collect();
+        assert_eq!(pending, vec![false, true, true]);
+
+        // The pending validators take over in the next epoch.
+        peers.set_validators(2, validators(&[2, 3]));
+        assert!(peers.check_pending(13, ids(&[])));
+        peers.set_pending_validators(BTreeSet::new());
+        peers.reconcile(&client);
+        assert_eq!(*client.reserved_peers.read(), validators(&[2, 3]));
+    }
+
+    #[test]
+    fn pins_are_persisted_and_outlive_the_validator_set() {
+        let client = TestBlockChainClient::new();
+        let mut peers = ValidatorPeers::default();
+        peers.set_validators(1, validators(&[1]));
+        peers.reconcile(&client);
+        peers.pin(&client, enode(1)).unwrap();
+        peers.pin(&client, enode(5)).unwrap();
+
+        let mut restarted = ValidatorPeers::restore(&client);
+        restarted.set_validators(2, validators(&[2]));
+        restarted.reconcile(&client);
+        assert_eq!(restarted.peers().len(), 3);
+        assert_eq!(*client.reserved_peers.read(), validators(&[1, 2, 5]));
+
+        restarted.unpin(&client, &enode(1)).unwrap();
+        assert!(restarted.unpin(&client, &enode(1)).is_err());
+        assert_eq!(*client.reserved_peers.read(), validators(&[2, 5]));
+    }
+}
@@ -0,0 +1,183 @@
+//! Instrumentation to debug lockups of the engine.
+//!
+//! The engine's central locks are wrapped in a `TimedRwLock`, which records the longest time
+//! the lock was held. The engine's timer ticks are watched by a `TickWatchdog` from a separate
+//! thread: if a tick takes too long, e.g. because it waits for a lock that is never released, the
+//! thread logs the recorded hold times together with a dump of the engine state while the tick is
+//! still running.
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A read-write lock that records the longest time it was held.
+pub struct TimedRwLock<T> {
+    lock: RwLock<T>,
+    longest_hold_nanos: AtomicU64,
+}
+
+/// A lock guard that records how long it was held when dropped.
+pub struct TimedGuard<'a, G> {
+    guard: G,
+    acquired: Instant,
+    longest_hold_nanos: &'a AtomicU64,
+}
+
+impl<T> TimedRwLock<T> {
+    pub fn new(value: T) -> Self {
+        TimedRwLock {
+            lock: RwLock::new(value),
+            longest_hold_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn read(&self) -> TimedGuard<RwLockReadGuard<T>> {
+        self.timed(self.lock.read())
+    }
+
+    pub fn write(&self) -> TimedGuard<RwLockWriteGuard<T>> {
+        self.timed(self.lock.write())
+    }
+
+    /// Acquires a read lock only if that is possible without blocking.
+    pub fn try_read(&self) -> Option<TimedGuard<RwLockReadGuard<T>>> {
+        self.lock.try_read().map(|guard| self.timed(guard))
+    }
+
+    /// Returns the longest time the lock was held since the last call, and resets it.
+    pub fn take_longest_hold(&self) -> Duration {
+        Duration::from_nanos(self.longest_hold_nanos.swap(0, Ordering::SeqCst))
+    }
+
+    fn timed<G>(&self, guard: G) -> TimedGuard<G> {
+        TimedGuard {
+            guard,
+            acquired: Instant::now(),
+            longest_hold_nanos: &self.longest_hold_nanos,
+        }
+    }
+}
+
+/// The start time of the running timer tick, shared with the thread watching it.
+pub struct TickWatchdog {
+    /// The reference point of `tick_start_millis`.
+    created: Instant,
+    /// When the running tick started, in milliseconds after `created` plus one, or 0 between
+    /// ticks.
+    tick_start_millis: AtomicU64,
+    /// Whether the running tick was already reported.
+    reported: AtomicBool,
+}
+
+impl Default for TickWatchdog {
+    fn default() -> Self {
+        TickWatchdog {
+            created: Instant::now(),
+            tick_start_millis: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+}
+
+impl TickWatchdog {
+    /// Records the start of a tick.
+    pub fn tick_started(&self, now: Instant) {
+        self.reported.store(false, Ordering::SeqCst);
+        self.tick_start_millis
+            .store(self.millis(now) + 1, Ordering::SeqCst);
+    }
+
+    /// Records the end of the running tick.
+    pub fn tick_finished(&self) {
+        self.tick_start_millis.store(0, Ordering::SeqCst);
+    }
+
+    /// Returns how long the running tick has been running if that is longer than `threshold`.
+    /// A slow tick is only returned once.
+    pub fn check(&self, now: Instant, threshold: Duration) -> Option<Duration> {
+        let start = self.tick_start_millis.load(Ordering::SeqCst);
+        if start == 0 {
+            return None;
+        }
+        let running = Duration::from_millis((self.millis(now) + 1).saturating_sub(start));
+        if running <= threshold || self.reported.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(running)
+    }
+
+    fn millis(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.created).as_millis() as u64
+    }
+}
+
+impl<'a, G: Deref> Deref for TimedGuard<'a, G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<'a, G: DerefMut> DerefMut for TimedGuard<'a, G> {
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+impl<'a, G> Drop for TimedGuard<'a, G> {
+    fn drop(&mut self) {
+        let held = self.acquired.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+        self.longest_hold_nanos.fetch_max(held, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn longest_hold_is_recorded_and_reset() {
+        let lock = TimedRwLock::new(0u32);
+        {
+            let mut value = lock.write();
+            *value += 1;
+            sleep(Duration::from_millis(20));
+        }
+        assert_eq!(*lock.read(), 1);
+        assert!(lock.take_longest_hold() >= Duration::from_millis(20));
+        assert!(lock.take_longest_hold() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn running_slow_tick_is_reported_once() {
+        let watchdog = TickWatchdog::default();
+        let start = Instant::now();
+        let threshold = Duration::from_secs(5);
+        assert_eq!(watchdog.check(start + 2 * threshold, threshold), None);
+
+        watchdog.tick_started(start);
+        assert_eq!(watchdog.check(start + threshold, threshold), None);
+        let stuck = start + 2 * threshold;
+        assert!(watchdog.check(stuck, threshold).unwrap() > threshold);
+        assert_eq!(watchdog.check(stuck, threshold), None);
+
+        // The next slow tick is reported again, but not once it finished.
+        watchdog.tick_started(stuck);
+        assert!(watchdog.check(stuck + 2 * threshold, threshold).is_some());
+        watchdog.tick_started(stuck);
+        watchdog.tick_finished();
+        assert_eq!(watchdog.check(stuck + 2 * threshold, threshold), None);
+    }
+
+    #[test]
+    fn try_read_does_not_block_on_a_writer() {
+        let lock = TimedRwLock::new(0u32);
+        let _writer = lock.write();
+        assert!(lock.try_read().is_none());
+    }
+}
@@ -0,0 +1,602 @@
+//! Versioned encoding of the data exchanged between hbbft validators.
+//!
+//! Parts and Acks are stored in the key generation history contract and consensus messages are
+//! sent between nodes, so their encoding has to stay readable while the validators upgrade the
+//! hbbft dependency one by one. Encoded data starts with a version header; data without a header
+//! uses the legacy format written before versions were introduced.
+//!
+//! Decoding always accepts every known version. The version used for encoding is chosen per
+//! POSDAO epoch by the `wireVersions` engine parameter, so a release able to decode a new version
+//! can be rolled out first, and the network switches to writing it at an agreed epoch. If a
+//! feature registry is configured, the switch also happens at the first epoch in which enough
+//! validators announce support for the new version.
+//!
+//! From `V2` on, the consensus messages sent to a node at once are batched into a single packet,
+//! encoded with bincode and compressed with snappy, instead of one JSON packet per message.
+//!
+//! From `V3` on, every packet of consensus messages carries an envelope with the chain ID and the
+//! sender's POSDAO epoch, so that messages recorded on one network or in an earlier epoch cannot be
+//! replayed to validators of another network sharing the same validator keys.
+//!
+//! From `V4` on, every packet of consensus messages is signed with the sender's validator key. The
+//! devp2p node ID a packet arrives with is only as trustworthy as the peer's session, so receivers
+//! check that the packet was signed by the node it claims to come from.
+//!
+//! Packets of consensus messages come from untrusted peers. Packets larger than
+//! `MAX_PACKET_BYTES` are rejected before any decoding, and compressed batches are rejected if
+//! they would decompress to more than `MAX_BATCH_BYTES`.
+
+use crypto::publickey::{recover, Public, Signature};
+use engines::signer::EngineSigner;
+use hash::keccak;
+use hbbft::sync_key_gen::{Ack, Part};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
+use snappy;
+use std::{collections::BTreeMap, fmt};
+
+/// Marks versioned data. Legacy data never starts with it: bincode encoded Parts and Acks start
+/// with a little endian degree or index far below `0x776268`, and JSON messages with `{`.
+const VERSION_MAGIC: &[u8] = b"hbw";
+
+/// The maximum number of consensus messages encoded into one batch.
+const MAX_MESSAGES_PER_BATCH: usize = 128;
+
+/// The maximum decompressed size of a batch of consensus messages, in bytes.
+const MAX_BATCH_BYTES: usize = 16 * 1024 * 1024;
+
+/// The maximum size of a received packet of consensus messages, in bytes.
+const MAX_PACKET_BYTES: usize = 16 * 1024 * 1024;
+
+/// The length of the ECDSA signature following the version header of signed packets.
+const SIGNATURE_LEN: usize = 65;
+
+/// A version of the encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WireVersion {
+    /// Unversioned bincode for Parts and Acks, and JSON for consensus messages.
+    Legacy,
+    /// The legacy encodings of the hbbft 0.1 types behind a version header.
+    V1,
+    /// Like `V1`, but consensus messages are batched, bincode encoded and snappy compressed.
+    V2,
+    /// Like `V2`, but the batches of consensus messages are wrapped in an [`Envelope`].
+    V3,
+    /// Like `V3`, but the packets of consensus messages are signed by their sender.
+    V4,
+}
+
+impl WireVersion {
+    /// Returns the version with the given number, as used in the `wireVersions` parameter.
+    pub fn from_number(number: u8) -> Option<Self> {
+        match number {
+            0 => Some(WireVersion::Legacy),
+            1 => Some(WireVersion::V1),
+            2 => Some(WireVersion::V2),
+            3 => Some(WireVersion::V3),
+            4 => Some(WireVersion::V4),
+            _ => None,
+        }
+    }
+
+    fn number(self) -> u8 {
+        match self {
+            WireVersion::Legacy => 0,
+            WireVersion::V1 => 1,
+            WireVersion::V2 => 2,
+            WireVersion::V3 => 3,
+            WireVersion::V4 => 4,
+        }
+    }
+}
+
+/// Encoding or decoding failed.
+#[derive(Debug)]
+pub enum WireError {
+    /// The data carries a version this node does not know.
+    UnknownVersion(u8),
+    /// Invalid bincode data.
+    Bincode(bincode::Error),
+    /// Invalid JSON data.
+    Json(serde_json::Error),
+    /// Invalid snappy compressed data.
+    Compression,
+    /// The decompressed batch of messages would exceed the given size.
+    BatchTooLarge(usize),
+    /// The packet of messages has the given size, which exceeds the limit.
+    PacketTooLarge(usize),
+    /// The version requires signed packets, but no signer is available.
+    Unsigned,
+    /// The packet's signature is missing or invalid.
+    InvalidSignature,
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireError::UnknownVersion(v) => write!(f, "Unknown wire format version {}", v),
+            WireError::Bincode(e) => write!(f, "Invalid bincode data: {}", e),
+            WireError::Json(e) => write!(f, "Invalid JSON data: {}", e),
+            WireError::Compression => write!(f, "Invalid compressed data"),
+            WireError::BatchTooLarge(size) => {
+                write!(f, "Message batch of {} bytes exceeds the size limit", size)
+            }
+            WireError::PacketTooLarge(size) => {
+                write!(f, "Message packet of {} bytes exceeds the size limit", size)
+            }
+            WireError::Unsigned => write!(f, "No signer available to sign the message packet"),
+            WireError::InvalidSignature => write!(f, "Invalid message packet signature"),
+        }
+    }
+}
+
+/// The network and POSDAO epoch a packet of consensus messages was sent in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Envelope {
+    /// The chain ID of the sender's network.
+    pub chain_id: u64,
+    /// The sender's current POSDAO epoch.
+    pub posdao_epoch: u64,
+}
+
+/// The versions to encode with, by the POSDAO epoch they are used from.
+#[derive(Clone, Debug, Default)]
+pub struct WireVersions(BTreeMap<u64, WireVersion>);
+
+impl WireVersions {
+    /// Creates the schedule from the `wireVersions` engine parameter.
+    pub fn from_params(transitions: &BTreeMap<u64, u8>) -> Result<Self, String> {
+        transitions
+            .iter()
+            .map(|(epoch, number)| {
+                WireVersion::from_number(*number)
+                    .map(|version| (*epoch, version))
+                    .ok_or_else(|| format!("Unknown hbbft wire format version {}", number))
+            })
+            .collect::<Result<_, _>>()
+            .map(WireVersions)
+    }
+
+    /// Encodes with at least the given version from the given POSDAO epoch on.
+    pub fn activate(&mut self, posdao_epoch: u64, version: WireVersion) {
+        if self.for_epoch(posdao_epoch) < version {
+            self.0.insert(posdao_epoch, version);
+        }
+    }
+
+    /// The version to encode with in the given POSDAO epoch.
+    pub fn for_epoch(&self, posdao_epoch: u64) -> WireVersion {
+        self.0
+            .range(..=posdao_epoch)
+            .next_back()
+            .map_or(WireVersion::Legacy, |(_, version)| *version)
+    }
+}
+
+fn split_header(bytes: &[u8]) -> Result<(WireVersion, &[u8]), WireError> {
+    if !bytes.starts_with(VERSION_MAGIC) || bytes.len() <= VERSION_MAGIC.len() {
+        return Ok((WireVersion::Legacy, bytes));
+    }
+    let number = bytes[VERSION_MAGIC.len()];
+    match WireVersion::from_number(number) {
+        Some(WireVersion::Legacy) | None => Err(WireError::UnknownVersion(number)),
+        Some(version) => Ok((version, &bytes[VERSION_MAGIC.len() + 1..])),
+    }
+}
+
+fn with_header(version: WireVersion, body: Vec<u8>) -> Vec<u8> {
+    match version {
+        WireVersion::Legacy => body,
+        _ => {
+            let mut bytes = Vec::with_capacity(VERSION_MAGIC.len() + 1 + body.len());
+            bytes.extend_from_slice(VERSION_MAGIC);
+            bytes.push(version.number());
+            bytes.extend(body);
+            bytes
+        }
+    }
+}
+
+/// Inserts the signer's signature of the packet after its version header.
+fn sign_packet(packet: Vec<u8>, signer: &dyn EngineSigner) -> Result<Vec<u8>, WireError> {
+    let signature = signer
+        .sign(keccak(&packet))
+        .map_err(|_| WireError::Unsigned)?;
+    let header_len = VERSION_MAGIC.len() + 1;
+    let mut signed = Vec::with_capacity(packet.len() + SIGNATURE_LEN);
+    signed.extend_from_slice(&packet[..header_len]);
+    signed.extend_from_slice(&signature[..]);
+    signed.extend_from_slice(&packet[header_len..]);
+    Ok(signed)
+}
+
+/// Splits the body of a signed packet into the signature and the signed payload.
+fn split_signature(body: &[u8]) -> Result<(Signature, &[u8]), WireError> {
+    if body.len() < SIGNATURE_LEN {
+        return Err(WireError::InvalidSignature);
+    }
+    let mut signature = [0u8; SIGNATURE_LEN];
+    signature.copy_from_slice(&body[..SIGNATURE_LEN]);
+    Ok((Signature::from(signature), &body[SIGNATURE_LEN..]))
+}
+
+fn encode_keygen_data<T: Serialize>(
+    value: &T,
+    version: WireVersion,
+) -> Result<Vec<u8>, WireError> {
+    let body = match version {
+        WireVersion::Legacy
+        | WireVersion::V1
+        | WireVersion::V2
+        | WireVersion::V3
+        | WireVersion::V4 => bincode::serialize(value).map_err(WireError::Bincode)?,
+    };
+    Ok(with_header(version, body))
+}
+
+fn decode_keygen_data<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, WireError> {
+    let (version, body) = split_header(bytes)?;
+    match version {
+        WireVersion::Legacy
+        | WireVersion::V1
+        | WireVersion::V2
+        | WireVersion::V3
+        | WireVersion::V4 => bincode::deserialize(body).map_err(WireError::Bincode),
+    }
+}
+
+/// Encodes a Part for the key generation history contract.
+pub fn encode_part(part: &Part, version: WireVersion) -> Result<Vec<u8>, WireError> {
+    encode_keygen_data(part, version)
+}
+
+/// Decodes a Part read from the key generation history contract.
+pub fn decode_part(bytes: &[u8]) -> Result<Part, WireError> {
+    decode_keygen_data(bytes)
+}
+
+/// Encodes an Ack for the key generation history contract.
+pub fn encode_ack(ack: &Ack, version: WireVersion) -> Result<Vec<u8>, WireError> {
+    encode_keygen_data(ack, version)
+}
+
+/// Decodes an Ack read from the key generation history contract.
+pub fn decode_ack(bytes: &[u8]) -> Result<Ack, WireError> {
+    decode_keygen_data(bytes)
+}
+
+/// Encodes consensus messages for the same node into packets.
+///
+/// Before `V2`, every message is encoded into a packet of its own. The envelope is only included
+/// from `V3` on, and the packets are signed with the given signer from `V4` on.
+pub fn encode_messages<T: Serialize>(
+    messages: &[T],
+    version: WireVersion,
+    envelope: Envelope,
+    signer: Option<&dyn EngineSigner>,
+) -> Result<Vec<Vec<u8>>, WireError> {
+    match version {
+        WireVersion::Legacy | WireVersion::V1 => messages
+            .iter()
+            .map(|message| {
+                let body = serde_json::to_vec(message).map_err(WireError::Json)?;
+                Ok(with_header(version, body))
+            })
+            .collect(),
+        WireVersion::V2 => messages
+            .chunks(MAX_MESSAGES_PER_BATCH)
+            .map(|batch| {
+                let encoded = bincode::serialize(batch).map_err(WireError::Bincode)?;
+                Ok(with_header(version, snappy::compress(&encoded)))
+            })
+            .collect(),
+        WireVersion::V3 => messages
+            .chunks(MAX_MESSAGES_PER_BATCH)
+            .map(|batch| {
+                let encoded = bincode::serialize(&(envelope, batch)).map_err(WireError::Bincode)?;
+                Ok(with_header(version, snappy::compress(&encoded)))
+            })
+            .collect(),
+        WireVersion::V4 => {
+            let signer = signer.ok_or(WireError::Unsigned)?;
+            messages
+                .chunks(MAX_MESSAGES_PER_BATCH)
+                .map(|batch| {
+                    let encoded =
+                        bincode::serialize(&(envelope, batch)).map_err(WireError::Bincode)?;
+                    sign_packet(with_header(version, snappy::compress(&encoded)), signer)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Returns the version a packet of consensus messages was encoded with.
+pub fn packet_version(bytes: &[u8]) -> Result<WireVersion, WireError> {
+    split_header(bytes).map(|(version, _)| version)
+}
+
+/// Returns the public key of the node that signed a packet of consensus messages, or `None` if
+/// the packet was encoded with a version before `V4`, which does not sign packets.
+pub fn packet_signer(bytes: &[u8]) -> Result<Option<Public>, WireError> {
+    let (version, body) = split_header(bytes)?;
+    if version < WireVersion::V4 {
+        return Ok(None);
+    }
+    let (signature, payload) = split_signature(body)?;
+    let hash = keccak(with_header(version, payload.to_vec()));
+    recover(&signature, &hash)
+        .map(Some)
+        .map_err(|_| WireError::InvalidSignature)
+}
+
+/// Decodes a packet of consensus messages, in any version, together with its envelope if it has
+/// one.
+pub fn decode_messages<T: DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<(Option<Envelope>, Vec<T>), WireError> {
+    if bytes.len() > MAX_PACKET_BYTES {
+        return Err(WireError::PacketTooLarge(bytes.len()));
+    }
+    let (version, body) = split_header(bytes)?;
+    match version {
+        WireVersion::Legacy | WireVersion::V1 => serde_json::from_slice(body)
+            .map(|message| (None, vec![message]))
+            .map_err(WireError::Json),
+        WireVersion::V2 => bincode::deserialize(&decompress_batch(body)?)
+            .map(|messages| (None, messages))
+            .map_err(WireError::Bincode),
+        WireVersion::V3 => bincode::deserialize(&decompress_batch(body)?)
+            .map(|(envelope, messages)| (Some(envelope), messages))
+            .map_err(WireError::Bincode),
+        WireVersion::V4 => {
+            let (_, payload) = split_signature(body)?;
+            bincode::deserialize(&decompress_batch(payload)?)
+                .map(|(envelope, messages)| (Some(envelope), messages))
+                .map_err(WireError::Bincode)
+        }
+    }
+}
+
+fn decompress_batch(body: &[u8]) -> Result<Vec<u8>, WireError> {
+    let size = snappy::decompressed_len(body).map_err(|_| WireError::Compression)?;
+    if size > MAX_BATCH_BYTES {
+        return Err(WireError::BatchTooLarge(size));
+    }
+    snappy::decompress(body).map_err(|_| WireError::Compression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{Generator, Random};
+    use engines::signer::from_keypair;
+    use hbbft::{
+        crypto::{PublicKey, SecretKey},
+        sync_key_gen::{PartOutcome, SyncKeyGen},
+    };
+    use rand_065::Rng;
+    use std::sync::Arc;
+
+    const ENVELOPE: Envelope = Envelope {
+        chain_id: 777001,
+        posdao_epoch: 3,
+    };
+
+    fn part_and_ack() -> (Part, Ack) {
+        let mut rng = rand_065::thread_rng();
+        let sec_keys: Vec<SecretKey> = (0..4).map(|_| rng.gen()).collect();
+        let pub_keys: BTreeMap<usize, PublicKey> = sec_keys
+            .iter()
+            .map(|sk| sk.public_key())
+            .enumerate()
+            .collect();
+        let pub_keys = Arc::new(pub_keys);
+        let (mut first, part) =
+            SyncKeyGen::new(0, sec_keys[0].clone(), pub_keys.clone(), 1, &mut rng)
+                .expect("SyncKeyGen creation must succeed");
+        let part = part.expect("validators must create a part");
+        let ack = match first
+            .handle_part(&0, part.clone(), &mut rng)
+            .expect("handling our own part must succeed")
+        {
+            PartOutcome::Valid(Some(ack)) => ack,
+            _ => panic!("our own part must be valid"),
+        };
+        (part, ack)
+    }
+
+    #[test]
+    fn keygen_data_decodes_in_all_versions() {
+        let (part, ack) = part_and_ack();
+        for version in &[
+            WireVersion::Legacy,
+            WireVersion::V1,
+            WireVersion::V2,
+            WireVersion::V3,
+            WireVersion::V4,
+        ] {
+            let encoded = encode_part(&part, *version).unwrap();
+            assert_eq!(decode_part(&encoded).unwrap(), part);
+            let encoded = encode_ack(&ack, *version).unwrap();
+            assert_eq!(decode_ack(&encoded).unwrap(), ack);
+        }
+    }
+
+    #[test]
+    fn legacy_keygen_data_is_decoded() {
+        let (part, ack) = part_and_ack();
+        let legacy_part = bincode::serialize(&part).unwrap();
+        assert_eq!(decode_part(&legacy_part).unwrap(), part);
+        let legacy_ack = bincode::serialize(&ack).unwrap();
+        assert_eq!(decode_ack(&legacy_ack).unwrap(), ack);
+    }
+
+    #[test]
+    fn messages_decode_in_all_versions() {
+        let message = (7usize, "consensus".to_owned());
+        let legacy = serde_json::to_vec(&message).unwrap();
+        assert_eq!(
+            decode_messages::<(usize, String)>(&legacy).unwrap(),
+            (None, vec![message.clone()])
+        );
+        let versioned =
+            encode_messages(&[message.clone()], WireVersion::V1, ENVELOPE, None).unwrap();
+        assert_eq!(versioned.len(), 1);
+        assert_ne!(versioned[0], legacy);
+        assert_eq!(
+            decode_messages::<(usize, String)>(&versioned[0]).unwrap(),
+            (None, vec![message.clone()])
+        );
+        let enveloped =
+            encode_messages(&[message.clone()], WireVersion::V3, ENVELOPE, None).unwrap();
+        assert_eq!(
+            decode_messages::<(usize, String)>(&enveloped[0]).unwrap(),
+            (Some(ENVELOPE), vec![message])
+        );
+    }
+
+    #[test]
+    fn messages_are_batched_from_v2() {
+        let messages: Vec<_> = (0..(MAX_MESSAGES_PER_BATCH + 1))
+            .map(|i| (i, "consensus".to_owned()))
+            .collect();
+        assert_eq!(
+            encode_messages(&messages, WireVersion::V1, ENVELOPE, None)
+                .unwrap()
+                .len(),
+            messages.len()
+        );
+        let packets = encode_messages(&messages, WireVersion::V2, ENVELOPE, None).unwrap();
+        assert_eq!(packets.len(), 2);
+        let decoded: Vec<(usize, String)> = packets
+            .iter()
+            .flat_map(|packet| decode_messages::<(usize, String)>(packet).unwrap().1)
+            .collect();
+        assert_eq!(decoded, messages);
+        let json: usize = encode_messages(&messages, WireVersion::V1, ENVELOPE, None)
+            .unwrap()
+            .iter()
+            .map(Vec::len)
+            .sum();
+        assert!(packets.iter().map(Vec::len).sum::<usize>() < json);
+    }
+
+    #[test]
+    fn message_test_vectors_decode() {
+        let message = (7usize, "consensus".to_owned());
+        let legacy = br#"[7,"consensus"]"#.to_vec();
+        let v1 = [&b"hbw\x01"[..], &legacy].concat();
+        // A bincode encoded batch with the one message, compressed as a single snappy literal.
+        let batch = [
+            &[1, 0, 0, 0, 0, 0, 0, 0][..],
+            &[7, 0, 0, 0, 0, 0, 0, 0],
+            &[9, 0, 0, 0, 0, 0, 0, 0],
+            b"consensus",
+        ]
+        .concat();
+        let v2 = [&b"hbw\x02"[..], &[33, 32 << 2], &batch].concat();
+        let envelope = [
+            &[0x29, 0xdb, 0x0b, 0, 0, 0, 0, 0][..],
+            &[3, 0, 0, 0, 0, 0, 0, 0],
+        ]
+        .concat();
+        let v3 = [&b"hbw\x03"[..], &[49, 48 << 2], &envelope, &batch].concat();
+
+        assert_eq!(
+            encode_messages(&[message.clone()], WireVersion::Legacy, ENVELOPE, None).unwrap(),
+            vec![legacy.clone()]
+        );
+        assert_eq!(
+            encode_messages(&[message.clone()], WireVersion::V1, ENVELOPE, None).unwrap(),
+            vec![v1.clone()]
+        );
+        for (packet, envelope) in &[(legacy, None), (v1, None), (v2, None), (v3, Some(ENVELOPE))] {
+            assert_eq!(
+                decode_messages::<(usize, String)>(packet).unwrap(),
+                (*envelope, vec![message.clone()])
+            );
+        }
+    }
+
+    #[test]
+    fn packets_are_signed_from_v4() {
+        let keypair = Random.generate();
+        let public = *keypair.public();
+        let signer = from_keypair(keypair);
+        let message = (7usize, "consensus".to_owned());
+        let unsigned =
+            encode_messages(&[message.clone()], WireVersion::V3, ENVELOPE, None).unwrap();
+        assert_eq!(packet_signer(&unsigned[0]).unwrap(), None);
+        match encode_messages(&[message.clone()], WireVersion::V4, ENVELOPE, None) {
+            Err(WireError::Unsigned) => {}
+            other => panic!("unexpected encoding result: {:?}", other),
+        }
+
+        let signed = encode_messages(
+            &[message.clone()],
+            WireVersion::V4,
+            ENVELOPE,
+            Some(&*signer),
+        )
+        .unwrap();
+        assert_eq!(packet_signer(&signed[0]).unwrap(), Some(public));
+        assert_eq!(
+            decode_messages::<(usize, String)>(&signed[0]).unwrap(),
+            (Some(ENVELOPE), vec![message])
+        );
+        // Tampering with the payload changes the recovered signer.
+        let mut tampered = signed[0].clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_ne!(packet_signer(&tampered).ok(), Some(Some(public)));
+        match packet_signer(b"hbw\x04short") {
+            Err(WireError::InvalidSignature) => {}
+            other => panic!("unexpected signer: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn oversized_packets_are_rejected() {
+        let mut packet = b"hbw\x01\"".to_vec();
+        packet.resize(MAX_PACKET_BYTES, b'a');
+        packet.push(b'"');
+        match decode_messages::<String>(&packet) {
+            Err(WireError::PacketTooLarge(size)) => assert_eq!(size, MAX_PACKET_BYTES + 1),
+            other => panic!("unexpected decoding result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let mut bytes = VERSION_MAGIC.to_vec();
+        bytes.push(200);
+        bytes.extend_from_slice(b"{}");
+        match decode_messages::<serde_json::Value>(&bytes) {
+            Err(WireError::UnknownVersion(200)) => {}
+            other => panic!("unexpected decoding result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn versions_follow_the_schedule() {
+        let transitions = vec![(10, 1)].into_iter().collect();
+        let versions = WireVersions::from_params(&transitions).unwrap();
+        assert_eq!(versions.for_epoch(0), WireVersion::Legacy);
+        assert_eq!(versions.for_epoch(9), WireVersion::Legacy);
+        assert_eq!(versions.for_epoch(10), WireVersion::V1);
+        assert_eq!(versions.for_epoch(11), WireVersion::V1);
+        assert!(WireVersions::from_params(&vec![(1, 9)].into_iter().collect()).is_err());
+    }
+
+    #[test]
+    fn activation_only_raises_the_version() {
+        let mut versions = WireVersions::default();
+        versions.activate(5, WireVersion::Legacy);
+        assert_eq!(versions.for_epoch(5), WireVersion::Legacy);
+        versions.activate(5, WireVersion::V1);
+        assert_eq!(versions.for_epoch(4), WireVersion::Legacy);
+        assert_eq!(versions.for_epoch(5), WireVersion::V1);
+        versions.activate(7, WireVersion::Legacy);
+        assert_eq!(versions.for_epoch(7), WireVersion::V1);
+    }
+}
@@ -19,12 +19,13 @@
 mod authority_round;
 mod basic_authority;
 mod clique;
-mod hbbft;
+pub mod hbbft;
 mod instant_seal;
 mod null_engine;
 mod validator_set;
 
 pub mod block_reward;
+pub mod remote_signer;
 pub mod signer;
 
 pub use self::{
@@ -44,6 +45,7 @@ pub use types::engines::{
 };
 
 use std::{
+    any::Any,
     collections::{BTreeMap, HashMap},
     error, fmt,
     sync::{Arc, Weak},
@@ -53,6 +55,7 @@ use builtin::Builtin;
 use error::Error;
 use snapshot::SnapshotComponents;
 use spec::CommonParams;
+use stats::PrometheusRegistry;
 use types::{
     header::{ExtendedHeader, Header},
     transaction::{self, SignedTransaction, UnverifiedTransaction},
@@ -486,6 +489,9 @@ pub trait Engine<M: Machine>: Sync + Send {
     /// Trigger next step of the consensus engine.
     fn step(&self) {}
 
+    /// Called when the client shuts down, so the engine can persist its state.
+    fn on_shutdown(&self) {}
+
     /// Create a factory for building snapshot chunks and restoring from them.
     /// Returning `None` indicates that this engine doesn't support snapshot creation.
     fn snapshot_components(&self) -> Option<Box<dyn SnapshotComponents>> {
@@ -543,11 +549,24 @@ pub trait Engine<M: Machine>: Sync + Send {
     }
 
     /// Overrides the block gas limit. Whenever this returns `Some` for a header, the next block's gas limit must be
-    /// exactly that value. used by AuRa engine.
+    /// exactly that value. Used by the AuRa and hbbft engines.
     fn gas_limit_override(&self, _header: &Header) -> Option<U256> {
         None
     }
 
+    /// Overrides the configured minimum gas price of the transaction queue while the given header
+    /// is the best block, e.g. with a price set by on-chain governance. Used by the hbbft engine.
+    fn min_gas_price_override(&self, _header: &Header) -> Option<U256> {
+        None
+    }
+
+    /// Whether a new block failing to import proves its sender faulty regardless of the local
+    /// state, so sync may disable peers sending several such blocks in a row. Engines whose seal
+    /// checks depend on local consensus state, which may lag behind the chain, must not opt in.
+    fn invalid_blocks_are_peer_faults(&self) -> bool {
+        false
+    }
+
     /// Whether the miner should prepare blocks for sealing for this engine.
     fn should_miner_prepare_blocks(&self) -> bool {
         true
@@ -557,6 +576,9 @@ pub trait Engine<M: Machine>: Sync + Send {
     fn use_block_author(&self) -> bool {
         true
     }
+
+    /// Registers the engine's metrics.
+    fn prometheus_metrics(&self, _registry: &mut PrometheusRegistry) {}
 }
 
 /// t_nb 9.3 Check whether a given block is the best block based on the default total difficulty rule.
@@ -667,10 +689,20 @@ pub trait EthEngine: Engine<::machine::EthereumMachine> {
     fn min_gas_limit(&self) -> U256 {
         self.params().min_gas_limit
     }
+
+    /// Returns the engine as `Any`, to downcast it to its concrete type.
+    fn as_any(&self) -> &dyn Any;
 }
 
 // convenience wrappers for existing functions.
-impl<T> EthEngine for T where T: Engine<::machine::EthereumMachine> {}
+impl<T> EthEngine for T
+where
+    T: Engine<::machine::EthereumMachine> + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
 
 /// Verifier for all blocks within an epoch with self-contained state.
 pub trait EpochVerifier<M: machine::Machine>: Send + Sync {
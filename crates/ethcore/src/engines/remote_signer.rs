@@ -0,0 +1,329 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An engine signer backed by an external signing service, e.g. in front of an HSM.
+//!
+//! The service holds the engine's key, so it never has to reside on the node host. The node
+//! connects over TCP or a Unix socket and sends one JSON-RPC 2.0 request per connection, as a
+//! single line, answered by a single line:
+//!
+//! - `signer_public(address)`: the account's 64 byte public key.
+//! - `signer_sign(address, hash)`: the 65 byte recoverable signature of the hash.
+//! - `signer_decrypt(address, authData, cipher)`: the ECIES decrypted message.
+//!
+//! All binary parameters and results are `0x` prefixed hex strings.
+
+use crypto::publickey::{
+    self, public_to_address, verify_public, Address, Error, Public, Signature,
+};
+use ethereum_types::{H256, H512, H520};
+use rustc_hex::{FromHex, ToHex};
+use serde_json;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use super::signer::{EngineDecrypt, EngineSign};
+
+/// How long to wait for the signing service before failing a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The address of a signing service.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    /// A TCP address, given as `tcp://host:port`.
+    Tcp(String),
+    /// A Unix socket, given as `unix://path`.
+    Unix(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("tcp://") {
+            Ok(Endpoint::Tcp(s["tcp://".len()..].into()))
+        } else if s.starts_with("unix://") {
+            Ok(Endpoint::Unix(s["unix://".len()..].into()))
+        } else {
+            Err(format!(
+                "Invalid signer URL {}, expected tcp://host:port or unix://path.",
+                s
+            ))
+        }
+    }
+}
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<String>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.to_hex())
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = if s.starts_with("0x") { &s[2..] } else { s };
+    s.from_hex()
+        .map_err(|e| format!("Invalid hex in signer response: {}", e))
+}
+
+/// An `EngineSigner` which signs and decrypts through a signing service.
+pub struct RemoteSigner {
+    endpoint: Endpoint,
+    address: Address,
+    public: Public,
+    next_id: AtomicU64,
+}
+
+impl RemoteSigner {
+    /// Connects to the signing service and fetches the public key of the given account.
+    ///
+    /// Fails if the service is unreachable, or if the key does not belong to the account.
+    pub fn connect(endpoint: Endpoint, address: Address) -> Result<Self, String> {
+        let mut signer = RemoteSigner {
+            endpoint,
+            address,
+            public: Public::zero(),
+            next_id: AtomicU64::new(0),
+        };
+        let public = from_hex(&signer.call("signer_public", &[to_hex(address.as_bytes())])?)?;
+        if public.len() != H512::len_bytes() {
+            return Err(format!("Invalid public key length {}.", public.len()));
+        }
+        signer.public = Public::from_slice(&public);
+        if public_to_address(&signer.public) != address {
+            return Err(format!(
+                "The signing service returned a public key not belonging to {}.",
+                address
+            ));
+        }
+        Ok(signer)
+    }
+
+    fn open(&self) -> Result<Box<dyn Stream>, String> {
+        let stream: Box<dyn Stream> = match self.endpoint {
+            Endpoint::Tcp(ref addr) => {
+                let stream = TcpStream::connect(addr.as_str()).map_err(|e| e.to_string())?;
+                stream
+                    .set_read_timeout(Some(REQUEST_TIMEOUT))
+                    .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+                    .map_err(|e| e.to_string())?;
+                Box::new(stream)
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(ref path) => {
+                let stream = UnixStream::connect(path).map_err(|e| e.to_string())?;
+                stream
+                    .set_read_timeout(Some(REQUEST_TIMEOUT))
+                    .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+                    .map_err(|e| e.to_string())?;
+                Box::new(stream)
+            }
+            #[cfg(not(unix))]
+            Endpoint::Unix(_) => return Err("Unix sockets are not supported.".into()),
+        };
+        Ok(stream)
+    }
+
+    fn call(&self, method: &str, params: &[String]) -> Result<String, String> {
+        let request = Request {
+            jsonrpc: "2.0",
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            method,
+            params,
+        };
+        let mut line = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+
+        let mut stream = self
+            .open()
+            .map_err(|e| format!("Could not connect to the signing service: {}", e))?;
+        stream.write_all(&line).map_err(|e| e.to_string())?;
+        let mut response = String::new();
+        BufReader::new(stream)
+            .read_line(&mut response)
+            .map_err(|e| e.to_string())?;
+
+        let response: Response = serde_json::from_str(&response)
+            .map_err(|e| format!("Invalid signer response: {}", e))?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(format!(
+                "{} failed with code {}: {}",
+                method, error.code, error.message
+            )),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(format!("{} returned no result.", method)),
+        }
+    }
+}
+
+impl EngineSign for RemoteSigner {
+    fn sign(&self, hash: H256) -> Result<Signature, publickey::Error> {
+        let params = [to_hex(self.address.as_bytes()), to_hex(hash.as_bytes())];
+        let signature = self
+            .call("signer_sign", &params)
+            .and_then(|result| from_hex(&result))
+            .map_err(|e| {
+                warn!(target: "engine", "Remote signing failed: {}", e);
+                Error::Custom(e)
+            })?;
+        if signature.len() != H520::len_bytes() {
+            return Err(Error::InvalidSignature);
+        }
+        let signature: Signature = H520::from_slice(&signature).into();
+        // A faulty service must not make us publish messages that fail verification elsewhere.
+        match verify_public(&self.public, &signature, &hash) {
+            Ok(true) => Ok(signature),
+            _ => Err(Error::InvalidSignature),
+        }
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+}
+
+impl EngineDecrypt for RemoteSigner {
+    fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
+        let params = [
+            to_hex(self.address.as_bytes()),
+            to_hex(auth_data),
+            to_hex(cipher),
+        ];
+        self.call("signer_decrypt", &params)
+            .and_then(|result| from_hex(&result))
+            .map_err(|e| {
+                warn!(target: "engine", "Remote decryption failed: {}", e);
+                Error::InvalidMessage
+            })
+    }
+
+    fn public(&self) -> Option<Public> {
+        Some(self.public)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::publickey::{ecies, sign, Generator, KeyPair, Random};
+    use engines::signer::EngineSigner;
+    use serde_json::Value;
+    use std::{net::TcpListener, thread};
+
+    /// Serves the given number of requests with the given key.
+    fn serve(key: KeyPair, requests: usize) -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = Endpoint::Tcp(listener.local_addr().unwrap().to_string());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let params: Vec<Vec<u8>> = request["params"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|param| from_hex(param.as_str().unwrap()).unwrap())
+                    .collect();
+                let result = match request["method"].as_str().unwrap() {
+                    "signer_public" => key.public().as_bytes().to_vec(),
+                    "signer_sign" => {
+                        let hash = H256::from_slice(&params[1]);
+                        H520::from(sign(key.secret(), &hash).unwrap())
+                            .as_bytes()
+                            .to_vec()
+                    }
+                    "signer_decrypt" => {
+                        ecies::decrypt(key.secret(), &params[1], &params[2]).unwrap()
+                    }
+                    method => panic!("unexpected method {}", method),
+                };
+                let response = format!(
+                    "{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":\"{}\"}}\n",
+                    request["id"],
+                    to_hex(&result)
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        endpoint
+    }
+
+    #[test]
+    fn signs_and_decrypts_through_the_service() {
+        let key = Random.generate();
+        let endpoint = serve(key.clone(), 3);
+        let signer: Box<dyn EngineSigner> =
+            Box::new(RemoteSigner::connect(endpoint, key.address()).unwrap());
+        assert_eq!(signer.public(), Some(*key.public()));
+
+        let hash = H256::from_low_u64_be(7);
+        let signature = signer.sign(hash).unwrap();
+        assert!(verify_public(key.public(), &signature, &hash).unwrap());
+
+        let cipher = ecies::encrypt(key.public(), b"auth", b"message").unwrap();
+        assert_eq!(signer.decrypt(b"auth", &cipher).unwrap(), b"message");
+    }
+
+    #[test]
+    fn a_service_holding_another_key_is_rejected() {
+        let endpoint = serve(Random.generate(), 1);
+        assert!(RemoteSigner::connect(endpoint, Random.generate().address()).is_err());
+    }
+
+    #[test]
+    fn endpoints_are_parsed_from_urls() {
+        assert_eq!(
+            "tcp://127.0.0.1:8551".parse(),
+            Ok(Endpoint::Tcp("127.0.0.1:8551".into()))
+        );
+        assert_eq!(
+            "unix:///run/signer.sock".parse(),
+            Ok(Endpoint::Unix("/run/signer.sock".into()))
+        );
+        assert!("http://127.0.0.1:8551".parse::<Endpoint>().is_err());
+    }
+}
@@ -20,7 +20,28 @@ use crypto::publickey::{self, ecies, Error, Public, Signature};
 use ethereum_types::{Address, H256};
 //TODO dr
 
+/// The capability to sign consensus messages.
+pub trait EngineSign: Send + Sync {
+    /// Sign a consensus message hash.
+    fn sign(&self, hash: H256) -> Result<Signature, publickey::Error>;
+
+    /// Signing address
+    fn address(&self) -> Address;
+}
+
+/// The capability to decrypt messages encrypted to the engine's key, e.g. key generation data.
+pub trait EngineDecrypt: Send + Sync {
+    /// Decrypt a message that was encrypted to this key.
+    fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// The public key messages are encrypted to, if available.
+    fn public(&self) -> Option<Public>;
+}
+
 /// Everything that an Engine needs to sign messages.
+///
+/// Implemented for everything that can both sign and decrypt. Signing backends which cannot
+/// decrypt, like some HSMs, can be combined with a separate decryption key using `with_decrypt_key`.
 pub trait EngineSigner: Send + Sync {
     /// Sign a consensus message hash.
     fn sign(&self, hash: H256) -> Result<Signature, publickey::Error>;
@@ -31,18 +52,47 @@ pub trait EngineSigner: Send + Sync {
     /// Decrypt a message that was encrypted to this signer's key.
     fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error>;
 
-    /// The signer's public key, if available.
+    /// The public key messages to this signer are encrypted to, if available.
     fn public(&self) -> Option<Public>;
 }
 
+impl<T: EngineSign + EngineDecrypt> EngineSigner for T {
+    fn sign(&self, hash: H256) -> Result<Signature, publickey::Error> {
+        EngineSign::sign(self, hash)
+    }
+
+    fn address(&self) -> Address {
+        EngineSign::address(self)
+    }
+
+    fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
+        EngineDecrypt::decrypt(self, auth_data, cipher)
+    }
+
+    fn public(&self) -> Option<Public> {
+        EngineDecrypt::public(self)
+    }
+}
+
 /// Creates a new `EngineSigner` from given key pair.
 pub fn from_keypair(keypair: publickey::KeyPair) -> Box<dyn EngineSigner> {
     Box::new(Signer(keypair))
 }
 
+/// Creates an `EngineSigner` which signs with `signer` and decrypts with the given key pair.
+pub fn with_decrypt_key(
+    signer: Box<dyn EngineSign>,
+    decrypt_key: publickey::KeyPair,
+) -> Box<dyn EngineSigner> {
+    Box::new(SplitSigner {
+        signer,
+        decrypter: Signer(decrypt_key),
+    })
+}
+
 struct Signer(publickey::KeyPair);
 
-impl EngineSigner for Signer {
+impl EngineSign for Signer {
     fn sign(&self, hash: H256) -> Result<Signature, publickey::Error> {
         publickey::sign(self.0.secret(), &hash)
     }
@@ -50,7 +100,9 @@ impl EngineSigner for Signer {
     fn address(&self) -> Address {
         self.0.address()
     }
+}
 
+impl EngineDecrypt for Signer {
     fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
         ecies::decrypt(self.0.secret(), auth_data, cipher).map_err(From::from)
     }
@@ -60,6 +112,32 @@ impl EngineSigner for Signer {
     }
 }
 
+/// Signs and decrypts with different keys.
+struct SplitSigner {
+    signer: Box<dyn EngineSign>,
+    decrypter: Signer,
+}
+
+impl EngineSign for SplitSigner {
+    fn sign(&self, hash: H256) -> Result<Signature, publickey::Error> {
+        self.signer.sign(hash)
+    }
+
+    fn address(&self) -> Address {
+        self.signer.address()
+    }
+}
+
+impl EngineDecrypt for SplitSigner {
+    fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
+        EngineDecrypt::decrypt(&self.decrypter, auth_data, cipher)
+    }
+
+    fn public(&self) -> Option<Public> {
+        EngineDecrypt::public(&self.decrypter)
+    }
+}
+
 #[cfg(test)]
 mod test_signer {
 
@@ -72,7 +150,7 @@ mod test_signer {
 
     use super::*;
 
-    impl EngineSigner for (Arc<AccountProvider>, Address, Password) {
+    impl EngineSign for (Arc<AccountProvider>, Address, Password) {
         fn sign(&self, hash: H256) -> Result<Signature, crypto::publickey::Error> {
             match self.0.sign(self.1, Some(self.2.clone()), hash) {
                 Err(SignError::NotUnlocked) => unreachable!(),
@@ -93,7 +171,9 @@ mod test_signer {
         fn address(&self) -> Address {
             self.1
         }
+    }
 
+    impl EngineDecrypt for (Arc<AccountProvider>, Address, Password) {
         fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
             self.0
                 .decrypt(self.1, None, auth_data, cipher)
@@ -255,6 +255,11 @@ impl Engine<EthereumMachine> for Arc<Ethash> {
         2
     }
 
+    // The proof of work and the state transition of a block do not depend on local state.
+    fn invalid_blocks_are_peer_faults(&self) -> bool {
+        true
+    }
+
     fn maximum_gas_limit(&self) -> Option<U256> {
         Some(0x7fff_ffff_ffff_ffffu64.into())
     }
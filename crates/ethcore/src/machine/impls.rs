@@ -120,6 +120,18 @@ impl EthereumMachine {
         machine
     }
 
+    /// A regular machine with the same params and builtins, for an engine that delegates part
+    /// of the chain to another engine.
+    pub fn to_regular(&self) -> EthereumMachine {
+        EthereumMachine {
+            params: self.params.clone(),
+            builtins: self.builtins.clone(),
+            tx_filter: self.tx_filter.clone(),
+            ethash_extensions: None,
+            schedule_rules: None,
+        }
+    }
+
     /// Attach special rules to the creation of schedule.
     pub fn set_schedule_creation_rules(&mut self, rules: Box<ScheduleCreationRules>) {
         self.schedule_rules = Some(rules);
@@ -379,12 +379,16 @@ impl Miner {
 
     /// Updates transaction queue verification limits.
     ///
-    /// Limits consist of current block gas limit and minimal gas price.
-    pub fn update_transaction_queue_limits(&self, block_gas_limit: U256) {
+    /// Limits consist of the best block's gas limit and minimal gas price. The engine may
+    /// override the configured minimal gas price.
+    pub fn update_transaction_queue_limits(&self, best_header: &Header) {
         trace!(target: "miner", "minimal_gas_price: recalibrating...");
+        let block_gas_limit = *best_header.gas_limit();
+        let gas_price_override = self.engine.min_gas_price_override(best_header);
         let txq = self.transaction_queue.clone();
         let mut options = self.options.pool_verification_options.clone();
         self.gas_pricer.lock().recalibrate(move |gas_price| {
+            let gas_price = gas_price_override.unwrap_or(gas_price);
             debug!(target: "miner", "minimal_gas_price: Got gas price! {}", gas_price);
             options.minimal_gas_price = gas_price;
             options.block_gas_limit = block_gas_limit;
@@ -397,6 +401,11 @@ impl Miner {
         self.service_transaction_checker.clone()
     }
 
+    /// Returns a consistent view of all transactions in the queue.
+    pub fn queued_transactions_snapshot(&self) -> pool::QueueSnapshot {
+        self.transaction_queue.snapshot()
+    }
+
     /// Retrieves an existing pending block iff it's not older than given block number.
     ///
     /// NOTE: This will not prepare a new pending block if it's not existing.
@@ -1571,8 +1580,7 @@ impl miner::MinerService for Miner {
         }
 
         // t_nb 10.1 First update gas limit in transaction queue and minimal gas price.
-        let gas_limit = *chain.best_block_header().gas_limit();
-        self.update_transaction_queue_limits(gas_limit);
+        self.update_transaction_queue_limits(&chain.best_block_header());
 
         // t_nb 10.2 Then import all transactions from retracted blocks (retracted means from side chain).
         let client = self.pool_client(chain);
@@ -64,8 +64,7 @@ fn fmt_err<F: ::std::fmt::Display>(f: F) -> String {
 ///
 /// we define a "bugfix" hard fork as any hard fork which
 /// you would put on-by-default in a new chain.
-#[derive(Debug, PartialEq, Default)]
-#[cfg_attr(any(test, feature = "test-helpers"), derive(Clone))]
+#[derive(Debug, PartialEq, Default, Clone)]
 pub struct CommonParams {
     /// Account start nonce.
     pub account_start_nonce: U256,
@@ -586,6 +585,10 @@ fn load_from(spec_params: SpecParams, s: ethjson::spec::Spec) -> Result<Spec, Er
         genesis_state: s.accounts.into(),
     };
 
+    if let Some(hbbft) = s.engine.as_any().downcast_ref::<HoneyBadgerBFT>() {
+        hbbft.verify_genesis(&s.genesis_state, &s.constructors)?;
+    }
+
     // use memoized state root if provided.
     match g.state_root {
         Some(root) => *s.state_root_memo.get_mut() = root,
@@ -728,7 +731,7 @@ impl Spec {
             }
             ethjson::spec::Engine::Hbbft(hbbft) => {
                 HoneyBadgerBFT::new(hbbft.params.into(), machine)
-                    .expect("Failed to start AuthorityRound consensus engine.")
+                    .expect("Failed to start HoneyBadgerBFT consensus engine.")
             }
         };
 
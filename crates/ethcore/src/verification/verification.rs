@@ -83,7 +83,7 @@ pub fn verify_block_basic(
         }
     }
 
-    // t_nb 4.6 call engine.gas_limit_override (Used only by Aura)
+    // t_nb 4.6 call engine.gas_limit_override (Used by AuRa and hbbft)
     if let Some(gas_limit) = engine.gas_limit_override(&block.header) {
         if *block.header.gas_limit() != gas_limit {
             return Err(From::from(BlockError::InvalidGasLimit(OutOfBounds {
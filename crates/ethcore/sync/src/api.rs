@@ -110,6 +110,9 @@ pub struct SyncConfig {
     pub fork_block: Option<(BlockNumber, H256)>,
     /// Enable snapshot sync
     pub warp_sync: WarpSync,
+    /// Disable peers that send several new blocks in a row that fail to import. Only enabled for
+    /// engines whose import failures do not depend on local state.
+    pub disable_peers_sending_invalid_blocks: bool,
 }
 
 impl Default for SyncConfig {
@@ -121,6 +124,7 @@ impl Default for SyncConfig {
             subprotocol_name: ETH_PROTOCOL,
             fork_block: None,
             warp_sync: WarpSync::Disabled,
+            disable_peers_sending_invalid_blocks: false,
         }
     }
 }
@@ -210,6 +210,7 @@ impl SyncHandler {
                 // abort current download of the same block
                 sync.complete_sync(io);
                 sync.new_blocks.mark_as_known(&hash, number);
+                sync.note_new_block_source(hash, peer_id);
                 trace!(target: "sync", "New block queued {:?} ({})", hash, number);
             }
             Err(EthcoreError(EthcoreErrorKind::Block(BlockError::UnknownParent(p)), _)) => {
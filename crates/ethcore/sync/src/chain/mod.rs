@@ -173,6 +173,10 @@ const MAX_PEERS_PROPAGATION: usize = 128;
 const MAX_PEER_LAG_PROPAGATION: BlockNumber = 20;
 const MAX_NEW_HASHES: usize = 64;
 const MAX_NEW_BLOCK_AGE: BlockNumber = 20;
+// number of invalid new blocks in a row after which the peer that sent them is disabled.
+const MAX_INVALID_NEW_BLOCKS: usize = 3;
+// maximal number of queued new blocks whose sending peer is remembered.
+const MAX_NEW_BLOCK_SOURCES: usize = 1024;
 // maximal packet size with transactions (cannot be greater than 16MB - protocol limitation).
 // keep it under 8MB as well, cause it seems that it may result oversized after compression.
 const MAX_TRANSACTION_PACKET_SIZE: usize = 5 * 1024 * 1024;
@@ -700,6 +704,12 @@ pub struct ChainSync {
     download_old_blocks: bool,
     /// Enable warp sync.
     warp_sync: WarpSync,
+    /// Disable peers that send too many invalid new blocks in a row.
+    disable_peers_sending_invalid_blocks: bool,
+    /// Peers that sent the new blocks in the import queue.
+    new_block_sources: HashMap<H256, PeerId>,
+    /// Number of invalid new blocks in a row, per peer.
+    invalid_new_blocks: HashMap<PeerId, usize>,
 }
 
 #[derive(Debug, Default)]
@@ -785,6 +795,9 @@ impl ChainSync {
             sync_start_time: None,
             transactions_stats: TransactionsStats::default(),
             warp_sync: config.warp_sync,
+            disable_peers_sending_invalid_blocks: config.disable_peers_sending_invalid_blocks,
+            new_block_sources: HashMap::new(),
+            invalid_new_blocks: HashMap::new(),
         };
         sync.update_targets(chain);
         sync
@@ -954,6 +967,47 @@ impl ChainSync {
         self.active_peers.remove(&peer_id);
     }
 
+    /// Remembers the peer that sent a new block queued for import.
+    fn note_new_block_source(&mut self, hash: H256, peer_id: PeerId) {
+        if !self.disable_peers_sending_invalid_blocks {
+            return;
+        }
+        if self.new_block_sources.len() >= MAX_NEW_BLOCK_SOURCES {
+            self.new_block_sources.clear();
+        }
+        self.new_block_sources.insert(hash, peer_id);
+    }
+
+    /// Holds the peers that sent new blocks responsible for the outcome of their import, and
+    /// disables peers that sent too many invalid blocks in a row.
+    fn handle_new_block_outcomes(
+        &mut self,
+        io: &mut dyn SyncIo,
+        imported: &[H256],
+        invalid: &[H256],
+    ) {
+        for hash in imported {
+            if let Some(peer_id) = self.new_block_sources.remove(hash) {
+                self.invalid_new_blocks.remove(&peer_id);
+            }
+        }
+        for hash in invalid {
+            let peer_id = match self.new_block_sources.remove(hash) {
+                Some(peer_id) => peer_id,
+                None => continue,
+            };
+            let count = self.invalid_new_blocks.entry(peer_id).or_insert(0);
+            *count += 1;
+            debug!(target: "sync", "{} -> Invalid new block {:?} ({} in a row)", peer_id, hash, count);
+            if *count >= MAX_INVALID_NEW_BLOCKS {
+                warn!(target: "sync", "Disabling peer {}: sent {} invalid blocks in a row", peer_id, count);
+                self.invalid_new_blocks.remove(&peer_id);
+                io.disable_peer(peer_id);
+                self.deactivate_peer(io, peer_id);
+            }
+        }
+    }
+
     fn maybe_start_snapshot_sync(&mut self, io: &mut dyn SyncIo) {
         if !self.warp_sync.is_enabled() || io.snapshot_service().supported_versions().is_none() {
             trace!(target: "sync", "Skipping warp sync. Disabled or not supported.");
@@ -1600,7 +1654,7 @@ impl ChainSync {
     pub fn chain_new_blocks(
         &mut self,
         io: &mut dyn SyncIo,
-        _imported: &[H256],
+        imported: &[H256],
         invalid: &[H256],
         enacted: &[H256],
         _retracted: &[H256],
@@ -1617,6 +1671,7 @@ impl ChainSync {
             // t_nb 11.4.4 propagate proposed blocks
             SyncPropagator::propagate_proposed_blocks(self, io, proposed);
         }
+        self.handle_new_block_outcomes(io, imported, invalid);
         if !invalid.is_empty() {
             info!(target: "sync", "Bad blocks in the queue, restarting sync");
             self.restart(io);
@@ -1856,6 +1911,43 @@ pub mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn disables_peers_sending_invalid_new_blocks() {
+        let mut client = TestBlockChainClient::new();
+        client.add_blocks(10, EachBlockWith::Nothing);
+        let queue = RwLock::new(VecDeque::new());
+        let mut sync = dummy_sync_with_peer(H256::zero(), &client);
+        insert_dummy_peer(&mut sync, 1, H256::zero());
+        let ss = TestSnapshotService::new();
+        let mut io = TestIo::new(&mut client, &ss, &queue, None);
+
+        let hash = H256::from_low_u64_be;
+        // Peers are only disabled if the engine opts in.
+        let invalid: Vec<H256> = (0..3).map(hash).collect();
+        for h in &invalid {
+            sync.note_new_block_source(*h, 0);
+        }
+        sync.chain_new_blocks(&mut io, &[], &invalid, &[], &[], &[], &[]);
+        assert!(io.to_disconnect.is_empty());
+
+        sync.disable_peers_sending_invalid_blocks = true;
+        for i in 0..5 {
+            sync.note_new_block_source(hash(i), 0);
+        }
+        sync.note_new_block_source(hash(100), 1);
+
+        sync.chain_new_blocks(&mut io, &[], &[hash(0), hash(1)], &[], &[], &[], &[]);
+        // An imported block resets the count of invalid blocks in a row.
+        sync.chain_new_blocks(&mut io, &[hash(2)], &[], &[], &[], &[], &[]);
+        sync.chain_new_blocks(&mut io, &[], &[hash(3), hash(100)], &[], &[], &[], &[]);
+        assert!(io.to_disconnect.is_empty());
+
+        sync.note_new_block_source(hash(5), 0);
+        sync.chain_new_blocks(&mut io, &[], &[hash(4), hash(5)], &[], &[], &[], &[]);
+        assert!(io.to_disconnect.contains(&0));
+        assert!(!io.to_disconnect.contains(&1));
+    }
+
     // idea is that what we produce when propagading latest block should be accepted in
     // on_peer_new_block  in our code as well
     #[test]
@@ -16,8 +16,37 @@
 
 //! Hbbft parameter deserialization.
 
+use super::AuthorityRoundParams;
 use ethereum_types::Address;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The hbbft epochs whose contributions are threshold encrypted.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HbbftEncryptionSchedule {
+    /// Every epoch.
+    Always,
+    /// No epoch.
+    Never,
+    /// Every nth epoch.
+    EveryNthEpoch(u32),
+    /// Alternately the given number of epochs with and without encryption.
+    TickTock(u32, u32),
+}
+
+/// The gas price of the Part and Acks transactions of pending validators.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HbbftKeygenGasPrice {
+    /// A fixed gas price, in wei.
+    Fixed(u64),
+    /// The given percentage of the median gas price of recent blocks.
+    MedianPercent(u64),
+    /// No gas price, for chains accepting the transactions as zero gas service transactions.
+    /// The mining addresses must be certified by the service transaction contract.
+    Zero,
+}
 
 /// Hbbft parameters.
 #[derive(Debug, PartialEq, Deserialize)]
@@ -30,10 +59,97 @@ pub struct HbbftParams {
     pub maximum_block_time: u64,
     /// The length of the transaction queue at which block creation should be triggered.
     pub transaction_queue_size_trigger: usize,
+    /// The cumulative gas limit of the queued transactions at which block creation should be
+    /// triggered. Not a trigger if not set.
+    pub transaction_queue_gas_trigger: Option<u64>,
+    /// The number of seconds after which a queued transaction triggers block creation, so
+    /// low-traffic networks need not wait for the maximum block time. Not a trigger if not set.
+    pub transaction_queue_age_trigger: Option<u64>,
+    /// Trigger block creation as soon as a local transaction is queued. Defaults to false.
+    pub local_transactions_trigger: Option<bool>,
     /// Should be true when running unit tests to avoid starting timers.
     pub is_unit_test: Option<bool>,
     /// Block reward contract address.
     pub block_reward_contract_address: Option<Address>,
+    /// The address of the contract the agreed random number of each block is committed to.
+    /// Random numbers are not committed if not set.
+    pub randomness_contract_address: Option<Address>,
+    /// Persist the last agreed batch, so its block can be recreated after a crash.
+    pub persist_last_batch: Option<bool>,
+    /// Order the transactions of each block grouped by sender, and by nonce within each group.
+    /// Transactions are ordered by contribution if not set.
+    pub group_transactions_by_sender: Option<bool>,
+    /// The wire format versions used for encoding Parts, Acks and consensus messages, by the
+    /// POSDAO epoch they are used from. Defaults to the unversioned legacy format.
+    pub wire_versions: Option<BTreeMap<u64, u8>>,
+    /// The time without a new block, in seconds, after which a single designated validator may
+    /// create an empty recovery block. Recovery blocks are disabled if not set.
+    pub recovery_block_timeout: Option<u64>,
+    /// The address of the registry contract validators announce their version and supported
+    /// features to once per POSDAO epoch. Features are not negotiated if not set.
+    pub feature_registry_contract_address: Option<Address>,
+    /// The first block sealed by hbbft, for chains switching from AuRa. Blocks before it are
+    /// verified, executed and rewarded by an AuRa engine with the `legacy_authority_round`
+    /// params. Defaults to 0.
+    pub activation_block: Option<u64>,
+    /// The params of the AuRa engine of the chain before the activation block.
+    pub legacy_authority_round: Option<AuthorityRoundParams>,
+    /// The number of blocks the key generation for the next POSDAO epoch may stay incomplete
+    /// before the engine raises an alert. Defaults to 100.
+    pub keygen_timeout: Option<u64>,
+    /// The hbbft epochs whose contributions are threshold encrypted, so their transactions are
+    /// only revealed once the validators agreed on the batch. All validators must use the same
+    /// schedule. Defaults to `always`.
+    pub encryption_schedule: Option<HbbftEncryptionSchedule>,
+    /// The address of the governance contract the minimum gas price of the transaction queue,
+    /// and of the transactions in a block, is read from at each block. The configured minimum gas
+    /// price and `minimumGasPrice` are used if not set, or if the contract cannot be read.
+    pub min_gas_price_contract_address: Option<Address>,
+    /// The first block whose block reward call passes the validators that contributed to its
+    /// batch. They are encoded in the extra data after the random number, one bit per validator,
+    /// so `maximumExtraDataSize` must leave room for them. Contributors are not reported if not set.
+    pub block_reward_contributors_transition: Option<u64>,
+    /// The first block from which the engine finalizes the validator set change at the end of a
+    /// POSDAO epoch itself, by calling the validator set contract's `finalizeChange` as a system
+    /// call, instead of leaving it to the block reward contract. Not called if not set.
+    pub finalize_change_transition: Option<u64>,
+    /// The maximum number of consensus messages cached for future hbbft epochs. Once it is
+    /// reached, messages of the furthest epochs are evicted first. Defaults to 100000.
+    pub max_future_messages: Option<usize>,
+    /// The first block whose batch may propose transactions encrypted with the validators'
+    /// public master key. They are only decrypted once the batch is agreed. Encrypted
+    /// transactions are not accepted if not set.
+    pub encrypted_transactions_transition: Option<u64>,
+    /// The gas price of the Part and Acks transactions of pending validators, and of the
+    /// validators' feature announcements. Defaults to a fixed price of 10 gwei.
+    pub keygen_gas_price: Option<HbbftKeygenGasPrice>,
+    /// The number of blocks of a new POSDAO epoch during which the previous validators'
+    /// consensus state is kept, to handle their messages for the last blocks they sealed. It is
+    /// dropped once the first block of the new epoch is finalized at the earliest. The handoff is
+    /// disabled if 0. Defaults to 1.
+    pub epoch_handoff_blocks: Option<u64>,
+    /// The maximum number of transactions in a contribution, including encrypted ones. Agreed
+    /// contributions exceeding it are dropped from the batch. Defaults to 25000.
+    pub max_contribution_transactions: Option<usize>,
+    /// The maximum total size of the transactions of a contribution, in bytes. Agreed
+    /// contributions exceeding it are dropped from the batch. Defaults to 2 MiB.
+    pub max_contribution_bytes: Option<usize>,
+    /// The minimum gas price of the transactions in a block, in wei. Validators do not propose
+    /// cheaper transactions, and drop them from agreed batches, reporting their proposers. All
+    /// validators must use the same price. This includes zero gas price service transactions of
+    /// certified addresses, so `keygenGasPrice` must not be `zero` with a minimum. There is no
+    /// minimum if not set. The price of `minGasPriceContractAddress` at the parent block takes
+    /// precedence if it is configured.
+    pub minimum_gas_price: Option<u64>,
+    /// The address of the contract the block gas limit is read from at each block. A block's gas
+    /// limit must be the value read at its parent block, so all validators create blocks with the
+    /// same gas limit. The gas limit targets configured by each operator are used if not set, or
+    /// while the contract returns 0.
+    pub block_gas_limit_contract_address: Option<Address>,
+    /// The maximum number of validators of a POSDAO epoch, as enforced by the validator set
+    /// contract. Only used to check that `maximumExtraDataSize` leaves room for the contributor
+    /// bits of the largest validator set. Defaults to 25.
+    pub max_validators: Option<usize>,
 }
 
 /// Hbbft engine config.
@@ -46,7 +162,8 @@ pub struct Hbbft {
 
 #[cfg(test)]
 mod tests {
-    use super::Hbbft;
+    use super::{Hbbft, HbbftEncryptionSchedule, HbbftKeygenGasPrice};
+    use crate::{hash, spec::ValidatorSet};
     use ethereum_types::Address;
     use std::str::FromStr;
 
@@ -57,8 +174,34 @@ mod tests {
 				"minimumBlockTime": 0,
 				"maximumBlockTime": 600,
 				"transactionQueueSizeTrigger": 1,
+				"transactionQueueGasTrigger": 8000000,
+				"transactionQueueAgeTrigger": 5,
+				"localTransactionsTrigger": true,
 				"isUnitTest": true,
-				"blockRewardContractAddress": "0x2000000000000000000000000000000000000002"
+				"blockRewardContractAddress": "0x2000000000000000000000000000000000000002",
+				"randomnessContractAddress": "0x3000000000000000000000000000000000000001",
+				"wireVersions": { "0": 0, "12": 1 },
+				"recoveryBlockTimeout": 3600,
+				"groupTransactionsBySender": true,
+				"activationBlock": 1000,
+				"legacyAuthorityRound": {
+					"stepDuration": 5,
+					"validators": { "contract": "0x4000000000000000000000000000000000000001" }
+				},
+				"keygenTimeout": 50,
+				"encryptionSchedule": { "tickTock": [1, 2] },
+				"minGasPriceContractAddress": "0x5000000000000000000000000000000000000001",
+				"blockRewardContributorsTransition": 2000,
+				"finalizeChangeTransition": 3000,
+				"maxFutureMessages": 50000,
+				"encryptedTransactionsTransition": 4000,
+				"keygenGasPrice": { "medianPercent": 150 },
+				"epochHandoffBlocks": 3,
+				"maxContributionTransactions": 5000,
+				"maxContributionBytes": 1048576,
+				"minimumGasPrice": 1000000000,
+				"blockGasLimitContractAddress": "0x6000000000000000000000000000000000000001",
+				"maxValidators": 50
 			}
 		}"#;
 
@@ -66,10 +209,77 @@ mod tests {
         assert_eq!(deserialized.params.minimum_block_time, 0);
         assert_eq!(deserialized.params.maximum_block_time, 600);
         assert_eq!(deserialized.params.transaction_queue_size_trigger, 1);
+        assert_eq!(
+            deserialized.params.transaction_queue_gas_trigger,
+            Some(8_000_000)
+        );
+        assert_eq!(deserialized.params.transaction_queue_age_trigger, Some(5));
+        assert_eq!(deserialized.params.local_transactions_trigger, Some(true));
         assert_eq!(deserialized.params.is_unit_test, Some(true));
         assert_eq!(
             deserialized.params.block_reward_contract_address,
             Address::from_str("2000000000000000000000000000000000000002").ok()
         );
+        assert_eq!(
+            deserialized.params.randomness_contract_address,
+            Address::from_str("3000000000000000000000000000000000000001").ok()
+        );
+        assert_eq!(
+            deserialized.params.wire_versions,
+            Some(vec![(0, 0), (12, 1)].into_iter().collect())
+        );
+        assert_eq!(deserialized.params.recovery_block_timeout, Some(3600));
+        assert_eq!(deserialized.params.group_transactions_by_sender, Some(true));
+        assert_eq!(deserialized.params.activation_block, Some(1000));
+        let legacy = deserialized.params.legacy_authority_round.unwrap();
+        assert_eq!(
+            legacy.validators,
+            ValidatorSet::Contract(hash::Address(
+                Address::from_str("4000000000000000000000000000000000000001").unwrap()
+            ))
+        );
+        assert_eq!(deserialized.params.keygen_timeout, Some(50));
+        assert_eq!(
+            deserialized.params.encryption_schedule,
+            Some(HbbftEncryptionSchedule::TickTock(1, 2))
+        );
+        assert_eq!(
+            deserialized.params.min_gas_price_contract_address,
+            Address::from_str("5000000000000000000000000000000000000001").ok()
+        );
+        assert_eq!(
+            deserialized.params.block_reward_contributors_transition,
+            Some(2000)
+        );
+        assert_eq!(deserialized.params.finalize_change_transition, Some(3000));
+        assert_eq!(deserialized.params.max_future_messages, Some(50000));
+        assert_eq!(
+            deserialized.params.encrypted_transactions_transition,
+            Some(4000)
+        );
+        assert_eq!(
+            deserialized.params.keygen_gas_price,
+            Some(HbbftKeygenGasPrice::MedianPercent(150))
+        );
+        assert_eq!(deserialized.params.epoch_handoff_blocks, Some(3));
+        assert_eq!(
+            deserialized.params.max_contribution_transactions,
+            Some(5000)
+        );
+        assert_eq!(deserialized.params.max_contribution_bytes, Some(1048576));
+        assert_eq!(deserialized.params.minimum_gas_price, Some(1000000000));
+        assert_eq!(
+            deserialized.params.block_gas_limit_contract_address,
+            Address::from_str("6000000000000000000000000000000000000001").ok()
+        );
+        assert_eq!(deserialized.params.max_validators, Some(50));
+    }
+
+    #[test]
+    fn keygen_gas_price_deserialization() {
+        let fixed: HbbftKeygenGasPrice = serde_json::from_str(r#"{ "fixed": 1000 }"#).unwrap();
+        assert_eq!(fixed, HbbftKeygenGasPrice::Fixed(1000));
+        let zero: HbbftKeygenGasPrice = serde_json::from_str(r#""zero""#).unwrap();
+        assert_eq!(zero, HbbftKeygenGasPrice::Zero);
     }
 }
@@ -38,7 +38,7 @@ impl EngineSigner {
     }
 }
 
-impl ethcore::engines::EngineSigner for EngineSigner {
+impl ethcore::engines::signer::EngineSign for EngineSigner {
     fn sign(&self, message: publickey::Message) -> Result<publickey::Signature, publickey::Error> {
         match self
             .accounts
@@ -49,6 +49,12 @@ impl ethcore::engines::EngineSigner for EngineSigner {
         }
     }
 
+    fn address(&self) -> Address {
+        self.address
+    }
+}
+
+impl ethcore::engines::signer::EngineDecrypt for EngineSigner {
     fn decrypt(&self, auth_data: &[u8], cipher: &[u8]) -> Result<Vec<u8>, Error> {
         self.accounts
             .decrypt(self.address, None, auth_data, cipher)
@@ -58,10 +64,6 @@ impl ethcore::engines::EngineSigner for EngineSigner {
             })
     }
 
-    fn address(&self) -> Address {
-        self.address
-    }
-
     fn public(&self) -> Option<Public> {
         self.accounts
             .account_public(self.address, &self.password)
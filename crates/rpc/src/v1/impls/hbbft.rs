@@ -0,0 +1,234 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Honey Badger BFT rpc implementation.
+
+use std::sync::Arc;
+
+use ethcore::{
+    engines::{hbbft::api::HbbftHandle, EthEngine},
+    miner::MinerService,
+};
+use ethereum_types::{H256, U256, U64};
+
+use jsonrpc_core::Result;
+use v1::{
+    helpers::errors,
+    impls::parity_set::set_engine_signer_secret,
+    traits::{Hbbft, HbbftSet},
+    types::{
+        Bytes, HbbftDebugState, HbbftEncryptionKey, HbbftForkEvidence, HbbftKeygenPhase,
+        HbbftKeygenStatus, HbbftNodeStatus, HbbftResetScope, HbbftValidator,
+        HbbftValidatorConnection, HbbftValidatorLiveness, HbbftValidatorPeer, HbbftWithdrawal,
+    },
+};
+
+/// Honey Badger BFT rpc implementation.
+pub struct HbbftClient {
+    handle: Option<HbbftHandle>,
+}
+
+impl HbbftClient {
+    /// Creates new hbbft client for the given engine.
+    pub fn new(engine: Arc<dyn EthEngine>) -> Self {
+        HbbftClient {
+            handle: HbbftHandle::from_engine(engine),
+        }
+    }
+
+    fn handle(&self) -> Result<&HbbftHandle> {
+        self.handle.as_ref().ok_or_else(|| {
+            errors::unsupported("The chain does not use the HoneyBadgerBFT engine.", None)
+        })
+    }
+}
+
+impl Hbbft for HbbftClient {
+    fn node_status(&self) -> Result<HbbftNodeStatus> {
+        let handle = self.handle()?;
+        Ok(HbbftNodeStatus::new(
+            handle.status(),
+            handle.keygen_progress().ok(),
+        ))
+    }
+
+    fn current_epoch(&self) -> Result<U64> {
+        Ok(self.handle()?.status().posdao_epoch.into())
+    }
+
+    fn validator_set(&self) -> Result<Vec<HbbftValidator>> {
+        self.handle()?
+            .validator_set()
+            .map(|validators| validators.into_iter().map(Into::into).collect())
+            .map_err(errors::exceptional)
+    }
+
+    fn validator_liveness(&self) -> Result<Vec<HbbftValidatorLiveness>> {
+        Ok(self
+            .handle()?
+            .validator_liveness()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn validator_connections(&self) -> Result<Vec<HbbftValidatorConnection>> {
+        Ok(self
+            .handle()?
+            .validator_connections()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn validator_peers(&self) -> Result<Vec<HbbftValidatorPeer>> {
+        Ok(self
+            .handle()?
+            .validator_peers()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn block_random(&self, block_number: U64) -> Result<Option<U256>> {
+        self.handle()?
+            .block_random(block_number.as_u64())
+            .map_err(errors::exceptional)
+    }
+
+    fn fork_evidence(&self) -> Result<Vec<HbbftForkEvidence>> {
+        Ok(self
+            .handle()?
+            .fork_evidence()
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    fn keygen_status(&self) -> Result<HbbftKeygenStatus> {
+        self.handle()?
+            .keygen_progress()
+            .map(Into::into)
+            .map_err(errors::exceptional)
+    }
+
+    fn keygen_phase_status(&self) -> Result<HbbftKeygenPhase> {
+        self.handle()?
+            .keygen_phase()
+            .map(Into::into)
+            .map_err(errors::exceptional)
+    }
+
+    fn keygen_ready(&self) -> Result<bool> {
+        self.handle()?
+            .keygen_progress()
+            .map(|progress| progress.is_ready())
+            .map_err(errors::exceptional)
+    }
+
+    fn debug_state(&self) -> Result<HbbftDebugState> {
+        Ok(self.handle()?.debug_state().into())
+    }
+
+    fn epoch_key_proof(&self) -> Result<Bytes> {
+        self.handle()?
+            .epoch_key_proof()
+            .map(Into::into)
+            .map_err(errors::exceptional)
+    }
+
+    fn encryption_key(&self) -> Result<HbbftEncryptionKey> {
+        self.handle()?
+            .encryption_key()
+            .map(Into::into)
+            .map_err(errors::exceptional)
+    }
+}
+
+/// Honey Badger BFT rpc implementation for operations altering the node's settings or consensus
+/// state.
+pub struct HbbftSetClient<M> {
+    handle: Option<HbbftHandle>,
+    miner: Arc<M>,
+}
+
+impl<M> HbbftSetClient<M> {
+    /// Creates new hbbft settings client for the given engine and miner.
+    pub fn new(engine: Arc<dyn EthEngine>, miner: &Arc<M>) -> Self {
+        HbbftSetClient {
+            handle: HbbftHandle::from_engine(engine),
+            miner: miner.clone(),
+        }
+    }
+
+    fn handle(&self) -> Result<&HbbftHandle> {
+        self.handle.as_ref().ok_or_else(|| {
+            errors::unsupported("The chain does not use the HoneyBadgerBFT engine.", None)
+        })
+    }
+}
+
+impl<M: MinerService + 'static> HbbftSet for HbbftSetClient<M> {
+    fn set_hbbft_signer(&self, secret: H256) -> Result<bool> {
+        self.handle()?;
+        set_engine_signer_secret(&*self.miner, secret)
+    }
+
+    fn set_hbbft_observer(&self, enabled: bool) -> Result<bool> {
+        self.handle()?.set_observer(enabled);
+        Ok(true)
+    }
+
+    fn pin_peer(&self, enode: String) -> Result<bool> {
+        self.handle()?
+            .pin_peer(enode)
+            .map(|_| true)
+            .map_err(errors::exceptional)
+    }
+
+    fn unpin_peer(&self, enode: String) -> Result<bool> {
+        self.handle()?
+            .unpin_peer(&enode)
+            .map(|_| true)
+            .map_err(errors::exceptional)
+    }
+
+    fn add_epoch_key_proof(&self, proof: Bytes) -> Result<bool> {
+        self.handle()?
+            .add_epoch_key_proof(&proof.into_vec())
+            .map_err(errors::exceptional)
+    }
+
+    fn reset_state(&self, scope: HbbftResetScope) -> Result<bool> {
+        self.handle()?
+            .reset_state(scope.into())
+            .map(|_| true)
+            .map_err(errors::exceptional)
+    }
+
+    fn withdraw(&self) -> Result<HbbftWithdrawal> {
+        self.handle()?
+            .withdraw()
+            .map(Into::into)
+            .map_err(errors::exceptional)
+    }
+
+    fn send_encrypted_transaction(&self, ciphertext: Bytes) -> Result<H256> {
+        self.handle()?
+            .submit_encrypted_transaction(ciphertext.into_vec())
+            .map_err(errors::exceptional)
+    }
+}
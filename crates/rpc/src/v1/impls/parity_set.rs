@@ -79,6 +79,18 @@ pub mod accounts {
     }
 }
 
+/// Makes the engine sign with the given secret, as `parity_setEngineSignerSecret` does.
+///
+/// Setting the author through the miner keeps the block author in line with the signer.
+pub fn set_engine_signer_secret<M: MinerService>(miner: &M, secret: H256) -> Result<bool> {
+    let keypair = crypto::publickey::KeyPair::from_secret(secret.into())
+        .map_err(|e| errors::account("Invalid secret", e))?;
+    miner.set_author(miner::Author::Sealer(
+        ethcore::engines::signer::from_keypair(keypair),
+    ));
+    Ok(true)
+}
+
 /// Parity-specific rpc interface for operations altering the settings.
 pub struct ParitySetClient<C, M, F = fetch::Client> {
     client: Arc<C>,
@@ -149,12 +161,7 @@ where
     }
 
     fn set_engine_signer_secret(&self, secret: H256) -> Result<bool> {
-        let keypair = crypto::publickey::KeyPair::from_secret(secret.into())
-            .map_err(|e| errors::account("Invalid secret", e))?;
-        self.miner.set_author(miner::Author::Sealer(
-            ethcore::engines::signer::from_keypair(keypair),
-        ));
-        Ok(true)
+        set_engine_signer_secret(&*self.miner, secret)
     }
 
     fn clear_engine_signer(&self) -> Result<bool> {
@@ -47,9 +47,9 @@ pub use self::{
     impls::*,
     metadata::Metadata,
     traits::{
-        Debug, Eth, EthFilter, EthPubSub, EthSigning, Net, Parity, ParityAccounts,
-        ParityAccountsInfo, ParitySet, ParitySetAccounts, ParitySigning, Personal, PubSub, Rpc,
-        SecretStore, Signer, Traces, Web3,
+        Debug, Eth, EthFilter, EthPubSub, EthSigning, Hbbft, HbbftSet, Net, Parity,
+        ParityAccounts, ParityAccountsInfo, ParitySet, ParitySetAccounts, ParitySigning, Personal,
+        PubSub, Rpc, SecretStore, Signer, Traces, Web3,
     },
     types::Origin,
 };
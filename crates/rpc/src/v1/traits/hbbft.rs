@@ -0,0 +1,165 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Honey Badger BFT rpc interface.
+
+use ethereum_types::{H256, U256, U64};
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+
+use v1::types::{
+    Bytes, HbbftDebugState, HbbftEncryptionKey, HbbftForkEvidence, HbbftKeygenPhase,
+    HbbftKeygenStatus, HbbftNodeStatus, HbbftResetScope, HbbftValidator, HbbftValidatorConnection,
+    HbbftValidatorLiveness, HbbftValidatorPeer, HbbftWithdrawal,
+};
+
+/// Honey Badger BFT rpc interface.
+#[rpc(server)]
+pub trait Hbbft {
+    /// Returns the consensus status of this node: its epochs, the size and fault tolerance of
+    /// the validator set, the seals being created, the key generation progress and the node's
+    /// own standing.
+    ///
+    /// The key generation progress is `null` if it could not be read from the chain.
+    #[rpc(name = "hbbft_nodeStatus")]
+    fn node_status(&self) -> Result<HbbftNodeStatus>;
+
+    /// Returns the current POSDAO epoch.
+    #[rpc(name = "hbbft_currentEpoch")]
+    fn current_epoch(&self) -> Result<U64>;
+
+    /// Returns the validators of the current POSDAO epoch.
+    #[rpc(name = "hbbft_validatorSet")]
+    fn validator_set(&self) -> Result<Vec<HbbftValidator>>;
+
+    /// Returns for how many hbbft epochs each of the other validators has been silent.
+    #[rpc(name = "hbbft_validatorLiveness")]
+    fn validator_liveness(&self) -> Result<Vec<HbbftValidatorLiveness>>;
+
+    /// Returns which of the other validators are connected peers, and how many consensus
+    /// message packets are waiting for the disconnected ones.
+    #[rpc(name = "hbbft_validatorConnections")]
+    fn validator_connections(&self) -> Result<Vec<HbbftValidatorConnection>>;
+
+    /// Returns the nodes of the other current and pending validators and the pinned nodes, which
+    /// the node keeps reserved peer connections to.
+    #[rpc(name = "hbbft_validatorPeers")]
+    fn validator_peers(&self) -> Result<Vec<HbbftValidatorPeer>>;
+
+    /// Returns the random number committed by the given block, or `null` if the block is
+    /// unknown.
+    ///
+    /// Fails if the chain has no randomness contract.
+    #[rpc(name = "hbbft_blockRandom")]
+    fn block_random(&self, block_number: U64) -> Result<Option<U256>>;
+
+    /// Returns the most recent competing blocks with valid seals the node has seen.
+    ///
+    /// Under hbbft this list must stay empty. Any entry means the epoch keys were compromised or
+    /// there is a serious bug.
+    #[rpc(name = "hbbft_forkEvidence")]
+    fn fork_evidence(&self) -> Result<Vec<HbbftForkEvidence>>;
+
+    /// Returns the node's progress in the key generation for the next POSDAO epoch.
+    #[rpc(name = "hbbft_keygenStatus")]
+    fn keygen_status(&self) -> Result<HbbftKeygenStatus>;
+
+    /// Returns which pending validators' Parts and Acks for the next POSDAO epoch are on chain,
+    /// and which of the node's own keygen transactions are sent but not included yet.
+    ///
+    /// The list of validators is empty if no key generation is in progress.
+    #[rpc(name = "parity_hbbftKeygenStatus")]
+    fn keygen_phase_status(&self) -> Result<HbbftKeygenPhase>;
+
+    /// Returns whether other validators can be restarted without disturbing the node's key
+    /// generation.
+    ///
+    /// Meant as a readiness probe for orchestration. Returns `false` while the node is a
+    /// validator of the next POSDAO epoch and its Part and Acks are not confirmed on chain or
+    /// the new epoch key cannot be generated yet. The state is read at most once per block.
+    #[rpc(name = "hbbft_keygenReady")]
+    fn keygen_ready(&self) -> Result<bool>;
+
+    /// Returns the node's in-memory consensus state, to debug a stalled chain.
+    ///
+    /// The layout of the state may change between releases.
+    #[rpc(name = "hbbft_debugState")]
+    fn debug_state(&self) -> Result<HbbftDebugState>;
+
+    /// Returns the key of the current POSDAO epoch, signed by the engine signer and encoded as an
+    /// epoch transition proof.
+    #[rpc(name = "hbbft_epochKeyProof")]
+    fn epoch_key_proof(&self) -> Result<Bytes>;
+
+    /// Returns the key to encrypt transactions with for the validators of the current POSDAO
+    /// epoch.
+    #[rpc(name = "hbbft_encryptionKey")]
+    fn encryption_key(&self) -> Result<HbbftEncryptionKey>;
+}
+
+/// Honey Badger BFT rpc interface for operations altering the node's settings or consensus state.
+#[rpc(server)]
+pub trait HbbftSet {
+    /// Replaces the engine signer with the account of the given secret, without a restart.
+    ///
+    /// The node rebuilds its consensus state for the new key, then checks the pool and announces
+    /// the features of the new mining address. Same as `parity_setEngineSignerSecret`, but fails
+    /// if the chain does not use the hbbft engine.
+    #[rpc(name = "parity_setHbbftSigner")]
+    fn set_hbbft_signer(&self, secret: H256) -> Result<bool>;
+
+    /// Observes the consensus of the current validators while the node is not one of them.
+    ///
+    /// Its progress is reported by `hbbft_nodeStatus`.
+    #[rpc(name = "parity_setHbbftObserver")]
+    fn set_hbbft_observer(&self, enabled: bool) -> Result<bool>;
+
+    /// Keeps a reserved peer connection to the given enode across validator set changes and
+    /// restarts.
+    #[rpc(name = "hbbft_pinPeer")]
+    fn pin_peer(&self, enode: String) -> Result<bool>;
+
+    /// Removes the pin of the given enode.
+    #[rpc(name = "hbbft_unpinPeer")]
+    fn unpin_peer(&self, enode: String) -> Result<bool>;
+
+    /// Adds the signatures of an epoch key proof, to verify seals without contract state.
+    ///
+    /// Returns `true` once more than two thirds of the validators of the previously registered
+    /// epoch signed the key, or of its own validators for the first key. Fails if a signature is
+    /// invalid or from another node, or if the key does not match the chain of a full client.
+    #[rpc(name = "hbbft_addEpochKeyProof")]
+    fn add_epoch_key_proof(&self, proof: Bytes) -> Result<bool>;
+
+    /// Discards the given engine state and re-initializes it from the chain.
+    #[rpc(name = "hbbft_resetState")]
+    fn reset_state(&self, scope: HbbftResetScope) -> Result<bool>;
+
+    /// Stops the validator from contributing to new epochs and flushes its consensus state.
+    ///
+    /// Returns the transaction removing the validator's pool, which has to be sent from the
+    /// pool's staking address.
+    #[rpc(name = "hbbft_withdraw")]
+    fn withdraw(&self) -> Result<HbbftWithdrawal>;
+
+    /// Proposes a transaction encrypted with the key of the current POSDAO epoch in this
+    /// validator's next contributions. It is only decrypted once the validators agreed on the
+    /// batch proposing it, so its contents cannot be used to front-run it.
+    ///
+    /// Returns the hash of the ciphertext.
+    #[rpc(name = "hbbft_sendEncryptedTransaction")]
+    fn send_encrypted_transaction(&self, ciphertext: Bytes) -> Result<H256>;
+}
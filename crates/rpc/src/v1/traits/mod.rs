@@ -20,6 +20,7 @@ pub mod debug;
 pub mod eth;
 pub mod eth_pubsub;
 pub mod eth_signing;
+pub mod hbbft;
 pub mod net;
 pub mod parity;
 pub mod parity_accounts;
@@ -38,6 +39,7 @@ pub use self::{
     eth::{Eth, EthFilter},
     eth_pubsub::EthPubSub,
     eth_signing::EthSigning,
+    hbbft::{Hbbft, HbbftSet},
     net::Net,
     parity::Parity,
     parity_accounts::{ParityAccounts, ParityAccountsInfo},
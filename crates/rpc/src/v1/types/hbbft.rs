@@ -0,0 +1,538 @@
+// Copyright 2015-2020 Parity Technologies (UK) Ltd.
+// This file is part of OpenEthereum.
+
+// OpenEthereum is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// OpenEthereum is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with OpenEthereum.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Honey Badger BFT status types.
+
+use ethcore::engines::hbbft::api;
+use ethereum_types::{H160, H256, H512, U64};
+use rlp;
+use std::collections::BTreeMap;
+use v1::types::Bytes;
+
+/// The consensus status of a Honey Badger BFT node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftNodeStatus {
+    /// The current POSDAO epoch.
+    pub posdao_epoch: U64,
+    /// The current hbbft epoch, if the node is a validator or an observer of the current POSDAO
+    /// epoch.
+    pub hbbft_epoch: Option<U64>,
+    /// Whether the node is a validator of the current POSDAO epoch.
+    pub is_validator: bool,
+    /// Whether the node observes the consensus of the current POSDAO epoch's validators.
+    pub is_observer: bool,
+    /// The number of contributions received in the current hbbft epoch, if the node is a
+    /// validator or an observer.
+    pub received_proposals: Option<U64>,
+    /// Whether more than `maxFaulty` contributions were received in the current hbbft epoch, if
+    /// the node is a validator or an observer.
+    pub proposal_threshold_reached: Option<bool>,
+    /// The number of validators of the current POSDAO epoch, if the node is one of them.
+    pub network_size: Option<U64>,
+    /// The number of faulty validators the current POSDAO epoch tolerates, if the node is one
+    /// of its validators.
+    pub max_faulty: Option<U64>,
+    /// The number of blocks whose seal is still being created.
+    pub pending_seals: U64,
+    /// The node's progress in the key generation for the next POSDAO epoch, if it could be read.
+    pub keygen: Option<HbbftKeygenStatus>,
+    /// Whether an engine signer is configured.
+    pub has_signer: bool,
+    /// The address of the engine signer, if one is configured.
+    pub signer: Option<H160>,
+    /// Whether too few of the other validators are connected to reach agreement.
+    pub is_isolated: bool,
+    /// The on-chain standing of the node's pool, if an engine signer is configured.
+    pub pool_status: Option<HbbftPoolStatus>,
+    /// Whether too many blocks in a row failed seal verification.
+    pub seal_failure_alert: bool,
+    /// Whether the validator withdrew.
+    pub withdrawn: bool,
+    /// The number of competing blocks with valid seals seen since startup.
+    pub forks_detected: U64,
+}
+
+/// The on-chain standing of a validator's pool.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HbbftPoolStatus {
+    /// The pool is active and the validator is marked as available.
+    Active,
+    /// The mining address does not belong to any pool.
+    NoPool,
+    /// The pool is not active.
+    Inactive,
+    /// The validator is banned.
+    Banned,
+    /// The validator is not marked as available.
+    Unavailable,
+}
+
+impl From<api::PoolStatus> for HbbftPoolStatus {
+    fn from(status: api::PoolStatus) -> Self {
+        match status {
+            api::PoolStatus::Active => HbbftPoolStatus::Active,
+            api::PoolStatus::NoPool => HbbftPoolStatus::NoPool,
+            api::PoolStatus::Inactive => HbbftPoolStatus::Inactive,
+            api::PoolStatus::Banned => HbbftPoolStatus::Banned,
+            api::PoolStatus::Unavailable => HbbftPoolStatus::Unavailable,
+        }
+    }
+}
+
+impl HbbftNodeStatus {
+    /// Combines the engine status with the key generation progress.
+    pub fn new(status: api::HbbftStatus, keygen: Option<api::KeygenProgress>) -> Self {
+        HbbftNodeStatus {
+            posdao_epoch: status.posdao_epoch.into(),
+            hbbft_epoch: status.hbbft_epoch.map(Into::into),
+            is_validator: status.is_validator,
+            is_observer: status.is_observer,
+            received_proposals: status.received_proposals.map(|n| (n as u64).into()),
+            proposal_threshold_reached: status.proposal_threshold_reached,
+            network_size: status.network_size.map(|n| (n as u64).into()),
+            max_faulty: status.max_faulty.map(|n| (n as u64).into()),
+            pending_seals: (status.pending_seals as u64).into(),
+            keygen: keygen.map(Into::into),
+            has_signer: status.has_signer,
+            signer: status.signer,
+            is_isolated: status.is_isolated,
+            pool_status: status.pool_status.map(Into::into),
+            seal_failure_alert: status.seal_failure_alert,
+            withdrawn: status.withdrawn,
+            forks_detected: status.forks_detected.into(),
+        }
+    }
+}
+
+/// The in-memory consensus state of a Honey Badger BFT node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftDebugState {
+    /// The current hbbft epoch, if the node is a validator of the current POSDAO epoch.
+    pub hbbft_epoch: Option<U64>,
+    /// The number of contributions received in the current hbbft epoch, if the node is a
+    /// validator of the current POSDAO epoch.
+    pub received_proposals: Option<U64>,
+    /// The progress of the seals being created, by block number.
+    pub sealing: BTreeMap<U64, HbbftSealingStatus>,
+    /// The number of consensus messages cached for future hbbft epochs, by epoch.
+    pub future_messages: BTreeMap<U64, U64>,
+}
+
+/// The progress of a block's seal.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HbbftSealingStatus {
+    /// Signature shares are still being collected.
+    InProgress,
+    /// The signature shares were combined into the seal.
+    Complete,
+}
+
+impl From<api::SealingStatus> for HbbftSealingStatus {
+    fn from(status: api::SealingStatus) -> Self {
+        match status {
+            api::SealingStatus::InProgress => HbbftSealingStatus::InProgress,
+            api::SealingStatus::Complete => HbbftSealingStatus::Complete,
+        }
+    }
+}
+
+impl From<api::DebugState> for HbbftDebugState {
+    fn from(state: api::DebugState) -> Self {
+        HbbftDebugState {
+            hbbft_epoch: state.hbbft_epoch.map(Into::into),
+            received_proposals: state.received_proposals.map(|n| (n as u64).into()),
+            sealing: state
+                .sealing
+                .into_iter()
+                .map(|(number, status)| (number.into(), status.into()))
+                .collect(),
+            future_messages: state
+                .future_messages
+                .into_iter()
+                .map(|(epoch, count)| (epoch.into(), (count as u64).into()))
+                .collect(),
+        }
+    }
+}
+
+/// A node's progress in the key generation for the next POSDAO epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftKeygenStatus {
+    /// Whether the node is a validator of the next POSDAO epoch.
+    pub is_pending_validator: bool,
+    /// Whether the node's Part is stored on chain.
+    pub part_confirmed: bool,
+    /// Whether the node's Acks are stored on chain.
+    pub acks_confirmed: bool,
+    /// Whether the key of the next epoch can be generated.
+    pub key_generated: bool,
+    /// Whether the node can be restarted without affecting the key generation.
+    pub ready: bool,
+}
+
+impl From<api::KeygenProgress> for HbbftKeygenStatus {
+    fn from(progress: api::KeygenProgress) -> Self {
+        HbbftKeygenStatus {
+            is_pending_validator: progress.is_pending_validator,
+            part_confirmed: progress.part_confirmed,
+            acks_confirmed: progress.acks_confirmed,
+            key_generated: progress.key_generated,
+            ready: progress.is_ready(),
+        }
+    }
+}
+
+/// The Part and Acks a validator of the next POSDAO epoch stored on chain.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftKeygenValidator {
+    /// The validator's mining address.
+    pub mining_address: H160,
+    /// Whether the validator's Part is stored on chain.
+    pub has_part: bool,
+    /// Whether all of the validator's Acks are stored on chain.
+    pub has_acks: bool,
+}
+
+impl From<api::KeygenValidatorStatus> for HbbftKeygenValidator {
+    fn from(status: api::KeygenValidatorStatus) -> Self {
+        HbbftKeygenValidator {
+            mining_address: status.mining_address,
+            has_part: status.has_part,
+            has_acks: status.has_acks,
+        }
+    }
+}
+
+/// The progress of all pending validators in the key generation for the next POSDAO epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftKeygenPhase {
+    /// The POSDAO epoch the key is generated for.
+    pub upcoming_epoch: U64,
+    /// The validators of the next POSDAO epoch.
+    pub validators: Vec<HbbftKeygenValidator>,
+    /// The block the node's Part transaction was last sent in, if it is not on chain yet.
+    pub part_in_flight: Option<U64>,
+    /// The block the node's Acks transactions were last sent in, if they are not on chain yet.
+    pub acks_in_flight: Option<U64>,
+}
+
+impl From<api::KeygenPhaseStatus> for HbbftKeygenPhase {
+    fn from(status: api::KeygenPhaseStatus) -> Self {
+        HbbftKeygenPhase {
+            upcoming_epoch: status.upcoming_epoch.into(),
+            validators: status.validators.into_iter().map(Into::into).collect(),
+            part_in_flight: status.part_in_flight.map(Into::into),
+            acks_in_flight: status.acks_in_flight.map(Into::into),
+        }
+    }
+}
+
+/// The engine state to discard in `hbbft_resetState`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HbbftResetScope {
+    /// Consensus messages cached for future hbbft epochs.
+    FutureMessages,
+    /// Signature shares collected for block seals.
+    Sealing,
+    /// The keys of the current POSDAO epoch.
+    EpochKeys,
+    /// The resend timers of keygen transactions.
+    KeygenSender,
+    /// All engine caches.
+    All,
+}
+
+impl From<HbbftResetScope> for api::ResetScope {
+    fn from(scope: HbbftResetScope) -> Self {
+        match scope {
+            HbbftResetScope::FutureMessages => api::ResetScope::FutureMessages,
+            HbbftResetScope::Sealing => api::ResetScope::Sealing,
+            HbbftResetScope::EpochKeys => api::ResetScope::EpochKeys,
+            HbbftResetScope::KeygenSender => api::ResetScope::KeygenSender,
+            HbbftResetScope::All => api::ResetScope::All,
+        }
+    }
+}
+
+/// A validator of the current POSDAO epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftValidator {
+    /// The validator's mining address.
+    pub mining_address: H160,
+    /// The validator's public key, which is also its node ID.
+    pub public_key: H512,
+    /// Whether the validator is the node answering the request.
+    pub is_self: bool,
+}
+
+impl From<api::HbbftValidator> for HbbftValidator {
+    fn from(validator: api::HbbftValidator) -> Self {
+        HbbftValidator {
+            mining_address: validator.mining_address,
+            public_key: validator.node_id.0,
+            is_self: validator.is_self,
+        }
+    }
+}
+
+/// How recently a node received consensus messages from another validator.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftValidatorLiveness {
+    /// The validator's public key, which is also its node ID.
+    pub public_key: H512,
+    /// The latest hbbft epoch a message was received for in the current POSDAO epoch, if any.
+    pub last_message_epoch: Option<U64>,
+    /// The number of hbbft epochs the validator has been silent for.
+    pub silent_epochs: U64,
+}
+
+impl From<api::ValidatorLiveness> for HbbftValidatorLiveness {
+    fn from(liveness: api::ValidatorLiveness) -> Self {
+        HbbftValidatorLiveness {
+            public_key: liveness.node_id.0,
+            last_message_epoch: liveness.last_message_epoch.map(Into::into),
+            silent_epochs: liveness.silent_epochs.into(),
+        }
+    }
+}
+
+/// Whether another validator has a peer session with a node.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftValidatorConnection {
+    /// The validator's public key, which is also its node ID.
+    pub public_key: H512,
+    /// Whether the validator's node is a connected peer.
+    pub is_connected: bool,
+    /// The number of consensus message packets waiting for the validator to reconnect.
+    pub queued_packets: U64,
+}
+
+impl From<api::ValidatorConnection> for HbbftValidatorConnection {
+    fn from(connection: api::ValidatorConnection) -> Self {
+        HbbftValidatorConnection {
+            public_key: connection.node_id.0,
+            is_connected: connection.is_connected,
+            queued_packets: (connection.queued_packets as u64).into(),
+        }
+    }
+}
+
+/// A node the engine keeps a reserved peer connection to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftValidatorPeer {
+    /// The node's enode URL.
+    pub enode: String,
+    /// Whether the node belongs to a validator of the current POSDAO epoch.
+    pub is_validator: bool,
+    /// Whether the node belongs to a validator elected for the next POSDAO epoch.
+    pub is_pending_validator: bool,
+    /// Whether the node was pinned by the operator.
+    pub is_pinned: bool,
+    /// Whether the reservation was accepted by the network.
+    pub is_reserved: bool,
+}
+
+impl From<api::ValidatorPeer> for HbbftValidatorPeer {
+    fn from(peer: api::ValidatorPeer) -> Self {
+        HbbftValidatorPeer {
+            enode: peer.enode,
+            is_validator: peer.is_validator,
+            is_pending_validator: peer.is_pending_validator,
+            is_pinned: peer.is_pinned,
+            is_reserved: peer.is_reserved,
+        }
+    }
+}
+
+/// Two competing blocks with valid seals at the same height.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftForkEvidence {
+    /// The height of the competing blocks.
+    pub number: U64,
+    /// The hash of the block that was chosen.
+    pub chosen_hash: H256,
+    /// The hash of the block that was rejected.
+    pub rejected_hash: H256,
+    /// The RLP encoded header of the block that was chosen.
+    pub chosen_header: Bytes,
+    /// The RLP encoded header of the block that was rejected.
+    pub rejected_header: Bytes,
+}
+
+impl From<api::ForkEvidence> for HbbftForkEvidence {
+    fn from(evidence: api::ForkEvidence) -> Self {
+        HbbftForkEvidence {
+            number: evidence.chosen.number().into(),
+            chosen_hash: evidence.chosen.hash(),
+            rejected_hash: evidence.rejected.hash(),
+            chosen_header: rlp::encode(&evidence.chosen).into(),
+            rejected_header: rlp::encode(&evidence.rejected).into(),
+        }
+    }
+}
+
+/// The key to encrypt transactions with for the validators of the current POSDAO epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftEncryptionKey {
+    /// The POSDAO epoch whose validators can decrypt with the key.
+    pub posdao_epoch: U64,
+    /// The compressed public master key of the epoch's validators.
+    pub public_key: Bytes,
+}
+
+impl From<api::EncryptionKey> for HbbftEncryptionKey {
+    fn from(key: api::EncryptionKey) -> Self {
+        HbbftEncryptionKey {
+            posdao_epoch: key.posdao_epoch.into(),
+            public_key: key.public_key.to_bytes().to_vec().into(),
+        }
+    }
+}
+
+/// The transaction removing a withdrawn validator's pool, to be sent from its staking address.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HbbftWithdrawal {
+    /// The staking address that has to send the transaction.
+    pub staking_address: H160,
+    /// The address of the staking contract, the transaction's recipient.
+    pub staking_contract: H160,
+    /// The transaction's call data.
+    pub call_data: Bytes,
+}
+
+impl From<api::Withdrawal> for HbbftWithdrawal {
+    fn from(withdrawal: api::Withdrawal) -> Self {
+        HbbftWithdrawal {
+            staking_address: withdrawal.staking_address,
+            staking_contract: withdrawal.staking_contract,
+            call_data: withdrawal.call_data.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        api, HbbftDebugState, HbbftKeygenPhase, HbbftKeygenStatus, HbbftKeygenValidator,
+        HbbftNodeStatus, HbbftPoolStatus, HbbftResetScope, HbbftValidator,
+    };
+    use ethereum_types::{H160, H512};
+    use serde_json;
+
+    #[test]
+    fn validator_serialization() {
+        let validator = HbbftValidator {
+            mining_address: H160::from_low_u64_be(1),
+            public_key: H512::from_low_u64_be(2),
+            is_self: true,
+        };
+        let serialized = serde_json::to_string(&validator).unwrap();
+        assert!(serialized.starts_with(
+            r#"{"miningAddress":"0x0000000000000000000000000000000000000001","publicKey":"0x"#
+        ));
+        assert!(serialized.ends_with(r#"02","isSelf":true}"#));
+    }
+
+    #[test]
+    fn node_status_serialization() {
+        let status = HbbftNodeStatus {
+            posdao_epoch: 3.into(),
+            hbbft_epoch: Some(7.into()),
+            is_validator: true,
+            is_observer: false,
+            received_proposals: Some(2.into()),
+            proposal_threshold_reached: Some(true),
+            network_size: Some(4.into()),
+            max_faulty: Some(1.into()),
+            pending_seals: 1.into(),
+            keygen: Some(HbbftKeygenStatus {
+                is_pending_validator: true,
+                part_confirmed: true,
+                acks_confirmed: false,
+                key_generated: false,
+                ready: false,
+            }),
+            has_signer: true,
+            signer: Some(H160::from_low_u64_be(1)),
+            is_isolated: false,
+            pool_status: Some(HbbftPoolStatus::Active),
+            seal_failure_alert: false,
+            withdrawn: false,
+            forks_detected: 0.into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"posdaoEpoch":"0x3","hbbftEpoch":"0x7","isValidator":true,"isObserver":false,"receivedProposals":"0x2","proposalThresholdReached":true,"networkSize":"0x4","maxFaulty":"0x1","pendingSeals":"0x1","keygen":{"isPendingValidator":true,"partConfirmed":true,"acksConfirmed":false,"keyGenerated":false,"ready":false},"hasSigner":true,"signer":"0x0000000000000000000000000000000000000001","isIsolated":false,"poolStatus":"active","sealFailureAlert":false,"withdrawn":false,"forksDetected":"0x0"}"#
+        );
+    }
+
+    #[test]
+    fn keygen_phase_serialization() {
+        let phase = HbbftKeygenPhase {
+            upcoming_epoch: 4.into(),
+            validators: vec![HbbftKeygenValidator {
+                mining_address: H160::from_low_u64_be(1),
+                has_part: true,
+                has_acks: false,
+            }],
+            part_in_flight: None,
+            acks_in_flight: Some(12.into()),
+        };
+        assert_eq!(
+            serde_json::to_string(&phase).unwrap(),
+            r#"{"upcomingEpoch":"0x4","validators":[{"miningAddress":"0x0000000000000000000000000000000000000001","hasPart":true,"hasAcks":false}],"partInFlight":null,"acksInFlight":"0xc"}"#
+        );
+    }
+
+    #[test]
+    fn debug_state_serialization() {
+        let mut state = api::DebugState::default();
+        state.hbbft_epoch = Some(7);
+        state.received_proposals = Some(2);
+        state.sealing.insert(6, api::SealingStatus::Complete);
+        state.sealing.insert(7, api::SealingStatus::InProgress);
+        state.future_messages.insert(8, 3);
+        let serialized = serde_json::to_string(&HbbftDebugState::from(state)).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"hbbftEpoch":"0x7","receivedProposals":"0x2","sealing":{"0x6":"complete","0x7":"inProgress"},"futureMessages":{"0x8":"0x3"}}"#
+        );
+    }
+
+    #[test]
+    fn reset_scope_deserialization() {
+        let scope: HbbftResetScope = serde_json::from_str(r#""futureMessages""#).unwrap();
+        assert_eq!(scope, HbbftResetScope::FutureMessages);
+        let scope: HbbftResetScope = serde_json::from_str(r#""all""#).unwrap();
+        assert_eq!(scope, HbbftResetScope::All);
+        assert!(serde_json::from_str::<HbbftResetScope>(r#""everything""#).is_err());
+    }
+}
@@ -30,6 +30,12 @@ pub use self::{
     derivation::{Derive, DeriveHash, DeriveHierarchical},
     eip191::{EIP191Version, PresignedTransaction},
     filter::{Filter, FilterChanges},
+    hbbft::{
+        HbbftDebugState, HbbftEncryptionKey, HbbftForkEvidence, HbbftKeygenPhase,
+        HbbftKeygenStatus, HbbftKeygenValidator, HbbftNodeStatus, HbbftPoolStatus, HbbftResetScope,
+        HbbftSealingStatus, HbbftValidator, HbbftValidatorConnection, HbbftValidatorLiveness,
+        HbbftValidatorPeer, HbbftWithdrawal,
+    },
     histogram::Histogram,
     index::Index,
     log::Log,
@@ -63,6 +69,7 @@ mod confirmations;
 mod derivation;
 mod eip191;
 mod filter;
+mod hbbft;
 mod histogram;
 mod index;
 mod log;
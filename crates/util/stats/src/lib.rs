@@ -16,7 +16,12 @@
 
 //! Statistical functions and helpers.
 
+use prometheus::{
+    core::{Collector, Desc},
+    proto,
+};
 use std::{
+    collections::HashMap,
     iter::FromIterator,
     ops::{Add, Deref, Div, Sub},
     time::Instant,
@@ -67,6 +72,40 @@ impl PrometheusRegistry {
             .expect("prometheus identifiers must be are unique");
     }
 
+    /// Adds a new prometheus histogram with the specified upper bucket bounds and cumulative
+    /// counts, number of observations and sum of observed values
+    pub fn register_histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        buckets: &[(f64, u64)],
+        count: u64,
+        sum: f64,
+    ) {
+        let name = format!("{}{}", self.prefix, name);
+        let desc = Desc::new(name.clone(), help.into(), Vec::new(), HashMap::new())
+            .expect("name and help must be non-empty");
+        let mut histogram = proto::Histogram::default();
+        histogram.set_sample_count(count);
+        histogram.set_sample_sum(sum);
+        for (upper_bound, cumulative_count) in buckets {
+            let mut bucket = proto::Bucket::default();
+            bucket.set_upper_bound(*upper_bound);
+            bucket.set_cumulative_count(*cumulative_count);
+            histogram.mut_bucket().push(bucket);
+        }
+        let mut metric = proto::Metric::default();
+        metric.set_histogram(histogram);
+        let mut family = proto::MetricFamily::default();
+        family.set_name(name);
+        family.set_help(help.into());
+        family.set_field_type(proto::MetricType::HISTOGRAM);
+        family.mut_metric().push(metric);
+        self.registry
+            .register(Box::new(HistogramSnapshot { desc, family }))
+            .expect("prometheus identifiers must be unique");
+    }
+
     /// Adds a new prometheus counter with the time spent in running the specified function
     pub fn register_optime<F: Fn() -> T, T>(&mut self, name: &str, f: &F) -> T {
         let start = Instant::now();
@@ -81,6 +120,22 @@ impl PrometheusRegistry {
     }
 }
 
+/// A histogram whose observations are recorded elsewhere.
+struct HistogramSnapshot {
+    desc: Desc,
+    family: proto::MetricFamily,
+}
+
+impl Collector for HistogramSnapshot {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        vec![self.family.clone()]
+    }
+}
+
 /// Implements a prometheus metrics collector
 pub trait PrometheusMetrics {
     fn prometheus_metrics(&self, registry: &mut PrometheusRegistry);
@@ -218,6 +273,18 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn register_histogram() {
+        let mut registry = PrometheusRegistry::new("oe_".into());
+        registry.register_histogram("latency", "Latency", &[(1.0, 2), (5.0, 3)], 4, 12.0);
+        let families = registry.registry().gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].get_name(), "oe_latency");
+        let histogram = families[0].get_metric()[0].get_histogram();
+        assert_eq!(histogram.get_sample_count(), 4);
+        assert_eq!(histogram.get_bucket()[1].get_cumulative_count(), 3);
+    }
+
     #[test]
     fn check_corpus() {
         let corpus = Corpus::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);